@@ -0,0 +1,142 @@
+use anyhow::{Context, Result, anyhow};
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::server::SERVER_CONFIG;
+use crate::server_cfg::ServerDzConfig;
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_PACKET_SIZE: usize = 1400;
+/// DayZ's query port when `steamQueryPort` isn't set
+#[allow(clippy::doc_markdown)]
+const DEFAULT_QUERY_PORT: u16 = 27016;
+
+const SINGLE_PACKET: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const A2S_INFO: u8 = 0x54;
+const A2S_INFO_PAYLOAD: &[u8] = b"Source Engine Query\0";
+const S2A_INFO: u8 = 0x49;
+const S2C_CHALLENGE: u8 = 0x41;
+
+/// What a server reports about itself over the Steam query protocol
+pub struct ServerInfo {
+    pub name: String,
+    pub map: String,
+    pub players: u8,
+    pub max_players: u8,
+    pub bots: u8,
+    pub version: String,
+    pub ping: Duration,
+}
+
+impl ServerInfo {
+    /// Real players, not counting bots
+    pub const fn human_players(&self) -> u8 {
+        self.players.saturating_sub(self.bots)
+    }
+}
+
+/// Query the server of this install on the `steamQueryPort` from `serverDZ.cfg`
+#[allow(clippy::doc_markdown)]
+pub fn query_server(server_install_dir: &Path) -> Result<ServerInfo> {
+    let port = ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))?
+        .get("steamQueryPort")
+        .map(|port| port.parse::<u16>().context(format!("steamQueryPort in {SERVER_CONFIG} is not a valid port")))
+        .transpose()?
+        .unwrap_or(DEFAULT_QUERY_PORT);
+
+    query_info(SocketAddr::from(([127, 0, 0, 1], port)))
+}
+
+/// Send `A2S_INFO` and parse the reply, answering a challenge if the server asks for one
+#[allow(clippy::doc_markdown)]
+pub fn query_info(address: SocketAddr) -> Result<ServerInfo> {
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .context("Failed to open query socket")?;
+    socket.connect(address)
+        .context(format!("Failed to connect to query port {address}"))?;
+    socket.set_read_timeout(Some(TIMEOUT))?;
+
+    let started = Instant::now();
+    let mut request = info_request(None);
+    // A challenge reply is answered once, a second one means something else is wrong
+    for _ in 0..2 {
+        socket.send(&request)
+            .context("Failed to send query")?;
+
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        let length = socket.recv(&mut buffer)
+            .context(format!("No reply from query port {address}, is the server running?"))?;
+        let ping = started.elapsed();
+
+        match buffer[..length].strip_prefix(&SINGLE_PACKET) {
+            Some([S2C_CHALLENGE, challenge @ ..]) if challenge.len() >= 4 => {
+                request = info_request(Some(&challenge[..4]));
+            }
+            Some([S2A_INFO, body @ ..]) => return parse_info(body, ping),
+            _ => return Err(anyhow!("Unexpected reply from query port {address}")),
+        }
+    }
+
+    Err(anyhow!("Query port {address} kept asking for a challenge"))
+}
+
+fn info_request(challenge: Option<&[u8]>) -> Vec<u8> {
+    let mut request = SINGLE_PACKET.to_vec();
+    request.push(A2S_INFO);
+    request.extend_from_slice(A2S_INFO_PAYLOAD);
+    if let Some(challenge) = challenge {
+        request.extend_from_slice(challenge);
+    }
+    request
+}
+
+fn parse_info(body: &[u8], ping: Duration) -> Result<ServerInfo> {
+    let mut reader = Reader { bytes: body };
+
+    let _protocol = reader.byte()?;
+    let name = reader.string()?;
+    let map = reader.string()?;
+    let _folder = reader.string()?;
+    let _game = reader.string()?;
+    let _app_id = reader.bytes(2)?;
+    let players = reader.byte()?;
+    let max_players = reader.byte()?;
+    let bots = reader.byte()?;
+    let _server_type = reader.byte()?;
+    let _environment = reader.byte()?;
+    let _visibility = reader.byte()?;
+    let _vac = reader.byte()?;
+    let version = reader.string()?;
+
+    Ok(ServerInfo { name, map, players, max_players, bots, version, ping })
+}
+
+/// Reads the fields of a query reply in order
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, count: usize) -> Result<&[u8]> {
+        if self.bytes.len() < count {
+            return Err(anyhow!("Query reply is truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// Null-terminated string
+    fn string(&mut self) -> Result<String> {
+        let end = self.bytes.iter().position(|&b| b == 0)
+            .ok_or_else(|| anyhow!("Query reply is truncated"))?;
+        let text = String::from_utf8_lossy(&self.bytes[..end]).to_string();
+        self.bytes = &self.bytes[end + 1..];
+        Ok(text)
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// Banned Steam64 IDs. The server reads it from its install dir next to the exe, not from the
+/// profile directory, which only holds what the server writes.
+pub const BAN_FILE: &str = "ban.txt";
+pub const WHITELIST_FILE: &str = "whitelist.txt";
+/// Players who skip the queue, their IDs separated by semicolons
+pub const PRIORITY_FILE: &str = "priority.txt";
+const STEAM64_ID_LENGTH: usize = 17;
+
+/// Whether `id` looks like a Steam64 ID, which is what these lists hold
+pub fn is_steam64_id(id: &str) -> bool {
+    id.len() == STEAM64_ID_LENGTH && id.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// A DayZ `ban.txt` or `whitelist.txt`: one player ID per line, optionally followed by a `//` comment.
+/// Entries are keyed by ID and keep their whole line so comments survive syncing, and saving
+/// keeps the lines that aren't entries, like a header comment.
+#[allow(clippy::doc_markdown)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessList {
+    pub entries: BTreeMap<String, String>,
+}
+
+impl AccessList {
+    /// Load a list, starting empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read {}", path.display()))?;
+        Ok(Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let entries = content.lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                Some((get_id(line)?.to_string(), line.to_string()))
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Write the list over the file at `path` in place: its comment and blank lines stay where
+    /// they are, entries still listed are updated where they are, and new ones go at the end
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let existing = if path.exists() {
+            fs::read_to_string(path)
+                .context(format!("Failed to read {}", path.display()))?
+        } else {
+            String::new()
+        };
+
+        let mut written = BTreeSet::new();
+        let mut lines = Vec::new();
+        for line in existing.lines() {
+            match get_id(line.trim()) {
+                None => lines.push(line),
+                Some(id) => {
+                    if let Some((id, entry)) = self.entries.get_key_value(id)
+                        && written.insert(id)
+                    {
+                        lines.push(entry);
+                    }
+                }
+            }
+        }
+        lines.extend(self.entries.iter()
+            .filter(|(id, _)| !written.contains(id))
+            .map(|(_, entry)| entry.as_str()));
+
+        let mut content = lines.join("\n");
+        content.push('\n');
+
+        fs::write(path, content)
+            .context(format!("Failed to write {}", path.display()))
+    }
+}
+
+/// The player ID a line lists, none for a comment or blank line
+fn get_id(line: &str) -> Option<&str> {
+    line.split("//").next()?.split_whitespace().next()
+}
@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::server::SERVER_PROFILES;
+
+const ADM_EXTENSION: &str = "adm";
+const ADM_HEADER: &str = "AdminLog started on ";
+
+/// Something a player did, from a line of the server's admin log, e.g.
+/// `12:05:00 | Player "Name" (id=... pos=<4567.8, 9876.5, 123.4>) placed Barrel_Blue<Barrel_ColorBase>`
+pub struct AdmEvent {
+    pub time: NaiveDateTime,
+    pub name: String,
+    pub id: String,
+    /// `[x, y, altitude]` in game metres, for lines that log one
+    pub position: Option<[f64; 3]>,
+    /// The rest of the line, e.g. `placed Barrel_Blue<Barrel_ColorBase>` or `is connected`;
+    /// empty for the periodic player list
+    pub action: String,
+}
+
+/// Reads an admin log line by line, keeping track of the date the lines leave out
+#[derive(Default)]
+struct LineParser {
+    date: Option<NaiveDate>,
+    last_time: Option<NaiveTime>,
+}
+
+impl LineParser {
+    /// The player event on `line`, if it has one
+    fn feed(&mut self, line: &str) -> Option<AdmEvent> {
+        if let Some(started) = line.trim().strip_prefix(ADM_HEADER) {
+            // "2024-05-01 at 12:00:00"
+            self.date = started.split_whitespace().next()
+                .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok());
+            self.last_time = None;
+            return None;
+        }
+
+        let (time, entry) = line.split_once(" | ")?;
+        let (Some(day), Ok(time)) = (self.date, NaiveTime::parse_from_str(time.trim(), "%H:%M:%S")) else {
+            return None;
+        };
+        // Lines only carry the time, the log runs past midnight
+        if self.last_time.is_some_and(|last| time < last) {
+            self.date = day.succ_opt();
+        }
+        self.last_time = Some(time);
+
+        let mut event = parse_entry(entry)?;
+        event.time = self.date.unwrap_or(day).and_time(time);
+        Some(event)
+    }
+}
+
+/// Every player event in an admin log
+pub fn parse(text: &str) -> Vec<AdmEvent> {
+    let mut parser = LineParser::default();
+    text.lines().filter_map(|line| parser.feed(line)).collect()
+}
+
+fn parse_entry(entry: &str) -> Option<AdmEvent> {
+    let rest = entry.strip_prefix("Player \"")?;
+    let (name, rest) = rest.split_once('"')?;
+
+    // The "(id=... pos=<...>)" group, which some lines put before the action and some after
+    let id_start = rest.find("(id=")?;
+    let group_end = id_start + rest[id_start..].find(')')?;
+    let group = &rest[id_start + "(id=".len()..group_end];
+    let id = group.split(' ').next()?;
+
+    let position = group.split_once("pos=<")
+        .and_then(|(_, position)| position.split_once('>'))
+        .and_then(|(position, _)| {
+            let mut coordinates = position.split(',').map(|value| value.trim().parse::<f64>().ok());
+            Some([coordinates.next()??, coordinates.next()??, coordinates.next()??])
+        });
+
+    let action = format!("{} {}", rest[..id_start].trim(), rest[group_end + 1..].trim());
+
+    Some(AdmEvent {
+        time: NaiveDateTime::default(),
+        name: name.to_string(),
+        id: id.to_string(),
+        position,
+        action: action.trim().to_string(),
+    })
+}
+
+/// Admin logs written to since `since`, oldest first
+fn find_files(server_install_dir: &Path, since: SystemTime) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(server_install_dir.join(SERVER_PROFILES)) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e.eq_ignore_ascii_case(ADM_EXTENSION)))
+        .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+        .filter(|(modified, _)| *modified >= since)
+        .collect();
+    files.sort();
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Player events logged since `since` across all admin logs, in time order
+pub fn read_since(server_install_dir: &Path, since: DateTime<Local>) -> Result<Vec<AdmEvent>> {
+    let mut events = Vec::new();
+    for path in find_files(server_install_dir, SystemTime::from(since)) {
+        let text = fs::read(&path)
+            .context(format!("Failed to read {}", path.display()))?;
+        events.extend(parse(&String::from_utf8_lossy(&text)));
+    }
+
+    events.retain(|event| event.time >= since.naive_local());
+    events.sort_by_key(|event| event.time);
+    Ok(events)
+}
+
+/// Follows the admin log of the running server as it's written
+pub struct AdmFollower {
+    server_install_dir: PathBuf,
+    since: SystemTime,
+    path: Option<PathBuf>,
+    /// How far into `path` has been read
+    offset: u64,
+    parser: LineParser,
+}
+
+impl AdmFollower {
+    /// Follow the newest admin log written to since `since`, such as when the server started
+    pub fn new(server_install_dir: &Path, since: SystemTime) -> Self {
+        Self {
+            server_install_dir: server_install_dir.to_path_buf(),
+            since,
+            path: None,
+            offset: 0,
+            parser: LineParser::default(),
+        }
+    }
+
+    /// Player events logged since the last call
+    pub fn read_new(&mut self) -> Result<Vec<AdmEvent>> {
+        // The server starts a new log each run
+        if let Some(newest) = find_files(&self.server_install_dir, self.since).pop()
+            && self.path.as_ref() != Some(&newest)
+        {
+            self.path = Some(newest);
+            self.offset = 0;
+            self.parser = LineParser::default();
+        }
+        let Some(path) = &self.path else {
+            return Ok(Vec::new());
+        };
+
+        let mut file = File::open(path)
+            .context(format!("Failed to open {}", path.display()))?;
+        file.seek(SeekFrom::Start(self.offset))
+            .context(format!("Failed to read {}", path.display()))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)
+            .context(format!("Failed to read {}", path.display()))?;
+
+        // A line the server is still writing is read next time
+        let Some(end) = bytes.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(Vec::new());
+        };
+        self.offset += u64::try_from(end + 1).unwrap_or_default();
+        Ok(String::from_utf8_lossy(&bytes[..=end])
+            .lines()
+            .filter_map(|line| self.parser.feed(line))
+            .collect())
+    }
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDateTime};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::adm::{AdmEvent, AdmFollower};
+use crate::config::mod_entry::matches_wildcard;
+use crate::config::{AlertsConfig, Config, DupesConfig, NotificationEvent, NotificationsConfig};
+use crate::dupes;
+use crate::notifier::{Notification, Notifier};
+use crate::privacy::Redactor;
+use crate::server::get_server_name;
+use crate::ui::status::println_failure;
+
+/// Items whose placing is an explosives alert, matched against the class name in `placed ...`
+const EXPLOSIVES: [&str; 4] = ["ClaymoreMine", "LandMineTrap", "Plastic_Explosive", "ImprovisedExplosive"];
+/// Lines per notification, a raid can log far more than anyone reads
+const MAX_ALERT_LINES: usize = 20;
+
+/// Watches the running server's admin log for `[alerts]`, notifying about what matches
+pub struct AdmAlerts {
+    config: AlertsConfig,
+    dupes: DupesConfig,
+    notifications: NotificationsConfig,
+    redactor: Redactor,
+    server_install_dir: PathBuf,
+    follower: AdmFollower,
+    next_read: DateTime<Local>,
+    /// Events within the `[dupes]` window, for the dupe alerts
+    recent: Vec<AdmEvent>,
+    /// When each player was last flagged for duping, so they're flagged once per window
+    flagged: BTreeMap<String, NaiveDateTime>,
+}
+
+impl AdmAlerts {
+    /// None when no alert is configured, or player IDs can't be redacted as `[privacy]` asks
+    pub fn from_config(config: &Config, server_install_dir: &Path, now: DateTime<Local>) -> Option<Self> {
+        if !config.alerts.is_enabled() {
+            return None;
+        }
+
+        let redactor = match Redactor::new(&config.privacy, server_install_dir) {
+            Ok(redactor) => redactor,
+            Err(e) => {
+                println_failure(&format!("Admin log alerts are off: {e:#}"), 0);
+                return None;
+            }
+        };
+
+        Some(Self {
+            config: config.alerts.clone(),
+            dupes: config.dupes.clone(),
+            notifications: config.notifications.clone(),
+            redactor,
+            server_install_dir: server_install_dir.to_path_buf(),
+            follower: AdmFollower::new(server_install_dir, SystemTime::from(now)),
+            next_read: now,
+            recent: Vec::new(),
+            flagged: BTreeMap::new(),
+        })
+    }
+
+    /// Read what the server logged since the last poll and notify about what matches;
+    /// called regularly while it runs
+    pub fn poll(&mut self, now: DateTime<Local>) {
+        if now < self.next_read {
+            return;
+        }
+        self.next_read = now + ChronoDuration::seconds(i64::try_from(self.config.interval_seconds.max(1)).unwrap_or(i64::MAX));
+
+        let events = match self.follower.read_new() {
+            Ok(events) => events,
+            Err(e) => {
+                println_failure(&format!("Failed to read the admin log: {e:#}"), 0);
+                return;
+            }
+        };
+        if events.is_empty() {
+            return;
+        }
+
+        let mut alerts: Vec<String> = events.iter()
+            .filter(|event| self.matches(event))
+            .map(|event| format!(
+                "{} {} ({}) {}",
+                event.time.format("%H:%M:%S"),
+                self.redactor.name(&event.name, &event.id),
+                self.redactor.id(&event.id),
+                event.action
+            ))
+            .collect();
+        if self.config.dupes {
+            alerts.extend(self.find_dupes(events));
+        }
+        if alerts.is_empty() {
+            return;
+        }
+
+        for alert in &alerts {
+            println_failure(&format!("Admin log: {alert}"), 0);
+        }
+        self.notify(&alerts);
+    }
+
+    fn matches(&self, event: &AdmEvent) -> bool {
+        let action = event.action.as_str();
+        (self.config.kills && action.contains("killed by Player"))
+            || (self.config.explosives
+                && action.starts_with("placed")
+                && EXPLOSIVES.iter().any(|item| action.to_lowercase().contains(&item.to_lowercase())))
+            || (action.starts_with("is connected")
+                && self.config.players.iter().any(|player| player.eq_ignore_ascii_case(&event.name) || *player == event.id))
+            || self.config.patterns.iter().any(|pattern| matches_wildcard(&format!("{} {action}", event.name), pattern))
+    }
+
+    /// Players newly over the `[dupes]` thresholds within its window
+    fn find_dupes(&mut self, events: Vec<AdmEvent>) -> Vec<String> {
+        let window = dupes::get_window(&self.dupes);
+        self.recent.extend(events);
+        let Some(newest) = self.recent.last().map(|event| event.time) else {
+            return Vec::new();
+        };
+        self.recent.retain(|event| newest - event.time <= window);
+        self.flagged.retain(|_, flagged| newest - *flagged <= window);
+
+        let mut alerts = Vec::new();
+        for finding in dupes::find_all(&self.recent, &self.dupes) {
+            if self.flagged.contains_key(&finding.id) {
+                continue;
+            }
+            alerts.push(format!(
+                "{} {} ({}) {}",
+                finding.time.format("%H:%M:%S"),
+                self.redactor.name(&finding.player, &finding.id),
+                self.redactor.id(&finding.id),
+                finding.detail
+            ));
+            self.flagged.insert(finding.id, newest);
+        }
+        alerts
+    }
+
+    fn notify(&self, alerts: &[String]) {
+        let notifier = Notifier::new(&self.notifications);
+        if !notifier.is_enabled() {
+            return;
+        }
+
+        let mut body = alerts.iter().take(MAX_ALERT_LINES).cloned().collect::<Vec<_>>().join("\n");
+        if alerts.len() > MAX_ALERT_LINES {
+            let _ = write!(body, "\n…and {} more", alerts.len() - MAX_ALERT_LINES);
+        }
+        let notification = Notification {
+            event: NotificationEvent::AdminLog,
+            title: format!("{}: admin log alert", get_server_name(&self.server_install_dir)),
+            body,
+        };
+        if let Err(e) = notifier.send(&notification) {
+            println_failure(&format!("Failed to post admin log alert: {e}"), 0);
+        }
+    }
+}
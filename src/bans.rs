@@ -0,0 +1,189 @@
+use anyhow::{Context, Result, anyhow};
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::access_list::{AccessList, BAN_FILE, is_steam64_id};
+use crate::cli::BanCommand;
+use crate::crypto;
+use crate::http;
+use crate::rcon::RconClient;
+use crate::ui::status::{println_step, println_success};
+
+/// Who a ban is for
+enum BanTarget {
+    /// A player, banned in `ban.txt`
+    Steam64(String),
+    /// An address, which `ban.txt` can't hold, so it's banned through BattlEye
+    #[allow(clippy::doc_markdown)]
+    Ip(IpAddr),
+}
+
+impl BanTarget {
+    fn parse(target: &str) -> Result<Self> {
+        let target = target.trim();
+        if is_steam64_id(target) {
+            return Ok(Self::Steam64(target.to_string()));
+        }
+        target.parse()
+            .map(Self::Ip)
+            .map_err(|_| anyhow!("'{target}' is neither a Steam64 ID nor an IP address"))
+    }
+
+    /// What BattlEye bans by: the player's GUID, or the address
+    #[allow(clippy::doc_markdown)]
+    fn get_battleye_id(&self) -> Result<String> {
+        match self {
+            Self::Steam64(id) => get_battleye_guid(id),
+            Self::Ip(ip) => Ok(ip.to_string()),
+        }
+    }
+}
+
+/// BattlEye's GUID for a Steam64 ID: MD5 of "BE" followed by the ID as 8 little-endian bytes
+#[allow(clippy::doc_markdown)]
+fn get_battleye_guid(steam64_id: &str) -> Result<String> {
+    let id: u64 = steam64_id.parse()
+        .context(format!("'{steam64_id}' is not a Steam64 ID"))?;
+    let mut data = b"BE".to_vec();
+    data.extend_from_slice(&id.to_le_bytes());
+    let mut guid = String::with_capacity(32);
+    for byte in crypto::md5(&data)? {
+        let _ = write!(guid, "{byte:02x}");
+    }
+    Ok(guid)
+}
+
+fn connect(server_install_dir: &Path) -> Result<RconClient> {
+    RconClient::connect_to_server(server_install_dir)
+        .context("Failed to reach the server over RCon, is it running?")
+}
+
+fn add(server_install_dir: &Path, target: &str, reason: Option<&str>, rcon: bool) -> Result<()> {
+    let target = BanTarget::parse(target)?;
+
+    if let BanTarget::Steam64(id) = &target {
+        let path = server_install_dir.join(BAN_FILE);
+        let mut bans = AccessList::load(&path)?;
+        let line = reason.map_or_else(|| id.clone(), |reason| format!("{id} // {reason}"));
+        let updated = bans.entries.insert(id.clone(), line).is_some();
+        bans.save(&path)?;
+        let verb = if updated { "Updated the ban of" } else { "Banned" };
+        println_success(&format!("{verb} {id} in {BAN_FILE}"), 0);
+        if !rcon {
+            return Ok(());
+        }
+    }
+
+    // 0 minutes is permanent
+    let battleye_id = target.get_battleye_id()?;
+    let command = format!("addBan {battleye_id} 0 {}", reason.unwrap_or_default());
+    connect(server_install_dir)?.command(command.trim_end())?;
+    println_success(&format!("Banned {battleye_id} through BattlEye"), 0);
+    Ok(())
+}
+
+fn remove(server_install_dir: &Path, target: &str, rcon: bool) -> Result<()> {
+    let target = BanTarget::parse(target)?;
+
+    if let BanTarget::Steam64(id) = &target {
+        let path = server_install_dir.join(BAN_FILE);
+        let mut bans = AccessList::load(&path)?;
+        if bans.entries.remove(id).is_some() {
+            bans.save(&path)?;
+            println_success(&format!("Lifted the ban of {id} in {BAN_FILE}"), 0);
+        } else if !rcon {
+            return Err(anyhow!("{id} isn't banned in {BAN_FILE}"));
+        }
+        if !rcon {
+            return Ok(());
+        }
+    }
+
+    let battleye_id = target.get_battleye_id()?;
+    let mut rcon = connect(server_install_dir)?;
+    let number = find_battleye_ban(&rcon.command("bans")?, &battleye_id)
+        .ok_or_else(|| anyhow!("{battleye_id} isn't banned through BattlEye"))?;
+    rcon.command(&format!("removeBan {number}"))?;
+    // Unlike `addBan`, `removeBan` only changes the bans in memory
+    rcon.command("writeBans")?;
+    println_success(&format!("Lifted the ban of {battleye_id} through BattlEye"), 0);
+    Ok(())
+}
+
+/// The number of `battleye_id`'s row in the `bans` output, `<#> <GUID or IP> <minutes left> <reason>`
+#[allow(clippy::doc_markdown)]
+fn find_battleye_ban(bans: &str, battleye_id: &str) -> Option<u32> {
+    bans.lines().find_map(|line| {
+        let mut columns = line.split_whitespace();
+        let number = columns.next()?.parse().ok()?;
+        columns.next()?.eq_ignore_ascii_case(battleye_id).then_some(number)
+    })
+}
+
+fn list(server_install_dir: &Path, rcon: bool) -> Result<()> {
+    let bans = AccessList::load(&server_install_dir.join(BAN_FILE))?;
+    if bans.entries.is_empty() {
+        println_step(&format!("No bans in {BAN_FILE}"), 0);
+    }
+    for line in bans.entries.values() {
+        println_step(line, 0);
+    }
+
+    if rcon {
+        println_step("BattlEye:", 0);
+        for line in connect(server_install_dir)?.command("bans")?.lines().filter(|line| !line.trim().is_empty()) {
+            println_step(line, 1);
+        }
+    }
+    Ok(())
+}
+
+/// Add the Steam64 IDs of a shared list that aren't banned yet, noting where they came from
+fn import(server_install_dir: &Path, url: &str, dry_run: bool) -> Result<()> {
+    println_step(&format!("Fetching ban list: {url}"), 0);
+    let shared = AccessList::parse(&http::get_text(url).context("Failed to fetch the ban list")?);
+
+    let path = server_install_dir.join(BAN_FILE);
+    let mut bans = AccessList::load(&path)?;
+    let mut added = 0;
+    let mut skipped = 0;
+    for (id, line) in shared.entries {
+        if !is_steam64_id(&id) {
+            skipped += 1;
+            continue;
+        }
+        if bans.entries.contains_key(&id) {
+            continue;
+        }
+
+        let line = if line.contains("//") { line } else { format!("{id} // imported from {url}") };
+        bans.entries.insert(id, line);
+        added += 1;
+    }
+
+    if skipped > 0 {
+        println_step(&format!("Skipped {skipped} line(s) that don't start with a Steam64 ID"), 1);
+    }
+    if dry_run {
+        println_success(&format!("Would ban {added} more player(s)"), 0);
+        return Ok(());
+    }
+    if added > 0 {
+        bans.save(&path)?;
+    }
+    println_success(&format!("Banned {added} more player(s) in {BAN_FILE}"), 0);
+    Ok(())
+}
+
+/// Entry point for `dzsm ban ...`
+pub fn run(command: &BanCommand, server_install_dir: &str) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+
+    match command {
+        BanCommand::Add { target, reason, rcon } => add(server_install_dir, target, reason.as_deref(), *rcon),
+        BanCommand::Remove { target, rcon } => remove(server_install_dir, target, *rcon),
+        BanCommand::List { rcon } => list(server_install_dir, *rcon),
+        BanCommand::Import { url, dry_run } => import(server_install_dir, url, *dry_run),
+    }
+}
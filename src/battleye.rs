@@ -0,0 +1,414 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::BattlEyeCommand;
+use crate::collection_sync::find_installed_mod_dir;
+use crate::config::{BattlEyeConfig, Config, ServerConfig};
+use crate::crypto;
+use crate::server::DEFAULT_GAME_PORT;
+use crate::state::state_dir;
+use crate::storage::list_files;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const BATTLEYE_DIR: &str = "battleye";
+const BE_SERVER_CONFIG: &str = "BEServer_x64.cfg";
+/// Filter files as they were before any mod's filters were merged in, `.dzsm/battleye/originals`
+const ORIGINALS_DIR: &str = "battleye/originals";
+/// Filter files as DZSM last wrote them, `.dzsm/battleye/merged`, to tell hand edits apart
+const MERGED_DIR: &str = "battleye/merged";
+/// RCON goes on the game port + 3 unless configured, clear of the ports the game itself uses
+const RCON_PORT_OFFSET: u16 = 3;
+const RCON_PASSWORD_LENGTH: usize = 16;
+
+/// BattlEye's `BEServer_x64.cfg`: one `Key value` pair per line
+#[allow(clippy::doc_markdown)]
+pub struct BeServerConfig {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl BeServerConfig {
+    /// Get the BattlEye directory of a server install
+    #[allow(clippy::doc_markdown)]
+    pub fn get_battleye_dir(server_install_dir: &Path) -> PathBuf {
+        server_install_dir.join(BATTLEYE_DIR)
+    }
+
+    /// Load the server's BattlEye config, starting empty if it doesn't exist yet
+    #[allow(clippy::doc_markdown)]
+    pub fn load(server_install_dir: &Path) -> Result<Self> {
+        let path = Self::get_battleye_dir(server_install_dir).join(BE_SERVER_CONFIG);
+
+        let lines = if path.exists() {
+            fs::read_to_string(&path)
+                .context(format!("Failed to read {}", path.display()))?
+                .lines()
+                .map(str::to_string)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self { path, lines })
+    }
+
+    /// Get the value of a setting such as `RConPort`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines.iter().find_map(|line| {
+            let (line_key, value) = line.trim().split_once(char::is_whitespace)?;
+            line_key.eq_ignore_ascii_case(key).then(|| value.trim())
+        })
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        let new_line = format!("{key} {value}");
+
+        let existing = self.lines.iter_mut().find(|line| {
+            line.split_whitespace()
+                .next()
+                .is_some_and(|line_key| line_key.eq_ignore_ascii_case(key))
+        });
+
+        match existing {
+            Some(line) => *line = new_line,
+            None => self.lines.push(new_line),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create BattlEye directory")?;
+        }
+
+        let mut content = self.lines.join("\n");
+        content.push('\n');
+
+        fs::write(&self.path, content)
+            .context(format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// One filter line, `<level> <pattern> !="exception" ...`
+#[derive(Clone)]
+struct FilterRule {
+    level: String,
+    pattern: String,
+    exceptions: Vec<String>,
+}
+
+/// A filter file's lines, rules parsed and everything else kept as written
+enum FilterLine {
+    Rule(FilterRule),
+    Other(String),
+}
+
+/// Two sources that want the same filter pattern at different restriction levels
+pub struct FilterConflict {
+    file: String,
+    pattern: String,
+    kept: (String, String),
+    ignored: (String, String),
+}
+
+/// Split a filter line on whitespace outside of quotes
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        if c.is_whitespace() && !quoted {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+        } else {
+            token.push(c);
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+fn parse_filter(text: &str) -> Vec<FilterLine> {
+    text.lines()
+        .map(|line| {
+            let tokens = tokenize(line);
+            match tokens.split_first() {
+                Some((level, rest)) if level.parse::<u32>().is_ok() && !rest.is_empty() && !rest[0].starts_with('!') => {
+                    FilterLine::Rule(FilterRule {
+                        level: level.clone(),
+                        pattern: rest[0].clone(),
+                        exceptions: rest[1..].to_vec(),
+                    })
+                }
+                _ => FilterLine::Other(line.to_string()),
+            }
+        })
+        .collect()
+}
+
+fn render_filter(lines: &[FilterLine]) -> String {
+    let mut text: String = lines.iter()
+        .map(|line| match line {
+            FilterLine::Rule(rule) => {
+                let mut tokens = vec![rule.level.as_str(), rule.pattern.as_str()];
+                tokens.extend(rule.exceptions.iter().map(String::as_str));
+                tokens.join(" ")
+            }
+            FilterLine::Other(line) => line.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    text.push('\n');
+    text
+}
+
+/// Add a mod's rules to a filter: new patterns are appended, the exceptions of known ones combined
+fn merge_filter(lines: &mut Vec<FilterLine>, sources: &mut BTreeMap<String, String>, mod_text: &str, source: &str, file: &str) -> Vec<FilterConflict> {
+    let mut conflicts = Vec::new();
+    for mod_line in parse_filter(mod_text) {
+        let FilterLine::Rule(mod_rule) = mod_line else {
+            continue;
+        };
+
+        let existing = lines.iter_mut().find_map(|line| match line {
+            FilterLine::Rule(rule) if rule.pattern == mod_rule.pattern => Some(rule),
+            _ => None,
+        });
+        let Some(rule) = existing else {
+            sources.insert(mod_rule.pattern.clone(), source.to_string());
+            lines.push(FilterLine::Rule(mod_rule));
+            continue;
+        };
+
+        if rule.level != mod_rule.level {
+            let kept_by = sources.get(&rule.pattern).cloned().unwrap_or_else(|| format!("{BATTLEYE_DIR}/{file}"));
+            conflicts.push(FilterConflict {
+                file: file.to_string(),
+                pattern: rule.pattern.clone(),
+                kept: (kept_by, rule.level.clone()),
+                ignored: (source.to_string(), mod_rule.level.clone()),
+            });
+        }
+        for exception in mod_rule.exceptions {
+            if !rule.exceptions.contains(&exception) {
+                rule.exceptions.push(exception);
+            }
+        }
+    }
+    conflicts
+}
+
+/// Filter files a mod ships in a `battleye` folder, by lowercase file name
+fn find_mod_filters(mod_dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let mut filters = BTreeMap::new();
+    for path in list_files(mod_dir)? {
+        let in_battleye_dir = path.parent()
+            .and_then(Path::file_name)
+            .is_some_and(|dir| dir.to_string_lossy().eq_ignore_ascii_case(BATTLEYE_DIR));
+        let is_filter = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("txt"));
+        if in_battleye_dir && is_filter
+            && let Some(name) = path.file_name()
+        {
+            filters.insert(name.to_string_lossy().to_lowercase(), path);
+        }
+    }
+    Ok(filters)
+}
+
+/// Rebuild the filter files from their originals plus every `filter_mods` mod's filters,
+/// returning how many files changed and any conflicting rules. A file edited by hand since
+/// DZSM last wrote it is left alone.
+#[allow(clippy::doc_markdown)]
+pub fn apply_filters(config: &BattlEyeConfig, server_install_dir: &Path, dry_run: bool) -> Result<(usize, Vec<FilterConflict>)> {
+    let battleye_dir = BeServerConfig::get_battleye_dir(server_install_dir);
+    let originals_dir = state_dir(server_install_dir).join(ORIGINALS_DIR);
+    let merged_dir = state_dir(server_install_dir).join(MERGED_DIR);
+
+    // Every file a mod adds to, plus any merged before, so removing a mod takes its rules out again
+    let mut mod_filters: BTreeMap<String, Vec<(String, PathBuf)>> = BTreeMap::new();
+    if let Ok(entries) = fs::read_dir(&originals_dir) {
+        for entry in entries.flatten() {
+            mod_filters.entry(entry.file_name().to_string_lossy().to_lowercase()).or_default();
+        }
+    }
+    for target in &config.filter_mods {
+        let Some(mod_dir) = find_installed_mod_dir(server_install_dir, target) else {
+            println_failure(&format!("Mod {target} in `battleye.filter_mods` is not installed, skipping its filters"), 1);
+            continue;
+        };
+        for (file, path) in find_mod_filters(&mod_dir)? {
+            mod_filters.entry(file).or_default().push((target.clone(), path));
+        }
+    }
+
+    let mut changed = 0;
+    let mut conflicts = Vec::new();
+    for (file, sources) in &mod_filters {
+        let path = battleye_dir.join(file);
+        let original_path = originals_dir.join(file);
+        let merged_path = merged_dir.join(file);
+        let current = fs::read_to_string(&path).ok();
+        let last_merged = fs::read_to_string(&merged_path).ok();
+        if let (Some(current), Some(last_merged)) = (&current, &last_merged) && current != last_merged {
+            println_failure(&format!(
+                "{file} was edited since DZSM last merged mods' filters into it, leaving it alone. To merge them again, make the edits in {} and delete {}",
+                original_path.display(),
+                path.display()
+            ), 1);
+            continue;
+        }
+        let original = match fs::read_to_string(&original_path) {
+            Ok(original) => Some(original),
+            Err(_) => current.clone(),
+        };
+
+        let mut lines = original.as_deref().map(parse_filter).unwrap_or_default();
+        let mut rule_sources = BTreeMap::new();
+        for (source, mod_path) in sources {
+            let mod_text = fs::read_to_string(mod_path)
+                .context(format!("Failed to read {}", mod_path.display()))?;
+            conflicts.extend(merge_filter(&mut lines, &mut rule_sources, &mod_text, source, file));
+        }
+
+        // A filter file only exists when there's something in it, BattlEye enables filters by file
+        let merged = (!lines.is_empty()).then(|| render_filter(&lines));
+        if merged == current {
+            // Files merged before DZSM kept what it wrote
+            if let Some(merged) = &merged && !dry_run && last_merged.as_ref() != Some(merged) {
+                save_merged(&merged_dir, file, merged)?;
+            }
+            continue;
+        }
+        changed += 1;
+        if dry_run {
+            continue;
+        }
+
+        if !original_path.exists() {
+            fs::create_dir_all(&originals_dir)
+                .context(format!("Failed to create {}", originals_dir.display()))?;
+            fs::write(&original_path, original.as_deref().unwrap_or_default())
+                .context(format!("Failed to write {}", original_path.display()))?;
+        }
+        if let Some(merged) = merged {
+            fs::create_dir_all(&battleye_dir)
+                .context("Failed to create BattlEye directory")?;
+            fs::write(&path, &merged)
+                .context(format!("Failed to write {}", path.display()))?;
+            save_merged(&merged_dir, file, &merged)?;
+        } else {
+            fs::remove_file(&path)
+                .context(format!("Failed to remove {}", path.display()))?;
+            if merged_path.exists() {
+                fs::remove_file(&merged_path)
+                    .context(format!("Failed to remove {}", merged_path.display()))?;
+            }
+        }
+    }
+
+    Ok((changed, conflicts))
+}
+
+/// Keep what DZSM wrote to a filter file, to notice it being edited by hand later
+#[allow(clippy::doc_markdown)]
+fn save_merged(merged_dir: &Path, file: &str, merged: &str) -> Result<()> {
+    fs::create_dir_all(merged_dir)
+        .context(format!("Failed to create {}", merged_dir.display()))?;
+    let path = merged_dir.join(file);
+    fs::write(&path, merged)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+/// Give `BEServer_x64.cfg` an RCON password and port if it lacks them, so RCON shutdowns and
+/// announcements work. Returns what was (or on a dry run would be) set.
+#[allow(clippy::doc_markdown)]
+pub fn ensure_rcon(config: &BattlEyeConfig, server: &ServerConfig, server_install_dir: &Path, dry_run: bool) -> Result<Vec<&'static str>> {
+    let mut be_config = BeServerConfig::load(server_install_dir)?;
+    let mut set = Vec::new();
+
+    if be_config.get("RConPassword").is_none_or(str::is_empty) {
+        be_config.set("RConPassword", &crypto::random_alphanumeric(RCON_PASSWORD_LENGTH)?);
+        set.push("RConPassword");
+    }
+
+    let port = config.rcon_port
+        .unwrap_or_else(|| server.port.unwrap_or(DEFAULT_GAME_PORT).saturating_add(RCON_PORT_OFFSET));
+    let current_port = be_config.get("RConPort").and_then(|port| port.parse::<u16>().ok());
+    // A port set by hand stays unless the config asks for another
+    if current_port.is_none() || (config.rcon_port.is_some() && current_port != Some(port)) {
+        be_config.set("RConPort", &port.to_string());
+        set.push("RConPort");
+    }
+
+    if !set.is_empty() && !dry_run {
+        be_config.save()?;
+    }
+    Ok(set)
+}
+
+fn print_conflicts(conflicts: &[FilterConflict]) {
+    for conflict in conflicts {
+        println_failure(&format!(
+            "{}: {} wants {} at level {}, kept level {} from {}",
+            conflict.file,
+            conflict.ignored.0,
+            conflict.pattern,
+            conflict.ignored.1,
+            conflict.kept.1,
+            conflict.kept.0
+        ), 1);
+    }
+}
+
+/// Check the RCON settings and merge mods' filters before a start
+pub fn apply_before_launch(config: &BattlEyeConfig, server: &ServerConfig, server_install_dir: &Path) -> Result<()> {
+    let set = ensure_rcon(config, server, server_install_dir, false)?;
+    if !set.is_empty() {
+        println_success(&format!("Set {} in {BE_SERVER_CONFIG}", set.join(" and ")), 1);
+    }
+
+    let (changed, conflicts) = apply_filters(config, server_install_dir, false)?;
+    print_conflicts(&conflicts);
+    if changed > 0 {
+        println_success(&format!("Updated {changed} BattlEye filter file(s)"), 1);
+    }
+    Ok(())
+}
+
+/// Entry point for `dzsm battleye ...`
+pub fn run(command: &BattlEyeCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+
+    match command {
+        BattlEyeCommand::Apply { dry_run } => {
+            println_step("Checking BattlEye settings and filters...", 0);
+            let set = ensure_rcon(&config.battleye, &config.server, server_install_dir, *dry_run)?;
+            if !set.is_empty() {
+                let verb = if *dry_run { "Would set" } else { "Set" };
+                println_success(&format!("{verb} {} in {BE_SERVER_CONFIG}", set.join(" and ")), 1);
+            }
+
+            let (changed, conflicts) = apply_filters(&config.battleye, server_install_dir, *dry_run)?;
+            print_conflicts(&conflicts);
+            if *dry_run {
+                println_step(&format!("Dry run, {changed} filter file(s) would change"), 0);
+            } else {
+                println_success(&format!("{changed} filter file(s) updated"), 0);
+            }
+
+            if conflicts.is_empty() {
+                Ok(())
+            } else {
+                Err(anyhow!("{} conflicting filter rule(s) between mods, check the levels they need", conflicts.len()))
+            }
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs::{self, File, OpenOptions};
+use std::hint::black_box;
+use std::io::{Read, Write};
+use std::os::windows::fs::OpenOptionsExt;
+use std::path::{Component, Path};
+use std::thread;
+use std::time::{Duration, Instant};
+use windows_sys::Win32::Storage::FileSystem::FILE_FLAG_NO_BUFFERING;
+
+use crate::cli::BenchCommand;
+use crate::collection_sync::get_installed_workshop_mods;
+use crate::config::Config;
+use crate::mod_validation::{FNV_OFFSET, fnv1a};
+use crate::state::state_dir;
+use crate::steamcmd::SteamCmdManager;
+use crate::storage::{dir_size, format_size};
+use crate::ui::status::{println_failure, println_step, println_success};
+
+/// Scratch install the timed downloads go to, deleted after each one
+const BENCH_DIR: &str = "bench";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// A cell has to beat Steam's own pick by this much to be worth pinning
+const MIN_IMPROVEMENT: f64 = 1.1;
+
+/// Scratch file for the disk tests, written next to the data being measured
+const DISK_TEST_FILE: &str = "dzsm_bench.tmp";
+const DISK_TEST_SIZE: usize = 256 * 1024 * 1024;
+const DISK_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+/// Unbuffered reads need sector-aligned buffers, 4 KiB covers every modern disk
+const SECTOR_SIZE: usize = 4096;
+/// Small synced writes, like the server's persistence saves
+const LATENCY_WRITES: u32 = 64;
+const CPU_TEST_DURATION: Duration = Duration::from_secs(3);
+const CPU_BUFFER_SIZE: usize = 1024 * 1024;
+
+const MEGABYTE: f64 = 1024.0 * 1024.0;
+/// Rough floors for a `DayZ` server; the ones that depend on the mods scale with their total size
+const MIN_DISK_SPEED: f64 = 100.0 * MEGABYTE;
+const MAX_WRITE_LATENCY_MS: f64 = 5.0;
+/// Reading every mod at startup shouldn't take longer than this
+const MAX_MOD_LOAD_SECONDS: f64 = 120.0;
+/// `FNV-1a` takes a multiply and an xor per byte, each waiting on the last, which comes to about
+/// 4 cycles a byte on x86 cores of the last decade; its throughput follows the core's clock
+const CPU_CYCLES_PER_BYTE: f64 = 4.0;
+/// Single core clock in GHz, as the hashing throughput works out to; the server simulation
+/// runs on one core, so many cores don't make up for a slow one
+const MIN_CPU_GHZ: f64 = 2.5;
+/// Script-heavy mod lists need more headroom on that core
+const MIN_CPU_GHZ_MODDED: f64 = 3.0;
+const MODDED_MOD_COUNT: usize = 20;
+const MIN_DOWNLOAD_SPEED: f64 = 5.0 * MEGABYTE;
+/// Downloading every mod again, after a wipe or a move, shouldn't take longer than this
+const MAX_MOD_DOWNLOAD_SECONDS: f64 = 30.0 * 60.0;
+
+/// Download into a scratch directory for `duration` and return the bytes per second
+fn time_download(steamcmd: &SteamCmdManager, config: &Config, bench_dir: &Path, duration: Duration) -> Result<f64> {
+    if bench_dir.exists() {
+        fs::remove_dir_all(bench_dir)
+            .context(format!("Failed to remove {}", bench_dir.display()))?;
+    }
+
+    let started = Instant::now();
+    let (mut child, _login_script) = steamcmd.spawn_app_download(bench_dir, config.steam.get_server_username(&config.server.username), config.server.branch.server_app_id())?;
+    while started.elapsed() < duration {
+        if child.try_wait().context("Failed to check on SteamCMD")?.is_some() {
+            break;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    // Already exited if it finished or failed early, both fine to ignore here
+    let _ = child.kill();
+    let _ = child.wait();
+
+    let downloaded = if bench_dir.exists() { dir_size(bench_dir)? } else { 0 };
+    fs::remove_dir_all(bench_dir).ok();
+
+    #[allow(clippy::cast_precision_loss)]
+    Ok(downloaded as f64 / elapsed.max(1.0))
+}
+
+fn format_speed(bytes_per_second: f64) -> String {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let bytes = bytes_per_second as u64;
+    format!("{}/s", format_size(bytes))
+}
+
+/// Time a server download from Steam's own pick and from each cell, then suggest the fastest
+fn download(config: &Config, server_install_dir: &Path, cells: &[u32], seconds: u64, offline: bool) -> Result<()> {
+    if offline {
+        return Err(anyhow!("Can't benchmark downloads in offline mode"));
+    }
+
+    let steamcmd = SteamCmdManager::new(&config.server.steamcmd_dir, offline)?.with_options(config)?;
+    let bench_dir = state_dir(server_install_dir).join(BENCH_DIR);
+    let duration = Duration::from_secs(seconds.max(1));
+    // The benchmark switches cells, the user's own settings go back afterwards
+    let original_config = steamcmd.read_config_vdf();
+
+    let candidates: Vec<Option<u32>> = std::iter::once(None).chain(cells.iter().copied().map(Some)).collect();
+    println_step(&format!(
+        "Downloading the DayZ server for {seconds} seconds from each of {} content server cell(s)...",
+        candidates.len()
+    ), 0);
+
+    let mut results = Vec::new();
+    let mut failure = None;
+    for cell in &candidates {
+        let label = cell.map_or_else(|| "Steam's pick".to_string(), |cell| format!("cell {cell}"));
+        let speed = steamcmd.set_cell_override(*cell)
+            .and_then(|()| time_download(&steamcmd, config, &bench_dir, duration));
+        match speed {
+            Ok(speed) => {
+                println_step(&format!("{label}: {}", format_speed(speed)), 1);
+                results.push((*cell, speed));
+            }
+            Err(e) => {
+                failure = Some(e);
+                break;
+            }
+        }
+    }
+
+    steamcmd.restore_config_vdf(original_config.as_deref())?;
+    if let Some(e) = failure {
+        return Err(e);
+    }
+
+    let baseline = results.first().map_or(0.0, |(_, speed)| *speed);
+    let Some((best_cell, best_speed)) = results.iter().copied().max_by(|a, b| a.1.total_cmp(&b.1)) else {
+        return Ok(());
+    };
+    if best_speed <= 0.0 {
+        println_failure("Nothing was downloaded, is the Steam login cached? Log in once with `steamcmd +login <username>`", 0);
+        return Ok(());
+    }
+
+    match best_cell {
+        Some(cell) if best_speed > baseline * MIN_IMPROVEMENT => println_success(&format!(
+            "Cell {cell} is fastest at {}, set `cell_id = {cell}` under [server] in config.toml",
+            format_speed(best_speed)
+        ), 0),
+        _ => println_success(&format!(
+            "Steam's own pick is as fast as any at {}, leave `server.cell_id` unset",
+            format_speed(baseline)
+        ), 0),
+    }
+    Ok(())
+}
+
+/// Sequential speeds and small write latency of the volume holding `dir`
+struct DiskResult {
+    write: f64,
+    read: f64,
+    latency_ms: f64,
+}
+
+/// Write, read back without the file cache, then time small synced writes, all in a scratch file
+fn bench_disk(dir: &Path) -> Result<DiskResult> {
+    fs::create_dir_all(dir)
+        .context(format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(DISK_TEST_FILE);
+    let result = time_disk(&path);
+    fs::remove_file(&path).ok();
+    result
+}
+
+fn time_disk(path: &Path) -> Result<DiskResult> {
+    let mut buffer = vec![0u8; DISK_CHUNK_SIZE + SECTOR_SIZE];
+    let offset = buffer.as_ptr().align_offset(SECTOR_SIZE);
+    let chunk = &mut buffer[offset..offset + DISK_CHUNK_SIZE];
+    // Incompressible, so compressing filesystems don't flatter the numbers
+    let mut seed = FNV_OFFSET;
+    for byte in chunk.iter_mut() {
+        seed = fnv1a(seed, &[0]);
+        *byte = seed.to_le_bytes()[7];
+    }
+
+    let started = Instant::now();
+    let mut file = File::create(path)
+        .context(format!("Failed to create {}", path.display()))?;
+    for _ in 0..DISK_TEST_SIZE / DISK_CHUNK_SIZE {
+        file.write_all(chunk)
+            .context(format!("Failed to write {}", path.display()))?;
+    }
+    file.sync_all()
+        .context(format!("Failed to flush {}", path.display()))?;
+    let write_seconds = started.elapsed().as_secs_f64();
+    drop(file);
+
+    let started = Instant::now();
+    let mut file = OpenOptions::new()
+        .read(true)
+        .custom_flags(FILE_FLAG_NO_BUFFERING)
+        .open(path)
+        .context(format!("Failed to open {}", path.display()))?;
+    while file.read(chunk).context(format!("Failed to read {}", path.display()))? > 0 {}
+    let read_seconds = started.elapsed().as_secs_f64();
+    drop(file);
+
+    let started = Instant::now();
+    let mut file = File::create(path)
+        .context(format!("Failed to create {}", path.display()))?;
+    for _ in 0..LATENCY_WRITES {
+        file.write_all(&chunk[..SECTOR_SIZE])
+            .context(format!("Failed to write {}", path.display()))?;
+        file.sync_data()
+            .context(format!("Failed to flush {}", path.display()))?;
+    }
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0 / f64::from(LATENCY_WRITES);
+
+    #[allow(clippy::cast_precision_loss)]
+    let size = DISK_TEST_SIZE as f64;
+    Ok(DiskResult {
+        write: size / write_seconds.max(f64::EPSILON),
+        read: size / read_seconds.max(f64::EPSILON),
+        latency_ms,
+    })
+}
+
+/// Bytes per second one core hashes, the same work the nightly mod check does
+fn bench_cpu() -> f64 {
+    let buffer = vec![0x5a; CPU_BUFFER_SIZE];
+    let mut hash = FNV_OFFSET;
+    let mut hashed = 0;
+
+    let started = Instant::now();
+    while started.elapsed() < CPU_TEST_DURATION {
+        hash = fnv1a(black_box(hash), black_box(&buffer));
+        hashed += CPU_BUFFER_SIZE;
+    }
+    black_box(hash);
+
+    #[allow(clippy::cast_precision_loss)]
+    let hashed = hashed as f64;
+    hashed / started.elapsed().as_secs_f64()
+}
+
+/// The drive a path is on, to avoid measuring the same volume twice
+fn get_volume(path: &Path) -> Option<String> {
+    match std::path::absolute(path).ok()?.components().next()? {
+        Component::Prefix(prefix) => Some(prefix.as_os_str().to_string_lossy().to_uppercase()),
+        _ => None,
+    }
+}
+
+/// Print one measurement against its limit and return whether it passed
+fn check(label: &str, value: &str, passed: bool, limit: &str) -> bool {
+    if passed {
+        println_success(&format!("{label}: {value}"), 1);
+    } else {
+        println_failure(&format!("{label}: {value}, {limit}"), 1);
+    }
+    passed
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn check_disk(volume: &str, disk: &DiskResult, mods_size: u64) -> bool {
+    let load_seconds = mods_size as f64 / disk.read;
+    let mut passed = check(
+        &format!("{volume} sequential write"),
+        &format_speed(disk.write),
+        disk.write >= MIN_DISK_SPEED,
+        &format!("recommended at least {}", format_speed(MIN_DISK_SPEED)),
+    );
+    passed &= check(
+        &format!("{volume} sequential read"),
+        &format!("{}, {load_seconds:.0}s to read the mods", format_speed(disk.read)),
+        disk.read >= MIN_DISK_SPEED && load_seconds <= MAX_MOD_LOAD_SECONDS,
+        &format!("recommended at least {} and under {MAX_MOD_LOAD_SECONDS:.0}s", format_speed(MIN_DISK_SPEED)),
+    );
+    passed &= check(
+        &format!("{volume} synced 4 KiB write"),
+        &format!("{:.2} ms", disk.latency_ms),
+        disk.latency_ms <= MAX_WRITE_LATENCY_MS,
+        &format!("recommended under {MAX_WRITE_LATENCY_MS:.0} ms, saves will stall the server (is this a hard drive?)"),
+    );
+    passed
+}
+
+/// Measure the server and workshop volumes, one core, and the download speed from Steam,
+/// and say whether they're enough for the installed mods
+fn host(config: &Config, server_install_dir: &Path, seconds: u64, offline: bool) -> Result<()> {
+    let mods = get_installed_workshop_mods(server_install_dir)?;
+    let mut mods_size = 0;
+    for mod_entry in &mods {
+        let mod_dir = server_install_dir.join(format!("@{}", mod_entry.name));
+        mods_size += dir_size(&mod_dir).unwrap_or(0);
+    }
+    println_step(&format!("Benchmarking this host for a server with {} mod(s), {} in total...", mods.len(), format_size(mods_size)), 0);
+
+    let mut passed = true;
+    let server_dir = state_dir(server_install_dir);
+    let steamcmd_dir = Path::new(&config.server.steamcmd_dir);
+    let server_volume = get_volume(&server_dir);
+    let mut volumes = vec![("Server disk", server_dir.clone())];
+    if server_volume.is_none() || get_volume(steamcmd_dir) != server_volume {
+        volumes.push(("Workshop disk", steamcmd_dir.to_path_buf()));
+    }
+    for (volume, dir) in &volumes {
+        println_step(&format!("Testing {}...", dir.display()), 1);
+        let disk = bench_disk(dir)?;
+        passed &= check_disk(volume, &disk, mods_size);
+    }
+
+    println_step("Testing single core speed...", 1);
+    let cpu = bench_cpu();
+    let cpu_ghz = cpu * CPU_CYCLES_PER_BYTE / 1e9;
+    let min_cpu_ghz = if mods.len() >= MODDED_MOD_COUNT { MIN_CPU_GHZ_MODDED } else { MIN_CPU_GHZ };
+    passed &= check(
+        "Single core hashing",
+        &format!("{}, like a {cpu_ghz:.1} GHz core", format_speed(cpu)),
+        cpu_ghz >= min_cpu_ghz,
+        &format!("recommended at least a {min_cpu_ghz:.1} GHz core for {} mod(s)", mods.len()),
+    );
+
+    if offline {
+        println_step("Offline, skipping the download test", 1);
+    } else {
+        println_step(&format!("Downloading the DayZ server for {seconds} seconds..."), 1);
+        let steamcmd = SteamCmdManager::new(&config.server.steamcmd_dir, offline)?.with_options(config)?;
+        let speed = time_download(&steamcmd, config, &server_dir.join(BENCH_DIR), Duration::from_secs(seconds.max(1)))?;
+        #[allow(clippy::cast_precision_loss)]
+        let download_seconds = mods_size as f64 / speed.max(1.0);
+        passed &= check(
+            "Download from Steam",
+            &format!("{}, {:.0} min to download every mod", format_speed(speed), download_seconds / 60.0),
+            speed >= MIN_DOWNLOAD_SPEED && download_seconds <= MAX_MOD_DOWNLOAD_SECONDS,
+            &format!(
+                "recommended at least {} and under {:.0} min (is the Steam login cached?)",
+                format_speed(MIN_DOWNLOAD_SPEED),
+                MAX_MOD_DOWNLOAD_SECONDS / 60.0
+            ),
+        );
+    }
+
+    if passed {
+        println_success("This host meets the recommendations for the installed mods", 0);
+    } else {
+        println_failure("This host falls short of the recommendations above, expect slow starts, lag, or save stalls", 0);
+    }
+    Ok(())
+}
+
+/// Entry point for `dzsm bench ...`
+pub fn run(command: &BenchCommand, config: &Config, server_install_dir: &str, offline: bool) -> Result<()> {
+    match command {
+        BenchCommand::Download { cells, seconds } => download(config, Path::new(server_install_dir), cells, *seconds, offline),
+        BenchCommand::Host { seconds } => host(config, Path::new(server_install_dir), *seconds, offline),
+    }
+}
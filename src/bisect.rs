@@ -0,0 +1,305 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::a2s;
+use crate::cli::{BisectCommand, CliArgs};
+use crate::collection_fetcher::CollectionFetcher;
+use crate::config::Config;
+use crate::config::mod_entry::ModEntry;
+use crate::interrupt;
+use crate::mod_list::set_mods_list;
+use crate::processes;
+use crate::server::{SERVER_EXE, ServerManager};
+use crate::state::state_dir;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const STATE_FILE: &str = "bisect.json";
+const DISABLED_KEY: &str = "disabled";
+/// How often an automatic step checks whether the server has loaded or crashed
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// A server that hasn't loaded its mission by then counts as broken, some mods hang instead of crashing
+const LOAD_TIMEOUT: Duration = Duration::from_mins(15);
+
+/// A bisect in progress: one of `suspects` causes the problem, the first `testing` of them are
+/// enabled for the current step and the rest are disabled
+#[derive(Serialize, Deserialize)]
+struct BisectState {
+    /// `mods.disabled` from before the bisect, put back when it ends
+    disabled_before: Vec<String>,
+    suspects: Vec<Suspect>,
+    testing: usize,
+    step: u32,
+    #[serde(default)]
+    outcomes: Vec<StepOutcome>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Suspect {
+    id: u64,
+    name: String,
+}
+
+/// How a finished step went, for the summary at the end
+#[derive(Serialize, Deserialize)]
+struct StepOutcome {
+    step: u32,
+    enabled: usize,
+    bad: bool,
+}
+
+/// How a server run of an automatic step ended
+enum RunOutcome {
+    Crashed(Option<i32>),
+    NeverLoaded,
+    StayedUp,
+    Interrupted,
+}
+
+impl BisectState {
+    fn load(server_install_dir: &Path) -> Option<Self> {
+        fs::read_to_string(get_state_path(server_install_dir))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+    }
+
+    fn save(&self, server_install_dir: &Path) -> Result<()> {
+        let path = get_state_path(server_install_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Enable the first half of the suspects, disable the rest, and say what to do next
+    fn next_step(&mut self, server_install_dir: &Path, profile: Option<&str>) -> Result<()> {
+        self.step += 1;
+        self.testing = self.suspects.len().div_ceil(2);
+
+        set_mods_list(DISABLED_KEY, &self.get_disabled(), profile)?;
+        self.save(server_install_dir)?;
+
+        let steps_left = usize::BITS - (self.suspects.len() - 1).leading_zeros();
+        println_success(&format!(
+            "Step {}: {} suspect mod(s) enabled, {} disabled (about {steps_left} step(s) to go)",
+            self.step,
+            self.testing,
+            self.suspects.len() - self.testing
+        ), 0);
+        for suspect in &self.suspects[..self.testing] {
+            println_step(&format!("Enabled: {} ({})", suspect.name, suspect.id), 1);
+        }
+        println_step("Run the server and try to reproduce the problem, then:", 1);
+        println_step("`dzsm bisect bad` if it still happens, `dzsm bisect good` if it doesn't", 2);
+        println_step("Or let `dzsm bisect auto` run the server and tell crashes apart itself", 2);
+        Ok(())
+    }
+
+    /// `mods.disabled` for the current step: what was disabled before, plus the suspects not being tested
+    fn get_disabled(&self) -> Vec<String> {
+        let mut disabled = self.disabled_before.clone();
+        disabled.extend(self.suspects[self.testing..].iter().map(|suspect| suspect.id.to_string()));
+        disabled
+    }
+
+    /// Record how the current step went and move on: the problem is among the enabled suspects
+    /// if it was `bad`, among the disabled ones otherwise
+    fn record(mut self, bad: bool, server_install_dir: &Path, profile: Option<&str>) -> Result<()> {
+        self.outcomes.push(StepOutcome { step: self.step, enabled: self.testing, bad });
+        let suspects = if bad {
+            self.suspects[..self.testing].to_vec()
+        } else {
+            self.suspects[self.testing..].to_vec()
+        };
+        self.narrow(suspects, server_install_dir, profile)
+    }
+
+    /// Narrow the suspects down to `suspects`, finishing once one is left
+    fn narrow(mut self, suspects: Vec<Suspect>, server_install_dir: &Path, profile: Option<&str>) -> Result<()> {
+        self.suspects = suspects;
+        match self.suspects.as_slice() {
+            [] => Err(anyhow!("No suspects left, the problem may need two mods together; `dzsm bisect reset` to start over")),
+            [culprit] => {
+                let culprit = culprit.clone();
+                self.finish(server_install_dir, profile)?;
+                println_success(&format!("Found it after {} step(s): {} ({})", self.step, culprit.name, culprit.id), 0);
+                for outcome in &self.outcomes {
+                    let result = if outcome.bad { "bad" } else { "good" };
+                    println_step(&format!("Step {}: {} suspect(s) enabled, {result}", outcome.step, outcome.enabled), 1);
+                }
+                println_step(&format!("Leave it out by adding \"{}\" to `disabled` under [mods]", culprit.id), 1);
+                Ok(())
+            }
+            _ => self.next_step(server_install_dir, profile),
+        }
+    }
+
+    /// Put `mods.disabled` back as it was and forget the bisect
+    fn finish(&self, server_install_dir: &Path, profile: Option<&str>) -> Result<()> {
+        set_mods_list(DISABLED_KEY, &self.disabled_before, profile)?;
+        let path = get_state_path(server_install_dir);
+        fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))
+    }
+}
+
+fn get_state_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(STATE_FILE)
+}
+
+/// The configured mods that are enabled, with the collections'
+fn get_enabled_mods(config: &Config) -> Result<Vec<ModEntry>> {
+    let mut mods: Vec<ModEntry> = config.mods.server_mod_list.iter().flatten().cloned().collect();
+    let collection_urls = config.mods.get_collection_urls();
+    let collection_mods = if collection_urls.is_empty() {
+        Vec::new()
+    } else {
+        CollectionFetcher::fetch_collections_mods(&collection_urls).complete()?
+    };
+    mods.extend(config.mods.filter_collection(collection_mods));
+    mods.retain(|mod_entry| !config.mods.is_disabled(mod_entry));
+    Ok(mods)
+}
+
+fn start(config: &Config, server_install_dir: &Path, keep: &[String], profile: Option<&str>) -> Result<()> {
+    if BisectState::load(server_install_dir).is_some() {
+        return Err(anyhow!("A bisect is already in progress, finish it or `dzsm bisect reset`"));
+    }
+
+    println_step("Collecting the enabled mods...", 0);
+    let suspects: Vec<Suspect> = get_enabled_mods(config)?
+        .into_iter()
+        .filter(|mod_entry| !keep.iter().any(|reference| mod_entry.is_named(reference)))
+        .map(|mod_entry| Suspect { id: mod_entry.id, name: mod_entry.name })
+        .collect();
+    if suspects.len() < 2 {
+        return Err(anyhow!("Bisecting needs at least two enabled mods that aren't kept, found {}", suspects.len()));
+    }
+
+    println_step("Mods needing a disabled framework fail to load too; keep frameworks with --keep", 1);
+    let mut state = BisectState {
+        disabled_before: config.mods.disabled.clone(),
+        suspects,
+        testing: 0,
+        step: 0,
+        outcomes: Vec::new(),
+    };
+    state.next_step(server_install_dir, profile)
+}
+
+fn load_in_progress(server_install_dir: &Path) -> Result<BisectState> {
+    BisectState::load(server_install_dir)
+        .ok_or_else(|| anyhow!("No bisect in progress, start one with `dzsm bisect start`"))
+}
+
+/// Run the server for each remaining step: a crash, or a server that never finishes loading, is bad;
+/// one still up `minutes` after loading its mission is good
+fn auto(args: &CliArgs, config: &Config, server_install_dir: &Path, minutes: u64) -> Result<()> {
+    load_in_progress(server_install_dir)?;
+    if !processes::find_by_exe_path(&server_install_dir.join(SERVER_EXE)).is_empty() {
+        return Err(anyhow!("The server is running, stop it before bisecting automatically"));
+    }
+
+    let profile = args.profile.as_deref();
+    let up_for = Duration::from_secs(minutes.saturating_mul(60));
+    println_step("Testing each step by running the server, Ctrl+C stops and keeps the bisect where it is", 0);
+
+    while let Some(state) = BisectState::load(server_install_dir) {
+        // Only the mods of this step are launched; the installed folders of the rest stay put
+        let mut step_config = config.clone();
+        step_config.mods.disabled = state.get_disabled();
+        let server_manager = ServerManager::new(args.clone(), step_config, &server_install_dir.to_string_lossy());
+
+        println_step(&format!("Step {}: starting the server...", state.step), 0);
+        let mut child = server_manager.launch_server()?;
+        let bad = match watch_step(&server_manager, &mut child, server_install_dir, up_for)? {
+            RunOutcome::Crashed(code) => {
+                println_failure(&format!("Step {}: the server exited with code {code:?}", state.step), 1);
+                true
+            }
+            RunOutcome::NeverLoaded => {
+                println_failure(&format!(
+                    "Step {}: the server didn't load its mission within {} minutes",
+                    state.step,
+                    LOAD_TIMEOUT.as_secs() / 60
+                ), 1);
+                true
+            }
+            RunOutcome::StayedUp => {
+                println_success(&format!("Step {}: the server stayed up for {minutes} minute(s)", state.step), 1);
+                false
+            }
+            RunOutcome::Interrupted => {
+                println_step("Bisect paused, carry on with `dzsm bisect auto` or `dzsm bisect bad`/`good`", 0);
+                return Ok(());
+            }
+        };
+        state.record(bad, server_install_dir, profile)?;
+    }
+
+    Ok(())
+}
+
+/// Watch a step's server until it crashes, fails to load in time, or stays up for `up_for` after loading,
+/// shutting it down unless it crashed
+fn watch_step(server_manager: &ServerManager, child: &mut Child, server_install_dir: &Path, up_for: Duration) -> Result<RunOutcome> {
+    let started = Instant::now();
+    let mut loaded_at: Option<Instant> = None;
+
+    loop {
+        if let Some(status) = child.try_wait().context("Failed to check on DayZ server process")? {
+            interrupt::set_server_running(false);
+            return Ok(RunOutcome::Crashed(status.code()));
+        }
+        if interrupt::shutdown_requested() {
+            server_manager.shutdown_server(child, true)?;
+            return Ok(RunOutcome::Interrupted);
+        }
+
+        match loaded_at {
+            // The query port only answers once the mission has loaded
+            None if a2s::query_server(server_install_dir).is_ok() => {
+                println_step("Server has loaded its mission, watching for a crash...", 1);
+                loaded_at = Some(Instant::now());
+            }
+            None if started.elapsed() >= LOAD_TIMEOUT => {
+                server_manager.shutdown_server(child, false)?;
+                return Ok(RunOutcome::NeverLoaded);
+            }
+            Some(loaded_at) if loaded_at.elapsed() >= up_for => {
+                server_manager.shutdown_server(child, true)?;
+                return Ok(RunOutcome::StayedUp);
+            }
+            _ => {}
+        }
+
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+/// Entry point for `dzsm bisect ...`
+pub fn run(command: &BisectCommand, args: &CliArgs, config: &Config, server_install_dir: &str) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+    let profile = args.profile.as_deref();
+
+    match command {
+        BisectCommand::Start { keep, auto: false, .. } => start(config, server_install_dir, keep, profile),
+        BisectCommand::Start { keep, auto: true, minutes } => {
+            start(config, server_install_dir, keep, profile)?;
+            auto(args, config, server_install_dir, *minutes)
+        }
+        BisectCommand::Auto { minutes } => auto(args, config, server_install_dir, *minutes),
+        BisectCommand::Bad => load_in_progress(server_install_dir)?.record(true, server_install_dir, profile),
+        BisectCommand::Good => load_in_progress(server_install_dir)?.record(false, server_install_dir, profile),
+        BisectCommand::Reset => {
+            load_in_progress(server_install_dir)?.finish(server_install_dir, profile)?;
+            println_success("Bisect ended, `mods.disabled` is back as it was", 0);
+            Ok(())
+        }
+    }
+}
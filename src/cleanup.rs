@@ -0,0 +1,147 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::CleanupConfig;
+use crate::server::SERVER_CONFIG;
+use crate::server_cfg::ServerDzConfig;
+use crate::state::state_dir;
+use crate::storage::{dir_size, format_size, list_backups};
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const STATE_FILE: &str = "cleanup.json";
+const GLOBALS_FILE: &str = "db/globals.xml";
+/// `globals.xml` variables, in seconds, after which the Central Economy removes bodies and ruined items
+const DEAD_PLAYER_VAR: &str = "CleanupLifetimeDeadPlayer";
+const RUINED_VAR: &str = "CleanupLifetimeRuined";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Default, Serialize, Deserialize)]
+struct CleanupState {
+    last_run: Option<DateTime<Local>>,
+}
+
+fn get_state_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(STATE_FILE)
+}
+
+fn load_state(server_install_dir: &Path) -> CleanupState {
+    fs::read_to_string(get_state_path(server_install_dir))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(server_install_dir: &Path, state: &CleanupState) -> Result<()> {
+    let path = get_state_path(server_install_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context("Failed to create state directory")?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(state)?)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+/// Set `<var name="..." value="..."/>` in `globals.xml`, None when the variable isn't there
+fn set_var(xml: &str, name: &str, value: u64) -> Option<String> {
+    let name_at = xml.find(&format!("name=\"{name}\""))?;
+    let tag_end = name_at + xml[name_at..].find('>')?;
+    let value_start = name_at + xml[name_at..tag_end].find("value=\"")? + "value=\"".len();
+    let value_end = value_start + xml[value_start..].find('"')?;
+    Some(format!("{}{value}{}", &xml[..value_start], &xml[value_end..]))
+}
+
+/// Write the body and ruined item lifetimes into the mission's `globals.xml`. DayZ's persistence
+/// is a binary format nothing else should edit, so the server clears them itself once loaded.
+#[allow(clippy::doc_markdown)]
+fn apply_lifetimes(config: &CleanupConfig, server_install_dir: &Path) -> Result<()> {
+    let lifetimes: Vec<(&str, u64)> = [(DEAD_PLAYER_VAR, config.dead_body_minutes), (RUINED_VAR, config.ruined_item_minutes)]
+        .into_iter()
+        .filter_map(|(name, minutes)| minutes.map(|minutes| (name, minutes.saturating_mul(60))))
+        .collect();
+    if lifetimes.is_empty() {
+        return Ok(());
+    }
+
+    let server_cfg = ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))?;
+    let path = server_cfg.get_mission_dir(server_install_dir)
+        .ok_or_else(|| anyhow!("No mission template set in {SERVER_CONFIG}"))?
+        .join(GLOBALS_FILE);
+    let original = fs::read_to_string(&path)
+        .context(format!("Failed to read {}", path.display()))?;
+
+    let mut xml = original.clone();
+    for (name, seconds) in lifetimes {
+        match set_var(&xml, name, seconds) {
+            Some(updated) => xml = updated,
+            None => println_failure(&format!("{GLOBALS_FILE} has no {name} to set"), 2),
+        }
+    }
+    if xml != original {
+        fs::write(&path, xml)
+            .context(format!("Failed to write {}", path.display()))?;
+        println_success(&format!("Updated the body and ruined item lifetimes in {GLOBALS_FILE}"), 2);
+    }
+    Ok(())
+}
+
+fn get_cutoff(days: u64) -> Option<SystemTime> {
+    SystemTime::now().checked_sub(Duration::from_secs(days.saturating_mul(SECONDS_PER_DAY)))
+}
+
+fn is_older(path: &Path, cutoff: SystemTime) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified < cutoff)
+}
+
+/// Delete storage backups older than `days`, never the newest; returns the bytes freed
+fn trim_backups(server_install_dir: &Path, days: u64) -> Result<u64> {
+    let Some(cutoff) = get_cutoff(days) else {
+        return Ok(0);
+    };
+
+    let mut backups = list_backups(server_install_dir)?;
+    backups.pop();
+    let mut freed = 0;
+    for backup in backups.iter().filter(|backup| is_older(backup, cutoff)) {
+        let size = dir_size(backup).unwrap_or_default();
+        match fs::remove_dir_all(backup) {
+            Ok(()) => freed += size,
+            Err(e) => println_failure(&format!("Failed to delete {}: {e}", backup.display()), 2),
+        }
+    }
+    Ok(freed)
+}
+
+/// Run the `[cleanup]` tasks before a start. The lifetimes are set every time, as a mission
+/// update replaces `globals.xml`; deleting backups waits `interval_hours` since it last ran.
+pub fn apply_before_launch(config: &CleanupConfig, server_install_dir: &Path) -> Result<()> {
+    if !config.is_enabled() {
+        return Ok(());
+    }
+
+    println_step("Running cleanup tasks...", 1);
+    apply_lifetimes(config, server_install_dir)?;
+
+    let Some(days) = config.backup_days else {
+        return Ok(());
+    };
+    let now = Local::now();
+    let interval = ChronoDuration::hours(i64::try_from(config.interval_hours).unwrap_or(i64::MAX / 3600));
+    let mut state = load_state(server_install_dir);
+    if state.last_run.is_some_and(|last_run| now - last_run < interval) {
+        return Ok(());
+    }
+
+    let freed = trim_backups(server_install_dir, days)?;
+    if freed > 0 {
+        println_success(&format!("Freed {} of old storage backups", format_size(freed)), 2);
+    }
+
+    state.last_run = Some(now);
+    save_state(server_install_dir, &state)
+}
@@ -1,31 +1,760 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::logging::Verbosity;
 
 #[derive(Parser, Debug, Clone)]
 #[command(
     name = "dzsm",
     version = env!("CARGO_PKG_VERSION"),
-    about = "DayZ Server Manager - Download, update, and run DayZ servers with mod support"
+    about = "DZSM: DayZ Server Manager - Download, update, and run DayZ servers with mod support"
 )]
 #[allow(clippy::struct_excessive_bools)]
 pub struct CliArgs {
+    /// Display the license information
+    #[arg(long = "license")]
+    pub license: bool,
+
+    /// Don't print the startup banner (also set by `DZSM_NO_BANNER`)
+    #[arg(long = "no-banner", env = "DZSM_NO_BANNER", global = true)]
+    pub no_banner: bool,
+
+    /// Never wait for input: prompts take their default answer and
+    /// SteamCMD/the server get no stdin (for Task Scheduler, systemd, or CI)
+    #[arg(long = "non-interactive", visible_alias = "yes", short = 'y', global = true)]
+    #[allow(clippy::doc_markdown)]
+    pub non_interactive: bool,
+
+    /// Print JSON events on stdout, one per line, ending with a result object, for panels and bots;
+    /// implies --non-interactive and --no-banner. SteamCMD and server output go to stderr
+    #[arg(long = "json", global = true)]
+    #[allow(clippy::doc_markdown)]
+    pub json: bool,
+
+    /// Show extra detail such as SteamCMD arguments and HTTP requests
+    #[arg(long = "verbose", short = 'v', global = true, conflicts_with = "quiet")]
+    #[allow(clippy::doc_markdown)]
+    pub verbose: bool,
+
+    /// Only show errors on the console (the log file is unaffected)
+    #[arg(long = "quiet", short = 'q', global = true)]
+    pub quiet: bool,
+
+    /// Print without colors, as does setting `NO_COLOR`
+    #[arg(long = "no-color", global = true)]
+    pub no_color: bool,
+
     /// Skip server validation during update
-    #[arg(long = "skip-server-validation")]
+    #[arg(long = "skip-server-validation", global = true)]
     pub skip_server_validation: bool,
-    
+
     /// Skip mod validation during update
-    #[arg(long = "skip-mod-validation")]
+    #[arg(long = "skip-mod-validation", global = true)]
     pub skip_mod_validation: bool,
-    
+
     /// Skip all validation (server and mods)
-    #[arg(long = "skip-validation")]
+    #[arg(long = "skip-validation", global = true)]
     pub skip_validation: bool,
 
+    /// Remove every @ folder and key before installing mods, not just the ones DZSM installed
+    #[arg(long = "force-clean", global = true)]
+    pub force_clean: bool,
+
     /// Skips all SteamCMD operations,
     /// throws an error if the DayZServer64.exe is missing
     /// or if a workshop mod's source dir is missing.
-    #[arg(long = "offline")]
+    /// Collections are loaded from the copy cached when they were last fetched.
+    #[arg(long = "offline", global = true)]
     #[allow(clippy::doc_markdown)]
     pub offline: bool,
+
+    /// Refuse to install or start when a collection can't be fetched or loaded from the cache,
+    /// like `mods.require_collection`
+    #[arg(long = "require-collection", global = true)]
+    pub require_collection: bool,
+
+    /// Use the server defined in `[profiles.<name>]` of config.toml instead of the one in this directory
+    #[arg(long = "profile", env = "DZSM_PROFILE", global = true)]
+    pub profile: Option<String>,
+
+    /// Command to run instead of the default update-and-run pipeline
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Commands {
+    /// Update and run the server (the default when no command is given)
+    Run(RunArgs),
+
+    /// Stop the server another DZSM is running, or one started some other way
+    Stop {
+        /// Kill the server if it doesn't shut down, players lose what wasn't saved
+        #[arg(long = "force")]
+        force: bool,
+    },
+
+    /// Restart the server another DZSM is running, with players warned; starts it here when none is
+    Restart {
+        /// Update the server and mods before starting it again
+        #[arg(long = "update")]
+        update: bool,
+        /// Kill a server no DZSM runs if it doesn't shut down
+        #[arg(long = "force")]
+        force: bool,
+    },
+
+    /// Report the health of this server install: files, mods, and whether the server is running
+    Status {
+        /// Put back missing or broken mod folders and keys from the mods already downloaded,
+        /// and remove ones for mods no longer configured, instead of reinstalling every mod
+        #[arg(long = "repair")]
+        repair: bool,
+    },
+
+    /// Measure this host's disks, CPU, and download speeds, e.g. to pick the fastest Steam content server
+    #[command(subcommand)]
+    Bench(BenchCommand),
+
+    /// `BattlEye` RCON settings and filters
+    #[command(subcommand)]
+    Battleye(BattlEyeCommand),
+
+    /// Players banned in `ban.txt` in the install dir, where the server reads it, and IPs banned
+    /// through `BattlEye`
+    #[command(subcommand)]
+    Ban(BanCommand),
+
+    /// Steam Workshop collection tools
+    #[command(subcommand)]
+    Collection(CollectionCommand),
+
+    /// Crash analysis tools
+    #[command(subcommand)]
+    Crash(CrashCommand),
+
+    /// Rotated server, admin, and RCON passwords
+    #[command(subcommand)]
+    Secrets(SecretsCommand),
+
+    /// Look through the admin logs for patterns that suggest duping
+    #[command(subcommand)]
+    Dupes(DupesCommand),
+
+    /// Mission file tools
+    #[command(subcommand)]
+    Mission(MissionCommand),
+
+    /// Add and remove mods in `config.toml`, keeping its comments
+    #[command(subcommand)]
+    Mods(ModsCommand),
+
+    /// Find the mod behind a crash or bug by disabling half of the suspects at a time
+    #[command(subcommand)]
+    Bisect(BisectCommand),
+
+    /// Player positions from the admin logs, for admins investigating duping or raids
+    #[command(subcommand)]
+    Positions(PositionsCommand),
+
+    /// Retention of player-identifying logs and reports
+    #[command(subcommand)]
+    Privacy(PrivacyCommand),
+
+    /// Read-only server info for community websites and launchers
+    #[command(subcommand)]
+    Public(PublicCommand),
+
+    /// Serve a dashboard to check on and control the server from a browser, protected by `web.token`
+    Web {
+        /// Port to listen on instead of the one in `web.listen`
+        #[arg(long = "port")]
+        port: Option<u16>,
+    },
+
+    /// Mods left out after a crash loop they were the only change before
+    #[command(subcommand)]
+    Quarantine(QuarantineCommand),
+
+    /// Keep players out behind a temporary join password, e.g. for wipe-day work
+    #[command(subcommand)]
+    Maintenance(MaintenanceCommand),
+
+    /// Put back a server build backed up before an update, e.g. when a game update breaks mods
+    Rollback {
+        /// Build to restore (defaults to the newest backed-up build other than the installed one)
+        #[arg(long = "build")]
+        build: Option<String>,
+        /// List the backed-up builds instead
+        #[arg(long = "list", conflicts_with = "build")]
+        list: bool,
+    },
+
+    /// Wipe day in one go: announce, back up, stop, wipe, refresh the mission, update, and start again
+    WipeDay(WipeDayArgs),
+
+    /// Files kept in the server's `profiles` folder, see `[profile_files]`
+    #[command(subcommand)]
+    ProfileFiles(ProfileFilesCommand),
+
+    /// Persistence corruption checks and backups
+    #[command(subcommand)]
+    Storage(StorageCommand),
+
+    /// Steam password stored in the Windows Credential Manager, see `steam.credential_source`
+    #[command(subcommand)]
+    Steam(SteamCommand),
+
+    /// Share bans and whitelist across a cluster of servers
+    #[command(subcommand)]
+    Sync(SyncCommand),
+
+    /// In-game time and day/night cycle
+    #[command(subcommand)]
+    Time(TimeCommand),
+
+    /// Run DZSM in the background as a Windows service
+    #[command(subcommand)]
+    Service(ServiceCommand),
+
+    /// Only keep the server and mods up to date, for another tool that launches the server
+    #[command(subcommand)]
+    Updater(UpdaterCommand),
+
+    /// Definitions for running DZSM inside a hosting panel
+    #[command(subcommand)]
+    Generate(GenerateCommand),
+}
+
+/// `dzsm run`, all off when no command is given
+#[derive(Args, Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RunArgs {
+    /// Keep the server running: restart it whenever it exits, until `dzsm stop`
+    #[arg(long = "supervise")]
+    pub supervise: bool,
+    /// Start the server in the background with its output in `.dzsm/logs/server-console.log`,
+    /// and return once it's running; it keeps going after this console or session closes
+    #[arg(long = "detach", conflicts_with = "supervise")]
+    pub detach: bool,
+    /// Only update the server and mods, without starting the server
+    #[arg(long = "no-run", conflicts_with_all = ["supervise", "detach"])]
+    pub no_run: bool,
+    /// Start the server as it's installed, skipping the server and mod updates
+    #[arg(long = "no-update", conflicts_with_all = ["supervise", "no_run"])]
+    pub no_update: bool,
+    /// Only install and launch the mods of this preset from `[mods.presets]`
+    #[arg(long = "mod-preset")]
+    pub mod_preset: Option<String>,
+    /// Show what would be deleted, downloaded, linked, and launched without changing files or
+    /// going online. With `--supervise`, walk through the supervision loop and restart schedule
+    /// instead; time is fast-forwarded and every action is logged.
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+    /// How much time the supervision dry run simulates
+    #[arg(long = "dry-run-hours", default_value_t = 24, requires_all = ["dry_run", "supervise"])]
+    pub dry_run_hours: u64,
+}
+
+#[derive(Args, Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct WipeDayArgs {
+    /// What to wipe: all of the storage, the "world" (loot, bases, and vehicles) but not the
+    /// characters, only the "characters", or "none"
+    #[arg(long = "wipe", default_value = "full", value_parser = ["full", "world", "characters", "none"])]
+    pub wipe: String,
+    /// Make another installed mission the active one first, e.g. `dayzOffline.enoch`
+    #[arg(long = "mission")]
+    pub mission: Option<String>,
+    /// Download the latest vanilla missions and merge the active one with them, see `dzsm mission refresh`
+    #[arg(long = "refresh-mission")]
+    pub refresh_mission: bool,
+    /// Raise `instanceId` in `serverDZ.cfg`, so the server starts on new storage and the old one stays as it is
+    #[arg(long = "new-instance")]
+    pub new_instance: bool,
+    /// What players are told over RCON and the notification channels get
+    #[arg(long = "message")]
+    pub message: Option<String>,
+    /// Keep the server running afterwards, like `dzsm run --supervise`
+    #[arg(long = "supervise")]
+    pub supervise: bool,
+    /// Don't ask before wiping
+    #[arg(long = "yes")]
+    pub yes: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BenchCommand {
+    /// Time a short server download from each content server cell and suggest the fastest for `server.cell_id`
+    Download {
+        /// Cell IDs to try, besides the one Steam picks, e.g. `--cells 1,4,52`
+        #[arg(long = "cells", value_delimiter = ',', required = true)]
+        cells: Vec<u32>,
+        /// How long to download from each cell
+        #[arg(long = "seconds", default_value_t = 60)]
+        seconds: u64,
+    },
+    /// Measure disk, CPU, and download speed and check them against what a server with the installed mods needs
+    Host {
+        /// How long to time the download from Steam
+        #[arg(long = "seconds", default_value_t = 20)]
+        seconds: u64,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+#[allow(clippy::doc_markdown)]
+pub enum BattlEyeCommand {
+    /// Fill in missing RCON settings and merge the filters of `battleye.filter_mods` into the BattlEye folder
+    Apply {
+        /// Show what would change without writing anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CollectionCommand {
+    /// List the items to add to or remove from a collection so it matches the installed server mods
+    Diff {
+        /// Collection to compare against (defaults to `mods.published_collection_url`, then the only one of `mods.mod_collection_urls`)
+        #[arg(long = "url")]
+        url: Option<String>,
+    },
+    /// Look for items that are the same mod twice, e.g. a translated re-upload or one replaced by a newer upload
+    Dupes {
+        /// Collection to check (defaults to every one in `mods.mod_collection_urls`)
+        #[arg(long = "url")]
+        url: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CrashCommand {
+    /// Fingerprint the latest server logs and check them against the known crash databases
+    Analyze,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SecretsCommand {
+    /// Show the current passwords and when they rotate next
+    Show,
+    /// Rotate the configured passwords now (takes effect at the next server start)
+    Rotate,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DupesCommand {
+    /// Flag reconnect churn, container churn, and repeated placements, and save the report to `.dzsm/reports/`.
+    /// Only the admin logs are read: the storage's binary format isn't documented, so identical
+    /// item stacks in persistence aren't checked
+    Report {
+        /// How many hours of admin logs to look through
+        #[arg(long = "hours", default_value_t = 24)]
+        hours: u64,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum MissionCommand {
+    /// List the missions in `mpmissions`, marking the active one
+    List,
+    /// Make a mission the active template in `serverDZ.cfg`, e.g. `dayzOffline.chernarusplus`
+    Set {
+        mission: String,
+    },
+    /// Install a community map's mission files into `mpmissions` from a mod's extras folder or a zip URL
+    Install {
+        /// Mission to install, e.g. `dayzOffline.namalsk`
+        mission: String,
+        /// Installed mod whose extras folder has the mission (defaults to `missions.sources`, then every mod)
+        #[arg(long = "mod", conflicts_with = "url")]
+        from_mod: Option<String>,
+        /// Zip archive to download the mission from (defaults to `missions.sources`)
+        #[arg(long = "url")]
+        url: Option<String>,
+        /// Replace the mission if it's already installed, backing it up first and keeping its storage
+        #[arg(long = "force")]
+        force: bool,
+    },
+    /// Carry your mission customizations over to a new vanilla mission after a game update,
+    /// three-way merging each file and flagging conflicts
+    Merge {
+        /// The new vanilla mission (defaults to the pristine copy from `dzsm mission refresh`)
+        #[arg(long = "new")]
+        new: Option<PathBuf>,
+        /// The vanilla mission the customizations were made against
+        /// (defaults to the copy saved by the last merge in `.dzsm/missions/vanilla/`)
+        #[arg(long = "old")]
+        old: Option<PathBuf>,
+        /// Mission to merge (defaults to the template in `serverDZ.cfg`)
+        #[arg(long = "mission")]
+        mission: Option<String>,
+        /// Show what would change without writing anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+    /// Merge the economy files of the mods in `[[mods.economy]]` into the active mission and apply
+    /// the `[economy]` patches to its `types.xml` (also done before every start)
+    Economy {
+        /// Show what would change without writing anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+    /// Download the installed server's vanilla missions and save them to `.dzsm/missions/pristine/`,
+    /// the untouched baseline for merges and checks. The server must be stopped.
+    Refresh,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ModsCommand {
+    /// Add a mod to `mods.server_mod_list`, named after its Workshop title
+    Add {
+        /// Workshop ID or item URL
+        reference: String,
+        /// Name to use instead of the Workshop title
+        #[arg(long = "name")]
+        name: Option<String>,
+        /// Where it loads, when not server-only
+        #[arg(long = "side", value_parser = ["server", "client", "both"])]
+        side: Option<String>,
+    },
+    /// Remove a mod from `mods.server_mod_list`
+    Remove {
+        /// Name or Workshop ID
+        reference: String,
+    },
+    /// Set where a mod loads; collection mods are set in `[mods.sides]`
+    Side {
+        /// Name or Workshop ID
+        reference: String,
+        #[arg(value_parser = ["server", "client", "both"])]
+        side: String,
+    },
+    /// Leave a group's mods out of installs and launches, see `mods.disabled_groups`
+    DisableGroup {
+        group: String,
+    },
+    /// Bring a disabled group's mods back
+    EnableGroup {
+        group: String,
+    },
+    /// Print the installed mods players need: a `-mod` launch parameter, the JSON DZSA Launcher
+    /// reads, and a Markdown list with Workshop links for Discord
+    #[allow(clippy::doc_markdown)]
+    Export {
+        /// Only this one, without headings, e.g. to write to a file
+        #[arg(long = "format", value_parser = ["launch", "json", "markdown"])]
+        format: Option<String>,
+        /// File to write instead of printing
+        #[arg(long = "output")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BisectCommand {
+    /// Disable half of the enabled mods for the first test
+    Start {
+        /// Mods to leave enabled throughout, such as frameworks others need, by name or Workshop ID
+        #[arg(long = "keep", value_delimiter = ',')]
+        keep: Vec<String>,
+        /// Find a crashing mod without further input, see `dzsm bisect auto`
+        #[arg(long = "auto")]
+        auto: bool,
+        /// With --auto, how long the server must stay up after loading for a step to pass
+        #[arg(long = "minutes", default_value_t = 10, requires = "auto")]
+        minutes: u64,
+    },
+    /// Run the server for each remaining step, counting a crash or a server that never loads as bad
+    /// and one that stays up as good, until the crashing mod is found
+    Auto {
+        /// How long the server must stay up after loading its mission for a step to pass
+        #[arg(long = "minutes", default_value_t = 10)]
+        minutes: u64,
+    },
+    /// The problem still happens with the enabled mods
+    Bad,
+    /// The problem is gone with the enabled mods
+    Good,
+    /// Stop bisecting and put `mods.disabled` back as it was
+    Reset,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum PositionsCommand {
+    /// Write recent player positions and trails to a GeoJSON file (game metres, as shown on iZurvive)
+    #[allow(clippy::doc_markdown)]
+    Export {
+        /// File to write (defaults to `positions.export_file`, then `positions.geojson`)
+        #[arg(long = "out")]
+        out: Option<PathBuf>,
+        /// How many minutes of logs to include (defaults to `positions.history_minutes`)
+        #[arg(long = "minutes")]
+        minutes: Option<u64>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum PrivacyCommand {
+    /// Delete reports, crash captures, and logs older than `privacy.retention_days` now
+    Purge,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum PublicCommand {
+    /// Print the server info JSON that `public.listen` and `public.file` publish
+    Show,
+    /// Serve the server info on `public.listen` without running the server
+    Serve,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum MaintenanceCommand {
+    /// Set a join password only admins know, lock the running server, and notify
+    On {
+        /// Why, for the notification and status outputs
+        #[arg(long = "reason")]
+        reason: Option<String>,
+        /// Join password to use instead of a generated one
+        #[arg(long = "password")]
+        password: Option<String>,
+    },
+    /// Restore the join password and unlock the running server
+    Off,
+    /// Whether maintenance mode is on, and its password
+    Status,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ProfileFilesCommand {
+    /// Compare each file in `profiles` with its source, showing any edited there since they were deployed
+    Status,
+    /// Deploy the files now instead of before the next start
+    Sync {
+        /// Show what would be copied without writing anything
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum QuarantineCommand {
+    /// List the quarantined mods
+    List,
+    /// Load a quarantined mod again from the next start
+    Release {
+        /// Mod name or Workshop ID
+        target: String,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SteamCommand {
+    /// Store the password of `server.username`, prompting for it
+    Login {
+        /// Store the password of `steam.workshop_username` instead
+        #[arg(long)]
+        workshop: bool,
+    },
+    /// Remove the stored password
+    Forget {
+        /// Remove the password of `steam.workshop_username` instead
+        #[arg(long)]
+        workshop: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum StorageCommand {
+    /// Check the persistence files for corruption
+    Check,
+    /// Replace the persistence files with the latest backup (the current ones are kept aside)
+    Restore,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BanCommand {
+    /// Ban a player by Steam64 ID, or an IP address through `BattlEye` while the server runs
+    Add {
+        /// Steam64 ID or IP address
+        target: String,
+        /// Why, kept as a comment in `ban.txt`
+        #[arg(long = "reason")]
+        reason: Option<String>,
+        /// Also ban a Steam64 ID through `BattlEye` on the running server, which applies at once
+        #[arg(long = "rcon")]
+        rcon: bool,
+    },
+    /// Lift a ban
+    Remove {
+        /// Steam64 ID or IP address
+        target: String,
+        /// Also lift it in `BattlEye` on the running server
+        #[arg(long = "rcon")]
+        rcon: bool,
+    },
+    /// List the bans in `ban.txt`
+    List {
+        /// Also list `BattlEye`'s bans from the running server
+        #[arg(long = "rcon")]
+        rcon: bool,
+    },
+    /// Add the Steam64 IDs of a shared ban list, one per line like `ban.txt`, that aren't banned yet
+    Import {
+        /// URL of the ban list
+        url: String,
+        /// Show how many would be added without changing `ban.txt`
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SyncCommand {
+    /// Run the hub that instances sync their ban and whitelist files through
+    Serve,
+    /// Sync this server's ban and whitelist files with the hub once
+    Now,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum TimeCommand {
+    /// Show the time settings applied at the next server start
+    Show,
+    /// List the named time presets
+    Presets,
+    /// Change the time settings in `config.toml` (takes effect at the next server start)
+    Set {
+        /// Preset to switch to, replacing any individual settings
+        preset: Option<String>,
+        /// Start time: `SystemTime` or `YYYY/MM/DD/HH/MM`
+        #[arg(long = "server-time")]
+        server_time: Option<String>,
+        /// How many times faster than real time the in-game clock runs (0 - 24)
+        #[arg(long = "acceleration")]
+        acceleration: Option<f64>,
+        /// Extra speed-up at night, on top of `--acceleration` (0.1 - 64)
+        #[arg(long = "night-acceleration")]
+        night_acceleration: Option<f64>,
+        /// Keep the in-game time across restarts
+        #[arg(long = "persistent")]
+        persistent: Option<bool>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum UpdaterCommand {
+    /// Update the server and mods, write a launch script with the current mod list, and exit
+    /// without launching the server. Pre-launch settings (economy, time, weather, passwords) are
+    /// left to DZSM runs that launch the server.
+    Run {
+        /// Launch script to write (defaults to `start_server.bat` in the server directory)
+        #[arg(long = "script")]
+        script: Option<PathBuf>,
+        /// Also write the launch parameters alone, on one line, for launchers that read them from a file
+        #[arg(long = "params")]
+        params: Option<PathBuf>,
+    },
+    /// Run `dzsm updater run` daily as a Windows scheduled task
+    Install {
+        /// Time of day to update at (HH:MM), while the launcher has the server stopped
+        #[arg(long = "at", default_value = "04:00")]
+        at: String,
+        /// Task name (defaults to `dzsm-update-<install dir name>`)
+        #[arg(long = "name")]
+        name: Option<String>,
+        /// Account to run the task as, so it runs whether or not anyone is logged on
+        #[arg(long = "user")]
+        user: Option<String>,
+        /// Password for `--user`
+        #[arg(long = "password", requires = "user")]
+        password: Option<String>,
+        /// Passed on to `dzsm updater run`
+        #[arg(long = "script")]
+        script: Option<PathBuf>,
+        /// Passed on to `dzsm updater run`
+        #[arg(long = "params")]
+        params: Option<PathBuf>,
+    },
+    /// Remove the scheduled task
+    Uninstall {
+        /// Task name (defaults to `dzsm-update-<install dir name>`)
+        #[arg(long = "name")]
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ServiceCommand {
+    /// Register a service that updates, runs, and restarts this server (requires administrator)
+    Install {
+        /// Service name (defaults to `dzsm-<install dir name>`)
+        #[arg(long = "name")]
+        name: Option<String>,
+        /// Account to run the service as (defaults to `LocalSystem`)
+        #[arg(long = "user")]
+        user: Option<String>,
+        /// Password for `--user`
+        #[arg(long = "password", requires = "user")]
+        password: Option<String>,
+    },
+    /// Stop and remove the service
+    Uninstall {
+        /// Service name (defaults to `dzsm-<install dir name>`)
+        #[arg(long = "name")]
+        name: Option<String>,
+    },
+    /// Start the service
+    Start {
+        /// Service name (defaults to `dzsm-<install dir name>`)
+        #[arg(long = "name")]
+        name: Option<String>,
+    },
+    /// Stop the service, shutting the server down
+    Stop {
+        /// Service name (defaults to `dzsm-<install dir name>`)
+        #[arg(long = "name")]
+        name: Option<String>,
+    },
+    /// Print a systemd unit that supervises this server, for Linux hosts
+    SystemdUnit,
+    /// Service entry point used by Windows
+    #[command(hide = true)]
+    Run {
+        #[arg(long = "name")]
+        name: String,
+        #[arg(long = "dir")]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum GenerateCommand {
+    /// Write a Pterodactyl egg that installs DZSM, fills in the Steam username and port, and stops with Ctrl+C
+    PterodactylEgg {
+        /// File to write (defaults to `egg-dzsm.json`)
+        #[arg(long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Write an AMP Generic module that downloads and runs DZSM
+    #[allow(clippy::doc_markdown)]
+    Amp {
+        /// Directory to write the module files to (defaults to `amp`)
+        #[arg(long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Write a script that starts the server with the current config and mods, for process managers
+    /// such as NSSM, FireDaemon, or systemd; nothing gets updated, so regenerate it after mod changes.
+    /// The sh and systemd formats run the server through Wine.
+    #[allow(clippy::doc_markdown)]
+    LaunchScript {
+        #[arg(long = "format", default_value = "bat", value_parser = ["bat", "ps1", "sh", "systemd"])]
+        format: String,
+        /// File to write (defaults to `start_server.<format>`, or `dayz-server.service` for systemd)
+        #[arg(long = "output")]
+        output: Option<PathBuf>,
+    },
 }
 
 impl CliArgs {
@@ -33,4 +762,14 @@ impl CliArgs {
     pub fn parse_args() -> Self {
         Self::parse()
     }
+
+    pub const fn verbosity(&self) -> Verbosity {
+        if self.quiet {
+            Verbosity::Quiet
+        } else if self.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
 }
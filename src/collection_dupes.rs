@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use crate::config::mod_entry::ModEntry;
+use crate::ui::status::{println_failure, println_step};
+use crate::workshop_details::WorkshopDetails;
+
+/// What a description says when it sends players to another upload
+const SUPERSEDED_WORDS: &[&str] = &[
+    "deprecated",
+    "outdated",
+    "discontinued",
+    "superseded",
+    "no longer",
+    "replaced by",
+    "moved to",
+    "new version",
+    "reupload",
+    "re-upload",
+];
+/// Words translated re-uploads add to a title, left out when comparing names
+const LANGUAGE_WORDS: &[&str] = &[
+    "ru", "rus", "russian", "ua", "ukrainian", "en", "eng", "english", "de", "ger", "german", "fr", "french",
+    "es", "spanish", "pl", "polish", "cz", "czech", "cn", "chinese", "translation", "translated",
+];
+
+/// Two collection items that look like the same mod, players would download both
+pub struct Duplicate {
+    pub keep: ModEntry,
+    pub remove: ModEntry,
+    pub reason: String,
+}
+
+/// Pairs of items that declare one replaces the other, or whose names only differ in
+/// language tags and punctuation; of a similar pair, the one updated last is kept
+pub fn find_duplicates(mods: &[ModEntry], details: &HashMap<u64, WorkshopDetails>) -> Vec<Duplicate> {
+    let mut duplicates = Vec::new();
+
+    for (i, a) in mods.iter().enumerate() {
+        for b in &mods[i + 1..] {
+            let (Some(a_details), Some(b_details)) = (details.get(&a.id), details.get(&b.id)) else {
+                continue;
+            };
+
+            let duplicate = if points_to(a_details, b.id) {
+                Some((b, a, format!("{}'s description points to {} as its replacement", a.name, b.name)))
+            } else if points_to(b_details, a.id) {
+                Some((a, b, format!("{}'s description points to {} as its replacement", b.name, a.name)))
+            } else if is_similar(&normalize_title(&a_details.title), &normalize_title(&b_details.title)) {
+                let (keep, remove) = if a_details.time_updated >= b_details.time_updated { (a, b) } else { (b, a) };
+                Some((keep, remove, format!("near-identical names, {} was updated more recently", keep.name)))
+            } else {
+                None
+            };
+
+            if let Some((keep, remove, reason)) = duplicate {
+                duplicates.push(Duplicate { keep: keep.clone(), remove: remove.clone(), reason });
+            }
+        }
+    }
+
+    duplicates
+}
+
+/// Warn about duplicates among a collection's items, returning how many were found
+pub fn report_duplicates(mods: &[ModEntry], details: &HashMap<u64, WorkshopDetails>, level: usize) -> usize {
+    let duplicates = find_duplicates(mods, details);
+
+    for duplicate in &duplicates {
+        println_failure(&format!(
+            "{} ({}) and {} ({}) look like the same mod: {}",
+            duplicate.keep.name, duplicate.keep.id, duplicate.remove.name, duplicate.remove.id, duplicate.reason
+        ), level);
+        println_step(&format!("Consider removing {} from the collection", duplicate.remove.workshop_url()), level + 1);
+    }
+    duplicates.len()
+}
+
+/// Whether an item's description links another item and says it's been replaced
+fn points_to(details: &WorkshopDetails, other_id: u64) -> bool {
+    let description = details.description.to_lowercase();
+    description.contains(&other_id.to_string())
+        && SUPERSEDED_WORDS.iter().any(|word| description.contains(word))
+}
+
+/// A title without bracketed tags, language words, case, or punctuation,
+/// e.g. "[RU] Better Vehicles (Russian)" becomes "bettervehicles"
+fn normalize_title(title: &str) -> String {
+    let mut depth = 0_usize;
+    let unbracketed: String = title.chars()
+        .filter(|c| match c {
+            '[' | '(' => { depth += 1; false }
+            ']' | ')' => { depth = depth.saturating_sub(1); false }
+            _ => depth == 0,
+        })
+        .collect();
+
+    unbracketed.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !LANGUAGE_WORDS.contains(word))
+        .collect()
+}
+
+/// Equal, or one typo apart per ten characters
+fn is_similar(a: &str, b: &str) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    let allowed = a.chars().count().min(b.chars().count()) / 10;
+    edit_distance(a, b) <= allowed
+}
+
+/// Levenshtein distance between two strings, by character
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
@@ -1,74 +1,253 @@
 use anyhow::{Context, Result, anyhow};
-use curl::easy::Easy;
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::thread;
 use crate::collection_parser::SteamCollectionParser;
-use crate::ui::status::{println_step, println_success};
+use crate::http;
+use crate::state::state_dir;
+use crate::ui::status::{println_failure, println_step, println_success};
 use crate::config::mod_entry::ModEntry;
 
+const CACHE_DIR: &str = "collections";
+/// A collection fetched this recently is used as is, sparing Steam another page request
+const CACHE_FRESH_MINUTES: i64 = 10;
+
+/// Where parsed collections are cached, set once the install dir is known
+static CACHE_PATH: OnceLock<PathBuf> = OnceLock::new();
+
 pub struct CollectionFetcher;
 
+/// A collection page, downloaded and parsed
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FetchedCollection {
+    pub url: String,
+    pub title: Option<String>,
+    pub mods: Vec<ModEntry>,
+    /// When it was downloaded
+    pub fetched: DateTime<Local>,
+    /// Why the cached copy is used instead of a fresh one: offline mode, or Steam couldn't be reached
+    #[serde(skip)]
+    pub stale: Option<String>,
+}
+
+/// Several collections' mods, each once, and the collections that couldn't be fetched
+pub struct MergedCollections {
+    pub mods: Vec<ModEntry>,
+    pub failed: Vec<anyhow::Error>,
+}
+
+impl MergedCollections {
+    /// The mods, or the first failure if any collection is missing from them
+    pub fn complete(self) -> Result<Vec<ModEntry>> {
+        match self.failed.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(self.mods),
+        }
+    }
+}
+
 impl CollectionFetcher {
+    /// Cache parsed collections in the install's state dir, so repeated runs don't
+    /// fetch the same pages and a Steam outage falls back to the last good copy
+    pub fn set_cache_dir(server_install_dir: &Path) {
+        let _ = CACHE_PATH.set(state_dir(server_install_dir).join(CACHE_DIR));
+    }
+
     /// Fetch and parse a Steam Workshop collection by URL
     pub fn fetch_collection_mods(collection_url: &str) -> Result<Vec<ModEntry>> {
         println_step(&format!("Fetching collection: {collection_url}"), 1);
-        
+        let collection = Self::download(collection_url)?;
+        Self::report(&collection);
+        Ok(collection.mods)
+    }
+
+    /// Fetch several collections, keeping each mod once (from the first collection it's in)
+    pub fn fetch_collections_mods(collection_urls: &[&str]) -> MergedCollections {
+        for collection_url in collection_urls {
+            println_step(&format!("Fetching collection: {collection_url}"), 1);
+        }
+        Self::merge(Self::download_all(collection_urls))
+    }
+
+    /// The last fetched copies of several collections, for offline mode, keeping each mod once;
+    /// any that was never fetched is among the failed
+    pub fn load_collections_mods(collection_urls: &[&str]) -> MergedCollections {
+        let collections = collection_urls.iter()
+            .map(|collection_url| {
+                println_step(&format!("Loading cached collection: {collection_url}"), 1);
+                get_cache_path(collection_url)
+                    .as_deref()
+                    .and_then(load_cached)
+                    .map(|cached| FetchedCollection {
+                        url: collection_url.to_string(),
+                        stale: Some("offline mode enabled".to_string()),
+                        ..cached
+                    })
+                    .ok_or_else(|| anyhow!("No cached copy of {collection_url}, run without --offline once to fetch it"))
+            })
+            .collect();
+        Self::merge(collections)
+    }
+
+    /// Download and parse several collections at once, without printing anything,
+    /// so it can also run in the background
+    pub fn download_all(collection_urls: &[&str]) -> Vec<Result<FetchedCollection>> {
+        thread::scope(|scope| {
+            let downloads: Vec<_> = collection_urls.iter()
+                .map(|collection_url| scope.spawn(|| Self::download(collection_url)))
+                .collect();
+            downloads.into_iter()
+                .map(|download| download.join().unwrap_or_else(|_| Err(anyhow!("Collection download panicked"))))
+                .collect()
+        })
+    }
+
+    /// Report downloaded collections, keeping each mod once (from the first collection it's in);
+    /// one that couldn't be fetched is left out and returned among the failed, for the caller
+    /// to decide whether it can go on without it
+    pub fn merge(collections: Vec<Result<FetchedCollection>>) -> MergedCollections {
+        let mut count = 0;
+        let mut all_mods: Vec<ModEntry> = Vec::new();
+        let mut failed = Vec::new();
+
+        for collection in collections {
+            let collection = match collection {
+                Ok(collection) => collection,
+                Err(e) => {
+                    failed.push(e);
+                    continue;
+                }
+            };
+            count += 1;
+            Self::report(&collection);
+
+            for mut mod_entry in collection.mods {
+                if let Some(existing) = all_mods.iter().find(|existing| existing.id == mod_entry.id) {
+                    println_step(&format!(
+                        "{} ({}) is also in {}, loading it once",
+                        mod_entry.name,
+                        mod_entry.id,
+                        existing.collection.as_deref().unwrap_or_default()
+                    ), 2);
+                    continue;
+                }
+                mod_entry.collection = Some(collection.url.clone());
+                all_mods.push(mod_entry);
+            }
+        }
+
+        if count > 1 {
+            println_success(&format!("{} unique mods across {count} collections", all_mods.len()), 1);
+        }
+        MergedCollections { mods: all_mods, failed }
+    }
+
+    /// A collection from the cache if it's fresh, else downloaded and parsed, falling back
+    /// to the cached copy if that fails
+    fn download(collection_url: &str) -> Result<FetchedCollection> {
         // Validate URL format
         if !collection_url.contains("steamcommunity.com") || !collection_url.contains("filedetails") {
-            return Err(anyhow!("Invalid Steam Workshop collection URL"));
+            return Err(anyhow!("Invalid Steam Workshop collection URL: {collection_url}"));
+        }
+
+        let cache_path = get_cache_path(collection_url);
+        let cached = cache_path.as_deref().and_then(load_cached);
+        if let Some(cached) = &cached
+            && Local::now() - cached.fetched < ChronoDuration::minutes(CACHE_FRESH_MINUTES)
+        {
+            return Ok(FetchedCollection { url: collection_url.to_string(), ..cached.clone() });
+        }
+
+        match Self::fetch(collection_url) {
+            Ok(collection) => {
+                if let Some(cache_path) = &cache_path {
+                    save_cached(cache_path, &collection);
+                }
+                Ok(collection)
+            }
+            Err(e) => match cached {
+                Some(cached) => Ok(FetchedCollection {
+                    url: collection_url.to_string(),
+                    stale: Some(format!("failed to fetch it: {e:#}")),
+                    ..cached
+                }),
+                None => Err(e),
+            },
         }
-        
+    }
+
+    /// Download and parse a collection page
+    fn fetch(collection_url: &str) -> Result<FetchedCollection> {
         // Download the HTML
-        let html_content = Self::download_page(collection_url)?;
-        
+        let html_content = http::get_page(collection_url)
+            .context("Failed to fetch collection page")?;
+
         // Verify it's a collection page
         if !SteamCollectionParser::is_collection_page(&html_content) {
-            return Err(anyhow!("URL does not appear to be a Steam Workshop collection"));
-        }
-        
-        // Get collection title for user feedback
-        if let Some(title) = SteamCollectionParser::get_collection_title(&html_content) {
-            println_step(&format!("Found collection: '{title}'"), 2);
+            return Err(anyhow!("{collection_url} does not appear to be a Steam Workshop collection"));
         }
-        
+
         // Parse the mods
         let mods = SteamCollectionParser::parse_collection_html(&html_content)
             .context("Failed to parse collection HTML")?;
-        
-        println_success(&format!("Successfully parsed {} mods from collection", mods.len()), 1);
-        
-        for (i, mod_entry) in mods.iter().enumerate() {
-            println_step(&format!("{}. {} ({})", i + 1, mod_entry.name, mod_entry.id), 2);
-        }
-        
-        Ok(mods)
+
+        Ok(FetchedCollection {
+            url: collection_url.to_string(),
+            title: SteamCollectionParser::get_collection_title(&html_content),
+            mods,
+            fetched: Local::now(),
+            stale: None,
+        })
     }
-    
-    /// Download HTML content from URL
-    fn download_page(url: &str) -> Result<String> {
-        let mut html_content = Vec::new();
-        let mut handle = Easy::new();
-        
-        handle.url(url)?;
-        handle.follow_location(true)?;
-        handle.timeout(std::time::Duration::from_secs(30))?;
-        
-        // Set a user agent to avoid being blocked
-        handle.useragent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")?;
-        
-        {
-            let mut transfer = handle.transfer();
-            transfer.write_function(|new_data| {
-                html_content.extend_from_slice(new_data);
-                Ok(new_data.len())
-            })?;
-            transfer.perform()?;
+
+    fn report(collection: &FetchedCollection) {
+        // Collection title for user feedback
+        if let Some(title) = &collection.title {
+            println_step(&format!("Found collection: '{title}'"), 2);
         }
-        
-        let response_code = handle.response_code()?;
-        if response_code != 200 {
-            return Err(anyhow!("HTTP error {}: Failed to fetch collection page", response_code));
+
+        if let Some(reason) = &collection.stale {
+            println_failure(&format!(
+                "Using the collection as fetched {}, {reason}",
+                collection.fetched.format("%Y-%m-%d %H:%M")
+            ), 2);
+        }
+
+        println_success(&format!("Successfully parsed {} mods from collection", collection.mods.len()), 1);
+
+        for (i, mod_entry) in collection.mods.iter().enumerate() {
+            println_step(&format!("{}. {} ({})", i + 1, mod_entry.name, mod_entry.id), 2);
         }
-        
-        String::from_utf8(html_content)
-            .context("Failed to decode HTML as UTF-8")
+    }
+}
+
+/// The collection's Workshop ID from its URL
+fn get_collection_id(url: &str) -> Option<u64> {
+    let start = url.find("?id=")? + 4;
+    url[start..].split('&').next()?.parse().ok()
+}
+
+/// Where a collection's last good copy is cached, once the install dir is known
+fn get_cache_path(url: &str) -> Option<PathBuf> {
+    let id = get_collection_id(url)?;
+    CACHE_PATH.get().map(|dir| dir.join(format!("{id}.json")))
+}
+
+fn load_cached(path: &Path) -> Option<FetchedCollection> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+}
+
+/// Caching is a nicety, a failure to write only costs a fetch next time
+fn save_cached(path: &Path, collection: &FetchedCollection) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(collection) {
+        let _ = fs::write(path, json);
     }
 }
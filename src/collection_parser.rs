@@ -26,7 +26,7 @@ impl SteamCollectionParser {
                             let name = title_element.text().collect::<String>().trim().to_string();
                             
                             if !name.is_empty() {
-                                mods.push(ModEntry { id, name });
+                                mods.push(ModEntry { id, name, ..ModEntry::default() });
                             }
                         }
                     }
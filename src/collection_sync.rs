@@ -0,0 +1,185 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::CollectionCommand;
+use crate::collection_dupes::report_duplicates;
+use crate::collection_fetcher::CollectionFetcher;
+use crate::config::Config;
+use crate::config::mod_entry::ModEntry;
+use crate::mod_links::InstalledLinks;
+use crate::workshop_details;
+use crate::ui::status::{println_blank, println_step, println_step_concat, println_success};
+
+const MANAGE_COLLECTION_URL: &str = "https://steamcommunity.com/sharedfiles/managecollection/?id=";
+
+/// Items that need to change in a collection for it to match the server
+pub struct CollectionDiff {
+    pub to_add: Vec<ModEntry>,
+    pub to_remove: Vec<ModEntry>,
+}
+
+impl CollectionDiff {
+    /// Compare the collection contents against the mods players need to download
+    pub fn compare(collection_mods: &[ModEntry], server_mods: &[ModEntry]) -> Self {
+        let collection_ids: HashSet<u64> = collection_mods.iter().map(|m| m.id).collect();
+        let server_ids: HashSet<u64> = server_mods.iter().map(|m| m.id).collect();
+
+        Self {
+            to_add: server_mods.iter()
+                .filter(|m| !collection_ids.contains(&m.id))
+                .cloned()
+                .collect(),
+            to_remove: collection_mods.iter()
+                .filter(|m| !server_ids.contains(&m.id))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.to_add.is_empty() && self.to_remove.is_empty()
+    }
+}
+
+/// Entry point for `dzsm collection ...`
+pub fn run(command: &CollectionCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    match command {
+        CollectionCommand::Diff { url } => {
+            // With several collections none of them alone is what players subscribe to
+            let only_collection = match config.mods.get_collection_urls()[..] {
+                [only] => Some(only),
+                _ => None,
+            };
+            let collection_url = url.as_deref()
+                .or(config.mods.published_collection_url.as_deref())
+                .filter(|url| !url.trim().is_empty())
+                .or(only_collection)
+                .ok_or_else(|| anyhow!(
+                    "No collection to compare against. Pass --url or set `mods.published_collection_url` in config.toml"
+                ))?;
+
+            print_diff(collection_url, config, Path::new(server_install_dir))
+        }
+        CollectionCommand::Dupes { url } => {
+            let collection_urls = url.as_deref().map_or_else(|| config.mods.get_collection_urls(), |url| vec![url]);
+            if collection_urls.is_empty() {
+                return Err(anyhow!("No collection to check. Pass --url or set `mods.mod_collection_urls` in config.toml"));
+            }
+
+            // As installed: excluded mods are left out and extras added
+            let mods = config.mods.filter_collection(CollectionFetcher::fetch_collections_mods(&collection_urls).complete()?);
+            println_step(&format!("Checking {} item(s) for duplicates...", mods.len()), 1);
+            let ids: Vec<u64> = mods.iter().map(|mod_entry| mod_entry.id).collect();
+            if report_duplicates(&mods, &workshop_details::fetch(&ids)?, 1) == 0 {
+                println_success("No duplicate mods found", 0);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Fetch the collection and print the manual steps needed to sync it
+fn print_diff(collection_url: &str, config: &Config, server_install_dir: &Path) -> Result<()> {
+    // Mods `collection_exclude` leaves out are in the collection on purpose
+    let collection_mods: Vec<ModEntry> = CollectionFetcher::fetch_collection_mods(collection_url)?
+        .into_iter()
+        .filter(|m| !config.mods.is_collection_excluded(m))
+        .collect();
+
+    // Server-side mods are never downloaded by players, so they don't belong in the collection,
+    // nor do `collection_extra` mods, which are installed without being in it on purpose
+    let installed_mods: Vec<ModEntry> = get_installed_workshop_mods(server_install_dir)?
+        .into_iter()
+        .filter(|m| !config.mods.is_server_only(m) && !config.mods.is_collection_extra(m))
+        .collect();
+
+    println_step(&format!(
+        "Comparing collection against {} installed client mod(s)...",
+        installed_mods.len()
+    ), 1);
+
+    let diff = CollectionDiff::compare(&collection_mods, &installed_mods);
+
+    if diff.is_empty() {
+        println_success("Collection matches the installed server mods", 0);
+        return Ok(());
+    }
+
+    if !diff.to_add.is_empty() {
+        println_step(&format!("Add to collection ({}):", diff.to_add.len()), 1);
+        for mod_entry in &diff.to_add {
+            println_step_concat(&format!("+ {} ({}) {}", mod_entry.name, mod_entry.id, mod_entry.workshop_url()), 1);
+        }
+    }
+
+    if !diff.to_remove.is_empty() {
+        println_step(&format!("Remove from collection ({}):", diff.to_remove.len()), 1);
+        for mod_entry in &diff.to_remove {
+            println_step_concat(&format!("- {} ({}) {}", mod_entry.name, mod_entry.id, mod_entry.workshop_url()), 1);
+        }
+    }
+
+    // Steam has no public API for editing collections, so the last step is manual
+    if let Some(collection_id) = extract_collection_id(collection_url) {
+        println_blank();
+        println_step(&format!("Apply these changes at: {MANAGE_COLLECTION_URL}{collection_id}"), 1);
+    }
+
+    Ok(())
+}
+
+/// Scan the server install dir for @ mod directories linked from the workshop content folder
+#[allow(clippy::doc_markdown)]
+pub fn get_installed_workshop_mods(server_install_dir: &Path) -> Result<Vec<ModEntry>> {
+    let mut mods = Vec::new();
+    let copies = InstalledLinks::load(server_install_dir).copies;
+
+    for entry in fs::read_dir(server_install_dir)?.flatten() {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+
+        if !name.starts_with('@') {
+            continue;
+        }
+
+        // Workshop content lives in .../content/<app id>/<workshop id>
+        // Copies have no link to follow, their IDs were recorded when they were made
+        let workshop_id = fs::read_link(&path).ok()
+            .and_then(|target| target.file_name().map(|n| n.to_string_lossy().to_string()))
+            .and_then(|id| id.parse::<u64>().ok())
+            .or_else(|| copies.get(name).copied());
+
+        if let Some(id) = workshop_id {
+            mods.push(ModEntry { id, name: name.trim_start_matches('@').to_string(), ..ModEntry::default() });
+        }
+    }
+
+    mods.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(mods)
+}
+
+/// The `@` directory of an installed mod, by directory name or Workshop ID
+pub fn find_installed_mod_dir(server_install_dir: &Path, target: &str) -> Option<PathBuf> {
+    let direct = server_install_dir.join(format!("@{}", target.trim().trim_start_matches('@')));
+    if direct.exists() {
+        return Some(direct);
+    }
+
+    get_installed_workshop_mods(server_install_dir).ok()?
+        .into_iter()
+        .find(|mod_entry| mod_entry.is_named(target))
+        .map(|mod_entry| server_install_dir.join(format!("@{}", mod_entry.name)))
+}
+
+/// Extract the collection ID from a Steam Workshop URL
+fn extract_collection_id(url: &str) -> Option<&str> {
+    url.find("?id=").map(|id_start| {
+        let id_part = &url[id_start + 4..];
+        let id_end = id_part.find('&').unwrap_or(id_part.len());
+        &id_part[..id_end]
+    })
+}
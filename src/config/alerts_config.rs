@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+/// Admin log lines worth a notification, watched while the server is supervised
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AlertsConfig {
+    /// Players killed by other players
+    #[serde(default)]
+    pub kills: bool,
+    /// Mines and explosive charges placed
+    #[serde(default)]
+    pub explosives: bool,
+    /// Players going over the `[dupes]` thresholds
+    #[serde(default)]
+    pub dupes: bool,
+    /// Players, by name or Steam64 ID, whose connecting is notified
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub players: Vec<String>,
+    /// Player events matching any of these, e.g. `*dismantled*Gate*`, with `*` standing for any text
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+    /// Seconds between reads of the admin log
+    #[serde(default = "default_interval_seconds")]
+    pub interval_seconds: u64,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            kills: false,
+            explosives: false,
+            dupes: false,
+            players: Vec::new(),
+            patterns: Vec::new(),
+            interval_seconds: default_interval_seconds(),
+        }
+    }
+}
+
+impl AlertsConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.kills || self.explosives || self.dupes || !self.players.is_empty() || !self.patterns.is_empty()
+    }
+}
+
+const fn default_interval_seconds() -> u64 {
+    10
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// RCon settings kept in `BEServer_x64.cfg`, and mods' BattlEye filters merged into `battleye/`
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[allow(clippy::doc_markdown)]
+pub struct BattlEyeConfig {
+    /// Port RCon listens on, the game port + 3 when unset
+    pub rcon_port: Option<u16>,
+    /// Mods, by name or Workshop ID, whose BattlEye filter files (`scripts.txt` etc.) are merged in
+    #[serde(default)]
+    pub filter_mods: Vec<String>,
+}
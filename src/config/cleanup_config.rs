@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// Housekeeping run on a restart, each task only when set
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CleanupConfig {
+    /// Delete old backups on the first restart this many hours after they last did; 0 does on every
+    /// start. The lifetimes are set on every start.
+    #[serde(default = "default_interval_hours")]
+    pub interval_hours: u64,
+    /// Minutes a dead player's body is kept before the server removes it, persisted ones included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dead_body_minutes: Option<u64>,
+    /// Minutes a ruined item is kept before the server removes it, persisted ones included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ruined_item_minutes: Option<u64>,
+    /// Delete storage backups older than this many days, the newest one is always kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup_days: Option<u64>,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            interval_hours: default_interval_hours(),
+            dead_body_minutes: None,
+            ruined_item_minutes: None,
+            backup_days: None,
+        }
+    }
+}
+
+impl CleanupConfig {
+    pub const fn is_enabled(&self) -> bool {
+        self.dead_body_minutes.is_some()
+            || self.ruined_item_minutes.is_some()
+            || self.backup_days.is_some()
+    }
+}
+
+const fn default_interval_hours() -> u64 {
+    24
+}
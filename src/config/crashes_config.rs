@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CrashesConfig {
+    /// Local database of known crash signatures, relative to the server install dir
+    #[serde(default = "default_known_issues_file")]
+    pub known_issues_file: String,
+    /// Community-maintained database of known crash signatures, checked after the local one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub known_issues_url: Option<String>,
+    /// Lines of the RPT to print after a crash
+    #[serde(default = "default_tail_lines")]
+    pub tail_lines: usize,
+}
+
+impl Default for CrashesConfig {
+    fn default() -> Self {
+        Self {
+            known_issues_file: default_known_issues_file(),
+            known_issues_url: None,
+            tail_lines: default_tail_lines(),
+        }
+    }
+}
+
+fn default_known_issues_file() -> String {
+    "known_crashes.toml".to_string()
+}
+
+const fn default_tail_lines() -> usize {
+    30
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+/// Keeping big downloads from saturating a shared connection
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DownloadsConfig {
+    /// Limit `SteamCMD` to this many kilobits per second, e.g. 50000 for 50 Mbit/s
+    #[serde(default)]
+    pub throttle_kbps: Option<u32>,
+    /// Outside the window, mod updates adding up to more than this many MB wait for it;
+    /// the installed versions are used until then. New mods are always downloaded.
+    #[serde(default)]
+    pub defer_over_mb: Option<u64>,
+    /// Start of the window large updates wait for, as HH:MM, may wrap past midnight
+    #[serde(default = "default_from")]
+    pub from: String,
+    /// End of the window as HH:MM
+    #[serde(default = "default_to")]
+    pub to: String,
+    /// Space to leave free on a drive after a download; downloads that wouldn't leave it stop
+    /// before `SteamCMD` starts. 0 turns the check off
+    #[serde(default = "default_min_free_mb")]
+    pub min_free_mb: u64,
+}
+
+impl Default for DownloadsConfig {
+    fn default() -> Self {
+        Self {
+            throttle_kbps: None,
+            defer_over_mb: None,
+            from: default_from(),
+            to: default_to(),
+            min_free_mb: default_min_free_mb(),
+        }
+    }
+}
+
+fn default_from() -> String {
+    "02:00".to_string()
+}
+
+fn default_to() -> String {
+    "07:00".to_string()
+}
+
+const fn default_min_free_mb() -> u64 {
+    1024
+}
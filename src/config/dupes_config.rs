@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+/// Thresholds for `dzsm dupes report`; anything above them is flagged for a closer look
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DupesConfig {
+    /// Length of the sliding window the per-player counts are taken over
+    #[serde(default = "default_window_minutes")]
+    pub window_minutes: u64,
+    /// Reconnects within the window, relogging is how most dupes are done
+    #[serde(default = "default_max_reconnects")]
+    pub max_reconnects: usize,
+    /// Containers and base parts placed, packed, folded, or dismantled within the window
+    #[serde(default = "default_max_container_actions")]
+    pub max_container_actions: usize,
+    /// Times the same item may be placed on the same spot over the whole report
+    #[serde(default = "default_max_same_spot_placements")]
+    pub max_same_spot_placements: usize,
+}
+
+impl Default for DupesConfig {
+    fn default() -> Self {
+        Self {
+            window_minutes: default_window_minutes(),
+            max_reconnects: default_max_reconnects(),
+            max_container_actions: default_max_container_actions(),
+            max_same_spot_placements: default_max_same_spot_placements(),
+        }
+    }
+}
+
+const fn default_window_minutes() -> u64 {
+    10
+}
+
+const fn default_max_reconnects() -> usize {
+    3
+}
+
+const fn default_max_container_actions() -> usize {
+    10
+}
+
+const fn default_max_same_spot_placements() -> usize {
+    2
+}
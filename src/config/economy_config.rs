@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Adjustments to the active mission's `types.xml`, applied before every start
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct EconomyConfig {
+    /// Patch files applied in order, relative to the install dir: TOML with `[types.<classname>]`
+    /// tables like the ones below, or XML `<type>` fragments. Mods' own patches work too, e.g.
+    /// `@SomeMod/extras/types_patch.toml`
+    #[serde(default)]
+    pub patch_files: Vec<String>,
+    /// Values for classnames, e.g. `[economy.types.AKM]` with `nominal = 5`; a trailing `*` matches
+    /// every classname with that prefix. Applied after the patch files.
+    #[serde(default)]
+    pub types: BTreeMap<String, BTreeMap<String, i64>>,
+}
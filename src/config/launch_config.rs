@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Parameters added to the server's command line, after `-config`, `-profiles`, and `-port`
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[allow(clippy::doc_markdown, clippy::struct_excessive_bools)]
+pub struct LaunchConfig {
+    /// `-cpuCount`, the cores the server spreads its work over
+    pub cpu_count: Option<u32>,
+    /// `-limitFPS`, the server's frame rate cap
+    pub limit_fps: Option<u32>,
+    /// `-doLogs`, script logging
+    #[serde(default)]
+    pub dologs: bool,
+    /// `-adminLog`, the `.ADM` log of player connections, kills, and positions
+    #[serde(default)]
+    pub adminlog: bool,
+    /// `-netLog`, network traffic logging
+    #[serde(default)]
+    pub netlog: bool,
+    /// `-freezeCheck`, which stops the server when a frame takes longer than five minutes
+    #[serde(default)]
+    pub freezecheck: bool,
+    /// `-BEpath`, where BattlEye's files are instead of `battleye` in the install directory
+    pub be_path: Option<String>,
+    /// `-mission`, the mission folder to load instead of the `template` in `serverDZ.cfg`
+    pub mission: Option<String>,
+    /// Anything else, passed as is after the above and before the mods
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+}
+
+impl LaunchConfig {
+    /// The command line arguments for these settings
+    #[allow(clippy::doc_markdown)]
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(cpu_count) = self.cpu_count {
+            args.push(format!("-cpuCount={cpu_count}"));
+        }
+        if let Some(limit_fps) = self.limit_fps {
+            args.push(format!("-limitFPS={limit_fps}"));
+        }
+
+        let flags = [
+            (self.dologs, "-doLogs"),
+            (self.adminlog, "-adminLog"),
+            (self.netlog, "-netLog"),
+            (self.freezecheck, "-freezeCheck"),
+        ];
+        args.extend(flags.into_iter().filter(|(enabled, _)| *enabled).map(|(_, flag)| flag.to_string()));
+
+        if let Some(be_path) = &self.be_path {
+            args.push(format!("-BEpath={be_path}"));
+        }
+        if let Some(mission) = &self.mission {
+            args.push(format!("-mission={mission}"));
+        }
+
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LoggingConfig {
+    /// Console verbosity: error, warn, info, debug, or trace (overridden by --verbose/--quiet)
+    #[serde(default = "default_level")]
+    pub level: String,
+    /// Also write output to `.dzsm/logs/dzsm.log`
+    #[serde(default = "default_file")]
+    pub file: bool,
+    /// Log file verbosity
+    #[serde(default = "default_file_level")]
+    pub file_level: String,
+    /// Rotate the log file once it reaches this size
+    #[serde(default = "default_max_file_size_mb")]
+    pub max_file_size_mb: u64,
+    /// Number of rotated log files to keep
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+    /// Remove the server's logs and crash dumps (in its profiles directory) older than this many days
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_days: Option<u64>,
+    /// Keep only this many of the newest server logs of each kind, e.g. RPTs or script logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_count: Option<usize>,
+    /// Zip the server logs being removed into `.dzsm/logs/archive/` instead of deleting them
+    #[serde(default)]
+    pub archive: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_level(),
+            file: default_file(),
+            file_level: default_file_level(),
+            max_file_size_mb: default_max_file_size_mb(),
+            max_files: default_max_files(),
+            keep_days: None,
+            keep_count: None,
+            archive: false,
+        }
+    }
+}
+
+fn default_level() -> String {
+    "info".to_string()
+}
+
+const fn default_file() -> bool {
+    true
+}
+
+fn default_file_level() -> String {
+    "debug".to_string()
+}
+
+const fn default_max_file_size_mb() -> u64 {
+    10
+}
+
+const fn default_max_files() -> usize {
+    5
+}
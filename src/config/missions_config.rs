@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Where `dzsm mission install` gets community maps' mission files
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MissionsConfig {
+    /// Mission name to a zip URL, or to the mod whose extras folder ships it, e.g.
+    /// `"dayzOffline.namalsk" = "Namalsk Island"`
+    #[serde(default)]
+    pub sources: BTreeMap<String, String>,
+}
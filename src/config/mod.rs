@@ -1,43 +1,212 @@
+pub mod alerts_config;
+pub mod battleye_config;
+pub mod cleanup_config;
+pub mod crashes_config;
+pub mod downloads_config;
+pub mod dupes_config;
+pub mod economy_config;
+pub mod launch_config;
+pub mod logging_config;
+pub mod missions_config;
 pub mod mod_entry;
 pub mod mods_config;
+pub mod notifications_config;
+pub mod players_config;
+pub mod positions_config;
+pub mod privacy_config;
+pub mod profile_files_config;
+pub mod public_config;
+pub mod schedule_config;
+pub mod secrets_config;
 pub mod server_config;
+pub mod shutdown_config;
+pub mod steam_config;
+pub mod steamcmd_config;
+pub mod storage_config;
+pub mod supervise_config;
+pub mod sync_config;
+pub mod time_config;
+pub mod validation_config;
+pub mod weather_config;
+pub mod web_config;
 
-use std::{fs, path::Path};
+use std::collections::BTreeMap;
+use std::{fs, path::{Path, PathBuf}};
 use serde::{Deserialize, Serialize};
 use anyhow::{Context, Result, anyhow};
+use toml_edit::DocumentMut;
 
+pub use alerts_config::AlertsConfig;
+pub use battleye_config::BattlEyeConfig;
+pub use cleanup_config::CleanupConfig;
+pub use crashes_config::CrashesConfig;
+pub use downloads_config::DownloadsConfig;
+pub use dupes_config::DupesConfig;
+pub use economy_config::EconomyConfig;
+pub use launch_config::LaunchConfig;
+pub use logging_config::LoggingConfig;
+pub use missions_config::MissionsConfig;
 pub use server_config::ServerConfig;
+pub use shutdown_config::ShutdownConfig;
+pub use steam_config::SteamConfig;
+pub use steamcmd_config::SteamCmdConfig;
+pub use storage_config::StorageConfig;
 pub use mods_config::ModsConfig;
+pub use notifications_config::{NotificationEvent, NotificationsConfig};
+pub use players_config::PlayersConfig;
+pub use positions_config::PositionsConfig;
+pub use privacy_config::PrivacyConfig;
+pub use profile_files_config::ProfileFilesConfig;
+pub use public_config::PublicConfig;
+pub use schedule_config::ScheduleConfig;
+pub use secrets_config::SecretsConfig;
+pub use supervise_config::SuperviseConfig;
+pub use sync_config::SyncConfig;
+pub use time_config::TimeConfig;
+pub use validation_config::ValidationConfig;
+pub use weather_config::WeatherConfig;
+pub use web_config::WebConfig;
 
+use crate::ui::json::is_json_output;
 use crate::ui::status::{println_failure, println_step, println_success};
 
-const CONFIG_FILE: &str = "config.toml";
-const DEFAULT_CONFIG: &str = include_str!("../../defaults/config.toml");
+pub const CONFIG_FILE: &str = "config.toml";
+pub const DEFAULT_CONFIG: &str = include_str!("../../defaults/config.toml");
+/// Where a profile's server is installed unless it sets `install_dir`
+const PROFILES_INSTALL_DIR: &str = "servers";
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
     pub server: ServerConfig,
+    #[serde(default)]
+    pub launch: LaunchConfig,
     pub mods: ModsConfig,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+    #[serde(default)]
+    pub supervise: SuperviseConfig,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
+    #[serde(default)]
+    pub sync: SyncConfig,
+    #[serde(default)]
+    pub players: PlayersConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub crashes: CrashesConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub cleanup: CleanupConfig,
+    #[serde(default)]
+    pub time: TimeConfig,
+    #[serde(default)]
+    pub weather: WeatherConfig,
+    #[serde(default)]
+    pub positions: PositionsConfig,
+    #[serde(default)]
+    pub dupes: DupesConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub validation: ValidationConfig,
+    #[serde(default)]
+    pub missions: MissionsConfig,
+    #[serde(default)]
+    pub economy: EconomyConfig,
+    #[serde(default)]
+    pub battleye: BattlEyeConfig,
+    #[serde(default)]
+    pub profile_files: ProfileFilesConfig,
+    #[serde(default)]
+    pub public: PublicConfig,
+    #[serde(default)]
+    pub steam: SteamConfig,
+    #[serde(default)]
+    pub steamcmd: SteamCmdConfig,
+    #[serde(default)]
+    pub downloads: DownloadsConfig,
+    #[serde(default)]
+    pub web: WebConfig,
+    /// Further servers sharing these settings, selected with `--profile <name>`.
+    /// Each is an `install_dir` plus any sections to override, e.g. `[profiles.test.mods]`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, toml::Table>,
 }
 
 impl Config {
-    pub fn load(config_path: &str) -> Result<Self> {
-        let config_content = fs::read_to_string(config_path)
-            .context("Failed to read config file")?;
-        Self::parse(&config_content)
-    }
-
     pub fn parse(raw_toml: &str) -> Result<Self> {
         toml::from_str(raw_toml)
             .context("Failed to parse config")
     }
 
+    /// The settings for `profile`, its `[profiles.<name>]` section laid over the shared ones,
+    /// and the directory its server is installed in
+    pub fn parse_profile(raw_toml: &str, profile: &str, root_dir: &Path) -> Result<(Self, PathBuf)> {
+        let mut table: toml::Table = toml::from_str(raw_toml)
+            .context("Failed to parse config")?;
+
+        let mut profiles = match table.remove("profiles") {
+            Some(toml::Value::Table(profiles)) => profiles,
+            _ => toml::Table::new(),
+        };
+        let Some(toml::Value::Table(mut overrides)) = profiles.remove(profile) else {
+            let defined: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            return Err(anyhow!(
+                "Unknown profile '{profile}', defined profiles: {}",
+                if defined.is_empty() { "(none)".to_string() } else { defined.join(", ") }
+            ));
+        };
+
+        let install_dir = match overrides.remove("install_dir") {
+            // Relative to the directory config.toml is in, absolute paths replace it
+            Some(toml::Value::String(install_dir)) => root_dir.join(install_dir),
+            Some(_) => return Err(anyhow!("`profiles.{profile}.install_dir` must be a path")),
+            None => root_dir.join(PROFILES_INSTALL_DIR).join(profile),
+        };
+
+        merge_tables(&mut table, overrides);
+        let config = toml::Value::Table(table).try_into()
+            .context(format!("Failed to parse config for profile '{profile}'"))?;
+        Ok((config, install_dir))
+    }
+
+    /// Load `config.toml` from `root_dir` for a profile, see `parse_profile`
+    pub fn load_profile(root_dir: &Path, profile: &str) -> Result<(Self, PathBuf)> {
+        let config_content = fs::read_to_string(root_dir.join(CONFIG_FILE))
+            .context("Failed to read config file")?;
+        Self::parse_profile(&config_content, profile, root_dir)
+    }
+
     /// Static function to save configuration content to file
     pub fn save(config_path: &str, config_content: &str) -> Result<()> {
         fs::write(config_path, config_content)
             .context("Failed to write config file")
     }
 
+    /// Edit `config.toml` in place, keeping its comments and layout.
+    /// Nothing is written unless the edited file still loads.
+    pub fn edit(edit: impl FnOnce(&mut DocumentMut) -> Result<()>) -> Result<Self> {
+        let config_content = fs::read_to_string(CONFIG_FILE)
+            .context("Failed to read config file")?;
+        let mut document: DocumentMut = config_content.parse()
+            .context("Failed to parse config")?;
+
+        edit(&mut document)?;
+
+        let edited_content = document.to_string();
+        let config = Self::parse(&edited_content)?;
+        Self::save(CONFIG_FILE, &edited_content)?;
+        Ok(config)
+    }
+
     /// Save this config instance to file (convenience method)
     pub fn _save_to_file(&self, config_path: &str) -> Result<()> {
         let config_content = toml::to_string_pretty(self)
@@ -46,19 +215,31 @@ impl Config {
     }
 
     /// Print configuration summary
-    pub fn print_summary(&self, server_install_dir: &str) {
+    pub fn print_summary(&self, server_install_dir: &str, profile: Option<&str>) {
+        // Scripts reading --json output can read config.toml themselves
+        if is_json_output() {
+            return;
+        }
+
         println!("\n=== Configuration Summary ===");
+        if let Some(profile) = profile {
+            println!("Profile: {profile}");
+        }
         println!("Server:");
         println!("  steamcmd_dir: {}", self.server.steamcmd_dir);
         println!("  username: {}", self.server.username);
         println!("  install_dir: {server_install_dir}");
         
         println!("Mods:");
-        // Show collection URL if present
-        if let Some(collection_url) = &self.mods.mod_collection_url {
-            if !collection_url.trim().is_empty() {
-                println!("  Collection URL: {collection_url}");
-            }
+        // Show collection URLs if present
+        for collection_url in self.mods.get_collection_urls() {
+            println!("  Collection URL: {collection_url}");
+        }
+
+        if let Some(published_url) = &self.mods.published_collection_url
+            && !published_url.trim().is_empty()
+        {
+            println!("  Published collection URL: {published_url}");
         }
 
         // Show individual mods if present
@@ -77,13 +258,14 @@ impl Config {
     }
 
     /// Check for configuration file and create if missing
-    /// Returns the loaded configuration and prints status messages
-    pub fn check_and_load(server_install_dir: &str) -> Result<Self> {
+    /// Returns the loaded configuration and the server install dir, and prints status messages
+    pub fn check_and_load(root_dir: &str, profile: Option<&str>) -> Result<(Self, String)> {
         let found_existing_config = Path::new(CONFIG_FILE).exists();
         
-        let config = if found_existing_config {
+        let config_content = if found_existing_config {
             println_success("Configuration found", 0);
-            Self::load(CONFIG_FILE)?
+            fs::read_to_string(CONFIG_FILE)
+                .context("Failed to read config file")?
         } else {
             println_failure("Configuration missing", 0);
             println_step("Creating default configuration", 1);
@@ -92,22 +274,33 @@ impl Config {
             Self::save(CONFIG_FILE, DEFAULT_CONFIG)?;
             
             println_success(&format!("Default configuration created: '{CONFIG_FILE}'"), 1);
-            Self::parse(DEFAULT_CONFIG)?
+            DEFAULT_CONFIG.to_string()
+        };
+
+        let (config, server_install_dir) = match profile {
+            Some(profile) if found_existing_config => {
+                let (config, install_dir) = Self::parse_profile(&config_content, profile, Path::new(root_dir))?;
+                (config, install_dir.to_string_lossy().to_string())
+            }
+            _ => (Self::parse(&config_content)?, root_dir.to_string()),
         };
 
         // Always show the config summary
-        config.print_summary(server_install_dir);
+        config.print_summary(&server_install_dir, profile);
 
         if found_existing_config {
-            Ok(config)
+            Ok((config, server_install_dir))
+        } else if is_json_output() {
+            Err(anyhow!("New configuration created - please customize '{CONFIG_FILE}' before running again"))
         } else {
             println!("⚠️  IMPORTANT: Please edit '{CONFIG_FILE}' before running DZSM again:");
             println!("   1. Set your Steam username (account must own DayZ)");
             println!("   2. Adjust steamcmd_dir path if needed");
             println!("   3. Add any mods you want to the mod_list");
             println!();
-            println!("   Note: 'anonymous' login will NOT work - you need a valid Steam account!");
-            println!("   You must login to SteamCMD manually once to cache credentials.");
+            println!("   Note: mods need a Steam account that owns DayZ, 'anonymous' can only download the");
+            println!("   server itself (see `steam.anonymous_server_updates`). Log in to SteamCMD manually once");
+            println!("   to cache credentials, or store them with `dzsm steam login` (see `steam.credential_source`).");
             
             Err(anyhow!(
                 "New configuration created - please customize '{}' before running again", 
@@ -115,4 +308,18 @@ impl Config {
             ))
         }
     }
-}
\ No newline at end of file
+}
+
+/// Recursively lay `overrides` over `base`: tables merge, anything else replaces
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(override_table)) => {
+                merge_tables(base_table, override_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
@@ -1,14 +1,161 @@
 use std::fmt;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+const WORKSHOP_ITEM_URL: &str = "https://steamcommunity.com/sharedfiles/filedetails/?id=";
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct ModEntry {
     pub id: u64,
     pub name: String,
+    /// Where the mod loads, see `ModsConfig::get_side`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<ModSide>,
+    /// Name of its `@` folder instead of the Workshop title, see `ModsConfig::get_folder`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder_name: Option<String>,
+    /// Group to enable and disable it with, e.g. "cosmetics", see `ModsConfig::get_group`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(flatten)]
+    pub order: ModOrder,
+    /// The collection the mod was found in, for mods that came from one
+    #[serde(skip)]
+    pub collection: Option<String>,
+}
+
+/// Which side a mod runs on, which decides whether it goes in `-mod` or `-serverMod`
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModSide {
+    /// Players need it; loaded with `-mod`, as anything clients load must run on the server too
+    Client,
+    /// Only the server runs it, players never download it; loaded with `-serverMod`
+    Server,
+    /// Players and the server both need it; loaded with `-mod`
+    Both,
+}
+
+impl ModSide {
+    /// Whether the mod goes in `-serverMod` rather than `-mod`
+    pub const fn is_server_only(self) -> bool {
+        matches!(self, Self::Server)
+    }
+}
+
+/// Where a mod goes in the `-mod`/`-serverMod` list; mods are referred to by name or Workshop ID
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ModOrder {
+    /// Lower loads first, mods without one count as 0 and otherwise keep their listed order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Mods that must load before this one, e.g. `["CF"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub load_after: Vec<String>,
+    /// Mods that must load after this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub load_before: Vec<String>,
+}
+
+/// Ordering for a mod that isn't listed in config.toml, such as one from the collection
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ModOrderRule {
+    /// Name or Workshop ID of the mod
+    #[serde(rename = "mod")]
+    pub target: String,
+    #[serde(flatten)]
+    pub order: ModOrder,
+}
+
+/// Economy XML files a mod ships (types, events, spawnable types...) to merge into the active mission
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ModEconomyRule {
+    /// Name or Workshop ID of the mod
+    #[serde(rename = "mod")]
+    pub target: String,
+    /// Files to merge, relative to the mod's directory; every economy file in the mod when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub strategy: EconomyMerge,
+}
+
+/// How a mod's entries are merged with the mission's, matching entries by their `name`
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EconomyMerge {
+    /// Add entries the mission doesn't have yet, leaving the mission's own alone
+    #[default]
+    Append,
+    /// Add new entries and replace the mission's entries of the same name
+    Overwrite,
+}
+
+impl ModEntry {
+    /// Whether `reference` names this mod, by name (with or without the `@`) or Workshop ID
+    pub fn is_named(&self, reference: &str) -> bool {
+        let reference = reference.trim().trim_start_matches('@');
+        reference.eq_ignore_ascii_case(&self.name) || reference == self.id.to_string()
+    }
+
+    /// Whether `pattern` names this mod like `is_named` does, or matches its name with `*`
+    /// standing for any text, e.g. `*Cosmetics*`
+    pub fn matches(&self, pattern: &str) -> bool {
+        let pattern = pattern.trim().trim_start_matches('@');
+        if !pattern.contains('*') {
+            return self.is_named(pattern);
+        }
+
+        matches_wildcard(&self.name, pattern)
+    }
+
+    /// Link to the mod's Steam Workshop page
+    pub fn workshop_url(&self) -> String {
+        format!("{WORKSHOP_ITEM_URL}{}", self.id)
+    }
+}
+
+/// A mod name made safe for a folder and the `-mod` list: characters Windows paths can't
+/// hold, `;` (which separates mods), `$` and anything outside ASCII are dropped, and the
+/// trailing dots and spaces Windows strips from names are trimmed
+pub fn sanitize_folder_name(name: &str) -> String {
+    let sanitized: String = name.chars()
+        .filter(|c| c.is_ascii_graphic() || *c == ' ')
+        .filter(|c| !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' | ';' | '$'))
+        .collect();
+    sanitized.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_start_matches('@')
+        .trim_end_matches(['.', ' '])
+        .to_string()
 }
 
 impl fmt::Display for ModEntry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{} ({})", self.id, self.name)
     }
-}
\ No newline at end of file
+}
+
+/// Whether `text` matches `pattern`, ignoring case, with `*` standing for any text
+pub fn matches_wildcard(text: &str, pattern: &str) -> bool {
+    let text = text.to_lowercase();
+    let pattern = pattern.to_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return text == pattern;
+    }
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) {
+        return false;
+    }
+
+    // The parts in between, in order, somewhere between the first and the last
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
@@ -1,10 +1,302 @@
-use serde::{Deserialize, Serialize};
-use crate::config::mod_entry::ModEntry;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::config::mod_entry::{ModEconomyRule, ModEntry, ModOrderRule, ModSide, sanitize_folder_name};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ModsConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub server_mod_list: Option<Vec<ModEntry>>,
+    /// Steam Workshop collections for client mods; `mod_collection_url` with a single URL still works
+    #[serde(default, alias = "mod_collection_url", deserialize_with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub mod_collection_urls: Vec<String>,
+    /// Collection mods the server leaves out, by name, Workshop ID, or name pattern with `*`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collection_exclude: Vec<String>,
+    /// Mods installed as if they were in the collections without being in them, client mods
+    /// unless their entry says otherwise
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collection_extra: Vec<ModEntry>,
+    /// Mods on this machine rather than the Workshop, e.g. ones being developed, by the path of
+    /// their folder. Linked in as `@<folder name>` and loaded like collection mods.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub local_mods: Vec<PathBuf>,
+    /// Refuse to install or start when a collection can't be fetched or loaded from the cache,
+    /// instead of leaving its mods out and having players kicked for content the server lacks
+    #[serde(default)]
+    pub require_collection: bool,
+    /// Player-facing collection compared by `dzsm collection diff`
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mod_collection_url: Option<String>,
-}
\ No newline at end of file
+    pub published_collection_url: Option<String>,
+    /// Load order for mods not listed here, such as collection mods
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub load_order: Vec<ModOrderRule>,
+    /// Sides for mods by name or Workshop ID, mainly for collection mods
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sides: BTreeMap<String, ModSide>,
+    /// `@` folder names for mods by name or Workshop ID, mainly for collection mods
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub folder_names: BTreeMap<String, String>,
+    /// Groups for mods by name or Workshop ID, mainly for collection mods
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, String>,
+    /// Groups whose mods are left out of installs and launches
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_groups: Vec<String>,
+    /// Mods left out of installs and launches, by name or Workshop ID
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled: Vec<String>,
+    /// Named mod sets to launch instead of every mod, with `dzsm run --mod-preset <name>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub presets: BTreeMap<String, ModPreset>,
+    /// The preset chosen for this run, see `select_preset`
+    #[serde(skip)]
+    pub active_preset: Option<String>,
+    /// Mods whose economy XML files are merged into the active mission before each start
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub economy: Vec<ModEconomyRule>,
+    /// How mods are put into the server install dir
+    #[serde(default)]
+    pub install_strategy: InstallStrategy,
+    /// How mod keys are put into the keys dir, see `get_key_strategy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_strategy: Option<KeyStrategy>,
+    /// Load well-known frameworks (CF, Dabs Framework, Expansion Core) before other mods,
+    /// unless their order is set by hand
+    #[serde(default = "default_frameworks_first")]
+    pub frameworks_first: bool,
+    /// Tries at a mod download that times out, each resuming the previous one's partial download
+    #[serde(default = "default_download_attempts")]
+    pub download_attempts: u32,
+    /// Seconds to wait after the first timeout, doubling after each one after that
+    #[serde(default = "default_download_retry_delay_seconds")]
+    pub download_retry_delay_seconds: u64,
+}
+
+/// A set of mods to run, e.g. a light one for testing; mods not in it are left out like disabled ones
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ModPreset {
+    /// Mods in the preset, by name, Workshop ID, or name pattern with `*`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mods: Vec<String>,
+    /// Groups whose mods are all in the preset
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+const fn default_frameworks_first() -> bool {
+    true
+}
+
+const fn default_download_attempts() -> u32 {
+    5
+}
+
+const fn default_download_retry_delay_seconds() -> u64 {
+    15
+}
+
+/// How a mod's `SteamCMD` download gets into the server install dir as its `@` folder
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallStrategy {
+    /// Directory symlink; needs admin rights or Developer Mode, and some shares don't support them
+    #[default]
+    Symlink,
+    /// Directory junction, works without admin rights but only to local drives
+    Junction,
+    /// A full copy, kept between runs and updated in place so unchanged files aren't copied again
+    Copy,
+    /// A copy made of hard links, taking no extra space; files on another volume are copied
+    Hardlink,
+}
+
+/// How a mod's `.bikey` files get into the server's keys dir
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyStrategy {
+    /// File symlink; always current, but breaks if the workshop content is removed
+    Symlink,
+    /// A copy, replaced whenever the mod's key changes; survives the workshop content going away
+    Copy,
+}
+
+impl KeyStrategy {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Symlink => "symlink",
+            Self::Copy => "copy",
+        }
+    }
+}
+
+impl InstallStrategy {
+    /// Whether the `@` folder is a real directory that's kept and synced, rather than a link
+    pub const fn is_copy(self) -> bool {
+        matches!(self, Self::Copy | Self::Hardlink)
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Symlink => "symlink",
+            Self::Junction => "junction",
+            Self::Copy => "copy",
+            Self::Hardlink => "hardlink",
+        }
+    }
+}
+
+impl ModsConfig {
+    /// The configured collection URLs, skipping blank ones
+    pub fn get_collection_urls(&self) -> Vec<&str> {
+        self.mod_collection_urls.iter()
+            .map(String::as_str)
+            .filter(|url| !url.trim().is_empty())
+            .collect()
+    }
+
+    /// The collections' mods with `collection_exclude` left out and `collection_extra` added
+    pub fn filter_collection(&self, mods: Vec<ModEntry>) -> Vec<ModEntry> {
+        let mut mods: Vec<ModEntry> = mods.into_iter()
+            .filter(|mod_entry| !self.is_collection_excluded(mod_entry))
+            .collect();
+        for extra in &self.collection_extra {
+            if !mods.iter().any(|mod_entry| mod_entry.id == extra.id) {
+                mods.push(extra.clone());
+            }
+        }
+        mods
+    }
+
+    /// Whether a collection mod is left out by `collection_exclude`
+    pub fn is_collection_excluded(&self, mod_entry: &ModEntry) -> bool {
+        self.collection_exclude.iter().any(|pattern| mod_entry.matches(pattern))
+    }
+
+    /// Whether a mod is one of `collection_extra`, installed without being in a collection
+    pub fn is_collection_extra(&self, mod_entry: &ModEntry) -> bool {
+        self.collection_extra.iter().any(|extra| extra.id == mod_entry.id)
+    }
+
+    /// `local_mods` as mods named after their folders, without the `@`, with the folder each is in.
+    /// They have no Workshop ID, so they're referred to by name in `sides`, `load_order`, and the like.
+    pub fn get_local_mods(&self) -> Vec<(ModEntry, PathBuf)> {
+        self.local_mods.iter()
+            .map(|path| {
+                let name = path.file_name().map(|name| name.to_string_lossy().trim_start_matches('@').to_string()).unwrap_or_default();
+                (ModEntry { name, ..ModEntry::default() }, path.clone())
+            })
+            .filter(|(mod_entry, _)| !self.is_disabled(mod_entry))
+            .collect()
+    }
+
+    /// Where a mod loads: its own `side`, else its entry in `[mods.sides]`, else `default`
+    /// for the list it came from (server for `server_mod_list`, client for the collection)
+    pub fn get_side(&self, mod_entry: &ModEntry, default: ModSide) -> ModSide {
+        mod_entry.side
+            .or_else(|| {
+                self.sides.iter()
+                    .find(|(reference, _)| mod_entry.is_named(reference))
+                    .map(|(_, side)| *side)
+            })
+            .unwrap_or(default)
+    }
+
+    /// The mod's group: its own `group`, else its entry in `[mods.groups]`
+    pub fn get_group<'a>(&'a self, mod_entry: &'a ModEntry) -> Option<&'a str> {
+        mod_entry.group.as_deref()
+            .or_else(|| {
+                self.groups.iter()
+                    .find(|(reference, _)| mod_entry.is_named(reference))
+                    .map(|(_, group)| group.as_str())
+            })
+    }
+
+    /// Whether a mod is left out, by `disabled` or by its group being in `disabled_groups`
+    pub fn is_disabled(&self, mod_entry: &ModEntry) -> bool {
+        self.disabled.iter().any(|reference| mod_entry.is_named(reference))
+            || self.get_group(mod_entry).is_some_and(|group| {
+                self.disabled_groups.iter().any(|disabled| disabled.eq_ignore_ascii_case(group))
+            })
+            || !self.is_in_active_preset(mod_entry)
+    }
+
+    /// Limit this run to the mods of a preset, anything else counts as disabled
+    pub fn select_preset(&mut self, name: &str) -> Result<()> {
+        if !self.presets.contains_key(name) {
+            let known: Vec<&str> = self.presets.keys().map(String::as_str).collect();
+            if known.is_empty() {
+                return Err(anyhow!("No mod preset named {name}, none are set up in [mods.presets]"));
+            }
+            return Err(anyhow!("No mod preset named {name}, the presets are: {}", known.join(", ")));
+        }
+        self.active_preset = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Whether a mod is in the preset chosen for this run, every mod is without one
+    fn is_in_active_preset(&self, mod_entry: &ModEntry) -> bool {
+        let Some(preset) = self.active_preset.as_ref().and_then(|name| self.presets.get(name)) else {
+            return true;
+        };
+        preset.mods.iter().any(|pattern| mod_entry.matches(pattern))
+            || self.get_group(mod_entry).is_some_and(|group| {
+                preset.groups.iter().any(|wanted| wanted.eq_ignore_ascii_case(group))
+            })
+    }
+
+    /// How keys are installed: `key_strategy`, else symlinks along with symlinked mods and
+    /// copies otherwise, as junctions only work for directories
+    pub fn get_key_strategy(&self) -> KeyStrategy {
+        self.key_strategy.unwrap_or(match self.install_strategy {
+            InstallStrategy::Symlink => KeyStrategy::Symlink,
+            InstallStrategy::Junction | InstallStrategy::Copy | InstallStrategy::Hardlink => KeyStrategy::Copy,
+        })
+    }
+
+    /// The mod's `@` folder in the server install: its own `folder_name`, else its entry in
+    /// `[mods.folder_names]`, else its Workshop title; sanitized, falling back to the Workshop ID
+    pub fn get_folder(&self, mod_entry: &ModEntry) -> String {
+        let name = mod_entry.folder_name.as_deref()
+            .or_else(|| {
+                self.folder_names.iter()
+                    .find(|(reference, _)| mod_entry.is_named(reference))
+                    .map(|(_, folder)| folder.as_str())
+            })
+            .unwrap_or(&mod_entry.name);
+        match sanitize_folder_name(name) {
+            folder if folder.is_empty() => format!("@{}", mod_entry.id),
+            folder => format!("@{folder}"),
+        }
+    }
+
+    /// Whether an installed mod only runs on the server and so never belongs in the players' collection
+    pub fn is_server_only(&self, mod_entry: &ModEntry) -> bool {
+        let listed = self.server_mod_list.as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .find(|listed| listed.id == mod_entry.id);
+        match listed {
+            Some(listed) => self.get_side(listed, ModSide::Server),
+            None => self.get_side(mod_entry, ModSide::Client),
+        }
+        .is_server_only()
+    }
+}
+
+/// A single string or a list of them
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(url) => vec![url],
+        OneOrMany::Many(urls) => urls,
+    })
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct NotificationsConfig {
+    /// Discord webhook that receives notifications
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discord_webhook_url: Option<String>,
+    /// Telegram bot token, from @`BotFather`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram_bot_token: Option<String>,
+    /// Telegram chat the bot posts to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram_chat_id: Option<String>,
+    /// Post a digest of changed mods after each update run
+    #[serde(default = "default_update_digest")]
+    pub update_digest: bool,
+    /// Post a report when the server crashes
+    #[serde(default = "default_crashes")]
+    pub crashes: bool,
+    /// Which notifications go to Discord
+    #[serde(default = "default_discord")]
+    pub discord: NotificationRoute,
+    /// Which notifications go to Telegram
+    #[serde(default = "default_telegram")]
+    pub telegram: NotificationRoute,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            discord_webhook_url: None,
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+            update_digest: default_update_digest(),
+            crashes: default_crashes(),
+            discord: default_discord(),
+            telegram: default_telegram(),
+        }
+    }
+}
+
+/// What a notification is about
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// The server exited unexpectedly
+    Crash,
+    /// A crashing mod was left out
+    Quarantine,
+    /// Updating the server or mods failed, the installed version was started
+    UpdateFailed,
+    /// Mods changed in an update run
+    UpdateDigest,
+    /// Maintenance mode started or ended, see `dzsm maintenance`
+    Maintenance,
+    /// A wipe started or finished, see `dzsm wipe-day`
+    Wipe,
+    /// Admin log lines matched `[alerts]`
+    AdminLog,
+}
+
+impl NotificationEvent {
+    pub const fn severity(self) -> Severity {
+        match self {
+            Self::Crash | Self::UpdateFailed => Severity::Critical,
+            Self::Quarantine | Self::Maintenance | Self::Wipe | Self::AdminLog => Severity::Warning,
+            Self::UpdateDigest => Severity::Info,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+/// The notifications a channel subscribes to
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct NotificationRoute {
+    /// Only notifications at least this severe are sent
+    #[serde(default)]
+    pub min_severity: Severity,
+    /// Only these events are sent, whatever their severity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<NotificationEvent>>,
+}
+
+impl NotificationRoute {
+    pub fn accepts(&self, event: NotificationEvent) -> bool {
+        match &self.events {
+            Some(events) => events.contains(&event),
+            None => event.severity() >= self.min_severity,
+        }
+    }
+}
+
+const fn default_update_digest() -> bool {
+    true
+}
+
+const fn default_crashes() -> bool {
+    true
+}
+
+const fn default_discord() -> NotificationRoute {
+    NotificationRoute { min_severity: Severity::Info, events: None }
+}
+
+const fn default_telegram() -> NotificationRoute {
+    NotificationRoute { min_severity: Severity::Critical, events: None }
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// Players kept in the server's `whitelist.txt` and `priority.txt` before every start, by Steam64 ID,
+/// optionally followed by a `//` comment such as their name
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PlayersConfig {
+    /// Always let in: added to both the whitelist and the priority queue
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub admins: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub whitelist: Vec<String>,
+    /// Players who skip the queue when the server is full
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub priority: Vec<String>,
+    /// `enableWhitelist` in `serverDZ.cfg`: only let whitelisted players join; unset leaves it as it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enforce_whitelist: Option<bool>,
+}
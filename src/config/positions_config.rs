@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// Player positions exported from the admin logs for admins to map
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PositionsConfig {
+    /// `GeoJSON` file rewritten while the server is supervised, relative to the install dir; no export when unset
+    pub export_file: Option<String>,
+    /// How often the export file is rewritten
+    #[serde(default = "default_interval_minutes")]
+    pub interval_minutes: u64,
+    /// How far back each player's trail goes
+    #[serde(default = "default_history_minutes")]
+    pub history_minutes: u64,
+}
+
+impl Default for PositionsConfig {
+    fn default() -> Self {
+        Self {
+            export_file: None,
+            interval_minutes: default_interval_minutes(),
+            history_minutes: default_history_minutes(),
+        }
+    }
+}
+
+const fn default_interval_minutes() -> u64 {
+    5
+}
+
+const fn default_history_minutes() -> u64 {
+    60
+}
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// Retention and redaction of player-identifying data DZSM collects
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PrivacyConfig {
+    /// Delete reports, crash captures, DZSM logs, storage backups, and the position export older
+    /// than this many days (checked before every start)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_days: Option<u64>,
+    /// Apply `retention_days` to the server's own logs (ADM, RPT, script logs, dumps) too
+    #[serde(default)]
+    pub include_server_logs: bool,
+    /// Replace player IDs in exports and reports with keyed hashes, still the same for the same player
+    #[serde(default)]
+    pub hash_player_ids: bool,
+    /// Replace player names in exports and reports with their (hashed) ID
+    #[serde(default)]
+    pub hide_player_names: bool,
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Files copied into the server's `profiles` folder before every start, e.g. `messages.xml`
+/// or an admin tool's JSON, so they don't have to be copied by hand
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ProfileFilesConfig {
+    /// Folder mirrored into `profiles`, relative to the install dir
+    pub source_dir: Option<String>,
+    /// Path in `profiles` to the file copied there, relative to the install dir; wins over `source_dir`
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// What to do with a deployed file that was edited in `profiles`: "overwrite" it, saving the
+    /// edited copy in `.dzsm/profile_files/drifted/`, or "keep" it
+    #[serde(default = "default_on_drift")]
+    pub on_drift: String,
+}
+
+impl Default for ProfileFilesConfig {
+    fn default() -> Self {
+        Self {
+            source_dir: None,
+            files: BTreeMap::new(),
+            on_drift: default_on_drift(),
+        }
+    }
+}
+
+impl ProfileFilesConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.source_dir.is_some() || !self.files.is_empty()
+    }
+}
+
+fn default_on_drift() -> String {
+    "overwrite".to_string()
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// Unauthenticated, read-only server info for community websites and launchers
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PublicConfig {
+    /// Address to serve `/info.json` on while the server runs, e.g. "0.0.0.0:8491"
+    pub listen: Option<String>,
+    /// File to write the same JSON to before each start, e.g. in a web server's root
+    pub file: Option<String>,
+    /// File to write the client mod list to after each mod install, in the format DZSA Launcher
+    /// reads; also served at `/mods.json` with `listen`
+    #[allow(clippy::doc_markdown)]
+    pub mods_file: Option<String>,
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScheduleConfig {
+    /// Local times to restart the server at while supervised, e.g. `["04:00", "16:00"]`
+    #[serde(default)]
+    pub restart_times: Vec<String>,
+    /// Minutes before a scheduled restart to warn players
+    #[serde(default = "default_warning_minutes")]
+    pub warning_minutes: Vec<u64>,
+    /// Warning sent to players, `{minutes}` is replaced with the time left
+    #[serde(default = "default_warning_message")]
+    pub warning_message: String,
+    /// Hold a scheduled restart while players are online, checked over the query port
+    #[serde(default)]
+    pub defer_while_players_online: bool,
+    /// Restart anyway once a restart has been held this long
+    #[serde(default = "default_max_defer_minutes")]
+    pub max_defer_minutes: u64,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            restart_times: Vec::new(),
+            warning_minutes: default_warning_minutes(),
+            warning_message: default_warning_message(),
+            defer_while_players_online: false,
+            max_defer_minutes: default_max_defer_minutes(),
+        }
+    }
+}
+
+fn default_warning_minutes() -> Vec<u64> {
+    vec![15, 5, 1]
+}
+
+fn default_warning_message() -> String {
+    "Server restart in {minutes} minute(s)".to_string()
+}
+
+const fn default_max_defer_minutes() -> u64 {
+    60
+}
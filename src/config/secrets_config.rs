@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// A password DZSM can rotate
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretKind {
+    /// Join password (`password` in `serverDZ.cfg`)
+    Server,
+    /// Admin login password (`passwordAdmin` in `serverDZ.cfg`)
+    Admin,
+    /// `BattlEye` RCON password (`RConPassword` in `BEServer_x64.cfg`)
+    Rcon,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SecretsConfig {
+    /// Passwords to rotate automatically, rotation is disabled when empty
+    #[serde(default)]
+    pub rotate: Vec<SecretKind>,
+    #[serde(default = "default_rotate_every_days")]
+    pub rotate_every_days: u32,
+    #[serde(default = "default_password_length")]
+    pub password_length: usize,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            rotate: Vec::new(),
+            rotate_every_days: default_rotate_every_days(),
+            password_length: default_password_length(),
+        }
+    }
+}
+
+const fn default_rotate_every_days() -> u32 {
+    30
+}
+
+const fn default_password_length() -> usize {
+    16
+}
\ No newline at end of file
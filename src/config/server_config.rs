@@ -4,4 +4,70 @@ use serde::{Deserialize, Serialize};
 pub struct ServerConfig {
     pub steamcmd_dir: String,
     pub username: String,
+    /// The stable server, or the Experimental one that gets updates before they're released
+    #[serde(default)]
+    pub branch: ServerBranch,
+    /// Game port passed as `-port`, leave unset to use the default 2302
+    pub port: Option<u16>,
+    /// Steam content server cell (region) to download from instead of the one Steam picks,
+    /// see `dzsm bench download`. Replaces any override in `SteamCMD`'s own config.
+    pub cell_id: Option<u32>,
+    /// Tries at a server update before giving up, each resuming the previous one's partial download
+    #[serde(default = "default_update_attempts")]
+    pub update_attempts: u32,
+    /// Seconds to wait between update attempts
+    #[serde(default = "default_update_retry_delay_seconds")]
+    pub update_retry_delay_seconds: u64,
+    /// Steam beta branch of the `branch` server app to install instead of its public one
+    pub beta_branch: Option<String>,
+    /// Steam build to stay on: server updates are skipped while it's installed, and its backup
+    /// from `.dzsm/server_builds/` is restored when another build is
+    pub app_build: Option<u64>,
+    /// Installed builds backed up before server updates, for `dzsm rollback`; 0 keeps none
+    #[serde(default = "default_keep_builds")]
+    pub keep_builds: usize,
+}
+
+/// Which server app the install runs
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerBranch {
+    #[default]
+    Stable,
+    /// A separate Steam app, which players join with the Experimental game
+    Experimental,
+}
+
+impl ServerBranch {
+    pub const ALL: [Self; 2] = [Self::Stable, Self::Experimental];
+
+    /// Steam app of the dedicated server
+    #[allow(clippy::unreadable_literal)]
+    pub const fn server_app_id(self) -> u32 {
+        match self {
+            Self::Stable => 223350,
+            Self::Experimental => 1042420,
+        }
+    }
+
+    /// Steam app whose Workshop the mods are downloaded from; the Experimental game loads
+    /// the stable game's Workshop mods, so both use it
+    #[allow(clippy::unreadable_literal)]
+    pub const fn game_app_id(self) -> u32 {
+        match self {
+            Self::Stable | Self::Experimental => 221100,
+        }
+    }
+}
+
+const fn default_update_attempts() -> u32 {
+    5
+}
+
+const fn default_update_retry_delay_seconds() -> u64 {
+    30
+}
+
+const fn default_keep_builds() -> usize {
+    2
 }
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ShutdownConfig {
+    /// Seconds between warning players and shutting down, after Ctrl+C or a stop request
+    #[serde(default = "default_grace_period_seconds")]
+    pub grace_period_seconds: u64,
+    /// Warning sent to each player over RCON, `{seconds}` is replaced with the time left
+    /// and `{name}` with the player's name
+    #[serde(default = "default_warning_message")]
+    pub warning_message: String,
+    /// Repeat the warning when this many seconds of the grace period are left
+    #[serde(default = "default_countdown_seconds")]
+    pub countdown_seconds: Vec<u64>,
+    /// Lock the server so nobody joins during the grace period
+    #[serde(default = "default_lock")]
+    pub lock: bool,
+    /// Seconds to wait for the server to exit after `#shutdown` before killing it
+    #[serde(default = "default_exit_timeout_seconds")]
+    pub exit_timeout_seconds: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            grace_period_seconds: default_grace_period_seconds(),
+            warning_message: default_warning_message(),
+            countdown_seconds: default_countdown_seconds(),
+            lock: default_lock(),
+            exit_timeout_seconds: default_exit_timeout_seconds(),
+        }
+    }
+}
+
+const fn default_grace_period_seconds() -> u64 {
+    60
+}
+
+fn default_warning_message() -> String {
+    "Server is shutting down in {seconds} seconds".to_string()
+}
+
+fn default_countdown_seconds() -> Vec<u64> {
+    vec![30, 10]
+}
+
+const fn default_lock() -> bool {
+    true
+}
+
+const fn default_exit_timeout_seconds() -> u64 {
+    60
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+/// Steam's anonymous login, enough for the dedicated server app but not Workshop items
+pub const ANONYMOUS: &str = "anonymous";
+
+/// How DZSM logs in to Steam for `SteamCMD`
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct SteamConfig {
+    pub credential_source: CredentialSource,
+    /// An account that owns the game, to download Workshop mods with when `server.username`
+    /// gets a license error, e.g. because it only has the game through Family Sharing
+    pub workshop_username: Option<String>,
+    /// Download the server app anonymously, so only mod downloads log in to the account
+    /// and ask for Steam Guard codes
+    pub anonymous_server_updates: bool,
+}
+
+impl SteamConfig {
+    /// The account to download the server app with, `username` unless that's done anonymously
+    pub fn get_server_username<'a>(&self, username: &'a str) -> &'a str {
+        if self.anonymous_server_updates { ANONYMOUS } else { username }
+    }
+}
+
+/// Where the Steam password comes from
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialSource {
+    /// `SteamCMD`'s own cached login, from logging in once by hand; DZSM never sees the password
+    #[default]
+    Cached,
+    /// The Windows Credential Manager, stored with `dzsm steam login` and passed to `SteamCMD`
+    /// in a login script. Stored per Windows user, so log in as the user DZSM runs as
+    CredentialManager,
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Extra settings passed to every `SteamCMD` run, for hosts that need them
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct SteamCmdConfig {
+    /// Arguments put before DZSM's own, e.g. `["+@sSteamCmdForcePlatformType", "windows"]`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Environment variables set for `SteamCMD`, on top of DZSM's own environment
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StorageConfig {
+    /// Check the persistence files for corruption before every start
+    #[serde(default = "default_check")]
+    pub check: bool,
+    /// Treat storage as suspicious when it shrank by more than this since the last healthy start
+    #[serde(default = "default_max_size_drop_percent")]
+    pub max_size_drop_percent: u8,
+    /// Back up healthy storage before every start
+    #[serde(default = "default_backup_on_start")]
+    pub backup_on_start: bool,
+    /// Number of storage backups to keep
+    #[serde(default = "default_keep_backups")]
+    pub keep_backups: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            check: default_check(),
+            max_size_drop_percent: default_max_size_drop_percent(),
+            backup_on_start: default_backup_on_start(),
+            keep_backups: default_keep_backups(),
+        }
+    }
+}
+
+const fn default_check() -> bool {
+    true
+}
+
+const fn default_max_size_drop_percent() -> u8 {
+    50
+}
+
+const fn default_backup_on_start() -> bool {
+    true
+}
+
+const fn default_keep_backups() -> usize {
+    5
+}
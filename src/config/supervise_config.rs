@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SuperviseConfig {
+    /// Seconds to wait before restarting the server after it exits
+    #[serde(default = "default_restart_delay_seconds")]
+    pub restart_delay_seconds: u64,
+    /// Give up when the server has to be restarted more often than this within an hour
+    #[serde(default = "default_max_restarts_per_hour")]
+    pub max_restarts_per_hour: usize,
+    /// Check for server and mod updates before every restart
+    #[serde(default = "default_update_on_restart")]
+    pub update_on_restart: bool,
+    /// After this many crashes within an hour, leave out the mod that was the only change since
+    /// the server last ran fine; 0 never quarantines
+    #[serde(default = "default_quarantine_after_crashes")]
+    pub quarantine_after_crashes: usize,
+    /// Restart the server once it uses this much memory; 0 never does
+    #[serde(default)]
+    pub max_memory_mb: u64,
+    /// Restart the server once its FPS, as it reports in the RPT log, stays below this; 0 never does
+    #[serde(default)]
+    pub min_fps: u32,
+    /// How long the FPS has to stay below `min_fps` before the server is restarted
+    #[serde(default = "default_low_fps_minutes")]
+    pub low_fps_minutes: u64,
+    /// How often `.dzsm/heartbeat.json` is rewritten for external monitors; 0 never writes it
+    #[serde(default = "default_heartbeat_seconds")]
+    pub heartbeat_seconds: u64,
+}
+
+impl Default for SuperviseConfig {
+    fn default() -> Self {
+        Self {
+            restart_delay_seconds: default_restart_delay_seconds(),
+            max_restarts_per_hour: default_max_restarts_per_hour(),
+            update_on_restart: default_update_on_restart(),
+            quarantine_after_crashes: default_quarantine_after_crashes(),
+            max_memory_mb: 0,
+            min_fps: 0,
+            low_fps_minutes: default_low_fps_minutes(),
+            heartbeat_seconds: default_heartbeat_seconds(),
+        }
+    }
+}
+
+const fn default_restart_delay_seconds() -> u64 {
+    10
+}
+
+const fn default_max_restarts_per_hour() -> usize {
+    6
+}
+
+const fn default_update_on_restart() -> bool {
+    true
+}
+
+const fn default_quarantine_after_crashes() -> usize {
+    3
+}
+
+const fn default_low_fps_minutes() -> u64 {
+    10
+}
+
+const fn default_heartbeat_seconds() -> u64 {
+    30
+}
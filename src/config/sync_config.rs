@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SyncConfig {
+    /// Hub that instances in the cluster sync their ban and whitelist files through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hub_url: Option<String>,
+    /// Shared secret between the hub and its instances
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Address `dzsm sync serve` listens on
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// Seconds between syncs while the server is running
+    #[serde(default = "default_interval_seconds")]
+    pub interval_seconds: u64,
+    /// Sync ban.txt
+    #[serde(default = "default_true")]
+    pub bans: bool,
+    /// Sync whitelist.txt
+    #[serde(default = "default_true")]
+    pub whitelist: bool,
+}
+
+impl SyncConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.hub_url.as_ref().is_some_and(|url| !url.trim().is_empty())
+    }
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            hub_url: None,
+            token: None,
+            listen: default_listen(),
+            interval_seconds: default_interval_seconds(),
+            bans: default_true(),
+            whitelist: default_true(),
+        }
+    }
+}
+
+fn default_listen() -> String {
+    "0.0.0.0:8490".to_string()
+}
+
+const fn default_interval_seconds() -> u64 {
+    5
+}
+
+const fn default_true() -> bool {
+    true
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// In-game time settings written into `serverDZ.cfg` before every start.
+/// Values left unset come from the preset, or leave `serverDZ.cfg` alone when there is no preset.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct TimeConfig {
+    /// Named preset, see `dzsm time presets`
+    pub preset: Option<String>,
+    /// `serverTime`: `SystemTime` or a start date and time as "YYYY/MM/DD/HH/MM"
+    pub server_time: Option<String>,
+    /// `serverTimeAcceleration`: how much faster than real time the in-game clock runs
+    pub acceleration: Option<f64>,
+    /// `serverNightTimeAcceleration`: extra speed-up at night, on top of `acceleration`
+    pub night_acceleration: Option<f64>,
+    /// `serverTimePersistent`: carry the in-game time over restarts instead of starting from `server_time`
+    pub persistent: Option<bool>,
+}
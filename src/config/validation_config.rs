@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+/// Background check of the installed mod files while the server is supervised
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ValidationConfig {
+    /// Check a few mods each night against their recorded file hashes
+    #[serde(default)]
+    pub enabled: bool,
+    /// Start of the off-peak window as HH:MM, may wrap past midnight
+    #[serde(default = "default_from")]
+    pub from: String,
+    /// End of the off-peak window as HH:MM
+    #[serde(default = "default_to")]
+    pub to: String,
+    /// Every installed mod is checked once within this many nights
+    #[serde(default = "default_cycle_days")]
+    pub cycle_days: u64,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            from: default_from(),
+            to: default_to(),
+            cycle_days: default_cycle_days(),
+        }
+    }
+}
+
+fn default_from() -> String {
+    "03:00".to_string()
+}
+
+fn default_to() -> String {
+    "06:00".to_string()
+}
+
+const fn default_cycle_days() -> u64 {
+    7
+}
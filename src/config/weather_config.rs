@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Weather written into the mission's `cfgweather.xml` before every start
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct WeatherConfig {
+    /// Preset used when no rule matches, the mission's own weather is kept when unset
+    pub preset: Option<String>,
+    /// Presets for particular days or times, the first matching rule wins
+    #[serde(default)]
+    pub rules: Vec<WeatherRule>,
+    /// Custom presets, alongside the built-in ones
+    #[serde(default)]
+    pub presets: BTreeMap<String, WeatherPreset>,
+}
+
+/// Use a preset for starts on certain days and/or between certain times
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WeatherRule {
+    pub preset: String,
+    /// Weekdays such as `["sat", "sun"]`, any day when empty
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// Start of the time window as HH:MM, may wrap past midnight
+    pub from: Option<String>,
+    /// End of the time window as HH:MM
+    pub to: Option<String>,
+}
+
+/// Weather ranges as `[min, max]`; anything left out keeps the mission's value
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct WeatherPreset {
+    /// Cloud cover, 0 - 1
+    pub overcast: Option<[f64; 2]>,
+    /// Fog density, 0 - 1
+    pub fog: Option<[f64; 2]>,
+    /// Rain intensity, 0 - 1
+    pub rain: Option<[f64; 2]>,
+    /// How readily it rains, 0 (never) - 1 (whenever there are clouds)
+    pub rain_chance: Option<f64>,
+    /// Wind speed in m/s, 0 - 20
+    pub wind: Option<[f64; 2]>,
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// The `dzsm web` dashboard, for managing the server from a phone or another machine
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WebConfig {
+    /// Address the dashboard listens on, only this machine by default; `--port` overrides the port
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// Secret the dashboard asks for before showing or changing anything; required
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Lines of the DZSM log the dashboard shows
+    #[serde(default = "default_log_lines")]
+    pub log_lines: usize,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            listen: default_listen(),
+            token: None,
+            log_lines: default_log_lines(),
+        }
+    }
+}
+
+fn default_listen() -> String {
+    "127.0.0.1:8080".to_string()
+}
+
+const fn default_log_lines() -> usize {
+    200
+}
@@ -0,0 +1,158 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, ShutdownConfig};
+use crate::instance;
+use crate::processes;
+use crate::rcon::RconClient;
+use crate::server::SERVER_EXE;
+use crate::state::state_dir;
+use crate::supervisor::{clear_stop_request, request_restart, request_stop};
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const PID_FILE: &str = "server.pid";
+/// Time the DZSM running the server gets on top of its grace period and exit timeout before
+/// the server is stopped from here
+const MARGIN_SECONDS: u64 = 30;
+/// Time a killed server gets to go away
+const KILL_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn get_pid_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(PID_FILE)
+}
+
+/// Write down the PID of the server just started, for `dzsm stop` and `dzsm restart` in
+/// another terminal
+pub fn record_server_pid(server_install_dir: &Path, pid: u32) -> Result<()> {
+    let path = get_pid_path(server_install_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context("Failed to create state directory")?;
+    }
+    fs::write(&path, pid.to_string())
+        .context(format!("Failed to write {}", path.display()))
+}
+
+/// The running server processes of this install: the one DZSM recorded while it's still
+/// running, otherwise any started from the install's exe, e.g. by hand
+fn find_server_pids(server_install_dir: &Path) -> Vec<u32> {
+    let exe_path = server_install_dir.join(SERVER_EXE);
+    let running: Vec<u32> = processes::find_by_exe_path(&exe_path).iter().map(|process| process.pid).collect();
+    let recorded = fs::read_to_string(get_pid_path(server_install_dir))
+        .ok()
+        .and_then(|text| text.trim().parse::<u32>().ok());
+    match recorded {
+        Some(pid) if running.contains(&pid) => vec![pid],
+        _ => running,
+    }
+}
+
+/// How long the DZSM running the server gets to warn players, shut it down, and exit
+pub fn get_stop_timeout(shutdown: &ShutdownConfig) -> Duration {
+    Duration::from_secs(shutdown.grace_period_seconds + shutdown.exit_timeout_seconds + MARGIN_SECONDS)
+}
+
+/// Whether `done` turned true within `timeout`
+fn wait_until(timeout: Duration, done: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !done() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    true
+}
+
+/// Stop the server of `server_install_dir` from outside the DZSM running it. That DZSM is
+/// asked to warn players and shut it down; a server nothing runs is sent `#shutdown` over
+/// RCON. With `force`, a server that's still running after that is killed.
+#[allow(clippy::doc_markdown)]
+pub fn stop_server(config: &Config, server_install_dir: &Path, force: bool) -> Result<()> {
+    let shutdown = &config.shutdown;
+    let is_stopped = || find_server_pids(server_install_dir).is_empty();
+
+    if let Some(pid) = instance::find_running(server_install_dir) {
+        println_step(&format!("Asking the DZSM running the server (PID {pid}) to stop it..."), 1);
+        request_stop(server_install_dir)?;
+        let stopped = wait_until(get_stop_timeout(shutdown), || {
+            instance::find_running(server_install_dir).is_none() && is_stopped()
+        });
+        if stopped {
+            println_success("Server stopped", 1);
+            return Ok(());
+        }
+        // Still updating before it starts the server: the request stays for it to act on
+        if instance::find_running(server_install_dir).is_some() && is_stopped() {
+            return Err(anyhow!(
+                "The DZSM running the server (PID {pid}) is still busy, e.g. updating; once it's done it exits instead of starting the server"
+            ));
+        }
+        if instance::find_running(server_install_dir).is_none() {
+            clear_stop_request(server_install_dir)?;
+        }
+        println_failure("It didn't stop the server in time", 1);
+    }
+
+    if is_stopped() {
+        println_success("The server isn't running", 1);
+        return Ok(());
+    }
+
+    println_step("Sending #shutdown over RCON...", 1);
+    let sent = RconClient::connect_to_server(server_install_dir).and_then(|mut rcon| rcon.command("#shutdown"));
+    match sent {
+        Ok(_) if wait_until(Duration::from_secs(shutdown.exit_timeout_seconds), is_stopped) => {
+            println_success("Server stopped", 1);
+            return Ok(());
+        }
+        Ok(_) => println_failure("The server is still running after #shutdown", 1),
+        Err(e) if force => println_failure(&format!("Failed to send #shutdown: {e:#}"), 1),
+        Err(e) => return Err(e).context("Failed to stop the server, pass --force to kill it"),
+    }
+
+    if !force {
+        return Err(anyhow!("The server is still running, pass --force to kill it"));
+    }
+    kill_server(server_install_dir)
+}
+
+/// End the server processes without a shutdown, players lose what wasn't saved
+pub fn kill_server(server_install_dir: &Path) -> Result<()> {
+    for pid in find_server_pids(server_install_dir) {
+        println_step(&format!("Killing the server (PID {pid})..."), 1);
+        processes::kill(pid)
+            .context(format!("Failed to kill the server (PID {pid})"))?;
+    }
+    if !wait_until(KILL_TIMEOUT, || find_server_pids(server_install_dir).is_empty()) {
+        return Err(anyhow!("The server is still running after being killed"));
+    }
+    println_success("Server killed", 1);
+    Ok(())
+}
+
+/// Entry point for `dzsm stop`
+pub fn run_stop(config: &Config, server_install_dir: &str, force: bool) -> Result<()> {
+    stop_server(config, Path::new(server_install_dir), force)
+}
+
+/// Entry point for `dzsm restart`. The DZSM running the server is asked to restart it, and
+/// true returned. When none is, any server left running is stopped and false returned, for
+/// the caller to start it like `dzsm run`.
+pub fn run_restart(config: &Config, server_install_dir: &str, update: bool, force: bool) -> Result<bool> {
+    let install_dir = Path::new(server_install_dir);
+    if let Some(pid) = instance::find_running(install_dir) {
+        request_restart(install_dir, update)?;
+        let then = if update { ", updating the server and mods first" } else { "" };
+        println_success(&format!("Asked the DZSM running the server (PID {pid}) to restart it{then}"), 0);
+        return Ok(true);
+    }
+
+    println_step("No DZSM is running the server, starting it here", 0);
+    stop_server(config, install_dir, force)?;
+    Ok(false)
+}
@@ -0,0 +1,395 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::cli::CrashCommand;
+use crate::config::{Config, CrashesConfig, NotificationEvent};
+use crate::http;
+use crate::notifier::Notification;
+use crate::server::SERVER_PROFILES;
+use crate::state::state_dir;
+use crate::ui::status::{println_failure, println_step, println_step_concat, println_success};
+
+/// Only the end of a log is read, RPTs of long-running servers get huge
+const TAIL_BYTES: u64 = 256 * 1024;
+const SIGNATURE_LINES: usize = 12;
+const REMOTE_CACHE_FILE: &str = "known_crashes.remote.toml";
+pub const CRASHES_DIR: &str = "crashes";
+
+/// Lines that describe a crash or the script error leading up to it
+const SIGNATURE_MARKERS: [&str; 8] = [
+    "exception code",
+    "unhandled exception",
+    "fault address",
+    "errormessage",
+    "script (e)",
+    "null pointer",
+    "function:",
+    "stack trace",
+];
+
+/// A crash reduced to the lines that identify it, with line numbers, addresses,
+/// and timestamps stripped so the same crash gets the same fingerprint every time
+pub struct CrashSignature {
+    pub lines: Vec<String>,
+    pub fingerprint: String,
+}
+
+impl CrashSignature {
+    fn from_log(text: &str) -> Option<Self> {
+        let mut lines: Vec<String> = Vec::new();
+        for line in text.lines().filter(|line| is_signature_line(line)) {
+            let normalized = normalize(line);
+            if lines.last() != Some(&normalized) {
+                lines.push(normalized);
+            }
+        }
+
+        if lines.is_empty() {
+            return None;
+        }
+
+        // The last error before the crash is the one that matters
+        let lines = lines.split_off(lines.len().saturating_sub(SIGNATURE_LINES));
+        let fingerprint = format!("{:016x}", fnv1a(lines.join("\n").as_bytes()));
+
+        Some(Self { lines, fingerprint })
+    }
+}
+
+/// An entry in a known-issue database
+#[derive(Debug, Clone, Deserialize)]
+pub struct KnownIssue {
+    pub name: String,
+    /// Exact crash fingerprints, as printed by `dzsm crash analyze`
+    #[serde(default)]
+    pub fingerprints: Vec<String>,
+    /// Text that must all appear near the end of the logs (case-insensitive)
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    pub fix: String,
+}
+
+impl KnownIssue {
+    fn matches(&self, signature: Option<&CrashSignature>, log_text: &str) -> bool {
+        if let Some(signature) = signature
+            && self.fingerprints.iter().any(|f| f.eq_ignore_ascii_case(&signature.fingerprint))
+        {
+            return true;
+        }
+
+        let log_text = log_text.to_lowercase();
+        !self.patterns.is_empty()
+            && self.patterns.iter().all(|pattern| log_text.contains(&pattern.to_lowercase()))
+    }
+}
+
+/// `known_crashes.toml`: a list of `[[issue]]` tables
+#[derive(Debug, Default, Deserialize)]
+struct KnownIssueDatabase {
+    #[serde(default, rename = "issue")]
+    issues: Vec<KnownIssue>,
+}
+
+impl KnownIssueDatabase {
+    fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content)
+            .context("Failed to parse known crash database")
+    }
+
+    /// Local entries first so they can override the community ones
+    fn load(server_install_dir: &Path, config: &CrashesConfig) -> Self {
+        let mut database = Self::default();
+
+        let local_path = server_install_dir.join(&config.known_issues_file);
+        if local_path.exists() {
+            match fs::read_to_string(&local_path).map_err(anyhow::Error::from).and_then(|c| Self::parse(&c)) {
+                Ok(local) => database.issues.extend(local.issues),
+                Err(e) => println_failure(&format!("Failed to load {}: {e}", local_path.display()), 1),
+            }
+        }
+
+        if let Some(url) = config.known_issues_url.as_deref().filter(|url| !url.trim().is_empty()) {
+            match Self::load_remote(server_install_dir, url) {
+                Ok(remote) => database.issues.extend(remote.issues),
+                Err(e) => println_failure(&format!("Failed to load known crashes from {url}: {e}"), 1),
+            }
+        }
+
+        database
+    }
+
+    /// Fetch the community database, falling back to the last copy fetched
+    fn load_remote(server_install_dir: &Path, url: &str) -> Result<Self> {
+        let cache_path = state_dir(server_install_dir).join(REMOTE_CACHE_FILE);
+
+        match http::get_text(url).and_then(|content| Self::parse(&content).map(|db| (db, content))) {
+            Ok((database, content)) => {
+                if let Some(parent) = cache_path.parent() {
+                    let _ = fs::create_dir_all(parent);
+                }
+                let _ = fs::write(&cache_path, content);
+                Ok(database)
+            }
+            Err(e) if cache_path.exists() => {
+                println_failure(&format!("Using cached known crashes ({e})"), 1);
+                Self::parse(&fs::read_to_string(&cache_path)?)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn find_match(&self, signature: Option<&CrashSignature>, log_text: &str) -> Option<&KnownIssue> {
+        self.issues.iter().find(|issue| issue.matches(signature, log_text))
+    }
+}
+
+/// What DZSM could work out about a crash from the server's logs
+pub struct CrashReport {
+    pub exit_code: Option<i32>,
+    pub rpt_path: Option<PathBuf>,
+    pub script_log_path: Option<PathBuf>,
+    pub dump_path: Option<PathBuf>,
+    pub signature: Option<CrashSignature>,
+    pub known_issue: Option<KnownIssue>,
+    /// Where the logs were copied to by `capture`
+    pub captured_dir: Option<PathBuf>,
+}
+
+impl CrashReport {
+    /// Fingerprint the newest RPT and script log and look the crash up in the known-issue databases
+    pub fn analyze(server_install_dir: &Path, config: &CrashesConfig, exit_code: Option<i32>) -> Self {
+        let profiles_dir = server_install_dir.join(SERVER_PROFILES);
+        let rpt_path = newest_file(&profiles_dir, |name| has_extension(name, "rpt"));
+        let script_log_path = newest_file(&profiles_dir, |name| name.starts_with("script") && has_extension(name, "log"));
+        let dump_path = newest_file(&profiles_dir, |name| has_extension(name, "mdmp"));
+
+        let log_text: String = [&rpt_path, &script_log_path].into_iter()
+            .flatten()
+            .filter_map(|path| read_tail(path).ok())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let signature = CrashSignature::from_log(&log_text);
+        let known_issue = KnownIssueDatabase::load(server_install_dir, config)
+            .find_match(signature.as_ref(), &log_text)
+            .cloned();
+
+        Self {
+            exit_code,
+            rpt_path,
+            script_log_path,
+            dump_path,
+            signature,
+            known_issue,
+            captured_dir: None,
+        }
+    }
+
+    /// Copy the RPT, crash dump, and script log into `.dzsm/crashes/<timestamp>/`
+    /// so they survive the server's next start
+    pub fn capture(&mut self, server_install_dir: &Path) -> Result<PathBuf> {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        let crash_dir = state_dir(server_install_dir).join(CRASHES_DIR).join(timestamp);
+        fs::create_dir_all(&crash_dir)
+            .context(format!("Failed to create {}", crash_dir.display()))?;
+
+        for path in [&self.rpt_path, &self.dump_path, &self.script_log_path].into_iter().flatten() {
+            if let Some(file_name) = path.file_name() {
+                fs::copy(path, crash_dir.join(file_name))
+                    .context(format!("Failed to copy {}", path.display()))?;
+            }
+        }
+
+        self.captured_dir = Some(crash_dir.clone());
+        Ok(crash_dir)
+    }
+
+    /// Print the last `lines` lines of the RPT
+    pub fn print_rpt_tail(&self, lines: usize) {
+        let Some(rpt_path) = &self.rpt_path else {
+            return;
+        };
+
+        match read_tail(rpt_path) {
+            Ok(text) => {
+                println_step(&format!("Last {lines} lines of {}:", rpt_path.display()), 1);
+                let all_lines: Vec<&str> = text.lines().collect();
+                for line in &all_lines[all_lines.len().saturating_sub(lines)..] {
+                    println_step_concat(line, 1);
+                }
+            }
+            Err(e) => println_failure(&format!("Failed to read {}: {e}", rpt_path.display()), 1),
+        }
+    }
+
+    pub fn print(&self) {
+        match &self.signature {
+            Some(signature) => {
+                println_step(&format!("Crash fingerprint: {}", signature.fingerprint), 1);
+                for line in &signature.lines {
+                    println_step_concat(line, 2);
+                }
+            }
+            None => println_step("No crash signature found in the server logs", 1),
+        }
+
+        match &self.known_issue {
+            Some(issue) => {
+                println_success(&format!("Known issue: {}", issue.name), 1);
+                println_step_concat(&format!("Suggested fix: {}", issue.fix), 1);
+            }
+            None => println_step("No known issue matches this crash", 1),
+        }
+    }
+
+    pub fn to_notification(&self, server_name: &str) -> Notification {
+        let mut body = format!("Exit code: `{:?}`\n", self.exit_code);
+
+        if let Some(issue) = &self.known_issue {
+            let _ = write!(body, "\n**Known issue: {}**\n{}\n", issue.name, issue.fix);
+        }
+
+        if let Some(signature) = &self.signature {
+            let _ = write!(body, "\nFingerprint `{}`\n```\n{}\n```\n", signature.fingerprint, signature.lines.join("\n"));
+        }
+
+        if let Some(captured_dir) = &self.captured_dir {
+            let _ = write!(body, "\nLogs saved to `{}`", captured_dir.display());
+        } else if let Some(rpt_path) = &self.rpt_path {
+            let _ = write!(body, "\nLog: `{}`", rpt_path.display());
+        }
+
+        Notification {
+            event: NotificationEvent::Crash,
+            title: format!("{server_name} crashed"),
+            body,
+        }
+    }
+}
+
+fn is_signature_line(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    let trimmed = lower.trim_start();
+
+    SIGNATURE_MARKERS.iter().any(|marker| lower.contains(marker))
+        // Script stack frames, e.g. "scripts/4_World/.../playerbase.c:1234"
+        || (trimmed.starts_with("scripts/") || trimmed.contains(".c:") || trimmed.contains(".c("))
+}
+
+/// Strip what changes between occurrences of the same crash: timestamps, addresses, and line numbers
+fn normalize(line: &str) -> String {
+    line.split_whitespace()
+        .enumerate()
+        .filter(|(index, token)| !(*index == 0 && is_timestamp(token)))
+        .map(|(_, token)| {
+            if is_address(token) {
+                "<addr>".to_string()
+            } else {
+                strip_line_number(token).to_lowercase()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// "12:34:56" or "12:34:56.789"
+fn is_timestamp(token: &str) -> bool {
+    token.split([':', '.']).count() >= 3
+        && token.chars().all(|c| c.is_ascii_digit() || c == ':' || c == '.')
+}
+
+fn is_address(token: &str) -> bool {
+    let token = token.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    let hex = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")).unwrap_or(token);
+    hex.len() >= 8 && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// "file.c:123" -> "file.c", "file.c(123)" -> "file.c"
+fn strip_line_number(token: &str) -> &str {
+    if let Some((file, number)) = token.rsplit_once(':')
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+    {
+        return file;
+    }
+    if let Some((file, rest)) = token.split_once('(')
+        && let Some(number) = rest.strip_suffix(')')
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+    {
+        return file;
+    }
+    token
+}
+
+/// 64-bit FNV-1a, stable across Rust versions unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn has_extension(name: &str, extension: &str) -> bool {
+    Path::new(name).extension().is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// The most recent crash saved by `CrashReport::capture`
+pub fn get_last_capture(server_install_dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(state_dir(server_install_dir).join(CRASHES_DIR)).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        // Directory names are timestamps, which sort chronologically
+        .max()
+}
+
+/// Most recently modified file in `dir` whose lowercase name passes `filter`
+pub fn newest_file(dir: &Path, filter: impl Fn(&str) -> bool) -> Option<PathBuf> {
+    fs::read_dir(dir).ok()?
+        .flatten()
+        .filter(|entry| filter(&entry.file_name().to_string_lossy().to_lowercase()))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// The last `TAIL_BYTES` of a log, starting at a line boundary
+pub fn read_tail(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .context(format!("Failed to open {}", path.display()))?;
+    let length = file.metadata()?.len();
+    let start = length.saturating_sub(TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes).to_string();
+
+    if start > 0 {
+        // Drop the partial first line
+        return Ok(text.split_once('\n').map_or(text.clone(), |(_, rest)| rest.to_string()));
+    }
+    Ok(text)
+}
+
+/// Entry point for `dzsm crash ...`
+pub fn run(command: &CrashCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    match command {
+        CrashCommand::Analyze => {
+            println_step("Analyzing the latest server logs...", 0);
+            let report = CrashReport::analyze(Path::new(server_install_dir), &config.crashes, None);
+            let Some(rpt_path) = &report.rpt_path else {
+                return Err(anyhow!("No server logs found in {SERVER_PROFILES}/"));
+            };
+
+            println_step(&format!("Log: {}", rpt_path.display()), 1);
+            report.print_rpt_tail(config.crashes.tail_lines);
+            report.print();
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{Result, anyhow};
+use std::io;
+use std::iter;
+use std::ptr;
+use std::slice;
+
+use windows_sys::Win32::Foundation::ERROR_NOT_FOUND;
+use windows_sys::Win32::Security::Credentials::{
+    CRED_PERSIST_LOCAL_MACHINE, CRED_TYPE_GENERIC, CREDENTIALW, CredDeleteW, CredFree, CredReadW, CredWriteW,
+};
+
+use crate::cli::SteamCommand;
+use crate::config::Config;
+use crate::config::steam_config::CredentialSource;
+use crate::ui::prompt::prompt_password;
+use crate::ui::status::{println_step, println_success};
+
+/// Credential Manager entries are named `dzsm:steam:<username>`, shown under Windows Credentials
+const TARGET_PREFIX: &str = "dzsm:steam:";
+
+/// The password of `username` to pass to `SteamCMD`, none when it uses its cached login
+pub fn get_steam_password(config: &Config, username: &str) -> Result<Option<String>> {
+    match config.steam.credential_source {
+        CredentialSource::Cached => Ok(None),
+        CredentialSource::CredentialManager => {
+            read_password(username)?.map(Some).ok_or_else(|| anyhow!(
+                "No Steam password for {username} in the Windows Credential Manager, store it with `dzsm steam login{}`",
+                if config.steam.workshop_username.as_deref() == Some(username) { " --workshop" } else { "" }
+            ))
+        }
+    }
+}
+
+/// Read a stored password, none if there isn't one for `username`
+pub fn read_password(username: &str) -> Result<Option<String>> {
+    let target = get_target_name(username);
+    let mut credential: *mut CREDENTIALW = ptr::null_mut();
+
+    let ok = unsafe { CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &raw mut credential) };
+    if ok == 0 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(ERROR_NOT_FOUND.cast_signed()) {
+            return Ok(None);
+        }
+        return Err(anyhow!("Failed to read the Steam password from the Credential Manager: {error}"));
+    }
+
+    let blob = unsafe {
+        let size = (*credential).CredentialBlobSize as usize;
+        if size == 0 {
+            Vec::new()
+        } else {
+            slice::from_raw_parts((*credential).CredentialBlob, size).to_vec()
+        }
+    };
+    unsafe { CredFree(credential.cast()) };
+
+    // Stored as UTF-16 like `cmdkey` does, so it can also be set from there
+    let units: Vec<u16> = blob.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16(&units)
+        .map(Some)
+        .map_err(|_| anyhow!("The stored Steam password for {username} isn't valid text, store it again with `dzsm steam login`"))
+}
+
+/// Store a password for `username`, replacing any stored before
+pub fn store_password(username: &str, password: &str) -> Result<()> {
+    let mut target = get_target_name(username);
+    let mut user = to_wide(username);
+    let mut blob: Vec<u8> = password.encode_utf16().flat_map(u16::to_le_bytes).collect();
+
+    let mut credential: CREDENTIALW = unsafe { std::mem::zeroed() };
+    credential.Type = CRED_TYPE_GENERIC;
+    credential.TargetName = target.as_mut_ptr();
+    credential.UserName = user.as_mut_ptr();
+    credential.CredentialBlobSize = u32::try_from(blob.len())?;
+    credential.CredentialBlob = blob.as_mut_ptr();
+    credential.Persist = CRED_PERSIST_LOCAL_MACHINE;
+
+    let ok = unsafe { CredWriteW(&raw const credential, 0) };
+    if ok == 0 {
+        return Err(anyhow!(
+            "Failed to store the Steam password in the Credential Manager: {}",
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// Remove the stored password for `username`, returning whether there was one
+pub fn delete_password(username: &str) -> Result<bool> {
+    let target = get_target_name(username);
+
+    let ok = unsafe { CredDeleteW(target.as_ptr(), CRED_TYPE_GENERIC, 0) };
+    if ok == 0 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(ERROR_NOT_FOUND.cast_signed()) {
+            return Ok(false);
+        }
+        return Err(anyhow!("Failed to remove the Steam password from the Credential Manager: {error}"));
+    }
+    Ok(true)
+}
+
+fn get_target_name(username: &str) -> Vec<u16> {
+    to_wide(&format!("{TARGET_PREFIX}{username}"))
+}
+
+fn to_wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(iter::once(0)).collect()
+}
+
+/// Entry point for `dzsm steam ...`
+pub fn run(command: &SteamCommand, config: &Config) -> Result<()> {
+    let (SteamCommand::Login { workshop } | SteamCommand::Forget { workshop }) = command;
+    let username = if *workshop {
+        config.steam.workshop_username.as_ref()
+            .ok_or_else(|| anyhow!("Set `steam.workshop_username` in config.toml first"))?
+    } else {
+        &config.server.username
+    };
+
+    match command {
+        SteamCommand::Login { .. } => {
+            let password = prompt_password(&format!("Steam password for {username}"), 0)?;
+            if password.is_empty() {
+                return Err(anyhow!("No password entered, nothing was stored"));
+            }
+            store_password(username, &password)?;
+            println_success(&format!("Stored the Steam password for {username} in the Windows Credential Manager"), 0);
+
+            if config.steam.credential_source == CredentialSource::Cached {
+                println_step("Set `steam.credential_source = \"credential_manager\"` in config.toml to use it", 1);
+            }
+            println_step("SteamCMD asks for a Steam Guard code the first time it logs in with it", 1);
+            Ok(())
+        }
+        SteamCommand::Forget { .. } => {
+            if delete_password(username)? {
+                println_success(&format!("Removed the stored Steam password for {username}"), 0);
+            } else {
+                println_success(&format!("No Steam password is stored for {username}"), 0);
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Result, anyhow};
+use std::ptr;
+
+use windows_sys::Win32::Foundation::LocalFree;
+use windows_sys::Win32::Security::Cryptography::{
+    BCRYPT_HMAC_SHA256_ALG_HANDLE, BCRYPT_MD5_ALG_HANDLE, BCRYPT_USE_SYSTEM_PREFERRED_RNG, BCryptGenRandom, BCryptHash, CRYPT_INTEGER_BLOB,
+    CRYPTPROTECT_UI_FORBIDDEN, CryptProtectData, CryptUnprotectData,
+};
+
+/// Encrypt data so only the current Windows user on this machine can read it (DPAPI)
+#[allow(clippy::doc_markdown)]
+pub fn protect(data: &[u8]) -> Result<Vec<u8>> {
+    let input = blob_from(data)?;
+    let mut output = CRYPT_INTEGER_BLOB { cbData: 0, pbData: ptr::null_mut() };
+
+    let ok = unsafe {
+        CryptProtectData(
+            &raw const input,
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &raw mut output,
+        )
+    };
+
+    if ok == 0 {
+        return Err(anyhow!("Failed to encrypt data: {}", std::io::Error::last_os_error()));
+    }
+
+    Ok(take_blob(output))
+}
+
+/// Decrypt data produced by [`protect`]
+pub fn unprotect(data: &[u8]) -> Result<Vec<u8>> {
+    let input = blob_from(data)?;
+    let mut output = CRYPT_INTEGER_BLOB { cbData: 0, pbData: ptr::null_mut() };
+
+    let ok = unsafe {
+        CryptUnprotectData(
+            &raw const input,
+            ptr::null_mut(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &raw mut output,
+        )
+    };
+
+    if ok == 0 {
+        return Err(anyhow!(
+            "Failed to decrypt data (it can only be read by the Windows user that wrote it): {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    Ok(take_blob(output))
+}
+
+/// Fill a buffer from the system's cryptographically secure RNG
+pub fn random_bytes(len: usize) -> Result<Vec<u8>> {
+    let mut buffer = vec![0u8; len];
+    let buffer_len = u32::try_from(len).map_err(|_| anyhow!("Requested too many random bytes"))?;
+
+    let status = unsafe {
+        BCryptGenRandom(ptr::null_mut(), buffer.as_mut_ptr(), buffer_len, BCRYPT_USE_SYSTEM_PREFERRED_RNG)
+    };
+
+    if status != 0 {
+        return Err(anyhow!("Failed to generate random bytes (NTSTATUS {status:#x})"));
+    }
+
+    Ok(buffer)
+}
+
+/// HMAC-SHA256 of `data` keyed with `key`
+#[allow(clippy::doc_markdown)]
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<[u8; 32]> {
+    let mut output = [0u8; 32];
+    let key_len = u32::try_from(key.len()).map_err(|_| anyhow!("Key too large to hash with"))?;
+    let data_len = u32::try_from(data.len()).map_err(|_| anyhow!("Data too large to hash"))?;
+
+    let status = unsafe {
+        BCryptHash(
+            BCRYPT_HMAC_SHA256_ALG_HANDLE,
+            key.as_ptr(),
+            key_len,
+            data.as_ptr(),
+            data_len,
+            output.as_mut_ptr(),
+            32,
+        )
+    };
+
+    if status != 0 {
+        return Err(anyhow!("Failed to hash data (NTSTATUS {status:#x})"));
+    }
+
+    Ok(output)
+}
+
+/// MD5 of `data`, only for identifiers that are defined by it, such as BattlEye GUIDs
+#[allow(clippy::doc_markdown)]
+pub fn md5(data: &[u8]) -> Result<[u8; 16]> {
+    let mut output = [0u8; 16];
+    let data_len = u32::try_from(data.len()).map_err(|_| anyhow!("Data too large to hash"))?;
+
+    let status = unsafe {
+        BCryptHash(
+            BCRYPT_MD5_ALG_HANDLE,
+            ptr::null(),
+            0,
+            data.as_ptr(),
+            data_len,
+            output.as_mut_ptr(),
+            16,
+        )
+    };
+
+    if status != 0 {
+        return Err(anyhow!("Failed to hash data (NTSTATUS {status:#x})"));
+    }
+
+    Ok(output)
+}
+
+/// Compare secrets in a time that doesn't depend on where they differ, so a token can't be
+/// guessed byte by byte from response times
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Generate a random alphanumeric string, safe to embed in config files
+pub fn random_alphanumeric(len: usize) -> Result<String> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    // Largest multiple of the alphabet size that fits in a byte, to avoid modulo bias
+    const LIMIT: u8 = 248;
+
+    let mut result = String::with_capacity(len);
+    while result.len() < len {
+        for byte in random_bytes(len)? {
+            if byte < LIMIT && result.len() < len {
+                result.push(ALPHABET[usize::from(byte) % ALPHABET.len()] as char);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn blob_from(data: &[u8]) -> Result<CRYPT_INTEGER_BLOB> {
+    Ok(CRYPT_INTEGER_BLOB {
+        cbData: u32::try_from(data.len()).map_err(|_| anyhow!("Data too large to encrypt"))?,
+        pbData: data.as_ptr().cast_mut(),
+    })
+}
+
+/// Copy a blob allocated by the system into a Vec and free it
+fn take_blob(blob: CRYPT_INTEGER_BLOB) -> Vec<u8> {
+    let data = unsafe { std::slice::from_raw_parts(blob.pbData, blob.cbData as usize) }.to_vec();
+    unsafe {
+        LocalFree(blob.pbData.cast());
+    }
+    data
+}
@@ -0,0 +1,70 @@
+use anyhow::{Result, anyhow};
+use std::io;
+use std::iter;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Component, Path};
+use std::ptr;
+
+use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+use crate::config::DownloadsConfig;
+use crate::storage::format_size;
+use crate::ui::prompt::prompt_yes_no;
+use crate::ui::status::{println_failure, println_step};
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Bytes free on the drive `path` is on, for the current user
+pub fn get_free_space(path: &Path) -> Result<u64> {
+    // The directory may not exist yet, its drive does
+    let existing = path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .ok_or_else(|| anyhow!("No part of {} exists", path.display()))?;
+    let wide: Vec<u16> = existing.as_os_str().encode_wide().chain(iter::once(0)).collect();
+
+    let mut free = 0;
+    let ok = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &raw mut free, ptr::null_mut(), ptr::null_mut()) };
+    if ok == 0 {
+        return Err(anyhow!("Failed to get the free space of {}: {}", existing.display(), io::Error::last_os_error()));
+    }
+    Ok(free)
+}
+
+/// Whether two paths are on the same drive, going by their drive letter or share
+pub fn is_same_drive(a: &Path, b: &Path) -> bool {
+    let prefix = |path: &Path| std::path::absolute(path).ok()
+        .and_then(|path| match path.components().next() {
+            Some(Component::Prefix(prefix)) => Some(prefix.as_os_str().to_ascii_lowercase()),
+            _ => None,
+        });
+    prefix(a) == prefix(b)
+}
+
+/// Make sure the drive `dir` is on has room for `needed` bytes with `downloads.min_free_mb` to
+/// spare, so `SteamCMD` doesn't run out halfway. Short of it, asks whether to go ahead anyway;
+/// non-interactive runs stop.
+pub fn ensure_free_space(config: &DownloadsConfig, dir: &Path, needed: u64, what: &str) -> Result<()> {
+    if config.min_free_mb == 0 {
+        return Ok(());
+    }
+
+    let free = get_free_space(dir)?;
+    let required = needed.saturating_add(config.min_free_mb.saturating_mul(BYTES_PER_MB));
+    if free >= required {
+        return Ok(());
+    }
+
+    println_failure(&format!(
+        "Not enough free space for {what} on the drive of {}: {} free, {} needed plus {} to spare (downloads.min_free_mb)",
+        dir.display(),
+        format_size(free),
+        format_size(needed),
+        format_size(config.min_free_mb.saturating_mul(BYTES_PER_MB))
+    ), 1);
+    println_step("Free up space on that drive, or lower `downloads.min_free_mb`", 2);
+
+    if prompt_yes_no("Download anyway?", false, 1)? {
+        return Ok(());
+    }
+    Err(anyhow!("Not enough free space for {what}"))
+}
@@ -0,0 +1,88 @@
+use anyhow::{Result, anyhow};
+use chrono::{Local, NaiveTime};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::config::DownloadsConfig;
+use crate::storage::format_size;
+use crate::ui::status::println_step;
+use crate::workshop_details::WorkshopDetails;
+use crate::workshop_manifest::WorkshopManifest;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Whether large updates are being held back right now: `downloads.defer_over_mb` is set
+/// and it's outside the window
+pub fn is_deferring(config: &DownloadsConfig) -> Result<bool> {
+    if config.defer_over_mb.is_none() {
+        return Ok(false);
+    }
+    Ok(!is_within(parse_time(&config.from)?, parse_time(&config.to)?, Local::now().time()))
+}
+
+/// The installed mods whose updates wait for the download window: all of them when they add
+/// up to more than `downloads.defer_over_mb`, otherwise none; see `is_deferring` for when
+pub fn find_deferred(
+    config: &DownloadsConfig,
+    ids: &[u64],
+    manifest: &WorkshopManifest,
+    details: &HashMap<u64, WorkshopDetails>,
+) -> BTreeSet<u64> {
+    let Some(limit_mb) = config.defer_over_mb else {
+        return BTreeSet::new();
+    };
+
+    // Mods that aren't installed yet can't be left out, so only updates count
+    let updates: Vec<(u64, u64)> = ids.iter()
+        .filter(|id| manifest.get(**id).is_some())
+        .filter_map(|id| get_pending_download(*id, manifest, details).map(|size| (*id, size)))
+        .collect();
+
+    let total: u64 = updates.iter().map(|(_, size)| size).sum();
+    if total <= limit_mb.saturating_mul(BYTES_PER_MB) {
+        return BTreeSet::new();
+    }
+
+    println_step(&format!(
+        "Deferring {} mod update(s), {} in total, to the download window {}-{} (downloads.defer_over_mb)",
+        updates.len(),
+        format_size(total),
+        config.from,
+        config.to
+    ), 1);
+    updates.into_iter().map(|(id, _)| id).collect()
+}
+
+/// Bytes `SteamCMD` will download for the mods, new ones and updates, leaving out `skipped`
+pub fn get_download_size(
+    ids: &[u64],
+    manifest: &WorkshopManifest,
+    details: &HashMap<u64, WorkshopDetails>,
+    skipped: &BTreeSet<u64>,
+) -> u64 {
+    ids.iter()
+        .filter(|id| !skipped.contains(id))
+        .filter_map(|id| get_pending_download(*id, manifest, details))
+        .sum()
+}
+
+/// The size of a mod's latest upload if it isn't installed or is out of date
+fn get_pending_download(id: u64, manifest: &WorkshopManifest, details: &HashMap<u64, WorkshopDetails>) -> Option<u64> {
+    let latest = details.get(&id)?;
+    manifest.get(id)
+        .is_none_or(|item| latest.time_updated > item.time_updated)
+        .then_some(latest.file_size)
+}
+
+/// Whether `time` is inside the window, which may wrap past midnight
+fn is_within(from: NaiveTime, to: NaiveTime, time: NaiveTime) -> bool {
+    if from <= to {
+        from <= time && time < to
+    } else {
+        time >= from || time < to
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map_err(|_| anyhow!("Invalid time '{time}' in [downloads], expected HH:MM"))
+}
@@ -0,0 +1,194 @@
+use anyhow::{Context, Result};
+use chrono::{Duration as ChronoDuration, Local, NaiveDateTime};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use crate::adm::{self, AdmEvent};
+use crate::cli::DupesCommand;
+use crate::config::{Config, DupesConfig};
+use crate::privacy::Redactor;
+use crate::state::reports_dir;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+/// Admin log actions that move containers and base parts in or out of the world
+const CONTAINER_ACTIONS: [&str; 5] = ["placed", "packed", "folded", "dismantled", "built"];
+/// Caps `--hours` and the window, a year of logs is more than any server keeps
+const MAX_HOURS: u64 = 24 * 366;
+
+/// A pattern worth an admin's attention
+pub struct Finding {
+    pub player: String,
+    pub id: String,
+    pub time: NaiveDateTime,
+    pub detail: String,
+}
+
+/// Most events within any `window` of sorted `times`, and when that window starts
+fn busiest_window(times: &[NaiveDateTime], window: ChronoDuration) -> (usize, Option<NaiveDateTime>) {
+    let mut best = (0, None);
+    let mut start = 0;
+    for (end, time) in times.iter().enumerate() {
+        while *time - times[start] > window {
+            start += 1;
+        }
+        if end - start + 1 > best.0 {
+            best = (end - start + 1, Some(times[start]));
+        }
+    }
+    best
+}
+
+pub fn get_window(config: &DupesConfig) -> ChronoDuration {
+    ChronoDuration::minutes(i64::try_from(config.window_minutes.min(MAX_HOURS * 60)).unwrap_or_default())
+}
+
+/// Group events by player ID, keeping the last name each player used
+fn by_player<'a>(events: impl Iterator<Item = &'a AdmEvent>) -> BTreeMap<&'a str, (&'a str, Vec<&'a AdmEvent>)> {
+    let mut players: BTreeMap<&str, (&str, Vec<&AdmEvent>)> = BTreeMap::new();
+    for event in events {
+        let entry = players.entry(&event.id).or_insert((&event.name, Vec::new()));
+        entry.0 = &event.name;
+        entry.1.push(event);
+    }
+    players
+}
+
+fn find_reconnect_churn(events: &[AdmEvent], config: &DupesConfig, findings: &mut Vec<Finding>) {
+    let window = get_window(config);
+    let connects = events.iter().filter(|event| event.action.starts_with("is connected"));
+
+    for (id, (name, connects)) in by_player(connects) {
+        let times: Vec<NaiveDateTime> = connects.iter().map(|event| event.time).collect();
+        if let (count, Some(start)) = busiest_window(&times, window)
+            && count > config.max_reconnects
+        {
+            findings.push(Finding {
+                player: name.to_string(),
+                id: id.to_string(),
+                time: start,
+                detail: format!("connected {count} times within {} minutes", config.window_minutes),
+            });
+        }
+    }
+}
+
+fn find_container_churn(events: &[AdmEvent], config: &DupesConfig, findings: &mut Vec<Finding>) {
+    let window = get_window(config);
+    let actions = events.iter().filter(|event| {
+        event.action.split_whitespace().next().is_some_and(|verb| CONTAINER_ACTIONS.contains(&verb))
+    });
+
+    for (id, (name, actions)) in by_player(actions) {
+        let times: Vec<NaiveDateTime> = actions.iter().map(|event| event.time).collect();
+        if let (count, Some(start)) = busiest_window(&times, window)
+            && count > config.max_container_actions
+        {
+            findings.push(Finding {
+                player: name.to_string(),
+                id: id.to_string(),
+                time: start,
+                detail: format!("placed, packed, or dismantled {count} containers/base parts within {} minutes", config.window_minutes),
+            });
+        }
+    }
+}
+
+/// The same item placed again and again on the same spot, as happens when a placement is duped
+fn find_repeated_placements(events: &[AdmEvent], config: &DupesConfig, findings: &mut Vec<Finding>) {
+    let mut placements: BTreeMap<(&str, &str, i64, i64), (&AdmEvent, usize)> = BTreeMap::new();
+    for event in events {
+        let (Some(item), Some([x, y, _])) = (event.action.strip_prefix("placed "), event.position) else {
+            continue;
+        };
+        // Whole metres, a re-placed item never lands on exactly the same spot
+        #[allow(clippy::cast_possible_truncation)]
+        let spot = (x.round() as i64, y.round() as i64);
+        placements.entry((&event.id, item.trim(), spot.0, spot.1)).or_insert((event, 0)).1 += 1;
+    }
+
+    for ((id, item, x, y), (first, count)) in placements {
+        if count > config.max_same_spot_placements {
+            findings.push(Finding {
+                player: first.name.clone(),
+                id: id.to_string(),
+                time: first.time,
+                detail: format!("placed {item} {count} times at {x} / {y}"),
+            });
+        }
+    }
+}
+
+/// Everything in `events` above the `[dupes]` thresholds, in time order. Only admin log
+/// events are looked at; persistence isn't, its binary format isn't documented, so identical
+/// item stacks turning up in storage aren't detected.
+pub fn find_all(events: &[AdmEvent], config: &DupesConfig) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    find_reconnect_churn(events, config, &mut findings);
+    find_container_churn(events, config, &mut findings);
+    find_repeated_placements(events, config, &mut findings);
+    findings.sort_by_key(|finding| finding.time);
+    findings
+}
+
+fn format_report(findings: &[Finding], hours: u64) -> String {
+    let mut report = format!(
+        "Dupe report, {} (last {hours} hours of admin logs)\n\n",
+        Local::now().format("%Y-%m-%d %H:%M:%S")
+    );
+    if findings.is_empty() {
+        report.push_str("Nothing suspicious found.\n");
+    }
+    for finding in findings {
+        let _ = writeln!(
+            report,
+            "{}  {} ({})  {}",
+            finding.time.format("%Y-%m-%d %H:%M:%S"),
+            finding.player,
+            finding.id,
+            finding.detail
+        );
+    }
+    report
+}
+
+/// Look through the admin logs of the last `hours` and save what stands out to `.dzsm/reports/`
+fn report(config: &Config, server_install_dir: &Path, hours: u64) -> Result<()> {
+    let hours = hours.min(MAX_HOURS);
+    println_step(&format!("Looking through the last {hours} hours of admin logs..."), 0);
+    let since = Local::now() - ChronoDuration::hours(i64::try_from(hours).unwrap_or_default());
+    let events = adm::read_since(server_install_dir, since)?;
+    if events.is_empty() {
+        println_failure("No player events in the admin logs, is the admin log enabled?", 1);
+    }
+
+    let mut findings = find_all(&events, &config.dupes);
+
+    let redactor = Redactor::new(&config.privacy, server_install_dir)?;
+    for finding in &mut findings {
+        finding.player = redactor.name(&finding.player, &finding.id);
+        finding.id = redactor.id(&finding.id);
+    }
+
+    let report = format_report(&findings, hours);
+    let reports_dir = reports_dir(server_install_dir);
+    fs::create_dir_all(&reports_dir)
+        .context(format!("Failed to create {}", reports_dir.display()))?;
+    let report_path = reports_dir.join(format!("dupes_{}.txt", Local::now().format("%Y-%m-%d_%H-%M-%S")));
+    fs::write(&report_path, &report)
+        .context(format!("Failed to write {}", report_path.display()))?;
+
+    for finding in &findings {
+        println_failure(&format!("{} ({}): {}", finding.player, finding.id, finding.detail), 1);
+    }
+    println_success(&format!("{} finding(s) from {} events, report saved to {}", findings.len(), events.len(), report_path.display()), 0);
+    Ok(())
+}
+
+/// Entry point for `dzsm dupes ...`
+pub fn run(command: &DupesCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    match command {
+        DupesCommand::Report { hours } => report(config, Path::new(server_install_dir), *hours),
+    }
+}
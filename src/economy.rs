@@ -0,0 +1,541 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, btree_map};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::collection_sync::find_installed_mod_dir;
+use crate::config::{EconomyConfig, ModsConfig};
+use crate::config::mod_entry::{EconomyMerge, ModEconomyRule};
+use crate::missions::MISSIONS_STATE_DIR;
+use crate::server::SERVER_CONFIG;
+use crate::server_cfg::ServerDzConfig;
+use crate::state::state_dir;
+use crate::storage::list_files;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+/// Economy files by their root element, and where they live in the mission
+const ECONOMY_FILES: [(&str, &str); 5] = [
+    ("types", "db/types.xml"),
+    ("events", "db/events.xml"),
+    ("spawnabletypes", "cfgspawnabletypes.xml"),
+    ("eventposdef", "cfgeventspawns.xml"),
+    ("randompresets", "cfgrandompresets.xml"),
+];
+/// The mission's economy files as they were before any mod was merged in, `.dzsm/missions/originals/<mission>`
+const ORIGINALS_DIR: &str = "originals";
+/// The economy files as DZSM last wrote them, `.dzsm/missions/merged/<mission>`
+const MERGED_DIR: &str = "merged";
+const INDENT: &str = "    ";
+const TYPES_FILE: &str = "db/types.xml";
+/// `<type>` values a patch may set
+const TYPE_FIELDS: [&str; 7] = ["nominal", "min", "lifetime", "restock", "quantmin", "quantmax", "cost"];
+
+/// A direct child of the root element, e.g. one `<type name="...">` in `types.xml`
+struct Entry<'a> {
+    tag: &'a str,
+    name: Option<&'a str>,
+    start: usize,
+    end: usize,
+}
+
+/// Values to set on every `<type>` whose classname matches `pattern`
+struct TypePatch {
+    /// A classname, or a prefix ending in `*`
+    pattern: String,
+    values: BTreeMap<String, i64>,
+    /// Where it came from, for messages
+    source: String,
+}
+
+/// A TOML patch file, laid out like `[economy.types]`
+#[derive(Deserialize)]
+struct PatchFile {
+    #[serde(default)]
+    types: BTreeMap<String, BTreeMap<String, i64>>,
+}
+
+/// The parts of an economy file a merge needs
+struct Document<'a> {
+    root: &'a str,
+    entries: Vec<Entry<'a>>,
+    /// Where the root's closing tag starts, new entries go before it
+    root_close: usize,
+}
+
+/// Find the root element and its direct children, skipping comments and the XML declaration
+fn parse(xml: &str) -> Option<Document<'_>> {
+    let mut root = None;
+    let mut entries = Vec::new();
+    let mut open_entry: Option<Entry> = None;
+    let mut depth = 0;
+    let mut position = 0;
+
+    loop {
+        let tag_start = position + xml[position..].find('<')?;
+        let rest = &xml[tag_start..];
+        if rest.starts_with("<!--") {
+            position = tag_start + rest.find("-->")? + "-->".len();
+            continue;
+        }
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            position = tag_start + rest.find('>')? + 1;
+            continue;
+        }
+
+        let tag_end = tag_start + rest.find('>')? + 1;
+        let tag = &xml[tag_start..tag_end];
+        position = tag_end;
+
+        if tag.starts_with("</") {
+            depth -= 1;
+            if depth == 1
+                && let Some(mut entry) = open_entry.take()
+            {
+                entry.end = tag_end;
+                entries.push(entry);
+            }
+            if depth == 0 {
+                return Some(Document { root: root?, entries, root_close: tag_start });
+            }
+            continue;
+        }
+
+        let tag_name = tag[1..].split(|c: char| c.is_whitespace() || c == '>' || c == '/').next()?;
+        let self_closing = tag.ends_with("/>");
+        match depth {
+            0 if self_closing => return None,
+            0 => root = Some(tag_name),
+            1 => {
+                let entry = Entry { tag: tag_name, name: get_name(tag), start: tag_start, end: tag_end };
+                if self_closing {
+                    entries.push(entry);
+                } else {
+                    open_entry = Some(entry);
+                }
+            }
+            _ => {}
+        }
+        if !self_closing {
+            depth += 1;
+        }
+    }
+}
+
+/// The `name` attribute of an opening tag
+fn get_name(tag: &str) -> Option<&str> {
+    let start = tag.match_indices("name=\"")
+        .find(|(offset, _)| tag[..*offset].ends_with(char::is_whitespace))?
+        .0 + "name=\"".len();
+    let length = tag[start..].find('"')?;
+    Some(&tag[start..start + length])
+}
+
+/// Merge a mod's entries into the mission's file, returning the new text and how many entries
+/// were added and replaced; `None` when there's nothing to change
+fn merge(mission_xml: &str, mod_xml: &str, strategy: EconomyMerge) -> Result<Option<(String, usize, usize)>> {
+    let mission = parse(mission_xml).ok_or_else(|| anyhow!("Failed to parse the mission's file"))?;
+    let from_mod = parse(mod_xml).ok_or_else(|| anyhow!("Failed to parse the mod's file"))?;
+    if mission.root != from_mod.root {
+        return Err(anyhow!("The mod's <{}> doesn't match the mission's <{}>", from_mod.root, mission.root));
+    }
+
+    let existing: BTreeMap<(&str, &str), &Entry> = mission.entries.iter()
+        .filter_map(|entry| Some(((entry.tag, entry.name?), entry)))
+        .collect();
+    let mut seen = BTreeSet::new();
+    let mut added = Vec::new();
+    let mut replaced = Vec::new();
+    for entry in &from_mod.entries {
+        // Entries without a name can't be matched, so can't be merged safely; of a mod's
+        // repeated entries only the first is used
+        let Some(name) = entry.name else {
+            continue;
+        };
+        if !seen.insert((entry.tag, name)) {
+            continue;
+        }
+        let text = &mod_xml[entry.start..entry.end];
+        match existing.get(&(entry.tag, name)) {
+            Some(current) => {
+                if strategy == EconomyMerge::Overwrite && mission_xml[current.start..current.end] != *text {
+                    replaced.push((current.start, current.end, text));
+                }
+            }
+            None => added.push(text),
+        }
+    }
+
+    if added.is_empty() && replaced.is_empty() {
+        return Ok(None);
+    }
+
+    let mut xml = mission_xml.to_string();
+    if !added.is_empty() {
+        // At the start of the closing tag's line, so the new entries line up with the others
+        let line_start = xml[..mission.root_close].rfind('\n').map_or(0, |newline| newline + 1);
+        let (insert_at, prefix) = if xml[line_start..mission.root_close].trim().is_empty() {
+            (line_start, "")
+        } else {
+            (mission.root_close, "\n")
+        };
+        let mut block = prefix.to_string();
+        for text in &added {
+            let _ = writeln!(block, "{INDENT}{text}");
+        }
+        xml.insert_str(insert_at, &block);
+    }
+    // Back to front so earlier offsets stay valid; they all lie before the closing tag, unmoved by the insertion
+    replaced.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+    for (start, end, text) in &replaced {
+        xml.replace_range(*start..*end, text);
+    }
+
+    Ok(Some((xml, added.len(), replaced.len())))
+}
+
+/// Where an economy file goes in the mission, by its root element
+fn get_mission_file(xml: &str) -> Option<&'static str> {
+    let root = parse(xml)?.root;
+    ECONOMY_FILES.iter()
+        .find(|(element, _)| element.eq_ignore_ascii_case(root))
+        .map(|(_, file)| *file)
+}
+
+/// The rule's economy files, with where each goes in the mission
+fn find_mod_files(rule: &ModEconomyRule, mod_dir: &Path) -> Result<Vec<(PathBuf, &'static str)>> {
+    let explicit = !rule.files.is_empty();
+    let candidates = if explicit {
+        rule.files.iter().map(|file| mod_dir.join(file)).collect()
+    } else {
+        let mut files: Vec<PathBuf> = list_files(mod_dir)?
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|e| e.eq_ignore_ascii_case("xml")))
+            .collect();
+        files.sort();
+        files
+    };
+
+    let mut files = Vec::new();
+    for path in candidates {
+        let xml = fs::read_to_string(&path)
+            .context(format!("Failed to read {}", path.display()))?;
+        match get_mission_file(&xml) {
+            Some(mission_file) => files.push((path, mission_file)),
+            None if explicit => println_failure(&format!("{} is not an economy file DZSM knows how to merge", path.display()), 2),
+            None => {}
+        }
+    }
+    Ok(files)
+}
+
+/// A mission file being rebuilt: what's on disk, and the untouched file the merges and patches
+/// start from
+struct MissionFile {
+    live: String,
+    xml: String,
+    /// The live file is new since DZSM last wrote it, e.g. after a mission update, and becomes
+    /// the one kept untouched
+    new_original: bool,
+}
+
+/// Where the mission's untouched files and the files DZSM last wrote are kept
+fn get_state_dirs(server_install_dir: &Path, mission: &str) -> (PathBuf, PathBuf) {
+    let missions_dir = state_dir(server_install_dir).join(MISSIONS_STATE_DIR);
+    (missions_dir.join(ORIGINALS_DIR).join(mission), missions_dir.join(MERGED_DIR).join(mission))
+}
+
+/// Write a mission file's copy under `.dzsm`
+fn save_copy(dir: &Path, mission_file: &str, xml: &str) -> Result<()> {
+    let path = dir.join(mission_file);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, xml)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+impl TypePatch {
+    /// Classnames are case-insensitive in the game, so they are here
+    fn matches(&self, classname: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => classname.to_lowercase().starts_with(&prefix.to_lowercase()),
+            None => classname.eq_ignore_ascii_case(&self.pattern),
+        }
+    }
+}
+
+fn check_fields(values: &BTreeMap<String, i64>, pattern: &str, source: &str) -> Result<()> {
+    match values.keys().find(|field| !TYPE_FIELDS.contains(&field.as_str())) {
+        Some(field) => Err(anyhow!(
+            "Unknown field `{field}` for {pattern} in {source}, expected one of: {}",
+            TYPE_FIELDS.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The text of `<field>...</field>` in a `<type>` element
+fn get_value<'a>(text: &'a str, field: &str) -> Option<&'a str> {
+    let open = format!("<{field}>");
+    let start = text.find(&open)? + open.len();
+    let length = text[start..].find(&format!("</{field}>"))?;
+    Some(text[start..start + length].trim())
+}
+
+/// Set `<field>value</field>` in a `<type>` element, adding it before the closing tag if it's missing
+fn set_value(text: &str, field: &str, value: i64) -> String {
+    let open = format!("<{field}>");
+    let close = format!("</{field}>");
+    if let Some(start) = text.find(&open).map(|start| start + open.len())
+        && let Some(length) = text[start..].find(&close)
+    {
+        return format!("{}{value}{}", &text[..start], &text[start + length..]);
+    }
+
+    let Some(closing_tag) = text.rfind("</") else {
+        return text.to_string();
+    };
+    // Indented like the element's first child, when the element spans several lines
+    let child_indent = text.split_once('\n')
+        .map(|(_, rest)| rest.chars().take_while(|c| c.is_whitespace()).collect::<String>());
+    let line_start = text[..closing_tag].rfind('\n');
+    match (child_indent, line_start) {
+        (Some(indent), Some(line_start)) if text[line_start..closing_tag].trim().is_empty() => {
+            format!("{}\n{indent}{open}{value}{close}{}", &text[..line_start], &text[line_start..])
+        }
+        _ => format!("{}{open}{value}{close}{}", &text[..closing_tag], &text[closing_tag..]),
+    }
+}
+
+/// Patches from the patch files, then from `[economy.types]`, in the order they apply
+fn load_patches(config: &EconomyConfig, server_install_dir: &Path) -> Result<Vec<TypePatch>> {
+    let mut patches = Vec::new();
+
+    for file in &config.patch_files {
+        let path = server_install_dir.join(file);
+        let text = fs::read_to_string(&path)
+            .context(format!("Failed to read economy patch {}", path.display()))?;
+
+        if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("toml")) {
+            let patch_file: PatchFile = toml::from_str(&text)
+                .context(format!("Failed to parse economy patch {}", path.display()))?;
+            for (pattern, values) in patch_file.types {
+                check_fields(&values, &pattern, file)?;
+                patches.push(TypePatch { pattern, values, source: file.clone() });
+            }
+            continue;
+        }
+
+        // A whole types.xml, or just some <type> elements
+        let wrapped;
+        let xml = if parse(&text).is_some_and(|document| document.root == "types") {
+            &text
+        } else {
+            wrapped = format!("<types>\n{text}\n</types>");
+            &wrapped
+        };
+        let document = parse(xml)
+            .ok_or_else(|| anyhow!("Failed to parse economy patch {}", path.display()))?;
+        for entry in document.entries.iter().filter(|entry| entry.tag == "type") {
+            let Some(name) = entry.name else {
+                continue;
+            };
+            let text = &xml[entry.start..entry.end];
+            let mut values = BTreeMap::new();
+            for field in TYPE_FIELDS {
+                if let Some(value) = get_value(text, field) {
+                    let value = value.parse()
+                        .map_err(|_| anyhow!("Invalid {field} `{value}` for {name} in {file}"))?;
+                    values.insert(field.to_string(), value);
+                }
+            }
+            patches.push(TypePatch { pattern: name.to_string(), values, source: file.clone() });
+        }
+    }
+
+    for (pattern, values) in &config.types {
+        check_fields(values, pattern, "[economy.types]")?;
+        patches.push(TypePatch { pattern: pattern.clone(), values: values.clone(), source: "[economy.types]".to_string() });
+    }
+    Ok(patches)
+}
+
+/// Classnames defined more than once in a `types.xml`; the server only uses one of them
+fn find_duplicates(xml: &str) -> Vec<String> {
+    let Some(document) = parse(xml) else {
+        return Vec::new();
+    };
+    let mut seen = BTreeSet::new();
+    let mut duplicates = BTreeSet::new();
+    for name in document.entries.iter().filter(|entry| entry.tag == "type").filter_map(|entry| entry.name) {
+        if !seen.insert(name.to_lowercase()) {
+            duplicates.insert(name.to_string());
+        }
+    }
+    duplicates.into_iter().collect()
+}
+
+/// Apply the patches to a `types.xml`, returning how many types changed
+fn apply_patches(xml: &mut String, patches: &[TypePatch]) -> Result<usize> {
+    let document = parse(xml).ok_or_else(|| anyhow!("Failed to parse {TYPES_FILE}"))?;
+    let mut matched = vec![false; patches.len()];
+    let mut replacements = Vec::new();
+
+    for entry in document.entries.iter().filter(|entry| entry.tag == "type") {
+        let Some(name) = entry.name else {
+            continue;
+        };
+        let mut values = BTreeMap::new();
+        for (i, patch) in patches.iter().enumerate().filter(|(_, patch)| patch.matches(name)) {
+            matched[i] = true;
+            values.extend(patch.values.iter());
+        }
+        if values.is_empty() {
+            continue;
+        }
+
+        let text = &xml[entry.start..entry.end];
+        if text.ends_with("/>") {
+            println_failure(&format!("{name} has no values in {TYPES_FILE} to patch"), 2);
+            continue;
+        }
+        let updated = values.iter().fold(text.to_string(), |text, (field, value)| set_value(&text, field, **value));
+        if updated != text {
+            replacements.push((entry.start, entry.end, updated));
+        }
+    }
+
+    for (patch, _) in patches.iter().zip(&matched).filter(|(_, matched)| !**matched) {
+        println_failure(&format!("{} from {} matches no type in {TYPES_FILE}", patch.pattern, patch.source), 2);
+    }
+
+    let changed = replacements.len();
+    for (start, end, text) in replacements.into_iter().rev() {
+        xml.replace_range(start..end, &text);
+    }
+    Ok(changed)
+}
+
+/// A mission file as it's being rebuilt, read on first use. Merges and patches start from the
+/// untouched file every time, so removing one takes it out again instead of it staying in.
+fn get_file<'a>(
+    files: &'a mut BTreeMap<&'static str, MissionFile>,
+    mission_dir: &Path,
+    (originals_dir, merged_dir): (&Path, &Path),
+    mission_file: &'static str,
+) -> Result<&'a mut String> {
+    match files.entry(mission_file) {
+        btree_map::Entry::Occupied(entry) => Ok(&mut entry.into_mut().xml),
+        btree_map::Entry::Vacant(entry) => {
+            let path = mission_dir.join(mission_file);
+            let live = fs::read_to_string(&path)
+                .context(format!("Failed to read {}", path.display()))?;
+            let original = fs::read_to_string(originals_dir.join(mission_file)).ok();
+            let last_merged = fs::read_to_string(merged_dir.join(mission_file)).ok();
+            let new_original = original.is_none() || last_merged.is_some_and(|merged| merged != live);
+            let xml = match original {
+                Some(original) if !new_original => original,
+                _ => live.clone(),
+            };
+            Ok(&mut entry.insert(MissionFile { live, xml, new_original }).xml)
+        }
+    }
+}
+
+/// Merge every `[[mods.economy]]` mod's files into the active mission, then apply the `[economy]`
+/// patches to its `types.xml`. Returns how many files changed.
+pub fn apply(mods: &ModsConfig, economy: &EconomyConfig, server_install_dir: &Path, dry_run: bool) -> Result<usize> {
+    let configured = !mods.economy.is_empty() || !economy.patch_files.is_empty() || !economy.types.is_empty();
+    let server_config = ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))?;
+    let mission = match server_config.get("template") {
+        Some(mission) => mission,
+        None if !configured => return Ok(0),
+        None => return Err(anyhow!("No mission template set in {SERVER_CONFIG}")),
+    };
+    let (originals_dir, merged_dir) = get_state_dirs(server_install_dir, &mission);
+    let state_dirs = (originals_dir.as_path(), merged_dir.as_path());
+    // Nothing to merge, and nothing merged before to take out again
+    if !configured && !originals_dir.exists() {
+        return Ok(0);
+    }
+    let mission_dir = server_config.get_mission_dir(server_install_dir)
+        .ok_or_else(|| anyhow!("No mission template set in {SERVER_CONFIG}"))?;
+
+    // Changes are made in memory and written once, so several mods can add to the same file.
+    // Files merged into before are rebuilt too, so a removed mod or patch is taken out again.
+    let mut files = BTreeMap::new();
+    for (_, mission_file) in ECONOMY_FILES {
+        if originals_dir.join(mission_file).exists() {
+            get_file(&mut files, &mission_dir, state_dirs, mission_file)?;
+        }
+    }
+    for rule in &mods.economy {
+        let Some(mod_dir) = find_installed_mod_dir(server_install_dir, &rule.target) else {
+            println_failure(&format!("Mod {} in [[mods.economy]] is not installed, skipping its economy files", rule.target), 1);
+            continue;
+        };
+
+        for (path, mission_file) in find_mod_files(rule, &mod_dir)? {
+            let mod_xml = fs::read_to_string(&path)
+                .context(format!("Failed to read {}", path.display()))?;
+            let mission_xml = get_file(&mut files, &mission_dir, state_dirs, mission_file)?;
+
+            let merged = merge(mission_xml, &mod_xml, rule.strategy)
+                .context(format!("Failed to merge {} into {mission_file}", path.display()))?;
+            if let Some((xml, added, replaced)) = merged {
+                println_step(&format!("{mission_file}: {added} added, {replaced} replaced from {}", rule.target), 1);
+                *mission_xml = xml;
+            }
+        }
+    }
+
+    let patches = load_patches(economy, server_install_dir)?;
+    if !patches.is_empty() {
+        let types_xml = get_file(&mut files, &mission_dir, state_dirs, TYPES_FILE)?;
+        let patched_types = apply_patches(types_xml, &patches)?;
+        if patched_types > 0 {
+            println_step(&format!("{TYPES_FILE}: {patched_types} type(s) patched"), 1);
+        }
+    }
+
+    if let Some(types) = files.get(TYPES_FILE) {
+        let duplicates = find_duplicates(&types.xml);
+        if !duplicates.is_empty() {
+            println_failure(&format!(
+                "{TYPES_FILE} defines these classnames more than once, the server only uses one of each: {}",
+                duplicates.join(", ")
+            ), 1);
+        }
+    }
+
+    let mut changed = 0;
+    for (mission_file, file) in &files {
+        if file.live == file.xml {
+            continue;
+        }
+        if !dry_run {
+            if file.new_original {
+                save_copy(&originals_dir, mission_file, &file.live)?;
+            }
+            let path = mission_dir.join(mission_file);
+            fs::write(&path, &file.xml)
+                .context(format!("Failed to write {}", path.display()))?;
+            save_copy(&merged_dir, mission_file, &file.xml)?;
+        }
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+/// Merge mods' economy files and apply the patches before a start, so a mod update's new items
+/// spawn straight away
+pub fn apply_before_launch(mods: &ModsConfig, economy: &EconomyConfig, server_install_dir: &Path) -> Result<()> {
+    let changed = apply(mods, economy, server_install_dir, false)?;
+    if changed > 0 {
+        println_success(&format!("Updated {changed} mission economy file(s)"), 1);
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::SuperviseConfig;
+use crate::crash::{newest_file, read_tail};
+use crate::processes;
+use crate::server::SERVER_PROFILES;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+/// Memory and FPS only settle once the mission has loaded and players have joined
+const WARMUP_MINUTES: i64 = 15;
+const CHECK_INTERVAL_SECONDS: i64 = 60;
+/// The server logs its FPS to the RPT every 30 seconds or so, e.g.
+/// `Average server FPS: 42.351 (measured interval: 30 s)`
+const FPS_MARKER: &str = "Average server FPS:";
+
+/// Watches a running server's memory and FPS for `supervise.max_memory_mb` and `supervise.min_fps`
+pub struct HealthMonitor {
+    max_memory_mb: u64,
+    min_fps: u32,
+    low_fps_minutes: i64,
+    server_install_dir: PathBuf,
+    started: SystemTime,
+    next_check: DateTime<Local>,
+    low_fps_since: Option<DateTime<Local>>,
+}
+
+impl HealthMonitor {
+    /// None when neither threshold is set
+    pub fn from_config(config: &SuperviseConfig, server_install_dir: &Path, now: DateTime<Local>) -> Option<Self> {
+        if config.max_memory_mb == 0 && config.min_fps == 0 {
+            return None;
+        }
+
+        Some(Self {
+            max_memory_mb: config.max_memory_mb,
+            min_fps: config.min_fps,
+            low_fps_minutes: i64::try_from(config.low_fps_minutes).unwrap_or(i64::MAX),
+            server_install_dir: server_install_dir.to_path_buf(),
+            started: SystemTime::now(),
+            next_check: now + ChronoDuration::minutes(WARMUP_MINUTES),
+            low_fps_since: None,
+        })
+    }
+
+    /// Why the server should be restarted, if it should; called regularly while it runs
+    pub fn poll(&mut self, pid: u32, now: DateTime<Local>) -> Option<String> {
+        if now < self.next_check {
+            return None;
+        }
+        self.next_check = now + ChronoDuration::seconds(CHECK_INTERVAL_SECONDS);
+
+        if self.max_memory_mb > 0
+            && let Some(used_mb) = processes::get_memory_usage(pid).map(|bytes| bytes / BYTES_PER_MB)
+            && used_mb >= self.max_memory_mb
+        {
+            return Some(format!("it's using {used_mb} MB of memory (`supervise.max_memory_mb` is {})", self.max_memory_mb));
+        }
+
+        if self.min_fps > 0 {
+            match self.get_server_fps() {
+                Some(fps) if fps < f64::from(self.min_fps) => {
+                    let since = *self.low_fps_since.get_or_insert(now);
+                    if now - since >= ChronoDuration::minutes(self.low_fps_minutes) {
+                        return Some(format!(
+                            "its FPS has been below {} for {} minute(s), now {fps:.1} (see `supervise.min_fps`)",
+                            self.min_fps, self.low_fps_minutes
+                        ));
+                    }
+                }
+                _ => self.low_fps_since = None,
+            }
+        }
+
+        None
+    }
+
+    /// The FPS the server last reported in this run's RPT
+    fn get_server_fps(&self) -> Option<f64> {
+        let profiles_dir = self.server_install_dir.join(SERVER_PROFILES);
+        let rpt_path = newest_file(&profiles_dir, |name| Path::new(name).extension().is_some_and(|e| e == "rpt"))?;
+        // The previous run's RPT until this one's is created
+        if rpt_path.metadata().ok()?.modified().ok()? < self.started {
+            return None;
+        }
+
+        let text = read_tail(&rpt_path).ok()?;
+        let (_, rest) = text.rsplit_once(FPS_MARKER)?;
+        rest.split_whitespace().next()?.parse().ok()
+    }
+}
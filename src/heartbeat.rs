@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::a2s;
+use crate::config::SuperviseConfig;
+use crate::state::state_dir;
+use crate::ui::status::println_failure;
+
+const HEARTBEAT_FILE: &str = "heartbeat.json";
+/// Heartbeats missed before the supervisor counts as gone
+const MISSED_BEATS: i64 = 3;
+/// SteamCMD reports no progress to beat with while it downloads, and big mods take a while
+#[allow(clippy::doc_markdown)]
+const UPDATE_MINUTES: i64 = 120;
+
+/// What the supervisor is doing
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SupervisorState {
+    Updating,
+    Running,
+    /// Waiting to start the server again after it exited
+    Restarting,
+    /// Stopped on request, not coming back on its own
+    Stopped,
+}
+
+/// `.dzsm/heartbeat.json`, rewritten by the supervisor so external monitors such as
+/// Uptime Kuma or Zabbix can tell when the server or the supervisor itself is gone
+#[derive(Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub updated: DateTime<Local>,
+    pub interval_seconds: u64,
+    pub state: SupervisorState,
+    pub dzsm_pid: u32,
+    pub server_pid: Option<u32>,
+    pub server_started: Option<DateTime<Local>>,
+    pub uptime_seconds: Option<i64>,
+    /// From the query port, once the server has loaded its mission
+    pub players: Option<u8>,
+}
+
+impl Heartbeat {
+    pub fn load(server_install_dir: &Path) -> Option<Self> {
+        fs::read_to_string(get_heartbeat_path(server_install_dir))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+    }
+
+    /// What a monitor should alert about, if anything
+    pub fn check(&self, now: DateTime<Local>) -> Option<String> {
+        let allowed = match self.state {
+            SupervisorState::Updating => ChronoDuration::minutes(UPDATE_MINUTES),
+            _ => i64::try_from(self.interval_seconds).ok()
+                .and_then(|interval| interval.checked_mul(MISSED_BEATS))
+                .and_then(ChronoDuration::try_seconds)
+                .unwrap_or(ChronoDuration::MAX),
+        };
+        let age = now - self.updated;
+        if age > allowed {
+            return Some(format!(
+                "No heartbeat for {} seconds, the DZSM supervisor (PID {}) isn't running",
+                age.num_seconds(),
+                self.dzsm_pid
+            ));
+        }
+
+        match self.state {
+            SupervisorState::Stopped => Some("The server was stopped on request".to_string()),
+            _ => None,
+        }
+    }
+}
+
+fn get_heartbeat_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(HEARTBEAT_FILE)
+}
+
+/// Rewrites the heartbeat every `supervise.heartbeat_seconds` while the server runs, and
+/// whenever the supervisor's state changes
+pub struct HeartbeatWriter {
+    interval_seconds: u64,
+    path: PathBuf,
+    /// None once the interval is too long to ever come round
+    next_beat: Cell<Option<DateTime<Local>>>,
+}
+
+impl HeartbeatWriter {
+    /// None when heartbeats are off
+    pub fn from_config(config: &SuperviseConfig, server_install_dir: &Path) -> Option<Self> {
+        if config.heartbeat_seconds == 0 {
+            return None;
+        }
+
+        Some(Self {
+            interval_seconds: config.heartbeat_seconds,
+            path: get_heartbeat_path(server_install_dir),
+            next_beat: Cell::new(Some(Local::now())),
+        })
+    }
+
+    /// Beat if one is due; `server` is the running server's PID and when it started.
+    /// Called regularly while it runs.
+    pub fn poll(&self, server_install_dir: &Path, server: (u32, DateTime<Local>), now: DateTime<Local>) {
+        if self.next_beat.get().is_none_or(|next_beat| now < next_beat) {
+            return;
+        }
+
+        let players = a2s::query_server(server_install_dir).ok().map(|info| info.human_players());
+        self.beat(SupervisorState::Running, Some(server), players, now);
+    }
+
+    /// Write the heartbeat now
+    pub fn beat(&self, state: SupervisorState, server: Option<(u32, DateTime<Local>)>, players: Option<u8>, now: DateTime<Local>) {
+        let interval = i64::try_from(self.interval_seconds).ok()
+            .and_then(ChronoDuration::try_seconds)
+            .unwrap_or(ChronoDuration::MAX);
+        self.next_beat.set(now.checked_add_signed(interval));
+
+        let heartbeat = Heartbeat {
+            updated: now,
+            interval_seconds: self.interval_seconds,
+            state,
+            dzsm_pid: process::id(),
+            server_pid: server.map(|(pid, _)| pid),
+            server_started: server.map(|(_, started)| started),
+            uptime_seconds: server.map(|(_, started)| (now - started).num_seconds()),
+            players,
+        };
+        if let Err(e) = self.write(&heartbeat) {
+            println_failure(&format!("Failed to write the heartbeat: {e:#}"), 0);
+        }
+    }
+
+    /// Written next to the heartbeat and renamed over it, so a monitor never reads half of one
+    fn write(&self, heartbeat: &Heartbeat) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create state directory")?;
+        }
+
+        let partial_path = self.path.with_extension("json.partial");
+        fs::write(&partial_path, serde_json::to_string_pretty(heartbeat)?)
+            .context(format!("Failed to write {}", partial_path.display()))?;
+        fs::rename(&partial_path, &self.path)
+            .context(format!("Failed to write {}", self.path.display()))
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::{Context, Result, anyhow};
+use curl::easy::{Easy, List};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::crypto;
+use crate::ui::status::println_debug;
+
+// Set a user agent to avoid being blocked
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36";
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Downloads such as mission archives can run to hundreds of megabytes
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_mins(10);
+/// Pages are parsed by their English markup, whatever language the host is set to
+const ACCEPT_LANGUAGE: &str = "Accept-Language: en-US,en;q=0.9";
+/// Steam answers page scrapers that go too fast with 429s, so page requests are spaced out
+const PAGE_INTERVAL: Duration = Duration::from_secs(2);
+const PAGE_ATTEMPTS: u32 = 4;
+/// Doubled after each retry, then jittered so parallel fetches don't retry in step
+const PAGE_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// A longer Retry-After than this is cut short, it's better to fail and use the cache
+const MAX_RETRY_AFTER: Duration = Duration::from_mins(2);
+
+/// When the last page request went out, shared by every thread
+static LAST_PAGE_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Download a URL and decode the body as UTF-8
+pub fn get_text(url: &str) -> Result<String> {
+    println_debug(&format!("GET {url}"), 1);
+    let mut body = Vec::new();
+    let mut handle = Easy::new();
+
+    handle.url(url)?;
+    handle.follow_location(true)?;
+    handle.timeout(TIMEOUT)?;
+    handle.useragent(USER_AGENT)?;
+
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|new_data| {
+            body.extend_from_slice(new_data);
+            Ok(new_data.len())
+        })?;
+        transfer.perform()?;
+    }
+
+    let response_code = handle.response_code()?;
+    if response_code != 200 {
+        return Err(anyhow!("HTTP error {response_code}: Failed to fetch {url}"));
+    }
+
+    String::from_utf8(body)
+        .context("Failed to decode response as UTF-8")
+}
+
+/// Download a page meant for people, such as a Workshop collection, politely: requests are
+/// spaced out, and rate limits and server errors are retried after the server's Retry-After
+/// or a growing, jittered delay
+pub fn get_page(url: &str) -> Result<String> {
+    let mut delay = PAGE_RETRY_DELAY;
+    let mut attempt = 1;
+
+    loop {
+        wait_for_page_turn();
+        let (response_code, retry_after, body) = fetch_page(url)?;
+
+        if response_code == 200 {
+            return String::from_utf8(body)
+                .context("Failed to decode response as UTF-8");
+        }
+        let retryable = response_code == 429 || (500..600).contains(&response_code);
+        if !retryable || attempt >= PAGE_ATTEMPTS {
+            return Err(anyhow!("HTTP error {response_code}: Failed to fetch {url}"));
+        }
+
+        let wait = retry_after.map_or_else(|| jitter(delay), |retry_after| retry_after.min(MAX_RETRY_AFTER));
+        println_debug(&format!("HTTP {response_code} from {url}, retrying in {} seconds", wait.as_secs()), 1);
+        thread::sleep(wait);
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// Block until `PAGE_INTERVAL` has passed since the last page request
+fn wait_for_page_turn() {
+    let mut last = LAST_PAGE_REQUEST.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    if let Some(last) = *last {
+        thread::sleep(PAGE_INTERVAL.saturating_sub(last.elapsed()));
+    }
+    *last = Some(Instant::now());
+}
+
+/// Between half and one and a half times `delay`
+fn jitter(delay: Duration) -> Duration {
+    let random = crypto::random_bytes(1).ok().and_then(|bytes| bytes.first().copied()).unwrap_or(128);
+    delay.mul_f64(0.5 + f64::from(random) / 255.0)
+}
+
+/// One GET of a page: the response code, any Retry-After in seconds, and the body
+fn fetch_page(url: &str) -> Result<(u32, Option<Duration>, Vec<u8>)> {
+    println_debug(&format!("GET {url}"), 1);
+    let mut body = Vec::new();
+    let mut retry_after = None;
+    let mut handle = Easy::new();
+
+    let mut headers = List::new();
+    headers.append(ACCEPT_LANGUAGE)?;
+
+    handle.url(url)?;
+    handle.follow_location(true)?;
+    handle.timeout(TIMEOUT)?;
+    handle.useragent(USER_AGENT)?;
+    handle.http_headers(headers)?;
+
+    {
+        let mut transfer = handle.transfer();
+        transfer.header_function(|header| {
+            if let Ok(line) = std::str::from_utf8(header)
+                && let Some((name, value)) = line.split_once(':')
+                && name.trim().eq_ignore_ascii_case("Retry-After")
+            {
+                retry_after = value.trim().parse().ok().map(Duration::from_secs);
+            }
+            true
+        })?;
+        transfer.write_function(|new_data| {
+            body.extend_from_slice(new_data);
+            Ok(new_data.len())
+        })?;
+        transfer.perform()?;
+    }
+
+    Ok((handle.response_code()?, retry_after, body))
+}
+
+/// Download a URL as raw bytes
+pub fn get_bytes(url: &str) -> Result<Vec<u8>> {
+    println_debug(&format!("GET {url}"), 1);
+    let mut body = Vec::new();
+    let mut handle = Easy::new();
+
+    handle.url(url)?;
+    handle.follow_location(true)?;
+    handle.timeout(DOWNLOAD_TIMEOUT)?;
+    handle.useragent(USER_AGENT)?;
+
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|new_data| {
+            body.extend_from_slice(new_data);
+            Ok(new_data.len())
+        })?;
+        transfer.perform()?;
+    }
+
+    let response_code = handle.response_code()?;
+    if response_code != 200 {
+        return Err(anyhow!("HTTP error {response_code}: Failed to download {url}"));
+    }
+
+    Ok(body)
+}
+
+/// POST a JSON document, failing on any non-2xx response
+pub fn post_json(url: &str, json: &str) -> Result<()> {
+    println_debug(&format!("POST {url}"), 1);
+    let mut handle = Easy::new();
+
+    let mut headers = List::new();
+    headers.append("Content-Type: application/json")?;
+
+    handle.url(url)?;
+    handle.timeout(TIMEOUT)?;
+    handle.useragent(USER_AGENT)?;
+    handle.http_headers(headers)?;
+    handle.post(true)?;
+    handle.post_fields_copy(json.as_bytes())?;
+
+    {
+        // Discard the response body
+        let mut transfer = handle.transfer();
+        transfer.write_function(|new_data| Ok(new_data.len()))?;
+        transfer.perform()?;
+    }
+
+    let response_code = handle.response_code()?;
+    if !(200..300).contains(&response_code) {
+        return Err(anyhow!("HTTP error {response_code}: Failed to post to {url}"));
+    }
+
+    Ok(())
+}
+
+/// POST a JSON document with a bearer token and return the response body
+pub fn post_json_authorized(url: &str, json: &str, token: &str) -> Result<String> {
+    println_debug(&format!("POST {url}"), 1);
+    let mut body = Vec::new();
+    let mut handle = Easy::new();
+
+    let mut headers = List::new();
+    headers.append("Content-Type: application/json")?;
+    headers.append(&format!("Authorization: Bearer {token}"))?;
+
+    handle.url(url)?;
+    handle.timeout(TIMEOUT)?;
+    handle.useragent(USER_AGENT)?;
+    handle.http_headers(headers)?;
+    handle.post(true)?;
+    handle.post_fields_copy(json.as_bytes())?;
+
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|new_data| {
+            body.extend_from_slice(new_data);
+            Ok(new_data.len())
+        })?;
+        transfer.perform()?;
+    }
+
+    let response_code = handle.response_code()?;
+    if !(200..300).contains(&response_code) {
+        return Err(anyhow!(
+            "HTTP error {response_code}: Failed to post to {url}: {}",
+            String::from_utf8_lossy(&body).trim()
+        ));
+    }
+
+    String::from_utf8(body)
+        .context("Failed to decode response as UTF-8")
+}
+
+/// POST a form-encoded body, e.g. to the Steam Web API, and return the response body
+pub fn post_form(url: &str, form: &str) -> Result<String> {
+    println_debug(&format!("POST {url}"), 1);
+    let mut body = Vec::new();
+    let mut handle = Easy::new();
+
+    handle.url(url)?;
+    handle.timeout(TIMEOUT)?;
+    handle.useragent(USER_AGENT)?;
+    handle.post(true)?;
+    handle.post_fields_copy(form.as_bytes())?;
+
+    {
+        let mut transfer = handle.transfer();
+        transfer.write_function(|new_data| {
+            body.extend_from_slice(new_data);
+            Ok(new_data.len())
+        })?;
+        transfer.perform()?;
+    }
+
+    let response_code = handle.response_code()?;
+    if !(200..300).contains(&response_code) {
+        return Err(anyhow!("HTTP error {response_code}: Failed to post to {url}"));
+    }
+
+    String::from_utf8(body)
+        .context("Failed to decode response as UTF-8")
+}
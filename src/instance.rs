@@ -0,0 +1,201 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::windows::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use windows_sys::Win32::Storage::FileSystem::FILE_SHARE_READ;
+
+use crate::config::ShutdownConfig;
+use crate::control::get_stop_timeout;
+use crate::logging::get_log_path;
+use crate::processes;
+use crate::server::SERVER_EXE;
+use crate::state::state_dir;
+use crate::supervisor::request_stop;
+use crate::ui::prompt::{is_non_interactive, prompt_yes_no};
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const LOCK_FILE: &str = "instance.lock";
+/// Windows' error for opening a file another process has open without sharing
+const ERROR_SHARING_VIOLATION: i32 = 32;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Bytes of the other instance's log shown before following it
+const LOG_TAIL_BYTES: u64 = 4096;
+
+/// Who holds the lock, written into it for a second DZSM to report
+#[derive(Serialize, Deserialize)]
+struct Holder {
+    pid: u32,
+    started: DateTime<Local>,
+    supervised: bool,
+}
+
+/// Held while DZSM updates and runs the server of an install, so a second DZSM started in the
+/// same directory doesn't race it on cleanup, mod links, and the server process. Unlike
+/// `.dzsm.lock`, which only marks the directory as managed, it's a file kept open without
+/// write sharing, so Windows releases it when DZSM exits, even if it's killed.
+#[allow(clippy::doc_markdown)]
+pub struct InstanceLock {
+    file: File,
+}
+
+fn get_lock_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(LOCK_FILE)
+}
+
+/// Open the lock, None while another DZSM holds it
+fn try_lock(path: &Path) -> Result<Option<File>> {
+    let result = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .share_mode(FILE_SHARE_READ)
+        .open(path);
+    match result {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+        Err(e) => Err(e).context(format!("Failed to lock {}", path.display())),
+    }
+}
+
+impl InstanceLock {
+    /// Take the lock of `server_install_dir`. When another DZSM has it, report it and offer to
+    /// stop it and go on, or to follow its output instead.
+    pub fn acquire(server_install_dir: &Path, supervised: bool, shutdown: &ShutdownConfig) -> Result<Option<Self>> {
+        let path = get_lock_path(server_install_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create state directory")?;
+        }
+
+        let file = if let Some(file) = try_lock(&path)? {
+            file
+        } else if let Some(file) = handle_running(server_install_dir, &path, shutdown)? {
+            file
+        } else {
+            return Ok(None);
+        };
+
+        let holder = Holder { pid: process::id(), started: Local::now(), supervised };
+        let mut lock = Self { file };
+        lock.write(&holder)
+            .context(format!("Failed to write {}", path.display()))?;
+        Ok(Some(lock))
+    }
+
+    fn write(&mut self, holder: &Holder) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(holder)?;
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(content.as_bytes())
+    }
+}
+
+fn load_holder(path: &Path) -> Option<Holder> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+}
+
+/// The PID of the other DZSM running the server of `server_install_dir`, if one is. Read from
+/// the lock rather than by opening it, which would keep a DZSM starting right then from taking it.
+pub fn find_running(server_install_dir: &Path) -> Option<u32> {
+    let holder = load_holder(&get_lock_path(server_install_dir))?;
+    let own_exe = std::env::current_exe().ok()?;
+    let exe = processes::get_image_path(holder.pid)?;
+    (holder.pid != process::id() && exe.file_name() == own_exe.file_name()).then_some(holder.pid)
+}
+
+/// When this DZSM took the lock of `server_install_dir`, None if it doesn't hold it
+pub fn locked_since(server_install_dir: &Path) -> Option<DateTime<Local>> {
+    let holder = load_holder(&get_lock_path(server_install_dir))?;
+    (holder.pid == process::id()).then_some(holder.started)
+}
+
+/// Describe the DZSM holding the lock, as far as it wrote down
+fn describe_holder(path: &Path) -> String {
+    match load_holder(path) {
+        Some(holder) => format!(
+            "DZSM is already running in this directory (PID {}, {} since {})",
+            holder.pid,
+            if holder.supervised { "supervising the server" } else { "running the server" },
+            holder.started.format("%Y-%m-%d %H:%M")
+        ),
+        None => "DZSM is already running in this directory".to_string(),
+    }
+}
+
+/// Another DZSM has the lock: stop it and return the lock once it's gone, or follow its output
+/// and return None
+fn handle_running(server_install_dir: &Path, path: &Path, shutdown: &ShutdownConfig) -> Result<Option<File>> {
+    let description = describe_holder(path);
+    println_failure(&description, 0);
+    if is_non_interactive() {
+        return Err(anyhow!("{description}, stop it first with `dzsm stop`"));
+    }
+
+    if prompt_yes_no("Stop it and start here instead?", false, 1)? {
+        request_stop(server_install_dir)?;
+        println_step("Asked it to stop, waiting for it to shut the server down...", 1);
+        let deadline = Instant::now() + get_stop_timeout(shutdown);
+        loop {
+            if let Some(file) = try_lock(path)? {
+                println_success("It has stopped", 1);
+                return Ok(Some(file));
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!(describe_still_running(server_install_dir, path)));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    if prompt_yes_no("Follow its output instead?", true, 1)? {
+        follow_log(server_install_dir, path)?;
+        return Ok(None);
+    }
+    Err(anyhow!("{description}"))
+}
+
+/// What's left running after the other DZSM was asked to stop and didn't in time
+fn describe_still_running(server_install_dir: &Path, path: &Path) -> String {
+    let dzsm = load_holder(path).map_or_else(|| "The other DZSM".to_string(), |holder| format!("DZSM (PID {})", holder.pid));
+    let server = processes::find_by_exe_path(&server_install_dir.join(SERVER_EXE));
+    if server.is_empty() {
+        format!("{dzsm} hasn't exited in time, e.g. it's still updating; it stops once it's done")
+    } else {
+        let pids: Vec<String> = server.iter().map(|process| process.pid.to_string()).collect();
+        format!("{dzsm} hasn't stopped in time, the server (PID {}) is still running", pids.join(", "))
+    }
+}
+
+/// Print the other DZSM's log as it's written, until it exits; Ctrl+C stops following
+fn follow_log(server_install_dir: &Path, lock_path: &Path) -> Result<()> {
+    let log_path = get_log_path(server_install_dir);
+    let mut log = File::open(&log_path)
+        .context(format!("Failed to open {}, is `logging.file` off?", log_path.display()))?;
+    let length = log.metadata()?.len();
+    log.seek(SeekFrom::Start(length.saturating_sub(LOG_TAIL_BYTES)))?;
+    println_step(&format!("Following {} (Ctrl+C to stop)", log_path.display()), 1);
+
+    let mut stdout = io::stdout();
+    let mut buffer = Vec::new();
+    loop {
+        buffer.clear();
+        log.read_to_end(&mut buffer)?;
+        stdout.write_all(&buffer)?;
+        stdout.flush()?;
+
+        if try_lock(lock_path)?.is_some() {
+            println_success("It has exited", 1);
+            return Ok(());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
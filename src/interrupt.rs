@@ -0,0 +1,53 @@
+use anyhow::{Result, anyhow};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use windows_sys::Win32::Foundation::{BOOL, FALSE, TRUE};
+use windows_sys::Win32::System::Console::{CTRL_BREAK_EVENT, CTRL_C_EVENT, SetConsoleCtrlHandler};
+
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+static SERVER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Exit code for a process ended by Ctrl+C
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Handle Ctrl+C ourselves: while the server runs it triggers a graceful shutdown
+/// (a second press skips the grace period), otherwise DZSM exits straight away
+pub fn install() -> Result<()> {
+    if unsafe { SetConsoleCtrlHandler(Some(handler), TRUE) } == 0 {
+        return Err(anyhow!("Failed to install Ctrl+C handler: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+unsafe extern "system" fn handler(ctrl_type: u32) -> BOOL {
+    if ctrl_type != CTRL_C_EVENT && ctrl_type != CTRL_BREAK_EVENT {
+        // Let Windows handle console close, logoff, and shutdown
+        return FALSE;
+    }
+
+    if !SERVER_RUNNING.load(Ordering::SeqCst) {
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+
+    INTERRUPTS.fetch_add(1, Ordering::SeqCst);
+    TRUE
+}
+
+/// Mark whether the DayZ server is running, which decides what Ctrl+C does
+#[allow(clippy::doc_markdown)]
+pub fn set_server_running(running: bool) {
+    SERVER_RUNNING.store(running, Ordering::SeqCst);
+    if !running {
+        INTERRUPTS.store(0, Ordering::SeqCst);
+    }
+}
+
+/// Ctrl+C was pressed while the server was running
+pub fn shutdown_requested() -> bool {
+    INTERRUPTS.load(Ordering::SeqCst) >= 1
+}
+
+/// Ctrl+C was pressed again during a graceful shutdown
+pub fn shutdown_forced() -> bool {
+    INTERRUPTS.load(Ordering::SeqCst) >= 2
+}
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::VERSION;
+use crate::cli::CliArgs;
+use crate::config::Config;
+use crate::server::{SERVER_EXE, ServerManager};
+use crate::ui::status::{println_step, println_success};
+
+/// Wine maps the Z: drive to the Linux root, which is where DZSM sees the server when run through it
+const WINE_ROOT_DRIVE: &str = "Z:";
+
+/// A batch file that starts the server from its directory with `launch_args`
+pub fn to_batch_script(server_install_dir: &Path, launch_args: &[String]) -> String {
+    // %% is a literal % in batch files
+    let args: Vec<String> = launch_args.iter().map(|arg| quote_if_spaced(arg).replace('%', "%%")).collect();
+
+    format!(
+        "@echo off\r\n\
+        rem {}\r\n\
+        cd /d \"{}\"\r\n\
+        \"{SERVER_EXE}\" {}\r\n",
+        get_header(),
+        server_install_dir.display(),
+        args.join(" ")
+    )
+}
+
+/// The same as a PowerShell script, passing the server's exit code on
+fn to_powershell_script(server_install_dir: &Path, launch_args: &[String]) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    let args: Vec<String> = launch_args.iter().map(|arg| quote(arg)).collect();
+
+    format!(
+        "# {}\r\n\
+        Set-Location -LiteralPath {}\r\n\
+        & {} {}\r\n\
+        exit $LASTEXITCODE\r\n",
+        get_header(),
+        quote(&server_install_dir.display().to_string()),
+        quote(&format!(".\\{SERVER_EXE}")),
+        args.join(" ")
+    )
+}
+
+/// A shell script starting the server through Wine, for Linux hosts
+fn to_shell_script(server_install_dir: &Path, launch_args: &[String]) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "'\\''"));
+    let args: Vec<String> = launch_args.iter().map(|arg| quote(arg)).collect();
+
+    format!(
+        "#!/bin/sh\n\
+        # {}\n\
+        cd {} || exit 1\n\
+        exec wine {} {}\n",
+        get_header(),
+        quote(&to_host_path(server_install_dir)),
+        quote(&format!("./{SERVER_EXE}")),
+        args.join(" ")
+    )
+}
+
+/// A systemd unit starting the server through Wine and restarting it when it exits
+fn to_systemd_unit(server_install_dir: &Path, launch_args: &[String]) -> String {
+    // systemd expands % and $ itself
+    let escape = |text: &str| quote_if_spaced(text).replace('%', "%%").replace('$', "$$");
+    let args: Vec<String> = launch_args.iter().map(|arg| escape(arg)).collect();
+    // WorkingDirectory takes the path as is, spaces and all
+    let dir = to_host_path(server_install_dir).replace('%', "%%");
+
+    format!(
+        "# {}\n\
+        [Unit]\n\
+        Description=DayZ server ({dir})\n\
+        After=network-online.target\n\
+        Wants=network-online.target\n\
+        \n\
+        [Service]\n\
+        Type=simple\n\
+        WorkingDirectory={}\n\
+        ExecStart=/usr/bin/env wine ./{SERVER_EXE} {}\n\
+        Restart=on-failure\n\
+        RestartSec=30\n\
+        \n\
+        [Install]\n\
+        WantedBy=multi-user.target\n",
+        get_header(),
+        dir,
+        args.join(" ")
+    )
+}
+
+fn get_header() -> String {
+    format!(
+        "Written by DZSM {VERSION} on {} from config.toml; regenerate it after changing mods rather than editing it",
+        Local::now().format("%Y-%m-%d %H:%M")
+    )
+}
+
+/// Quote an argument with spaces, such as a mod folder named after its Workshop title
+pub fn quote_if_spaced(arg: &str) -> String {
+    if arg.contains(' ') {
+        format!("\"{arg}\"")
+    } else {
+        arg.to_string()
+    }
+}
+
+/// The install directory as Linux sees it, when DZSM runs through Wine
+fn to_host_path(server_install_dir: &Path) -> String {
+    let path = server_install_dir.display().to_string();
+    match path.get(..WINE_ROOT_DRIVE.len()) {
+        Some(drive) if drive.eq_ignore_ascii_case(WINE_ROOT_DRIVE) => path[WINE_ROOT_DRIVE.len()..].replace('\\', "/"),
+        _ => path.replace('\\', "/"),
+    }
+}
+
+/// File a format is written to without `--output`
+fn get_default_file(format: &str) -> &'static str {
+    match format {
+        "ps1" => "start_server.ps1",
+        "sh" => "start_server.sh",
+        "systemd" => "dayz-server.service",
+        _ => "start_server.bat",
+    }
+}
+
+/// Entry point for `dzsm generate launch-script`
+pub fn run(format: &str, output: Option<&Path>, args: &CliArgs, config: &Config, server_install_dir: &str) -> Result<()> {
+    println_step("Generating launch script...", 0);
+
+    let server_manager = ServerManager::new(args.clone(), config.clone(), server_install_dir);
+    let launch_args = server_manager.build_launch_args()?;
+    let install_dir = Path::new(server_install_dir);
+    let script = match format {
+        "bat" => to_batch_script(install_dir, &launch_args),
+        "ps1" => to_powershell_script(install_dir, &launch_args),
+        "sh" => to_shell_script(install_dir, &launch_args),
+        "systemd" => to_systemd_unit(install_dir, &launch_args),
+        _ => return Err(anyhow!("Unknown launch script format '{format}'")),
+    };
+
+    let path = output.map_or_else(|| PathBuf::from(get_default_file(format)), Path::to_path_buf);
+    fs::write(&path, script)
+        .context(format!("Failed to write {}", path.display()))?;
+    println_success(&format!(
+        "Launch script written to {}; it doesn't update anything, so regenerate it after mod changes",
+        path.display()
+    ), 0);
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::access_list::{AccessList, BAN_FILE, WHITELIST_FILE};
+use crate::cli::SyncCommand;
+use crate::config::{Config, SyncConfig};
+use crate::crypto::constant_time_eq;
+use crate::http;
+use crate::state::state_dir;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const SYNC_DIR: &str = "sync";
+const HUB_DIR: &str = "hub";
+
+/// A player list shared across the cluster
+#[derive(Debug, Clone, Copy)]
+enum SyncedList {
+    Bans,
+    Whitelist,
+}
+
+impl SyncedList {
+    const ALL: [Self; 2] = [Self::Bans, Self::Whitelist];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Bans => "bans",
+            Self::Whitelist => "whitelist",
+        }
+    }
+
+    const fn file_name(self) -> &'static str {
+        match self {
+            Self::Bans => BAN_FILE,
+            Self::Whitelist => WHITELIST_FILE,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|list| list.name() == name)
+    }
+
+    const fn is_enabled(self, config: &SyncConfig) -> bool {
+        match self {
+            Self::Bans => config.bans,
+            Self::Whitelist => config.whitelist,
+        }
+    }
+}
+
+/// Changes made to a list on one instance since it last synced
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct ListDelta {
+    added: BTreeMap<String, String>,
+    removed: Vec<String>,
+}
+
+impl ListDelta {
+    fn between(before: &AccessList, after: &AccessList) -> Self {
+        let added = after.entries.iter()
+            .filter(|(id, line)| before.entries.get(*id) != Some(*line))
+            .map(|(id, line)| (id.clone(), line.clone()))
+            .collect();
+        let removed = before.entries.keys()
+            .filter(|id| !after.entries.contains_key(*id))
+            .cloned()
+            .collect();
+
+        Self { added, removed }
+    }
+
+    fn apply(&self, list: &mut AccessList) {
+        for id in &self.removed {
+            list.entries.remove(id);
+        }
+        list.entries.extend(self.added.clone());
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// The hub's view of a list, returned after every sync
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct ListSnapshot {
+    entries: BTreeMap<String, String>,
+}
+
+/// Pushes local ban/whitelist changes to the hub and pulls everyone else's
+pub struct SyncClient {
+    config: SyncConfig,
+    server_install_dir: PathBuf,
+}
+
+impl SyncClient {
+    pub fn new(config: &SyncConfig, server_install_dir: &Path) -> Self {
+        Self {
+            config: config.clone(),
+            server_install_dir: server_install_dir.to_path_buf(),
+        }
+    }
+
+    /// Sync every enabled list once
+    pub fn sync(&self) -> Result<()> {
+        for list in SyncedList::ALL {
+            if list.is_enabled(&self.config) {
+                self.sync_list(list)
+                    .context(format!("Failed to sync {}", list.file_name()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep syncing in the background for as long as DZSM runs
+    pub fn spawn(self) {
+        let interval = Duration::from_secs(self.config.interval_seconds.max(1));
+
+        thread::spawn(move || {
+            let mut failing = false;
+            loop {
+                match self.sync() {
+                    Ok(()) if failing => {
+                        println_success("Ban/whitelist sync recovered", 0);
+                        failing = false;
+                    }
+                    // Only report the first failure of a streak, the hub may be down for a while
+                    Err(e) if !failing => {
+                        println_failure(&format!("Ban/whitelist sync failed: {e:#}"), 0);
+                        failing = true;
+                    }
+                    Ok(()) | Err(_) => {}
+                }
+                thread::sleep(interval);
+            }
+        });
+    }
+
+    fn sync_list(&self, list: SyncedList) -> Result<()> {
+        let list_path = self.server_install_dir.join(list.file_name());
+        let snapshot_path = state_dir(&self.server_install_dir)
+            .join(SYNC_DIR)
+            .join(format!("{}.json", list.name()));
+
+        let local = AccessList::load(&list_path)?;
+        // Before the first sync, everything local is new to the hub
+        let last_synced = load_snapshot(&snapshot_path)?.unwrap_or_default();
+        let delta = ListDelta::between(&last_synced, &local);
+
+        let merged = self.exchange(list, &delta)?;
+
+        if merged != local {
+            merged.save(&list_path)?;
+        }
+        if merged != last_synced {
+            save_snapshot(&snapshot_path, &merged)?;
+        }
+
+        Ok(())
+    }
+
+    fn exchange(&self, list: SyncedList, delta: &ListDelta) -> Result<AccessList> {
+        let hub_url = self.config.hub_url.as_deref().unwrap_or_default().trim_end_matches('/');
+        let token = self.config.token.as_deref()
+            .ok_or_else(|| anyhow!("`sync.token` must be set to sync with the hub"))?;
+
+        let body = serde_json::to_string(delta)
+            .context("Failed to serialize list changes")?;
+        let response = http::post_json_authorized(&format!("{hub_url}/lists/{}", list.name()), &body, token)?;
+        let snapshot: ListSnapshot = serde_json::from_str(&response)
+            .context("Hub sent an invalid list")?;
+
+        Ok(AccessList { entries: snapshot.entries })
+    }
+}
+
+/// Holds the cluster's lists and merges the changes each instance sends in
+struct SyncHub {
+    token: String,
+    data_dir: PathBuf,
+}
+
+impl SyncHub {
+    fn serve(&self, listen: &str) -> Result<()> {
+        fs::create_dir_all(&self.data_dir)
+            .context("Failed to create hub data directory")?;
+
+        let server = tiny_http::Server::http(listen)
+            .map_err(|e| anyhow!("Failed to listen on {listen}: {e}"))?;
+        println_success(&format!("Sync hub listening on {listen}"), 0);
+
+        for mut request in server.incoming_requests() {
+            let (status, body) = match self.handle(&mut request) {
+                Ok(body) => (200, body),
+                Err((status, message)) => (status, message),
+            };
+
+            let response = tiny_http::Response::from_string(body).with_status_code(status);
+            if let Err(e) = request.respond(response) {
+                println_failure(&format!("Failed to respond to sync request: {e}"), 1);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&self, request: &mut tiny_http::Request) -> Result<String, (u16, String)> {
+        let expected = format!("Bearer {}", self.token);
+        let authorized = request.headers().iter().any(|header| {
+            header.field.equiv("Authorization") && constant_time_eq(header.value.as_str().as_bytes(), expected.as_bytes())
+        });
+        if !authorized {
+            return Err((401, "Invalid sync token".to_string()));
+        }
+
+        if *request.method() != tiny_http::Method::Post {
+            return Err((405, "Only POST is supported".to_string()));
+        }
+
+        let list = request.url()
+            .strip_prefix("/lists/")
+            .and_then(SyncedList::from_name)
+            .ok_or_else(|| (404, "Unknown list".to_string()))?;
+
+        let mut body = String::new();
+        request.as_reader().read_to_string(&mut body)
+            .map_err(|e| (400, format!("Failed to read request: {e}")))?;
+        let delta: ListDelta = serde_json::from_str(&body)
+            .map_err(|e| (400, format!("Invalid list changes: {e}")))?;
+
+        let snapshot = self.apply(list, &delta)
+            .map_err(|e| (500, format!("{e:#}")))?;
+
+        serde_json::to_string(&ListSnapshot { entries: snapshot.entries })
+            .map_err(|e| (500, e.to_string()))
+    }
+
+    fn apply(&self, list: SyncedList, delta: &ListDelta) -> Result<AccessList> {
+        let path = self.data_dir.join(format!("{}.json", list.name()));
+        let mut current = load_snapshot(&path)?.unwrap_or_default();
+
+        if !delta.is_empty() {
+            delta.apply(&mut current);
+            save_snapshot(&path, &current)?;
+            println_step(&format!(
+                "{}: {} added/changed, {} removed ({} entries)",
+                list.file_name(),
+                delta.added.len(),
+                delta.removed.len(),
+                current.entries.len()
+            ), 1);
+        }
+
+        Ok(current)
+    }
+}
+
+fn load_snapshot(path: &Path) -> Result<Option<AccessList>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read {}", path.display()))?;
+    let snapshot: ListSnapshot = serde_json::from_str(&content)
+        .context(format!("Failed to parse {}", path.display()))?;
+
+    Ok(Some(AccessList { entries: snapshot.entries }))
+}
+
+fn save_snapshot(path: &Path, list: &AccessList) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context("Failed to create sync state directory")?;
+    }
+
+    let content = serde_json::to_string_pretty(&ListSnapshot { entries: list.entries.clone() })
+        .context("Failed to serialize list")?;
+    fs::write(path, content)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+/// Entry point for `dzsm sync ...`
+pub fn run(command: &SyncCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+
+    match command {
+        SyncCommand::Serve => {
+            let token = config.sync.token.clone()
+                .filter(|token| !token.trim().is_empty())
+                .ok_or_else(|| anyhow!("Set `sync.token` in config.toml before running the sync hub"))?;
+
+            let hub = SyncHub {
+                token,
+                data_dir: state_dir(server_install_dir).join(SYNC_DIR).join(HUB_DIR),
+            };
+            hub.serve(&config.sync.listen)
+        }
+        SyncCommand::Now => {
+            if !config.sync.is_enabled() {
+                return Err(anyhow!("Set `sync.hub_url` in config.toml to sync bans and whitelist"));
+            }
+
+            println_step("Syncing bans and whitelist with the hub...", 1);
+            SyncClient::new(&config.sync, server_install_dir).sync()?;
+            println_success("Bans and whitelist are in sync", 1);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::{Result, anyhow};
+
+use crate::config::ModsConfig;
+use crate::config::mod_entry::{ModEntry, ModOrder, ModOrderRule};
+
+/// Frameworks other mods are built on, in the order they load: Workshop ID and the
+/// names they're commonly installed under, compared without case or punctuation
+const FRAMEWORKS: &[(u64, &[&str])] = &[
+    (1_559_212_036, &["cf", "communityframework"]),
+    (2_545_327_648, &["dabsframework"]),
+    (2_291_785_308, &["dayzexpansioncore", "expansioncore"]),
+];
+/// Below any priority set by hand, so frameworks load first
+const FRAMEWORK_PRIORITY: i32 = -1_000_000;
+
+/// Put mods in load order: by priority, then as listed, with every `load_after`/`load_before`
+/// constraint honoured. Frameworks without a priority of their own go first, unless
+/// `frameworks_first` is off. The same input always gives the same order.
+pub fn sort_mods(mods: &[ModEntry], config: &ModsConfig) -> Result<Vec<ModEntry>> {
+    let orders: Vec<ModOrder> = mods.iter()
+        .map(|mod_entry| {
+            let mut order = combined_order(mod_entry, &config.load_order);
+            if config.frameworks_first && order.priority.is_none() {
+                order.priority = find_framework(mod_entry).map(|rank| FRAMEWORK_PRIORITY + rank);
+            }
+            order
+        })
+        .collect();
+
+    // Priority first; the sort is stable, so equal priorities keep the listed order
+    let mut ranked: Vec<usize> = (0..mods.len()).collect();
+    ranked.sort_by_key(|&i| orders[i].priority.unwrap_or(0));
+
+    // loads_before[a] holds every mod that has to come after a
+    let mut loads_before: Vec<Vec<usize>> = vec![Vec::new(); mods.len()];
+    let mut waiting_on = vec![0; mods.len()];
+    let mut add_constraint = |first: usize, then: usize| {
+        if first != then && !loads_before[first].contains(&then) {
+            loads_before[first].push(then);
+            waiting_on[then] += 1;
+        }
+    };
+    for (i, order) in orders.iter().enumerate() {
+        // Mods that aren't installed are skipped, they can't be out of order
+        for other in find_mods(mods, &order.load_after) {
+            add_constraint(other, i);
+        }
+        for other in find_mods(mods, &order.load_before) {
+            add_constraint(i, other);
+        }
+    }
+
+    // Repeatedly take the highest ranked mod with nothing left to wait for
+    let mut sorted = Vec::with_capacity(mods.len());
+    let mut done = vec![false; mods.len()];
+    while sorted.len() < mods.len() {
+        let next = ranked.iter()
+            .copied()
+            .find(|&i| !done[i] && waiting_on[i] == 0);
+        let Some(next) = next else {
+            let stuck: Vec<&str> = ranked.iter()
+                .filter(|&&i| !done[i])
+                .map(|&i| mods[i].name.as_str())
+                .collect();
+            return Err(anyhow!(
+                "Mod load order constraints contradict each other, check load_after/load_before of: {}",
+                stuck.join(", ")
+            ));
+        };
+
+        done[next] = true;
+        sorted.push(mods[next].clone());
+        for &then in &loads_before[next] {
+            waiting_on[then] -= 1;
+        }
+    }
+
+    Ok(sorted)
+}
+
+/// A mod's own ordering combined with any `[[mods.load_order]]` rules naming it
+fn combined_order(mod_entry: &ModEntry, rules: &[ModOrderRule]) -> ModOrder {
+    let mut order = mod_entry.order.clone();
+    for rule in rules.iter().filter(|rule| mod_entry.is_named(&rule.target)) {
+        order.priority = rule.order.priority.or(order.priority);
+        order.load_after.extend(rule.order.load_after.iter().cloned());
+        order.load_before.extend(rule.order.load_before.iter().cloned());
+    }
+    order
+}
+
+/// The mod's place in `FRAMEWORKS`, if it's one of them
+fn find_framework(mod_entry: &ModEntry) -> Option<i32> {
+    let name: String = mod_entry.name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    FRAMEWORKS.iter()
+        .position(|(id, names)| *id == mod_entry.id || names.contains(&name.as_str()))
+        .and_then(|rank| i32::try_from(rank).ok())
+}
+
+fn find_mods(mods: &[ModEntry], references: &[String]) -> Vec<usize> {
+    references.iter()
+        .filter_map(|reference| mods.iter().position(|mod_entry| mod_entry.is_named(reference)))
+        .collect()
+}
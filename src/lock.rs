@@ -1,12 +1,12 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use std::fs;
 use std::path::Path;
 
 use crate::ui::status::{println_failure, println_step, println_step_concat, println_success};
-use crate::ui::prompt::prompt_yes_no;
+use crate::ui::prompt::{is_non_interactive, prompt_yes_no};
 use crate::VERSION;
 
-const LOCK_FILE: &str = ".dzsm.lock";
+pub const LOCK_FILE: &str = ".dzsm.lock";
 
 /// Check if the current directory is already initialized with DZSM
 pub fn check_if_initialized() -> Result<bool> {
@@ -16,10 +16,20 @@ pub fn check_if_initialized() -> Result<bool> {
         Ok(true)
     } else {
         println_failure("No existing DZSM setup found", 0);
+        if is_non_interactive() {
+            return Err(anyhow!(
+                "Refusing to initialize DZSM in non-interactive mode. Run dzsm interactively once in this directory first."
+            ));
+        }
         initialize()
     }
 }
 
+/// Contents of the lock file, if this directory is managed by DZSM
+pub fn read_lock_file() -> Option<String> {
+    fs::read_to_string(LOCK_FILE).ok()
+}
+
 /// Initialize DZSM in the current directory
 fn initialize() -> Result<bool> {
     let cwd = std::env::current_dir().context("Failed to get current working directory")?;
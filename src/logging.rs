@@ -0,0 +1,202 @@
+use anyhow::{Context, Result, anyhow};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::config::LoggingConfig;
+use crate::state::logs_dir;
+use crate::ui::json::is_json_output;
+use crate::ui::status::paint_line;
+
+const LOG_FILE: &str = "dzsm";
+const LOG_EXTENSION: &str = "log";
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+static LOGGER: Logger = Logger {
+    console_level: Mutex::new(LevelFilter::Info),
+    file: Mutex::new(None),
+};
+
+/// Tees everything printed through `ui::status` to the console and a rotating log file
+struct Logger {
+    console_level: Mutex<LevelFilter>,
+    file: Mutex<Option<RotatingFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // Lines arrive already formatted by ui::status, which prints its own JSON events instead
+        if is_console_enabled(record.level()) && !is_json_output() {
+            println!("{}", paint_line(record.target(), &record.args().to_string()));
+        }
+
+        if let Ok(mut file) = self.file.lock()
+            && let Some(file) = file.as_mut()
+            && record.level() <= file.level
+        {
+            let line = format!(
+                "{} {:<5} {}\n",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                record.level(),
+                record.args()
+            );
+            // Logging must never take DZSM down, a failed write only loses the line
+            let _ = file.write(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+        if let Ok(mut file) = self.file.lock()
+            && let Some(file) = file.as_mut()
+        {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// `.dzsm/logs/dzsm.log`, rolled over to `dzsm.1.log`, `dzsm.2.log`, ... when it gets too big
+struct RotatingFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+    level: LevelFilter,
+}
+
+impl RotatingFile {
+    fn open(dir: &Path, config: &LoggingConfig) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .context("Failed to create logs directory")?;
+
+        let file = open_append(&Self::path(dir, 0))?;
+        let size = file.metadata().map_or(0, |metadata| metadata.len());
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            size,
+            max_size: config.max_file_size_mb.max(1) * BYTES_PER_MB,
+            max_files: config.max_files,
+            level: parse_level(&config.file_level)?,
+        })
+    }
+
+    fn path(dir: &Path, index: usize) -> PathBuf {
+        if index == 0 {
+            dir.join(format!("{LOG_FILE}.{LOG_EXTENSION}"))
+        } else {
+            dir.join(format!("{LOG_FILE}.{index}.{LOG_EXTENSION}"))
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        if self.size + bytes.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(bytes)?;
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        // Oldest file falls off the end, the rest shift up by one
+        let _ = fs::remove_file(Self::path(&self.dir, self.max_files));
+        for index in (0..self.max_files).rev() {
+            let from = Self::path(&self.dir, index);
+            if from.exists() {
+                fs::rename(&from, Self::path(&self.dir, index + 1))?;
+            }
+        }
+
+        self.file = open_append(&Self::path(&self.dir, 0))?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(format!("Failed to open {}", path.display()))
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter> {
+    LevelFilter::from_str(level)
+        .map_err(|_| anyhow!("Invalid log level '{level}', expected one of: error, warn, info, debug, trace"))
+}
+
+/// Console verbosity requested on the command line, which wins over `logging.level`
+#[derive(Debug, Clone, Copy)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Install the logger with console output only; the log file is attached once the config is loaded
+pub fn init(verbosity: Verbosity) {
+    let level = match verbosity {
+        Verbosity::Quiet => LevelFilter::Error,
+        Verbosity::Normal => LevelFilter::Info,
+        Verbosity::Verbose => LevelFilter::Debug,
+    };
+
+    set_console_level(level);
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Apply the `[logging]` config: console level (unless set on the command line) and the log file
+pub fn configure(config: &LoggingConfig, server_install_dir: &Path, verbosity: Verbosity) -> Result<()> {
+    if matches!(verbosity, Verbosity::Normal) {
+        set_console_level(parse_level(&config.level)?);
+    }
+
+    if config.file {
+        let file = RotatingFile::open(&logs_dir(server_install_dir), config)?;
+        if let Ok(mut slot) = LOGGER.file.lock() {
+            *slot = Some(file);
+        }
+    }
+
+    let console_level = LOGGER.console_level.lock().map_or(LevelFilter::Info, |level| *level);
+    let file_level = LOGGER.file.lock().ok()
+        .and_then(|file| file.as_ref().map(|file| file.level))
+        .unwrap_or(LevelFilter::Off);
+    log::set_max_level(console_level.max(file_level));
+
+    Ok(())
+}
+
+/// Whether a line at `level` is shown on the console
+pub fn is_console_enabled(level: Level) -> bool {
+    LOGGER.console_level.lock().map_or(LevelFilter::Info, |console_level| *console_level) >= level
+}
+
+/// The current log file, where everything printed lands when `logging.file` is on
+pub fn get_log_path(server_install_dir: &Path) -> PathBuf {
+    RotatingFile::path(&logs_dir(server_install_dir), 0)
+}
+
+fn set_console_level(level: LevelFilter) {
+    if let Ok(mut console_level) = LOGGER.console_level.lock() {
+        *console_level = level;
+    }
+}
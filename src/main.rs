@@ -1,104 +1,322 @@
-use anyhow::{Result};
-use clap::{Arg, Command};
+use anyhow::{Result, anyhow};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::ExitCode;
 
 mod ui;
-use ui::banner::print_banner;
+use ui::banner::{disable_banner, print_banner};
+use ui::json::{is_json_output, print_output, print_result, set_json_output};
+use ui::prompt::set_non_interactive;
+use ui::status::{RED, RESET, configure_color, is_color_enabled, println_failure, println_step, println_success};
+use ui::timing::PhaseTimer;
 
 mod lock;
 use lock::check_if_initialized;
+mod instance;
+use instance::InstanceLock;
+mod control;
 
 mod config;
 use config::Config;
 
 mod steamcmd;
+mod steamcmd_errors;
+mod mod_cache;
+mod bench;
+mod collection_dupes;
 mod collection_parser;
 mod collection_fetcher;
+use collection_fetcher::CollectionFetcher;
+mod collection_sync;
+mod workshop_manifest;
+mod workshop_details;
+mod mod_info;
+mod update_digest;
+mod download_schedule;
+mod disk_space;
+mod vdf;
+mod load_order;
+mod mod_validation;
+mod mod_links;
+mod mods;
+mod mod_list;
+mod bisect;
+
+mod logging;
+
+mod http;
+mod notifier;
+mod state;
+mod migrations;
 
 mod server;
-use server::ServerManager;
+mod server_cfg;
+mod server_builds;
+mod server_time;
+mod weather;
+mod merge;
+mod missions;
+mod economy;
+mod adm;
+mod positions;
+mod dupes;
+mod privacy;
+mod public_info;
+mod web;
+mod quarantine;
+mod maintenance;
+mod wipe_day;
+mod profile_files;
+use public_info::PublicServer;
+mod processes;
+mod status;
+mod rcon;
+mod a2s;
+mod storage;
+mod cleanup;
+mod server_logs;
+mod interrupt;
+mod crash;
+mod battleye;
+mod secrets;
+mod crypto;
+mod credentials;
+use server::{DRY_RUN, SERVER_EXE, ServerManager};
+
+mod access_list;
+mod bans;
+mod list_sync;
+mod players;
+use list_sync::SyncClient;
+
+mod schedule;
+mod supervisor;
+mod health;
+mod heartbeat;
+mod adm_alerts;
+use supervisor::Supervisor;
+mod service;
+mod updater;
+mod launch_script;
+mod panels;
 
 mod cli;
-use cli::CliArgs;
+use cli::{CliArgs, Commands, GenerateCommand, RunArgs, ServiceCommand};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
 const LICENSE: &str = include_str!("../LICENSE");
 
-fn main() -> Result<()> {
-    // Handle global flags first using clap's Command builder
-    let matches = Command::new("dzsm")
-        .version(VERSION)
-        .about("DZSM: DayZ Server Manager - Download, update, and run DayZ servers with mod support")
-        .arg(
-            Arg::new("license")
-                .long("license")
-                .help("Display the license information")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("offline")
-                .long("offline")
-                .help("Skip Steam connections and use cached files only.")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("skip-server-validation")
-                .long("skip-server-validation")
-                .help("Skip Steam's validation step of DayZ server files.")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("skip-mod-validation")
-                .long("skip-mod-validation")
-                .help("Skip Steam's validation step of DayZ workshop mod files.")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .arg(
-            Arg::new("skip-validation")
-                .long("skip-validation")
-                .help("Skip validation of both DayZ server and workshop mod files.")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .get_matches();
+fn main() -> ExitCode {
+    let result = run();
+    // SteamCMD failures get their own codes, so scripts and panels can react to them
+    let exit_code = match &result {
+        Ok(()) => 0,
+        Err(e) => steamcmd_errors::find_failure(e).map_or(1, steamcmd_errors::SteamCmdFailure::exit_code),
+    };
 
-    // Handle license flag
-    if matches.get_flag("license") {
-        println!("{LICENSE}");
-        return Ok(());
+    if is_json_output() {
+        print_result(result.as_ref().err(), exit_code);
+    } else if let Err(e) = &result {
+        // Color was set up for stdout, this goes to stderr, which may be redirected on its own
+        let (red, reset) = if is_color_enabled() && io::stderr().is_terminal() { (RED, RESET) } else { ("", "") };
+        eprintln!("{red}Error:{reset} {e:?}");
     }
+    ExitCode::from(exit_code)
+}
 
+fn run() -> Result<()> {
     // Parse CLI arguments using the CliArgs struct
     let args = CliArgs::parse_args();
 
+    // Events for scripts on stdout, so nothing else may print there or wait for input
+    if args.json {
+        set_json_output();
+        set_non_interactive();
+        disable_banner();
+    }
+
+    // Handle license flag
+    if args.license {
+        print_output(LICENSE);
+        return Ok(());
+    }
+
+    logging::init(args.verbosity());
+    configure_color(args.no_color);
+
+    // Started by Windows: no console, no prompts, the install dir comes from the service definition
+    if let Some(Commands::Service(command @ ServiceCommand::Run { dir, .. })) = &args.command {
+        return service::run(command, dir, args.profile.as_deref());
+    }
+
+    interrupt::install()?;
+
+    if args.non_interactive {
+        set_non_interactive();
+    }
+
     // Continue with normal application execution
+    if args.no_banner {
+        disable_banner();
+    }
     print_banner();
 
-    // Get current working directory for server installation
-    let server_install_dir = std::env::current_dir()?
+    // Panel definitions don't belong to a server, so no DZSM setup or config is needed
+    if let Some(Commands::Generate(command)) = &args.command
+        && !matches!(command, GenerateCommand::LaunchScript { .. })
+    {
+        return panels::run(command);
+    }
+
+    // Get current working directory, which holds config.toml and, without a profile, the server installation
+    let root_dir = std::env::current_dir()?
         .to_string_lossy()
         .to_string();
 
     if !check_if_initialized()? {
-        println!("\nInstallation aborted.");
-        return Ok(());
+        println_failure("Installation aborted", 0);
+        return Err(anyhow!("DZSM was not set up in {root_dir}"));
     }
 
     // Check and load configuration - exits gracefully if config needs editing
-    let config = Config::check_and_load(&server_install_dir)?;
+    let (config, server_install_dir) = Config::check_and_load(&root_dir, args.profile.as_deref())?;
+    std::fs::create_dir_all(&server_install_dir)?;
+    logging::configure(&config.logging, Path::new(&server_install_dir), args.verbosity())?;
+    CollectionFetcher::set_cache_dir(Path::new(&server_install_dir));
 
-    let mut server_manager = ServerManager::new(args, config, &server_install_dir);
+    let run = match &args.command {
+        Some(Commands::Run(run)) => run.clone(),
+        // Started here when no DZSM runs the server, only updating it if asked to
+        Some(Commands::Restart { update, .. }) => RunArgs { no_update: !update, ..RunArgs::default() },
+        _ => RunArgs::default(),
+    };
 
-    // Initialize SteamCMD
-    server_manager.setup_steamcmd()?;
+    // An install an older DZSM set up is brought up to date before anything reads it
+    migrations::apply_pending(Path::new(&server_install_dir), run.dry_run)?;
 
-    // Update server (always validates)
-    server_manager.install_or_update_server()?;
+    match &args.command {
+        Some(Commands::Stop { force }) => return control::run_stop(&config, &server_install_dir, *force),
+        Some(Commands::Restart { update, force }) => {
+            if control::run_restart(&config, &server_install_dir, *update, *force)? {
+                return Ok(());
+            }
+        }
+        Some(Commands::Status { repair }) => return status::run(&config, &server_install_dir, args.offline, *repair),
+        Some(Commands::Bench(command)) => return bench::run(command, &config, &server_install_dir, args.offline),
+        Some(Commands::Battleye(command)) => return battleye::run(command, &config, &server_install_dir),
+        Some(Commands::Ban(command)) => return bans::run(command, &server_install_dir),
+        Some(Commands::Collection(command)) => return collection_sync::run(command, &config, &server_install_dir),
+        Some(Commands::Crash(command)) => return crash::run(command, &config, &server_install_dir),
+        Some(Commands::Secrets(command)) => return secrets::run(command, &config, &server_install_dir),
+        Some(Commands::Dupes(command)) => return dupes::run(command, &config, &server_install_dir),
+        Some(Commands::Mission(command)) => return missions::run(command, &config, &server_install_dir, args.offline),
+        Some(Commands::Bisect(command)) => return bisect::run(command, &args, &config, &server_install_dir),
+        Some(Commands::Mods(command)) => return mod_list::run(command, &config, &server_install_dir, args.profile.as_deref()),
+        Some(Commands::Positions(command)) => return positions::run(command, &config, &server_install_dir),
+        Some(Commands::Privacy(command)) => return privacy::run(command, &config, &server_install_dir),
+        Some(Commands::Public(command)) => return public_info::run(command, &config, &server_install_dir),
+        Some(Commands::Web { port }) => return web::run(&config, Path::new(&root_dir), &server_install_dir, *port, args.profile.as_deref()),
+        Some(Commands::Quarantine(command)) => return quarantine::run(command, &server_install_dir),
+        Some(Commands::Maintenance(command)) => return maintenance::run(command, &config, &server_install_dir),
+        Some(Commands::Rollback { build, list }) => return server_builds::run(&config.server, &server_install_dir, build.as_deref(), *list),
+        Some(Commands::WipeDay(wipe)) => return wipe_day::run(wipe, &args, &config, &server_install_dir),
+        Some(Commands::ProfileFiles(command)) => return profile_files::run(command, &config, &server_install_dir),
+        Some(Commands::Storage(command)) => return storage::run(command, &config, &server_install_dir),
+        Some(Commands::Steam(command)) => return credentials::run(command, &config),
+        Some(Commands::Sync(command)) => return list_sync::run(command, &config, &server_install_dir),
+        Some(Commands::Time(command)) => return server_time::run(command, &config, args.profile.as_deref()),
+        Some(Commands::Service(command)) => return service::run(command, Path::new(&root_dir), args.profile.as_deref()),
+        Some(Commands::Generate(GenerateCommand::LaunchScript { format, output })) => {
+            return launch_script::run(format, output.as_deref(), &args, &config, &server_install_dir);
+        }
+        Some(Commands::Updater(command)) => return updater::run(command, &args, &config, Path::new(&root_dir), &server_install_dir),
+        Some(Commands::Generate(_) | Commands::Run(_)) | None => {}
+    }
 
-    // Update/validate mods
-    server_manager.install_or_update_mods()?;
+    start(args, config, &server_install_dir, &run)
+}
 
-    // Run the DayZ server
-    server_manager.run_server()?;
-    
+/// Set up SteamCMD and update the server and mods, timing each phase
+#[allow(clippy::doc_markdown)]
+fn update(server_manager: &mut ServerManager, timer: &mut PhaseTimer) -> Result<()> {
+    timer.time("SteamCMD setup", || server_manager.setup_steamcmd())?;
+    // Always validates
+    timer.time("server update", || server_manager.install_or_update_server())?;
+    timer.time("mods", || server_manager.install_or_update_mods())?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Update and run the server, or supervise it: `dzsm run` and no subcommand
+fn start(args: CliArgs, mut config: Config, server_install_dir: &str, run: &RunArgs) -> Result<()> {
+    if let Some(preset) = &run.mod_preset {
+        config.mods.select_preset(preset)?;
+        println_step(&format!("Using mod preset {preset}, other mods are left out"), 0);
+    }
+
+    // One DZSM at a time updates and runs the server of an install
+    let _instance_lock = if run.dry_run {
+        None
+    } else {
+        match InstanceLock::acquire(Path::new(server_install_dir), run.supervise, &config.shutdown)? {
+            Some(lock) => Some(lock),
+            // Followed the running one's output instead
+            None => return Ok(()),
+        }
+    };
+
+    // A detached server outlives the DZSM that started it, and with it the instance lock;
+    // updating its files or starting another would break it
+    if !run.dry_run && !processes::find_by_exe_path(&Path::new(server_install_dir).join(SERVER_EXE)).is_empty() {
+        return Err(anyhow!(
+            "The server is already running without a DZSM, e.g. started with --detach. \
+            Stop it with `dzsm stop` first, or restart it with `dzsm restart`"
+        ));
+    }
+
+    // Keep bans and whitelist in step with the rest of the cluster while the server runs, which
+    // a detached server does without DZSM
+    let stays_running = !run.dry_run && !run.detach && !run.no_run;
+    if config.sync.is_enabled() && stays_running {
+        SyncClient::new(&config.sync, Path::new(server_install_dir)).spawn();
+    }
+
+    // Mod list and server info for community websites, when `public.listen` is set
+    if config.public.listen.is_some() && stays_running {
+        PublicServer::new(&config, Path::new(server_install_dir)).spawn();
+    }
+
+    if run.supervise {
+        let server_manager = ServerManager::new(args, config, server_install_dir);
+        let mut supervisor = Supervisor::new(server_manager, server_install_dir)?;
+        if run.dry_run {
+            supervisor = supervisor.dry_run(run.dry_run_hours);
+        }
+        return supervisor.run();
+    }
+
+    let mut server_manager = ServerManager::new(args, config, server_install_dir);
+    if run.dry_run {
+        println_step(&format!("{DRY_RUN} Nothing will be deleted, downloaded, linked, or launched"), 0);
+        server_manager = server_manager.dry_run();
+    }
+    if run.detach {
+        server_manager = server_manager.detach();
+    }
+
+    let mut timer = PhaseTimer::default();
+    if run.no_update {
+        println_step("Skipping the server and mod updates", 0);
+    } else {
+        let updated = update(&mut server_manager, &mut timer);
+        // Also when a phase failed, to show how far it got
+        timer.print_summary(0);
+        updated?;
+    }
+
+    if run.no_run {
+        println_success("Server and mods are up to date, not starting the server", 0);
+        return Ok(());
+    }
+    server_manager.run_server()
+}
@@ -0,0 +1,212 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::MaintenanceCommand;
+use crate::config::{Config, NotificationEvent};
+use crate::crypto;
+use crate::notifier::{Notification, Notifier};
+use crate::processes;
+use crate::rcon::RconClient;
+use crate::server::{SERVER_CONFIG, SERVER_EXE, get_server_name};
+use crate::server_cfg::ServerDzConfig;
+use crate::state::state_dir;
+use crate::ui::json::print_output;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+/// Encrypted like the rotated secrets, it holds the real join password while maintenance is on
+const STATE_FILE: &str = "maintenance.dat";
+const PASSWORD_LENGTH: usize = 12;
+
+/// Maintenance mode: the server keeps players out behind a password only admins know
+#[derive(Serialize, Deserialize)]
+pub struct Maintenance {
+    pub since: DateTime<Local>,
+    pub reason: Option<String>,
+    /// Join password while maintenance is on
+    password: String,
+    /// Join password to restore afterwards, none if the server had none
+    previous_password: Option<String>,
+}
+
+impl Maintenance {
+    /// The maintenance in progress, if any
+    pub fn load(server_install_dir: &Path) -> Result<Option<Self>> {
+        let path = get_state_path(server_install_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let encrypted = fs::read(&path)
+            .context(format!("Failed to read {}", path.display()))?;
+        let content = String::from_utf8(crypto::unprotect(&encrypted)?)
+            .context("Stored maintenance state is not valid UTF-8")?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .context("Failed to parse stored maintenance state")
+    }
+
+    fn save(&self, server_install_dir: &Path) -> Result<()> {
+        let path = get_state_path(server_install_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create state directory")?;
+        }
+        let encrypted = crypto::protect(serde_json::to_string(self)?.as_bytes())?;
+        fs::write(&path, encrypted)
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    /// One line for status outputs
+    pub fn describe(&self) -> String {
+        let since = self.since.format("%Y-%m-%d %H:%M");
+        match &self.reason {
+            Some(reason) => format!("Maintenance mode since {since}: {reason}"),
+            None => format!("Maintenance mode since {since}"),
+        }
+    }
+}
+
+fn get_state_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(STATE_FILE)
+}
+
+fn load_server_cfg(server_install_dir: &Path) -> Result<ServerDzConfig> {
+    ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))
+}
+
+/// Get the join password, none when it's unset or empty
+fn get_password(server_cfg: &ServerDzConfig) -> Option<String> {
+    server_cfg.get("password").filter(|password| !password.is_empty())
+}
+
+/// Keep the maintenance password in place before a start. A password rotated or edited in
+/// since maintenance began becomes the one restored afterwards.
+pub fn apply_before_launch(server_install_dir: &Path) -> Result<()> {
+    let Some(mut maintenance) = Maintenance::load(server_install_dir)? else {
+        return Ok(());
+    };
+
+    let mut server_cfg = load_server_cfg(server_install_dir)?;
+    let current = get_password(&server_cfg);
+    if current.as_deref() != Some(maintenance.password.as_str()) {
+        maintenance.previous_password = current;
+        maintenance.save(server_install_dir)?;
+        server_cfg.set_string("password", &maintenance.password);
+        server_cfg.save()?;
+    }
+
+    println_failure(&format!("{}, only players with the maintenance password can join", maintenance.describe()), 1);
+    Ok(())
+}
+
+fn is_server_running(server_install_dir: &Path) -> bool {
+    !processes::find_by_exe_path(&server_install_dir.join(SERVER_EXE)).is_empty()
+}
+
+/// `#lock` or `#unlock` a running server, whose join password only changes when it restarts
+fn set_locked(server_install_dir: &Path, locked: bool) {
+    if !is_server_running(server_install_dir) {
+        return;
+    }
+
+    let command = if locked { "#lock" } else { "#unlock" };
+    match RconClient::connect_to_server(server_install_dir).and_then(|mut rcon| rcon.command(command)) {
+        Ok(_) if locked => println_success("Running server locked, the password applies from its next start", 1),
+        Ok(_) => println_success("Running server unlocked", 1),
+        Err(e) => println_failure(&format!("Failed to {} the running server: {e:#}", &command[1..]), 1),
+    }
+}
+
+fn notify(config: &Config, server_install_dir: &Path, title: &str, body: String) {
+    let notifier = Notifier::new(&config.notifications);
+    if !notifier.is_enabled() {
+        return;
+    }
+
+    let notification = Notification {
+        event: NotificationEvent::Maintenance,
+        title: format!("{}: {title}", get_server_name(server_install_dir)),
+        body,
+    };
+    if let Err(e) = notifier.send(&notification) {
+        println_failure(&format!("Failed to post maintenance notice: {e}"), 1);
+    }
+}
+
+fn start(config: &Config, server_install_dir: &Path, reason: Option<&str>, password: Option<&str>) -> Result<()> {
+    if let Some(maintenance) = Maintenance::load(server_install_dir)? {
+        return Err(anyhow!("{}, turn it off first", maintenance.describe()));
+    }
+
+    let mut server_cfg = load_server_cfg(server_install_dir)?;
+    let password = match password {
+        Some(password) => password.to_string(),
+        None => crypto::random_alphanumeric(PASSWORD_LENGTH)?,
+    };
+    let maintenance = Maintenance {
+        since: Local::now(),
+        reason: reason.map(str::to_string),
+        password,
+        previous_password: get_password(&server_cfg),
+    };
+    // Saved first, so the real password is never lost
+    maintenance.save(server_install_dir)?;
+    server_cfg.set_string("password", &maintenance.password);
+    server_cfg.save()?;
+
+    println_success("Maintenance mode on", 0);
+    // Printed directly so the password never ends up in the log file
+    print_output(&format!("    Join password: {}", maintenance.password));
+    set_locked(server_install_dir, true);
+
+    notify(config, server_install_dir, "maintenance started", match reason {
+        Some(reason) => format!("The server is closed to players: {reason}"),
+        None => "The server is closed to players.".to_string(),
+    });
+    Ok(())
+}
+
+fn stop(config: &Config, server_install_dir: &Path) -> Result<()> {
+    let Some(maintenance) = Maintenance::load(server_install_dir)? else {
+        return Err(anyhow!("Maintenance mode isn't on"));
+    };
+
+    let mut server_cfg = load_server_cfg(server_install_dir)?;
+    server_cfg.set_string("password", maintenance.previous_password.as_deref().unwrap_or_default());
+    server_cfg.save()?;
+    let path = get_state_path(server_install_dir);
+    fs::remove_file(&path)
+        .context(format!("Failed to remove {}", path.display()))?;
+
+    println_success("Maintenance mode off, the join password is restored", 0);
+    if is_server_running(server_install_dir) {
+        println_step("The running server keeps the maintenance password until it restarts", 1);
+    }
+    set_locked(server_install_dir, false);
+
+    notify(config, server_install_dir, "maintenance over", "The server is open to players again.".to_string());
+    Ok(())
+}
+
+/// Entry point for `dzsm maintenance ...`
+pub fn run(command: &MaintenanceCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+
+    match command {
+        MaintenanceCommand::On { reason, password } => start(config, server_install_dir, reason.as_deref(), password.as_deref()),
+        MaintenanceCommand::Off => stop(config, server_install_dir),
+        MaintenanceCommand::Status => {
+            match Maintenance::load(server_install_dir)? {
+                Some(maintenance) => {
+                    println_step(&maintenance.describe(), 0);
+                    print_output(&format!("    Join password: {}", maintenance.password));
+                }
+                None => println_success("Maintenance mode is off", 0),
+            }
+            Ok(())
+        }
+    }
+}
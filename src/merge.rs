@@ -0,0 +1,177 @@
+/// Edit scripts longer than this aren't worth merging line by line, the files have little in common
+const MAX_EDIT_DISTANCE: usize = 4000;
+
+/// Result of a three-way text merge
+pub struct MergeResult {
+    pub text: String,
+    pub conflicts: usize,
+}
+
+/// Labels written into conflict markers
+pub struct MergeLabels<'a> {
+    pub ours: &'a str,
+    pub base: &'a str,
+    pub theirs: &'a str,
+}
+
+/// Merge the changes `ours` and `theirs` each made to `base`, line by line.
+/// Regions both sides changed differently are kept with diff3-style conflict markers.
+pub fn merge3(base: &str, ours: &str, theirs: &str, labels: &MergeLabels) -> MergeResult {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let mut merger = Merger { output: Vec::new(), conflicts: 0, labels };
+
+    match (matching_lines(&base, &ours), matching_lines(&base, &theirs)) {
+        (Some(ours_matches), Some(theirs_matches)) => {
+            let (mut b, mut o, mut t) = (0, 0, 0);
+            loop {
+                // Next base line both sides kept, everything before it is a changed chunk
+                let stable = (b..base.len()).find_map(|i| Some((i, ours_matches[i]?, theirs_matches[i]?)));
+                let Some((next_b, next_o, next_t)) = stable else {
+                    merger.resolve(&base[b..], &ours[o..], &theirs[t..]);
+                    break;
+                };
+
+                merger.resolve(&base[b..next_b], &ours[o..next_o], &theirs[t..next_t]);
+                merger.output.push(base[next_b].to_string());
+                (b, o, t) = (next_b + 1, next_o + 1, next_t + 1);
+            }
+        }
+        // Too different to line up, treat the whole file as one chunk
+        _ => merger.resolve(&base, &ours, &theirs),
+    }
+
+    MergeResult { text: merger.output.concat(), conflicts: merger.conflicts }
+}
+
+struct Merger<'a> {
+    output: Vec<String>,
+    conflicts: usize,
+    labels: &'a MergeLabels<'a>,
+}
+
+impl Merger<'_> {
+    fn resolve(&mut self, base: &[&str], ours: &[&str], theirs: &[&str]) {
+        if ours == theirs || theirs == base {
+            self.push_lines(ours);
+        } else if ours == base {
+            self.push_lines(theirs);
+        } else {
+            self.conflicts += 1;
+            self.push_marker(&format!("<<<<<<< {}", self.labels.ours));
+            self.push_lines(ours);
+            self.push_marker(&format!("||||||| {}", self.labels.base));
+            self.push_lines(base);
+            self.push_marker("=======");
+            self.push_lines(theirs);
+            self.push_marker(&format!(">>>>>>> {}", self.labels.theirs));
+        }
+    }
+
+    fn push_lines(&mut self, lines: &[&str]) {
+        self.output.extend(lines.iter().map(|line| (*line).to_string()));
+    }
+
+    /// Markers go on their own line, even after a last line without a newline
+    fn push_marker(&mut self, marker: &str) {
+        if self.output.last().is_some_and(|line| !line.ends_with('\n')) {
+            self.output.push("\n".to_string());
+        }
+        self.output.push(format!("{marker}\n"));
+    }
+}
+
+/// For every line of `a`, the line of `b` it lines up with in a shortest edit script,
+/// or `None` if the files are too different to be worth lining up
+fn matching_lines(a: &[&str], b: &[&str]) -> Option<Vec<Option<usize>>> {
+    // Common ends are cheap to match and keep the diff itself small
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut matches = vec![None; a.len()];
+    for (i, matched) in matches.iter_mut().enumerate().take(prefix) {
+        *matched = Some(i);
+    }
+    for i in 0..suffix {
+        matches[a.len() - 1 - i] = Some(b.len() - 1 - i);
+    }
+
+    let middle_a = &a[prefix..a.len() - suffix];
+    let middle_b = &b[prefix..b.len() - suffix];
+    for (x, y) in diff_pairs(middle_a, middle_b)? {
+        matches[prefix + x] = Some(prefix + y);
+    }
+
+    Some(matches)
+}
+
+/// Myers' diff: the pairs of equal lines kept by a shortest edit script from `a` to `b`
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss, clippy::many_single_char_names)]
+fn diff_pairs(a: &[&str], b: &[&str]) -> Option<Vec<(usize, usize)>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    let offset = max + 1;
+
+    // Furthest x reached on each diagonal k = x - y, one snapshot per edit distance
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max {
+        if d as usize > MAX_EDIT_DISTANCE {
+            return None;
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+
+        for k in (-d..=d).step_by(2) {
+            let down = k == -d || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize]);
+            let mut x = if down { v[(offset + k + 1) as usize] } else { v[(offset + k - 1) as usize] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk back from the end, collecting the diagonal (equal) moves
+    let mut pairs = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, snapshot) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        if d == 0 {
+            while x > 0 && y > 0 {
+                x -= 1;
+                y -= 1;
+                pairs.push((x as usize, y as usize));
+            }
+            break;
+        }
+
+        let get = |k: isize| snapshot[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            pairs.push((x as usize, y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    pairs.reverse();
+    Some(pairs)
+}
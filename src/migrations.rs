@@ -0,0 +1,182 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::mod_links::InstalledLinks;
+use crate::server::{DRY_RUN, SERVER_EXE};
+use crate::state::state_dir;
+use crate::ui::status::{println_step, println_step_concat, println_success};
+
+const STATE_FILE: &str = "layout.json";
+const BACKUPS_DIR: &str = "layout_backups";
+/// The layout this DZSM keeps an install in
+const LAYOUT_VERSION: u32 = 1;
+
+/// One step from a layout version to the next
+struct Migration {
+    /// The version it migrates to, from the one before
+    to: u32,
+    description: &'static str,
+    /// Changes the install in place, returning what it changed for the report
+    apply: fn(&Path) -> Result<Vec<String>>,
+}
+
+/// Every layout change, oldest first. Changing where DZSM puts things on disk means adding
+/// a step here and bumping `LAYOUT_VERSION`, so existing installs follow without a reinstall.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        to: 1,
+        description: "Record the mod folders and keys DZSM installed",
+        apply: record_installed_links,
+    },
+];
+
+#[derive(Default, Serialize, Deserialize)]
+struct LayoutState {
+    /// Installs from before the layout was versioned are version 0
+    version: u32,
+    #[serde(default)]
+    history: Vec<MigrationRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MigrationRecord {
+    from: u32,
+    to: u32,
+    at: DateTime<Local>,
+    backup: PathBuf,
+    changes: Vec<String>,
+}
+
+impl LayoutState {
+    fn load(server_install_dir: &Path) -> Option<Self> {
+        fs::read_to_string(get_state_path(server_install_dir))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+    }
+
+    fn save(&self, server_install_dir: &Path) -> Result<()> {
+        let path = get_state_path(server_install_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+}
+
+fn get_state_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(STATE_FILE)
+}
+
+/// Bring an install an older DZSM set up to the current layout, backing up DZSM's state
+/// first. Each step is recorded as it completes, so a failed one is retried on the next start.
+pub fn apply_pending(server_install_dir: &Path, dry_run: bool) -> Result<()> {
+    let state = match LayoutState::load(server_install_dir) {
+        Some(state) => state,
+        // Nothing to migrate on a fresh install, it starts out in the current layout
+        None if !server_install_dir.join(SERVER_EXE).exists() => {
+            if !dry_run {
+                LayoutState { version: LAYOUT_VERSION, history: Vec::new() }.save(server_install_dir)?;
+            }
+            return Ok(());
+        }
+        None => LayoutState::default(),
+    };
+
+    if state.version > LAYOUT_VERSION {
+        return Err(anyhow!(
+            "{} was last run by a newer DZSM (install layout version {}, this version knows up to {LAYOUT_VERSION}), update DZSM",
+            server_install_dir.display(),
+            state.version
+        ));
+    }
+
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|migration| migration.to > state.version).collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    if dry_run {
+        for migration in pending {
+            println_step(&format!("{DRY_RUN} Would migrate the install layout to version {}: {}", migration.to, migration.description), 0);
+        }
+        return Ok(());
+    }
+
+    migrate(server_install_dir, state, &pending)
+}
+
+fn migrate(server_install_dir: &Path, mut state: LayoutState, pending: &[&Migration]) -> Result<()> {
+    println_step(&format!("Migrating the install layout from version {} to {LAYOUT_VERSION}...", state.version), 0);
+    let backup = backup_state(server_install_dir, state.version)?;
+    println_step(&format!("Backed up DZSM's state to {}", backup.display()), 1);
+
+    for migration in pending {
+        println_step(migration.description, 1);
+        let changes = (migration.apply)(server_install_dir).context(format!(
+            "Failed to migrate to install layout version {}, DZSM's state from before is in {}",
+            migration.to,
+            backup.display()
+        ))?;
+        for change in &changes {
+            println_step_concat(change, 2);
+        }
+
+        state.history.push(MigrationRecord {
+            from: state.version,
+            to: migration.to,
+            at: Local::now(),
+            backup: backup.clone(),
+            changes,
+        });
+        state.version = migration.to;
+        state.save(server_install_dir)?;
+    }
+
+    println_success(&format!(
+        "Install layout migrated to version {LAYOUT_VERSION}, recorded in {}",
+        get_state_path(server_install_dir).display()
+    ), 0);
+    Ok(())
+}
+
+/// Copy DZSM's state files, not its logs and reports, to a new backup directory
+fn backup_state(server_install_dir: &Path, version: u32) -> Result<PathBuf> {
+    let state_dir = state_dir(server_install_dir);
+    let backup_dir = state_dir.join(BACKUPS_DIR)
+        .join(format!("v{version}_{}", Local::now().format("%Y-%m-%d_%H%M%S")));
+    fs::create_dir_all(&backup_dir)
+        .context(format!("Failed to create {}", backup_dir.display()))?;
+
+    for path in fs::read_dir(&state_dir).into_iter().flatten().flatten().map(|entry| entry.path()) {
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(name) = path.file_name() {
+            fs::copy(&path, backup_dir.join(name))
+                .context(format!("Failed to back up {}", path.display()))?;
+        }
+    }
+
+    Ok(backup_dir)
+}
+
+/// Before the links record, every `@` link and key link in an install was taken to be DZSM's
+/// on each run; writing that down once stops folders linked by hand later being taken for its own
+fn record_installed_links(server_install_dir: &Path) -> Result<Vec<String>> {
+    if InstalledLinks::is_recorded(server_install_dir) {
+        return Ok(Vec::new());
+    }
+
+    let links = InstalledLinks::load(server_install_dir);
+    links.save(server_install_dir)?;
+    Ok(vec![format!(
+        "{} mod folder(s) and {} key(s) recorded as installed by DZSM",
+        links.mods.len(),
+        links.keys.len()
+    )])
+}
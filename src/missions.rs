@@ -0,0 +1,718 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+use zip::ZipArchive;
+
+use crate::cli::MissionCommand;
+use crate::config::Config;
+use crate::economy;
+use crate::http;
+use crate::instance;
+use crate::merge::{MergeLabels, merge3};
+use crate::processes;
+use crate::server::{MISSIONS_DIR, SERVER_CONFIG, SERVER_EXE};
+use crate::server_builds;
+use crate::server_cfg::ServerDzConfig;
+use crate::state::state_dir;
+use crate::steamcmd::SteamCmdManager;
+use crate::storage::{copy_dir, list_files};
+use crate::ui::status::{println_blank, println_failure, println_step, println_success};
+
+/// Where DZSM keeps its copies of missions, `.dzsm/missions`
+pub const MISSIONS_STATE_DIR: &str = "missions";
+const VANILLA_DIR: &str = "vanilla";
+const PRISTINE_DIR: &str = "pristine";
+/// Where a refresh takes the vanilla missions `SteamCMD` downloaded
+const DOWNLOAD_DIR: &str = "downloaded";
+/// Where earlier versions kept a whole second copy of the server to refresh from
+const OLD_DOWNLOAD_DIR: &str = "server";
+/// The install's missions while a refresh downloads the vanilla ones
+const ASIDE_DIR: &str = "customized";
+const BACKUPS_DIR: &str = "backups";
+/// Where installs are unpacked before being moved into `mpmissions`
+const STAGING_DIR: &str = "staging";
+/// Folder mods ship optional files in, such as a map's mission
+const EXTRAS_DIR: &str = "extras";
+/// Every mission has one, it's how a mission is found inside an archive
+const MISSION_INIT: &str = "init.c";
+
+/// Where `dzsm mission install` gets a mission from
+enum MissionSource {
+    Url(String),
+    Mod(String),
+    /// Search the extras of every installed mod
+    AnyMod,
+}
+
+/// What happened to one mission file in a merge
+enum FileMerge {
+    /// Only the new vanilla changed it
+    Updated(Option<Vec<u8>>),
+    /// Both sides changed it and the changes merged cleanly
+    Merged(String),
+    /// Both sides changed the same lines, written with conflict markers
+    Conflicted(String, usize),
+    /// Both sides changed it in ways that can't be merged line by line (deleted, binary)
+    Unmergeable(&'static str),
+}
+
+/// Reference copies of the vanilla missions, `.dzsm/missions/vanilla/<mission>`
+fn get_vanilla_dir(server_install_dir: &Path, mission: &str) -> PathBuf {
+    state_dir(server_install_dir).join(MISSIONS_STATE_DIR).join(VANILLA_DIR).join(mission)
+}
+
+/// Vanilla missions exactly as the current game version ships them, `.dzsm/missions/pristine/<mission>`
+pub fn get_pristine_dir(server_install_dir: &Path, mission: &str) -> PathBuf {
+    get_pristine_root(server_install_dir).join(mission)
+}
+
+fn get_pristine_root(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(MISSIONS_STATE_DIR).join(PRISTINE_DIR)
+}
+
+/// The mission named on the command line, or the active one from `serverDZ.cfg`
+#[allow(clippy::doc_markdown)]
+fn resolve_mission(server_install_dir: &Path, mission: Option<&str>) -> Result<String> {
+    if let Some(mission) = mission {
+        return Ok(mission.to_string());
+    }
+
+    ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))?
+        .get("template")
+        .ok_or_else(|| anyhow!("No mission template set in {SERVER_CONFIG}, pass --mission"))
+}
+
+/// Relative paths of every file in a mission, empty if the directory doesn't exist
+fn list_mission_files(dir: &Path) -> Result<BTreeSet<PathBuf>> {
+    if !dir.exists() {
+        return Ok(BTreeSet::new());
+    }
+
+    Ok(list_files(dir)?
+        .into_iter()
+        .filter_map(|path| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+        .collect())
+}
+
+fn read_optional(path: &Path) -> Result<Option<Vec<u8>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    fs::read(path)
+        .map(Some)
+        .context(format!("Failed to read {}", path.display()))
+}
+
+/// Three-way merge of one file; `None` when the customized file can stay as it is
+fn merge_file(file: &Path, base: Option<Vec<u8>>, ours: Option<Vec<u8>>, theirs: Option<Vec<u8>>) -> Option<FileMerge> {
+    if ours == theirs || base == theirs {
+        return None;
+    }
+    if base == ours {
+        return Some(FileMerge::Updated(theirs));
+    }
+
+    let (Some(base), Some(ours), Some(theirs)) = (base, ours, theirs) else {
+        return Some(FileMerge::Unmergeable("customized here but added or deleted in the new vanilla mission"));
+    };
+    let (Ok(base), Ok(ours), Ok(theirs)) = (String::from_utf8(base), String::from_utf8(ours), String::from_utf8(theirs)) else {
+        return Some(FileMerge::Unmergeable("binary file changed on both sides"));
+    };
+
+    let file = file.display().to_string();
+    let labels = MergeLabels {
+        ours: &format!("customized {file}"),
+        base: &format!("old vanilla {file}"),
+        theirs: &format!("new vanilla {file}"),
+    };
+    let result = merge3(&base, &ours, &theirs, &labels);
+
+    Some(if result.conflicts == 0 {
+        FileMerge::Merged(result.text)
+    } else {
+        FileMerge::Conflicted(result.text, result.conflicts)
+    })
+}
+
+fn write_file(path: &Path, content: Option<&[u8]>) -> Result<()> {
+    match content {
+        Some(content) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .context(format!("Failed to create {}", parent.display()))?;
+            }
+            fs::write(path, content)
+                .context(format!("Failed to write {}", path.display()))
+        }
+        None => fs::remove_file(path)
+            .context(format!("Failed to remove {}", path.display())),
+    }
+}
+
+/// Bring a customized mission up to date with a new vanilla version, keeping the customizations.
+/// The new vanilla mission becomes the reference for the next merge.
+fn merge(server_install_dir: &Path, mission: &str, new_dir: &Path, old_dir: Option<&Path>, dry_run: bool) -> Result<()> {
+    let mission_dir = server_install_dir.join(MISSIONS_DIR).join(mission);
+    let vanilla_dir = get_vanilla_dir(server_install_dir, mission);
+    let old_dir = old_dir.unwrap_or(&vanilla_dir);
+
+    if !mission_dir.exists() {
+        return Err(anyhow!("Mission not found: {}", mission_dir.display()));
+    }
+    if !new_dir.exists() {
+        return Err(anyhow!(
+            "New vanilla mission not found: {}, run `dzsm mission refresh` or pass it with --new",
+            new_dir.display()
+        ));
+    }
+    if !old_dir.exists() {
+        return Err(anyhow!(
+            "No reference copy of the vanilla {mission} mission at {}, pass the one your customizations are based on with --old",
+            old_dir.display()
+        ));
+    }
+
+    println_step(&format!("Merging {mission} with the new vanilla mission..."), 0);
+
+    let mut files = list_mission_files(old_dir)?;
+    files.extend(list_mission_files(&mission_dir)?);
+    files.extend(list_mission_files(new_dir)?);
+
+    let mut changes = Vec::new();
+    for file in files {
+        let merged = merge_file(
+            &file,
+            read_optional(&old_dir.join(&file))?,
+            read_optional(&mission_dir.join(&file))?,
+            read_optional(&new_dir.join(&file))?,
+        );
+        if let Some(merged) = merged {
+            changes.push((file, merged));
+        }
+    }
+
+    if changes.is_empty() {
+        println_success("Nothing to merge, the customized mission already matches the new vanilla changes", 1);
+    }
+
+    let mut conflicts = 0;
+    for (file, change) in &changes {
+        let file = file.display();
+        match change {
+            FileMerge::Updated(Some(_)) => println_step(&format!("{file}: updated from the new vanilla mission"), 1),
+            FileMerge::Updated(None) => println_step(&format!("{file}: removed in the new vanilla mission"), 1),
+            FileMerge::Merged(_) => println_success(&format!("{file}: merged"), 1),
+            FileMerge::Conflicted(_, count) => {
+                println_failure(&format!("{file}: {count} conflict(s), resolve the <<<<<<< markers"), 1);
+                conflicts += 1;
+            }
+            FileMerge::Unmergeable(reason) => {
+                println_failure(&format!("{file}: {reason}, kept the customized version"), 1);
+                conflicts += 1;
+            }
+        }
+    }
+
+    if dry_run {
+        println_step("Dry run, nothing was written", 0);
+        return Ok(());
+    }
+
+    if !changes.is_empty() {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        let backup_dir = state_dir(server_install_dir)
+            .join(MISSIONS_STATE_DIR)
+            .join(BACKUPS_DIR)
+            .join(format!("{mission}_{timestamp}"));
+        copy_dir(&mission_dir, &backup_dir)
+            .context("Failed to back up the mission")?;
+        println_success(&format!("Mission backed up to {}", backup_dir.display()), 1);
+    }
+
+    for (file, change) in &changes {
+        let path = mission_dir.join(file);
+        match change {
+            FileMerge::Updated(content) => write_file(&path, content.as_deref())?,
+            FileMerge::Merged(text) | FileMerge::Conflicted(text, _) => write_file(&path, Some(text.as_bytes()))?,
+            FileMerge::Unmergeable(_) => {}
+        }
+    }
+
+    // The customizations are now based on the new vanilla mission
+    if fs::canonicalize(new_dir).ok() != fs::canonicalize(&vanilla_dir).ok() {
+        if vanilla_dir.exists() {
+            fs::remove_dir_all(&vanilla_dir)
+                .context(format!("Failed to remove {}", vanilla_dir.display()))?;
+        }
+        copy_dir(new_dir, &vanilla_dir)
+            .context("Failed to save the new vanilla mission as the reference")?;
+    }
+
+    if conflicts > 0 {
+        return Err(anyhow!("{conflicts} file(s) need attention before the next start"));
+    }
+    println_success(&format!("{mission} is up to date with the new vanilla mission"), 0);
+    Ok(())
+}
+
+/// Carry the active mission's customizations over to its pristine copy from the last `refresh`
+pub fn merge_active(server_install_dir: &Path) -> Result<()> {
+    let mission = resolve_mission(server_install_dir, None)?;
+    merge(server_install_dir, &mission, &get_pristine_dir(server_install_dir, &mission), None, false)
+}
+
+/// Whether two directories hold the same files with the same contents
+fn same_files(a: &Path, b: &Path) -> Result<bool> {
+    let files = list_mission_files(a)?;
+    if files != list_mission_files(b)? {
+        return Ok(false);
+    }
+    for file in &files {
+        if read_optional(&a.join(file))? != read_optional(&b.join(file))? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The install's customized missions, moved aside while SteamCMD downloads the vanilla ones in
+/// their place. Dropping it puts them back, whatever happened in between.
+#[allow(clippy::doc_markdown)]
+struct MissionsAside {
+    missions_dir: PathBuf,
+    aside: PathBuf,
+    /// `serverDZ.cfg` as it was, validating resets it to the shipped one
+    server_config: (PathBuf, Option<Vec<u8>>),
+}
+
+impl MissionsAside {
+    fn new(server_install_dir: &Path) -> Result<Self> {
+        let missions_dir = server_install_dir.join(MISSIONS_DIR);
+        let aside = state_dir(server_install_dir).join(MISSIONS_STATE_DIR).join(ASIDE_DIR);
+        if aside.exists() {
+            return Err(anyhow!(
+                "A refresh was interrupted, the customized missions are still in {}; move them back to {} first",
+                aside.display(),
+                missions_dir.display()
+            ));
+        }
+        let config_path = server_install_dir.join(SERVER_CONFIG);
+        let server_config = fs::read(&config_path).ok();
+
+        if let Some(parent) = aside.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        // Inside the install dir, so a rename and not a copy
+        fs::rename(&missions_dir, &aside)
+            .context(format!("Failed to move {} aside", missions_dir.display()))?;
+        Ok(Self { missions_dir, aside, server_config: (config_path, server_config) })
+    }
+
+    /// Take the vanilla missions SteamCMD downloaded to `to`, then put the customized ones back
+    #[allow(clippy::doc_markdown)]
+    fn take_vanilla(self, to: &Path) -> Result<()> {
+        if to.exists() {
+            fs::remove_dir_all(to)
+                .context(format!("Failed to remove {}", to.display()))?;
+        }
+        fs::rename(&self.missions_dir, to)
+            .context(format!("Failed to move the vanilla missions to {}", to.display()))?;
+        Ok(())
+    }
+}
+
+impl Drop for MissionsAside {
+    fn drop(&mut self) {
+        if self.missions_dir.exists()
+            && let Err(e) = fs::remove_dir_all(&self.missions_dir)
+        {
+            println_failure(&format!("Failed to remove {}: {e}", self.missions_dir.display()), 1);
+        }
+        if let Err(e) = fs::rename(&self.aside, &self.missions_dir) {
+            println_failure(&format!(
+                "Failed to move the customized missions back from {}: {e}",
+                self.aside.display()
+            ), 1);
+        }
+        if let (path, Some(content)) = &self.server_config
+            && let Err(e) = fs::write(path, content)
+        {
+            println_failure(&format!("Failed to restore {}: {e}", path.display()), 1);
+        }
+    }
+}
+
+/// Save the vanilla missions of the installed server as the pristine baseline. Its own missions
+/// are the ones being customized, so they're moved aside while SteamCMD validates the install,
+/// which downloads only the mission files it finds missing.
+#[allow(clippy::doc_markdown)]
+pub fn refresh(config: &Config, server_install_dir: &Path, offline: bool) -> Result<()> {
+    if offline {
+        return Err(anyhow!("Refreshing the vanilla missions downloads them, run without --offline"));
+    }
+    if let Some(pid) = instance::find_running(server_install_dir) {
+        return Err(anyhow!("DZSM (PID {pid}) is running the server, stop it with `dzsm stop` before refreshing the missions"));
+    }
+    if !processes::find_by_exe_path(&server_install_dir.join(SERVER_EXE)).is_empty() {
+        return Err(anyhow!("The server is running, stop it before refreshing the missions"));
+    }
+
+    let old_download_dir = state_dir(server_install_dir).join(MISSIONS_STATE_DIR).join(OLD_DOWNLOAD_DIR);
+    if old_download_dir.exists() {
+        fs::remove_dir_all(&old_download_dir)
+            .context(format!("Failed to remove {}", old_download_dir.display()))?;
+        println_step(&format!("Removed the no longer needed server copy in {}", old_download_dir.display()), 1);
+    }
+
+    println_step("Downloading the vanilla missions...\n", 0);
+    let steamcmd = SteamCmdManager::new(&config.server.steamcmd_dir, offline)?.with_options(config)?;
+    steamcmd.set_cell_override(config.server.cell_id)?;
+    let branch = server_builds::get_branch(&config.server, server_install_dir);
+    let downloaded_missions = state_dir(server_install_dir).join(MISSIONS_STATE_DIR).join(DOWNLOAD_DIR);
+    let aside = MissionsAside::new(server_install_dir)?;
+    // Validating finds the missions missing and downloads them, the rest is already there
+    steamcmd.install_or_update_app(
+        server_install_dir,
+        config.steam.get_server_username(&config.server.username),
+        config.server.branch.server_app_id(),
+        branch.as_deref(),
+        true
+    )?;
+    println_blank();
+    aside.take_vanilla(&downloaded_missions)?;
+
+    let pristine_root = get_pristine_root(server_install_dir);
+    let mut missions: Vec<PathBuf> = fs::read_dir(&downloaded_missions)
+        .context(format!("Failed to read {}", downloaded_missions.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    missions.sort();
+
+    for source in &missions {
+        let Some(mission) = source.file_name() else {
+            continue;
+        };
+        let mission = mission.to_string_lossy();
+        let pristine_dir = pristine_root.join(mission.as_ref());
+
+        if pristine_dir.exists() {
+            if same_files(source, &pristine_dir)? {
+                println_success(&format!("{mission}: unchanged"), 1);
+                continue;
+            }
+            fs::remove_dir_all(&pristine_dir)
+                .context(format!("Failed to remove {}", pristine_dir.display()))?;
+            copy_dir(source, &pristine_dir)?;
+            println_step(&format!("{mission}: updated, run `dzsm mission merge --mission {mission}` to carry your customizations over"), 1);
+        } else {
+            copy_dir(source, &pristine_dir)?;
+            println_success(&format!("{mission}: saved"), 1);
+        }
+    }
+
+    fs::remove_dir_all(&downloaded_missions)
+        .context(format!("Failed to remove {}", downloaded_missions.display()))?;
+    println_success(&format!("Pristine missions saved to {}", pristine_root.display()), 0);
+    Ok(())
+}
+
+/// Missions in `mpmissions`, sorted by name
+fn list_missions(server_install_dir: &Path) -> Result<Vec<String>> {
+    let missions_dir = server_install_dir.join(MISSIONS_DIR);
+    let mut missions: Vec<String> = fs::read_dir(&missions_dir)
+        .context(format!("Failed to read {}", missions_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .filter_map(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+        .collect();
+    missions.sort_by_key(|mission| mission.to_lowercase());
+    Ok(missions)
+}
+
+fn list(server_install_dir: &Path) -> Result<()> {
+    let active = ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))
+        .ok()
+        .and_then(|server_config| server_config.get("template"));
+    let missions = list_missions(server_install_dir)?;
+    if missions.is_empty() {
+        println_failure(&format!("No missions in {}", server_install_dir.join(MISSIONS_DIR).display()), 0);
+        return Ok(());
+    }
+
+    for mission in &missions {
+        if active.as_ref().is_some_and(|active| active.eq_ignore_ascii_case(mission)) {
+            println_success(&format!("{mission} (active)"), 0);
+        } else {
+            println_step(mission, 0);
+        }
+    }
+    if let Some(active) = active.filter(|active| !missions.iter().any(|mission| mission.eq_ignore_ascii_case(active))) {
+        println_failure(&format!("The active template {active} is not installed, the server won't start"), 0);
+    }
+    Ok(())
+}
+
+/// Point the `template` in `serverDZ.cfg` at another installed mission
+#[allow(clippy::doc_markdown)]
+pub fn set(server_install_dir: &Path, mission: &str) -> Result<()> {
+    let installed = list_missions(server_install_dir)?;
+    let Some(mission) = installed.iter().find(|installed| installed.eq_ignore_ascii_case(mission)) else {
+        return Err(anyhow!(
+            "Mission {mission} is not in {MISSIONS_DIR}, install it with `dzsm mission install {mission}` (installed: {})",
+            installed.join(", ")
+        ));
+    };
+
+    let mut server_config = ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))?;
+    let previous = server_config.get("template")
+        .ok_or_else(|| anyhow!("No template in {SERVER_CONFIG}, add a `class Missions` block first"))?;
+    if previous == *mission {
+        println_success(&format!("{mission} is already the active mission"), 0);
+        return Ok(());
+    }
+
+    server_config.set_string("template", mission);
+    server_config.save()?;
+    println_success(&format!("Active mission changed from {previous} to {mission}, takes effect on the next start"), 0);
+    Ok(())
+}
+
+/// The mission's source from the command line or `[missions.sources]`
+fn resolve_source(config: &Config, mission: &str, from_mod: Option<&str>, url: Option<&str>) -> MissionSource {
+    if let Some(url) = url {
+        return MissionSource::Url(url.to_string());
+    }
+    if let Some(from_mod) = from_mod {
+        return MissionSource::Mod(from_mod.to_string());
+    }
+
+    let configured = config.missions.sources.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(mission))
+        .map(|(_, source)| source.trim());
+    match configured {
+        Some(source) if source.starts_with("http://") || source.starts_with("https://") => MissionSource::Url(source.to_string()),
+        Some(source) => MissionSource::Mod(source.to_string()),
+        None => MissionSource::AnyMod,
+    }
+}
+
+/// Find a child of `dir` by name, ignoring case as Windows does
+fn find_child(dir: &Path, name: &str) -> Option<PathBuf> {
+    fs::read_dir(dir).ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.file_name().is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case(name)))
+}
+
+/// Copy the mission out of a mod's extras folder, either as a folder or inside a zip
+fn install_from_mod(mod_dir: &Path, mission: &str, staging: &Path) -> Result<bool> {
+    let Some(extras) = find_child(mod_dir, EXTRAS_DIR) else {
+        return Ok(false);
+    };
+
+    let files = list_files(&extras)?;
+    // A folder named after the mission, as close to the top as possible
+    let mut folders: Vec<&Path> = files.iter()
+        .filter_map(|file| file.ancestors().find(|dir| {
+            dir.starts_with(&extras) && dir.file_name().is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case(mission))
+        }))
+        .collect();
+    folders.sort_by_key(|dir| dir.components().count());
+    if let Some(folder) = folders.first() {
+        copy_dir(folder, staging)?;
+        return Ok(true);
+    }
+
+    for archive in files.iter().filter(|file| file.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip"))) {
+        let bytes = fs::read(archive)
+            .context(format!("Failed to read {}", archive.display()))?;
+        if extract_mission(bytes, mission, staging)
+            .context(format!("Failed to extract {}", archive.display()))?
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Extract the mission from a zip: the folder named after it, or failing that the shallowest
+/// folder with an `init.c`, as in an archive of a mission's own repository
+fn extract_mission(bytes: Vec<u8>, mission: &str, staging: &Path) -> Result<bool> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))
+        .context("Failed to read zip archive")?;
+    let paths: Vec<Option<PathBuf>> = (0..archive.len())
+        .map(|i| archive.by_index(i).ok().and_then(|file| file.enclosed_name()))
+        .collect();
+
+    let named_root = paths.iter().flatten().find_map(|path| {
+        let depth = path.components().position(|component| {
+            matches!(component, Component::Normal(name) if name.to_string_lossy().eq_ignore_ascii_case(mission))
+        })?;
+        Some(path.components().take(depth + 1).collect::<PathBuf>())
+    });
+    let init_root = || paths.iter()
+        .flatten()
+        .filter(|path| path.file_name().is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case(MISSION_INIT)))
+        .min_by_key(|path| path.components().count())
+        .and_then(|path| path.parent().map(Path::to_path_buf));
+    let Some(root) = named_root.or_else(init_root) else {
+        return Ok(false);
+    };
+
+    for (i, path) in paths.iter().enumerate() {
+        let Some(relative) = path.as_ref().and_then(|path| path.strip_prefix(&root).ok()) else {
+            continue;
+        };
+        let mut file = archive.by_index(i)
+            .context("Failed to access file in zip")?;
+        if file.is_dir() {
+            continue;
+        }
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)
+            .context(format!("Failed to read {} from zip", path.as_deref().unwrap_or(&root).display()))?;
+        write_file(&staging.join(relative), Some(&contents))?;
+    }
+    Ok(true)
+}
+
+/// Move the `storage_*` folders of the mission being replaced into the new one, the world
+/// and player data must survive a reinstall
+fn carry_over_storage(mission_dir: &Path, staging: &Path) -> Result<()> {
+    for entry in fs::read_dir(mission_dir)?.flatten() {
+        let name = entry.file_name();
+        if !entry.path().is_dir() || !name.to_string_lossy().starts_with("storage_") {
+            continue;
+        }
+        let target = staging.join(&name);
+        // The live persistence wins over any shipped with the mission
+        if target.exists() {
+            fs::remove_dir_all(&target)
+                .context(format!("Failed to remove {}", target.display()))?;
+        }
+        fs::rename(entry.path(), &target)
+            .context(format!("Failed to move {} into the new mission", entry.path().display()))?;
+        println_success(&format!("Kept {}", name.to_string_lossy()), 1);
+    }
+    Ok(())
+}
+
+/// Install a community map's mission into `mpmissions`, replacing it only with `force`.
+/// The persistence of the mission being replaced is kept.
+fn install(server_install_dir: &Path, mission: &str, source: &MissionSource, force: bool, offline: bool) -> Result<()> {
+    let mission_dir = server_install_dir.join(MISSIONS_DIR).join(mission);
+    if mission_dir.exists() && !force {
+        return Err(anyhow!("{mission} is already installed, pass --force to replace it"));
+    }
+
+    let staging = state_dir(server_install_dir).join(MISSIONS_STATE_DIR).join(STAGING_DIR).join(mission);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .context(format!("Failed to remove {}", staging.display()))?;
+    }
+
+    let found = match source {
+        MissionSource::Url(url) => {
+            if offline {
+                return Err(anyhow!("Can't download {url} in offline mode"));
+            }
+            println_step(&format!("Downloading {mission} from {url}..."), 0);
+            extract_mission(http::get_bytes(url)?, mission, &staging)?
+        }
+        MissionSource::Mod(name) => {
+            let mod_dir = server_install_dir.join(format!("@{}", name.trim_start_matches('@')));
+            if !mod_dir.exists() {
+                return Err(anyhow!("Mod {name} is not installed at {}", mod_dir.display()));
+            }
+            println_step(&format!("Looking for {mission} in the extras of {name}..."), 0);
+            install_from_mod(&mod_dir, mission, &staging)?
+        }
+        MissionSource::AnyMod => {
+            println_step(&format!("Looking for {mission} in the extras of the installed mods..."), 0);
+            let mut found = false;
+            for entry in fs::read_dir(server_install_dir)?.flatten() {
+                if entry.file_name().to_string_lossy().starts_with('@') && install_from_mod(&entry.path(), mission, &staging)? {
+                    println_success(&format!("Found in {}", entry.file_name().to_string_lossy()), 1);
+                    found = true;
+                    break;
+                }
+            }
+            found
+        }
+    };
+    if !found {
+        return Err(anyhow!(
+            "{mission} not found, pass --url or --mod, or add it to [missions.sources] in config.toml"
+        ));
+    }
+    if find_child(&staging, MISSION_INIT).is_none() {
+        println_failure(&format!("No {MISSION_INIT} in the installed files, check that this is a complete mission"), 1);
+    }
+
+    if mission_dir.exists() {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        let backup_dir = state_dir(server_install_dir)
+            .join(MISSIONS_STATE_DIR)
+            .join(BACKUPS_DIR)
+            .join(format!("{mission}_{timestamp}"));
+        copy_dir(&mission_dir, &backup_dir)
+            .context("Failed to back up the mission")?;
+        carry_over_storage(&mission_dir, &staging)?;
+        fs::remove_dir_all(&mission_dir)
+            .context(format!("Failed to remove {}", mission_dir.display()))?;
+        println_success(&format!("Previous mission backed up to {}", backup_dir.display()), 1);
+    }
+    fs::create_dir_all(server_install_dir.join(MISSIONS_DIR))
+        .context(format!("Failed to create {}", server_install_dir.join(MISSIONS_DIR).display()))?;
+    fs::rename(&staging, &mission_dir)
+        .context(format!("Failed to move the mission into {}", mission_dir.display()))?;
+
+    // As shipped, so later versions of the map can be merged with `dzsm mission merge`
+    let vanilla_dir = get_vanilla_dir(server_install_dir, mission);
+    if vanilla_dir.exists() {
+        fs::remove_dir_all(&vanilla_dir)
+            .context(format!("Failed to remove {}", vanilla_dir.display()))?;
+    }
+    copy_dir(&mission_dir, &vanilla_dir)
+        .context("Failed to save the mission as the reference for merges")?;
+
+    println_success(&format!("{mission} installed, run `dzsm mission set {mission}` to make it the active mission"), 0);
+    Ok(())
+}
+
+/// Entry point for `dzsm mission ...`
+pub fn run(command: &MissionCommand, config: &Config, server_install_dir: &str, offline: bool) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+
+    match command {
+        MissionCommand::Merge { new, old, mission, dry_run } => {
+            let mission = resolve_mission(server_install_dir, mission.as_deref())?;
+            let new = new.clone().unwrap_or_else(|| get_pristine_dir(server_install_dir, &mission));
+            merge(server_install_dir, &mission, &new, old.as_deref(), *dry_run)
+        }
+        MissionCommand::Refresh => refresh(config, server_install_dir, offline),
+        MissionCommand::Economy { dry_run } => {
+            if config.mods.economy.is_empty() && config.economy.patch_files.is_empty() && config.economy.types.is_empty() {
+                return Err(anyhow!("No mods in [[mods.economy]] and no [economy] patches, see config.toml"));
+            }
+            println_step("Updating the active mission's economy files...", 0);
+            let changed = economy::apply(&config.mods, &config.economy, server_install_dir, *dry_run)?;
+            if *dry_run {
+                println_step("Dry run, nothing was written", 0);
+            } else {
+                println_success(&format!("{changed} mission file(s) updated"), 0);
+            }
+            Ok(())
+        }
+        MissionCommand::List => list(server_install_dir),
+        MissionCommand::Set { mission } => set(server_install_dir, mission),
+        MissionCommand::Install { mission, from_mod, url, force } => {
+            let source = resolve_source(config, mission, from_mod.as_deref(), url.as_deref());
+            install(server_install_dir, mission, &source, *force, offline)
+        }
+    }
+}
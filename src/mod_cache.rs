@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A mod as some DZSM last downloaded it
+#[derive(Serialize, Deserialize)]
+pub struct CachedMod {
+    /// The Workshop upload it has, from SteamCMD's manifest right after the download
+    #[allow(clippy::doc_markdown)]
+    pub time_updated: i64,
+    pub downloaded: DateTime<Local>,
+    /// The server install that downloaded it
+    pub by: PathBuf,
+}
+
+impl CachedMod {
+    /// Whether it has the Workshop's latest upload
+    pub const fn is_current(&self, latest_time_updated: i64) -> bool {
+        self.time_updated >= latest_time_updated
+    }
+}
+
+/// `dzsm_cache_<app id>.json` in SteamCMD's workshop dir, shared by every DZSM using that
+/// SteamCMD so one server doesn't run SteamCMD again for a mod another just downloaded.
+/// Only successful downloads are recorded, SteamCMD's own manifest also lists ones cut off.
+#[allow(clippy::doc_markdown)]
+#[derive(Default, Serialize, Deserialize)]
+pub struct ModCache {
+    #[serde(skip)]
+    path: PathBuf,
+    mods: BTreeMap<u64, CachedMod>,
+}
+
+impl ModCache {
+    pub fn get_path(workshop_dir: &Path, app_id: u32) -> PathBuf {
+        workshop_dir.join(format!("dzsm_cache_{app_id}.json"))
+    }
+
+    /// Load the cache, an empty one if no DZSM has written it yet or it can't be read
+    pub fn load(path: &Path) -> Self {
+        let mut cache: Self = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        cache.path = path.to_path_buf();
+        cache
+    }
+
+    pub fn get(&self, workshop_id: u64) -> Option<&CachedMod> {
+        self.mods.get(&workshop_id)
+    }
+
+    pub fn record(&mut self, workshop_id: u64, time_updated: i64, by: &Path) {
+        self.mods.insert(workshop_id, CachedMod {
+            time_updated,
+            downloaded: Local::now(),
+            by: std::path::absolute(by).unwrap_or_else(|_| by.to_path_buf()),
+        });
+    }
+
+    /// Written next to the cache and renamed over it, so another DZSM never reads half of one
+    pub fn save(&self) -> Result<()> {
+        let partial_path = self.path.with_extension("json.partial");
+        fs::write(&partial_path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", partial_path.display()))?;
+        fs::rename(&partial_path, &self.path)
+            .context(format!("Failed to write {}", self.path.display()))
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::thread::{self, JoinHandle};
+
+use crate::collection_fetcher::{CollectionFetcher, FetchedCollection};
+use crate::workshop_details::{self, WorkshopDetails};
+
+/// What a prefetch found, each part only if it was asked for
+struct ModInfo {
+    collections: Option<Vec<Result<FetchedCollection>>>,
+    details: Option<Result<HashMap<u64, WorkshopDetails>>>,
+}
+
+/// Collection pages and Workshop details fetched on a background thread while the server
+/// updates, so they're ready by the time mods are installed rather than waited for after it
+pub struct ModInfoPrefetch {
+    handle: Option<JoinHandle<ModInfo>>,
+    info: Option<ModInfo>,
+}
+
+impl ModInfoPrefetch {
+    /// Fetch the collections, then the details of their mods and `ids` if `fetch_details`
+    pub fn start(collection_urls: Vec<String>, ids: Vec<u64>, fetch_details: bool) -> Self {
+        let handle = thread::spawn(move || {
+            let collections = (!collection_urls.is_empty()).then(|| {
+                let urls: Vec<&str> = collection_urls.iter().map(String::as_str).collect();
+                CollectionFetcher::download_all(&urls)
+            });
+
+            let details = fetch_details.then(|| {
+                let mut ids = ids;
+                ids.extend(collections.iter().flatten().flatten().flat_map(|collection| &collection.mods).map(|mod_entry| mod_entry.id));
+                ids.sort_unstable();
+                ids.dedup();
+                workshop_details::fetch(&ids)
+            });
+
+            ModInfo { collections, details }
+        });
+
+        Self { handle: Some(handle), info: None }
+    }
+
+    /// The fetched collections, once; waits for the prefetch if it's still running
+    pub fn take_collections(&mut self) -> Option<Vec<Result<FetchedCollection>>> {
+        self.wait()?.collections.take()
+    }
+
+    /// The fetched Workshop details; waits for the prefetch if it's still running
+    pub fn get_details(&mut self) -> Option<Result<HashMap<u64, WorkshopDetails>>> {
+        match self.wait()?.details.as_ref()? {
+            Ok(details) => Some(Ok(details.clone())),
+            Err(e) => Some(Err(anyhow!("{e:#}"))),
+        }
+    }
+
+    fn wait(&mut self) -> Option<&mut ModInfo> {
+        if let Some(handle) = self.handle.take() {
+            self.info = handle.join().ok();
+        }
+        self.info.as_mut()
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::os::windows::fs::{symlink_dir, symlink_file};
+use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::mods_config::{InstallStrategy, KeyStrategy};
+use crate::server::SERVER_KEYS;
+use crate::state::state_dir;
+
+const LINKS_FILE: &str = "links.json";
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// The `@mod` folders and keys DZSM linked into the server install, so cleanup
+/// leaves locally developed mods and hand-placed keys alone
+#[derive(Default, Serialize, Deserialize)]
+pub struct InstalledLinks {
+    /// Folder names in the install dir, e.g. `@CF`
+    #[serde(default)]
+    pub mods: BTreeSet<String>,
+    /// File names in the keys directory
+    #[serde(default)]
+    pub keys: BTreeSet<String>,
+    /// Folders in `mods` that are copies rather than links, to their Workshop IDs,
+    /// which a copy has no link target to tell
+    #[serde(default)]
+    pub copies: BTreeMap<String, u64>,
+    /// Key file names to the Workshop IDs of the mods that ship them, so cleanup only
+    /// removes keys no planned mod needs any more
+    #[serde(default)]
+    pub key_owners: BTreeMap<String, BTreeSet<u64>>,
+}
+
+impl InstalledLinks {
+    /// Load the record of DZSM's links. Installs from before it was kept only ever
+    /// got links from DZSM, so there every link is taken to be its own.
+    pub fn load(server_install_dir: &Path) -> Self {
+        match fs::read_to_string(get_links_path(server_install_dir)) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_default(),
+            Err(_) => Self {
+                mods: find_links(server_install_dir, |name| name.starts_with('@')),
+                keys: find_links(&server_install_dir.join(SERVER_KEYS), |_| true),
+                copies: BTreeMap::new(),
+                key_owners: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Whether the record has been written, rather than worked out from the links on disk
+    pub fn is_recorded(server_install_dir: &Path) -> bool {
+        get_links_path(server_install_dir).exists()
+    }
+
+    pub fn save(&self, server_install_dir: &Path) -> Result<()> {
+        let path = get_links_path(server_install_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+}
+
+fn get_links_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(LINKS_FILE)
+}
+
+/// Whether `path` is a symlink itself, without following it
+pub fn is_link(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// Names of the links in `dir` that `filter` accepts
+fn find_links(dir: &Path, filter: impl Fn(&str) -> bool) -> BTreeSet<String> {
+    fs::read_dir(dir).into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| is_link(&entry.path()))
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .filter(|name| filter(name))
+        .collect()
+}
+
+/// Files a sync copied, removed, and left alone
+#[derive(Default)]
+pub struct SyncStats {
+    pub copied: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// Link a mod's folder into the server install with a symlink or junction
+pub fn link_dir(strategy: InstallStrategy, source: &Path, target: &Path) -> Result<()> {
+    match strategy {
+        InstallStrategy::Junction => create_junction(source, target),
+        _ => symlink_dir(source, target).map_err(|e| anyhow!(
+            "Failed to create a directory symlink from {} to {}: {e}. \
+            Symlinks need admin rights or Developer Mode, or set `install_strategy = \"junction\"` under [mods]",
+            source.display(),
+            target.display()
+        )),
+    }
+}
+
+/// Junctions don't need the symlink privilege; std has no way to create one, `mklink` does
+fn create_junction(source: &Path, target: &Path) -> Result<()> {
+    let output = Command::new("cmd")
+        .args(["/C", "mklink", "/J"])
+        .arg(target)
+        .arg(source)
+        .stdin(Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .context("Failed to run mklink")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to create a junction from {} to {}: {}",
+            source.display(),
+            target.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Put a key in place, replacing a previous key at `target` unless it's already a link to
+/// `source` or an unchanged copy of it. Returns whether anything was written.
+pub fn install_key(strategy: KeyStrategy, source: &Path, target: &Path) -> Result<bool> {
+    let up_to_date = match strategy {
+        KeyStrategy::Symlink => fs::read_link(target).is_ok_and(|linked| linked == source),
+        KeyStrategy::Copy => !is_link(target) && fs::metadata(source).is_ok_and(|metadata| is_unchanged(&metadata, target)),
+    };
+    if up_to_date {
+        return Ok(false);
+    }
+
+    remove_path(target)?;
+    let result = match strategy {
+        KeyStrategy::Symlink => symlink_file(source, target),
+        KeyStrategy::Copy => fs::copy(source, target).map(|_| ()),
+    };
+    result.context(format!("Failed to {} {} to {}", strategy.as_str(), source.display(), target.display()))?;
+    Ok(true)
+}
+
+/// Make `target` a copy of `source`, only copying files whose size or modified time differ and
+/// removing files `source` no longer has. With `hardlink`, files are hard linked where the volume allows.
+pub fn sync_dir(source: &Path, target: &Path, hardlink: bool) -> Result<SyncStats> {
+    let mut stats = SyncStats::default();
+    sync_dir_into(source, target, hardlink, &mut stats)?;
+    Ok(stats)
+}
+
+fn sync_dir_into(source: &Path, target: &Path, hardlink: bool, stats: &mut SyncStats) -> Result<()> {
+    if fs::symlink_metadata(target).is_ok_and(|metadata| !metadata.is_dir()) {
+        fs::remove_file(target).context(format!("Failed to remove {}", target.display()))?;
+    }
+    fs::create_dir_all(target).context(format!("Failed to create {}", target.display()))?;
+
+    let mut names = BTreeSet::new();
+    for entry in fs::read_dir(source).context(format!("Failed to read {}", source.display()))?.flatten() {
+        let source_path = entry.path();
+        let target_path = target.join(entry.file_name());
+        names.insert(entry.file_name());
+
+        let metadata = entry.metadata().context(format!("Failed to read {}", source_path.display()))?;
+        if metadata.is_dir() {
+            sync_dir_into(&source_path, &target_path, hardlink, stats)?;
+            continue;
+        }
+        if is_unchanged(&metadata, &target_path) {
+            stats.unchanged += 1;
+            continue;
+        }
+
+        remove_path(&target_path)?;
+        // A hard link shares the source's size and modified time, and fs::copy keeps
+        // the modified time, so either way the file compares unchanged next time
+        let linked = hardlink && fs::hard_link(&source_path, &target_path).is_ok();
+        if !linked {
+            fs::copy(&source_path, &target_path)
+                .context(format!("Failed to copy {} to {}", source_path.display(), target_path.display()))?;
+        }
+        stats.copied += 1;
+    }
+
+    for entry in fs::read_dir(target).context(format!("Failed to read {}", target.display()))?.flatten() {
+        if !names.contains(&entry.file_name()) {
+            remove_path(&entry.path())?;
+            stats.removed += 1;
+        }
+    }
+    Ok(())
+}
+
+fn is_unchanged(source: &fs::Metadata, target: &Path) -> bool {
+    fs::metadata(target).is_ok_and(|target| {
+        target.is_file()
+            && target.len() == source.len()
+            && target.modified().ok().is_some_and(|modified| source.modified().ok() == Some(modified))
+    })
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    let result = if metadata.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+    result.context(format!("Failed to remove {}", path.display()))
+}
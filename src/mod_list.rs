@@ -0,0 +1,303 @@
+use anyhow::{Context, Result, anyhow};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table, Value, value};
+
+use crate::cli::ModsCommand;
+use crate::config::Config;
+use crate::config::mod_entry::{ModEntry, ModSide};
+use crate::public_info::{get_client_mods, to_launcher_json};
+use crate::ui::json::print_output;
+use crate::ui::status::{println_step, println_success};
+use crate::workshop_details;
+
+const MOD_LIST_KEY: &str = "server_mod_list";
+const SIDES_KEY: &str = "sides";
+const DISABLED_GROUPS_KEY: &str = "disabled_groups";
+
+/// A Workshop ID, on its own or in an item's URL
+fn parse_workshop_id(reference: &str) -> Result<u64> {
+    let reference = reference.trim();
+    let id = match reference.find("?id=") {
+        Some(start) => reference[start + 4..].split('&').next().unwrap_or_default(),
+        None => reference,
+    };
+    id.parse().context(format!("'{reference}' is not a Workshop ID or item URL"))
+}
+
+const fn parse_side(side: &str) -> Option<ModSide> {
+    match side.as_bytes() {
+        b"server" => Some(ModSide::Server),
+        b"client" => Some(ModSide::Client),
+        b"both" => Some(ModSide::Both),
+        _ => None,
+    }
+}
+
+const fn side_name(side: ModSide) -> &'static str {
+    match side {
+        ModSide::Server => "server",
+        ModSide::Client => "client",
+        ModSide::Both => "both",
+    }
+}
+
+/// The `[mods]` table edits go to: the profile's when it has its own mod list, as a profile's
+/// list replaces the shared one rather than adding to it, otherwise the shared one
+fn get_mods_table<'a>(document: &'a mut DocumentMut, profile: Option<&str>) -> Result<&'a mut Table> {
+    let has_own_list = profile.is_some_and(|profile| {
+        document.get("profiles")
+            .and_then(|profiles| profiles.get(profile))
+            .and_then(|profile| profile.get("mods"))
+            .is_some_and(|mods| mods.get(MOD_LIST_KEY).is_some())
+    });
+    let path = match profile {
+        Some(profile) if has_own_list => vec!["profiles", profile, "mods"],
+        _ => vec!["mods"],
+    };
+
+    let mut table = document.as_table_mut();
+    for key in &path {
+        table = table
+            .entry(key)
+            .or_insert(Item::Table(Table::new()))
+            .as_table_mut()
+            .context(format!("[{}] in config.toml is not a table", path.join(".")))?;
+    }
+    Ok(table)
+}
+
+/// `server_mod_list` as either an array of inline tables or `[[mods.server_mod_list]]`,
+/// each entry as a table-like view
+fn for_each_entry(mods: &mut Table, mut visit: impl FnMut(&mut dyn toml_edit::TableLike) -> bool) -> Option<usize> {
+    match mods.get_mut(MOD_LIST_KEY)? {
+        Item::Value(Value::Array(array)) => array.iter_mut()
+            .position(|entry| entry.as_inline_table_mut().is_some_and(|entry| visit(entry))),
+        Item::ArrayOfTables(tables) => tables.iter_mut().position(|entry| visit(entry)),
+        _ => None,
+    }
+}
+
+/// Whether an entry is the mod `reference` names, by name (with or without the `@`) or Workshop ID
+fn is_entry_named(entry: &dyn toml_edit::TableLike, reference: &str) -> bool {
+    let reference = reference.trim().trim_start_matches('@');
+    let id = entry.get("id").and_then(Item::as_integer);
+    let name = entry.get("name").and_then(Item::as_str);
+    id.is_some_and(|id| reference == id.to_string()) || name.is_some_and(|name| reference.eq_ignore_ascii_case(name))
+}
+
+fn add(reference: &str, name: Option<&str>, side: Option<&str>, config: &Config, profile: Option<&str>) -> Result<()> {
+    let id = parse_workshop_id(reference)?;
+    let side = side.map(|side| parse_side(side).ok_or_else(|| anyhow!("Unknown side '{side}'"))).transpose()?;
+
+    if let Some(existing) = config.mods.server_mod_list.iter().flatten().find(|mod_entry| mod_entry.id == id) {
+        return Err(anyhow!("{} ({id}) is already in server_mod_list", existing.name));
+    }
+
+    let name = if let Some(name) = name {
+        name.to_string()
+    } else {
+        println_step(&format!("Looking up Workshop item {id}..."), 0);
+        workshop_details::fetch(&[id])?
+            .remove(&id)
+            .map(|details| details.title)
+            .ok_or_else(|| anyhow!("Workshop item {id} not found or not public, pass --name to add it anyway"))?
+    };
+    let id_value = i64::try_from(id).context("Workshop ID is too large")?;
+
+    Config::edit(|document| {
+        let mods = get_mods_table(document, profile)?;
+
+        let mut entry = InlineTable::new();
+        entry.insert("id", id_value.into());
+        entry.insert("name", name.as_str().into());
+        if let Some(side) = side {
+            entry.insert("side", side_name(side).into());
+        }
+
+        match mods.entry(MOD_LIST_KEY).or_insert(Item::Value(Value::Array(Array::new()))) {
+            Item::Value(Value::Array(array)) => {
+                // Follow the list's layout, one entry per line unless it's all on one
+                let multiline = array.is_empty() || array.iter().any(|entry| {
+                    entry.decor().prefix().and_then(|prefix| prefix.as_str()).is_some_and(|prefix| prefix.contains('\n'))
+                });
+                let mut entry = Value::InlineTable(entry);
+                if multiline {
+                    entry.decor_mut().set_prefix("\n    ");
+                    array.set_trailing("\n");
+                    array.set_trailing_comma(true);
+                } else if !array.is_empty() {
+                    entry.decor_mut().set_prefix(" ");
+                }
+                array.push_formatted(entry);
+            }
+            Item::ArrayOfTables(tables) => {
+                tables.push(entry.into_table());
+            }
+            _ => return Err(anyhow!("{MOD_LIST_KEY} in config.toml is not a list")),
+        }
+        Ok(())
+    })?;
+
+    println_success(&format!("Added {name} ({id}) to {MOD_LIST_KEY}"), 0);
+    println_step("Installed at the next update", 1);
+    Ok(())
+}
+
+fn remove(reference: &str, profile: Option<&str>) -> Result<()> {
+    let mut removed = None;
+
+    Config::edit(|document| {
+        let mods = get_mods_table(document, profile)?;
+        let index = for_each_entry(mods, |entry| is_entry_named(entry, reference))
+            .ok_or_else(|| anyhow!("No mod named '{reference}' in {MOD_LIST_KEY}"))?;
+
+        removed = match mods.get_mut(MOD_LIST_KEY) {
+            Some(Item::Value(Value::Array(array))) => {
+                let entry = array.remove(index);
+                entry.as_inline_table().and_then(|entry| entry.get("name")).and_then(Value::as_str).map(str::to_string)
+            }
+            Some(Item::ArrayOfTables(tables)) => {
+                let name = tables.get(index).and_then(|entry| entry.get("name")).and_then(Item::as_str).map(str::to_string);
+                tables.remove(index);
+                name
+            }
+            _ => None,
+        };
+        Ok(())
+    })?;
+
+    println_success(&format!("Removed {} from {MOD_LIST_KEY}", removed.as_deref().unwrap_or(reference)), 0);
+    println_step("Its files are cleaned up at the next update", 1);
+    Ok(())
+}
+
+/// Set where a mod loads: on its `server_mod_list` entry when it has one, otherwise in
+/// `[mods.sides]`, which is how collection mods get a side
+fn set_side(reference: &str, side: &str, profile: Option<&str>) -> Result<()> {
+    let side = parse_side(side).ok_or_else(|| anyhow!("Unknown side '{side}'"))?;
+    let mut in_list = false;
+
+    Config::edit(|document| {
+        let mods = get_mods_table(document, profile)?;
+
+        in_list = for_each_entry(mods, |entry| {
+            if !is_entry_named(entry, reference) {
+                return false;
+            }
+            entry.insert("side", value(side_name(side)));
+            true
+        }).is_some();
+
+        if !in_list {
+            let sides = mods.entry(SIDES_KEY)
+                .or_insert(Item::Table(Table::new()))
+                .as_table_like_mut()
+                .context(format!("{SIDES_KEY} in config.toml is not a table"))?;
+            sides.insert(reference.trim(), value(side_name(side)));
+        }
+        Ok(())
+    })?;
+
+    let location = if in_list { MOD_LIST_KEY } else { "[mods.sides], for collection mods" };
+    println_success(&format!("{reference} now loads as a {} mod ({location})", side_name(side)), 0);
+    println_step("Takes effect at the next server start", 1);
+    Ok(())
+}
+
+/// Replace a list of names under `[mods]`, such as `disabled`, removing it when empty
+pub fn set_mods_list(key: &str, values: &[String], profile: Option<&str>) -> Result<Config> {
+    Config::edit(|document| {
+        let mods = get_mods_table(document, profile)?;
+        if values.is_empty() {
+            mods.remove(key);
+        } else {
+            mods.insert(key, value(values.iter().map(String::as_str).collect::<Array>()));
+        }
+        Ok(())
+    })
+}
+
+/// Add a group to `disabled_groups`, or take it out, telling how many configured mods it covers
+fn set_group_disabled(config: &Config, group: &str, disabled: bool, profile: Option<&str>) -> Result<()> {
+    let mut groups: Vec<String> = config.mods.disabled_groups.iter()
+        .filter(|disabled_group| !disabled_group.eq_ignore_ascii_case(group))
+        .cloned()
+        .collect();
+    if disabled {
+        groups.push(group.to_string());
+    }
+    let config = set_mods_list(DISABLED_GROUPS_KEY, &groups, profile)?;
+
+    let listed = config.mods.server_mod_list.iter().flatten()
+        .filter(|mod_entry| mod_entry.group.as_deref().is_some_and(|mod_group| mod_group.eq_ignore_ascii_case(group)))
+        .count();
+    let tagged = config.mods.groups.values().filter(|mod_group| mod_group.eq_ignore_ascii_case(group)).count();
+    if listed + tagged == 0 {
+        println_step(&format!("No mod is in group '{group}' yet, set `group` on a mod or add it to [mods.groups]"), 1);
+    }
+
+    let action = if disabled { "disabled" } else { "enabled" };
+    println_success(&format!("Group '{group}' {action} ({} mod(s) in config.toml)", listed + tagged), 0);
+    println_step("Takes effect at the next update and start", 1);
+    Ok(())
+}
+
+/// `-mod=@CF;@Expansion`, what players launching the game directly pass
+fn to_launch_parameter(config: &Config, mods: &[ModEntry]) -> String {
+    let folders: Vec<String> = mods.iter().map(|mod_entry| config.mods.get_folder(mod_entry)).collect();
+    format!("-mod={}", folders.join(";"))
+}
+
+/// A numbered list of Workshop links in load order, with the published collection when there is one
+fn to_markdown(config: &Config, mods: &[ModEntry]) -> String {
+    let mut markdown = format!("**Required mods ({})**\n", mods.len());
+    for (i, mod_entry) in mods.iter().enumerate() {
+        let _ = write!(markdown, "\n{}. [{}]({})", i + 1, mod_entry.name, mod_entry.workshop_url());
+    }
+    if let Some(url) = &config.mods.published_collection_url {
+        let _ = write!(markdown, "\n\nSubscribe to all of them at once: {url}");
+    }
+    markdown
+}
+
+fn export(config: &Config, server_install_dir: &Path, format: Option<&str>, output: Option<&PathBuf>) -> Result<()> {
+    let mods = get_client_mods(config, server_install_dir)?;
+    if mods.is_empty() {
+        return Err(anyhow!("No client mods are installed, run an update first"));
+    }
+
+    let text = match format {
+        Some("launch") => to_launch_parameter(config, &mods),
+        Some("json") => to_launcher_json(&mods)?,
+        Some("markdown") => to_markdown(config, &mods),
+        _ => format!(
+            "Launch parameter:\n{}\n\nDZSA Launcher JSON:\n{}\n\nMarkdown:\n{}",
+            to_launch_parameter(config, &mods),
+            to_launcher_json(&mods)?,
+            to_markdown(config, &mods)
+        ),
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(path, format!("{text}\n")).context(format!("Failed to write {}", path.display()))?;
+            println_success(&format!("{} mod(s) exported to {}", mods.len(), path.display()), 0);
+        }
+        None => print_output(&text),
+    }
+    Ok(())
+}
+
+/// Entry point for `dzsm mods ...`
+pub fn run(command: &ModsCommand, config: &Config, server_install_dir: &str, profile: Option<&str>) -> Result<()> {
+    match command {
+        ModsCommand::DisableGroup { group } => set_group_disabled(config, group, true, profile),
+        ModsCommand::EnableGroup { group } => set_group_disabled(config, group, false, profile),
+        ModsCommand::Export { format, output } => export(config, Path::new(server_install_dir), format.as_deref(), output.as_ref()),
+        ModsCommand::Add { reference, name, side } => add(reference, name.as_deref(), side.as_deref(), config, profile),
+        ModsCommand::Remove { reference } => remove(reference, profile),
+        ModsCommand::Side { reference, side } => set_side(reference, side, profile),
+    }
+}
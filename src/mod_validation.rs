@@ -0,0 +1,270 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::UNIX_EPOCH;
+use windows_sys::Win32::System::Threading::{GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_LOWEST};
+
+use crate::collection_sync::get_installed_workshop_mods;
+use crate::config::ValidationConfig;
+use crate::state::state_dir;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const STATE_FILE: &str = "mod_validation.json";
+const READ_CHUNK_SIZE: usize = 1024 * 1024;
+pub const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// What the nightly checks have seen so far
+#[derive(Default, Serialize, Deserialize)]
+struct ValidationState {
+    /// The night the last check ran, so a restarted supervisor doesn't run it twice
+    last_night: Option<NaiveDate>,
+    #[serde(default)]
+    mods: BTreeMap<u64, ModRecord>,
+    /// Mods found damaged, validated by `SteamCMD` on the next update
+    #[serde(default)]
+    pending: BTreeSet<u64>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ModRecord {
+    checked: Option<DateTime<Local>>,
+    /// By path relative to the mod directory
+    files: BTreeMap<String, FileRecord>,
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FileRecord {
+    size: u64,
+    /// Seconds since the epoch; `SteamCMD` sets it on every file it writes
+    modified: u64,
+    hash: u64,
+}
+
+impl ValidationState {
+    fn load(server_install_dir: &Path) -> Self {
+        fs::read_to_string(get_state_path(server_install_dir))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, server_install_dir: &Path) -> Result<()> {
+        let path = get_state_path(server_install_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Checks a few mods each night during the off-peak window, on a low priority thread,
+/// so every mod is looked at once per cycle without a full validate during a restart
+pub struct NightlyValidator {
+    from: NaiveTime,
+    to: NaiveTime,
+    cycle_days: u64,
+    server_install_dir: PathBuf,
+    last_night: Cell<Option<NaiveDate>>,
+    check: RefCell<Option<JoinHandle<()>>>,
+}
+
+impl NightlyValidator {
+    /// None when `[validation]` is disabled
+    pub fn from_config(config: &ValidationConfig, server_install_dir: &Path) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            from: parse_time(&config.from)?,
+            to: parse_time(&config.to)?,
+            cycle_days: config.cycle_days.max(1),
+            server_install_dir: server_install_dir.to_path_buf(),
+            last_night: Cell::new(ValidationState::load(server_install_dir).last_night),
+            check: RefCell::new(None),
+        }))
+    }
+
+    /// Start tonight's check if it's due, called regularly while the server runs
+    pub fn poll(&self, now: DateTime<Local>) {
+        let Some(night) = self.get_night(now) else {
+            return;
+        };
+        if self.last_night.get() == Some(night) {
+            return;
+        }
+
+        let mut check = self.check.borrow_mut();
+        if check.as_ref().is_some_and(|check| !check.is_finished()) {
+            return;
+        }
+
+        self.last_night.set(Some(night));
+        let server_install_dir = self.server_install_dir.clone();
+        let cycle_days = self.cycle_days;
+        *check = Some(thread::spawn(move || {
+            // Hashing gigabytes of mods shouldn't take CPU time from the server
+            unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_LOWEST) };
+            if let Err(e) = check_mods(&server_install_dir, night, cycle_days) {
+                println_failure(&format!("Nightly mod check failed: {e:#}"), 0);
+            }
+        }));
+    }
+
+    /// The night `now` belongs to if it's inside the window, which is the day the window opened
+    fn get_night(&self, now: DateTime<Local>) -> Option<NaiveDate> {
+        let time = now.time();
+        let today = now.date_naive();
+        if self.from <= self.to {
+            (self.from <= time && time < self.to).then_some(today)
+        } else if time >= self.from {
+            Some(today)
+        } else if time < self.to {
+            today.pred_opt()
+        } else {
+            None
+        }
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map_err(|_| anyhow!("Invalid time '{time}' in [validation], expected HH:MM"))
+}
+
+fn get_state_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(STATE_FILE)
+}
+
+/// Check tonight's share of the installed mods, the ones checked longest ago first
+fn check_mods(server_install_dir: &Path, night: NaiveDate, cycle_days: u64) -> Result<()> {
+    let mut state = ValidationState::load(server_install_dir);
+    state.last_night = Some(night);
+    state.save(server_install_dir)?;
+
+    let mut mods = get_installed_workshop_mods(server_install_dir)?;
+    if mods.is_empty() {
+        return Ok(());
+    }
+    mods.sort_by_key(|mod_entry| state.mods.get(&mod_entry.id).and_then(|record| record.checked));
+    let per_night = mods.len().div_ceil(usize::try_from(cycle_days).unwrap_or(usize::MAX));
+    mods.truncate(per_night);
+
+    println_step(&format!("Checking {} mod(s) for damaged files...", mods.len()), 0);
+    let mut damaged = Vec::new();
+    for mod_entry in &mods {
+        let files = hash_mod(&server_install_dir.join(format!("@{}", mod_entry.name)))?;
+
+        // Saved after each mod, a restart mid-check keeps what was done
+        let mut state = ValidationState::load(server_install_dir);
+        let record = state.mods.entry(mod_entry.id).or_default();
+        let changed = find_damaged(&record.files, &files);
+        if !changed.is_empty() {
+            println_failure(&format!("{} ({}) has damaged files: {}", mod_entry.name, mod_entry.id, changed.join(", ")), 1);
+            state.pending.insert(mod_entry.id);
+            damaged.push(mod_entry);
+        }
+        record.files = files;
+        record.checked = Some(Local::now());
+        state.save(server_install_dir)?;
+    }
+
+    if damaged.is_empty() {
+        println_success(&format!("{} mod(s) checked, no damaged files", mods.len()), 0);
+    } else {
+        let names: Vec<&str> = damaged.iter().map(|mod_entry| mod_entry.name.as_str()).collect();
+        println_failure(&format!("Damaged mod(s) will be validated at the next restart: {}", names.join(", ")), 0);
+    }
+    Ok(())
+}
+
+/// Files whose content changed although `SteamCMD` didn't touch them, or that went missing
+fn find_damaged(before: &BTreeMap<String, FileRecord>, after: &BTreeMap<String, FileRecord>) -> Vec<String> {
+    // A mod updated since the last check has new timestamps, there's nothing to compare against
+    let updated = after.iter().any(|(path, file)| {
+        before.get(path).is_none_or(|old| old.modified != file.modified)
+    });
+
+    before.iter()
+        .filter(|(path, old)| match after.get(*path) {
+            Some(file) => file.modified == old.modified && *file != **old,
+            None => !updated,
+        })
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+fn hash_mod(mod_dir: &Path) -> Result<BTreeMap<String, FileRecord>> {
+    let mut files = BTreeMap::new();
+    let mut dirs = vec![mod_dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir)
+            .context(format!("Failed to read {}", dir.display()))?;
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            let metadata = fs::metadata(&path)
+                .context(format!("Failed to read {}", path.display()))?;
+            let modified = metadata.modified().ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs());
+            let relative = path.strip_prefix(mod_dir).unwrap_or(&path).to_string_lossy().to_string();
+            files.insert(relative, FileRecord { size: metadata.len(), modified, hash: hash_file(&path)? });
+        }
+    }
+
+    Ok(files)
+}
+
+/// 64-bit FNV-1a of the file, read in chunks to keep memory flat on multi-gigabyte PBOs
+pub fn hash_file(path: &Path) -> Result<u64> {
+    let mut file = File::open(path)
+        .context(format!("Failed to open {}", path.display()))?;
+    let mut buffer = vec![0; READ_CHUNK_SIZE];
+    let mut hash = FNV_OFFSET;
+
+    loop {
+        let read = file.read(&mut buffer)
+            .context(format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            return Ok(hash);
+        }
+        hash = fnv1a(hash, &buffer[..read]);
+    }
+}
+
+/// Continue a 64-bit FNV-1a hash over more bytes, starting from `FNV_OFFSET`
+pub fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash = (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Mods a nightly check found damaged, to be validated on the next update
+pub fn get_pending(server_install_dir: &Path) -> BTreeSet<u64> {
+    ValidationState::load(server_install_dir).pending
+}
+
+/// Forget a mod's damage and recorded files once `SteamCMD` has validated it
+pub fn clear_pending(server_install_dir: &Path, workshop_id: u64) -> Result<()> {
+    let mut state = ValidationState::load(server_install_dir);
+    if state.pending.remove(&workshop_id) {
+        state.mods.remove(&workshop_id);
+        state.save(server_install_dir)?;
+    }
+    Ok(())
+}
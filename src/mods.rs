@@ -0,0 +1,869 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::ModsConfig;
+use crate::config::mod_entry::ModEntry;
+use crate::config::mods_config::{InstallStrategy, KeyStrategy};
+use crate::mod_links::{self, InstalledLinks, is_link};
+use crate::mod_validation::hash_file;
+use crate::server::{DRY_RUN, SERVER_CONFIG, SERVER_KEYS, VANILLA_KEY};
+use crate::server_cfg::ServerDzConfig;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+/// A mod as it'll be installed: where `SteamCMD` keeps it and the `@` folder it gets
+pub struct PlannedMod {
+    pub entry: ModEntry,
+    pub source: PathBuf,
+    pub folder: String,
+    /// Loaded with `-serverMod`, so players never need it and its signatures aren't checked
+    pub server_only: bool,
+    /// One of `mods.local_mods`, always linked so changes to it show up straight away
+    pub local: bool,
+}
+
+/// Which mods go into the server install and how, worked out before anything changes on disk.
+/// `clean` removes what the last install left, `apply` puts each mod in place once it's
+/// downloaded, and `verify` checks it's usable. Downloading is left to the caller.
+pub struct InstallPlan {
+    server_install_dir: PathBuf,
+    strategy: InstallStrategy,
+    key_strategy: KeyStrategy,
+    force_clean: bool,
+    dry_run: bool,
+    incomplete: bool,
+    pub mods: Vec<PlannedMod>,
+    /// `mods.local_mods`, installed from where they are without `SteamCMD`
+    pub local_mods: Vec<PlannedMod>,
+}
+
+impl InstallPlan {
+    /// Plan `mods`, each with the directory `SteamCMD` downloads it to, and the local mods
+    pub fn new(config: &ModsConfig, server_install_dir: &Path, mods: Vec<(ModEntry, PathBuf)>) -> Self {
+        let plan = |mods: Vec<(ModEntry, PathBuf)>, local: bool| -> Vec<PlannedMod> {
+            mods.into_iter()
+                .map(|(entry, source)| PlannedMod {
+                    folder: config.get_folder(&entry),
+                    server_only: config.is_server_only(&entry),
+                    entry,
+                    source,
+                    local,
+                })
+                .collect()
+        };
+        Self {
+            server_install_dir: server_install_dir.to_path_buf(),
+            strategy: config.install_strategy,
+            key_strategy: config.get_key_strategy(),
+            force_clean: false,
+            dry_run: false,
+            incomplete: false,
+            mods: plan(mods, false),
+            local_mods: plan(config.get_local_mods(), true),
+        }
+    }
+
+    /// Also remove `@` folders and keys DZSM didn't install
+    pub const fn force_clean(mut self, force_clean: bool) -> Self {
+        self.force_clean = force_clean;
+        self
+    }
+
+    /// Only report what would change
+    pub const fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Some configured mods couldn't be planned, e.g. a collection failed to load, so mods
+    /// that aren't planned may still be configured and nothing is removed as stale
+    pub const fn incomplete(mut self, incomplete: bool) -> Self {
+        self.incomplete = incomplete;
+        self
+    }
+
+    /// Clean up all previous mod installations before installing new ones
+    pub fn clean(&self) {
+        println_step("Cleaning up previous mod installations...", 1);
+
+        let mut links = InstalledLinks::load(&self.server_install_dir);
+
+        // Remove the @* directories DZSM linked
+        self.cleanup_mod_directories(&mut links);
+
+        // Clear the keys DZSM linked
+        self.cleanup_keys_directory(&mut links);
+
+        if self.dry_run {
+            return;
+        }
+        if let Err(e) = links.save(&self.server_install_dir) {
+            println_failure(&format!("Failed to record the removed links: {e:#}"), 2);
+        }
+        println_success("Previous mod installations cleaned up", 2);
+    }
+
+    /// Remove the @* directories DZSM linked from the server install directory,
+    /// or every one of them with `--force-clean`. Copies of planned mods are kept,
+    /// applying updates them in place.
+    fn cleanup_mod_directories(&self, links: &mut InstalledLinks) {
+        let kept_copies: BTreeSet<&str> = if self.strategy.is_copy() {
+            self.mods.iter()
+                .map(|planned| planned.folder.as_str())
+                .filter(|folder| links.copies.contains_key(*folder))
+                .collect()
+        } else {
+            BTreeSet::new()
+        };
+
+        for path in fs::read_dir(&self.server_install_dir).into_iter().flatten().flatten().map(|entry| entry.path()) {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if !name.starts_with('@') {
+                continue;
+            }
+
+            if !self.force_clean && !links.mods.contains(name) {
+                println_step(&format!("Keeping {name}, DZSM didn't install it (--force-clean removes it)"), 2);
+                continue;
+            }
+            if kept_copies.contains(name) {
+                println_step(&format!("Keeping copy {name}, only its changed files are updated"), 2);
+                continue;
+            }
+            if self.dry_run {
+                // Installed mods are links into SteamCMD's workshop folder; anything else is lost for good
+                let note = if is_link(&path) { "" } else { ", not a link: its files would be deleted" };
+                println_step(&format!("{DRY_RUN} Would remove: {name}{note}"), 2);
+                continue;
+            }
+            println_step(&format!("Removing: {name}"), 2);
+            if fs::remove_dir_all(&path).is_ok() {
+                links.mods.remove(name);
+                links.copies.remove(name);
+            }
+        }
+    }
+
+    /// Remove the keys DZSM installed that no planned mod ships any more, or everything
+    /// but dayz.bikey with `--force-clean`. Keys from before their mods were recorded are
+    /// removed too, installing puts them back with their owners.
+    fn cleanup_keys_directory(&self, links: &mut InstalledLinks) {
+        let keys_dir = self.server_install_dir.join(SERVER_KEYS);
+        if !keys_dir.exists() {
+            return;
+        }
+
+        let planned_ids: BTreeSet<u64> = self.mods.iter().map(|planned| planned.entry.id).collect();
+        let is_needed = |links: &InstalledLinks, filename: &str| {
+            links.key_owners.get(filename).is_some_and(|owners| !owners.is_disjoint(&planned_ids))
+        };
+
+        println_step("Removing orphaned mod keys (keeping dayz.bikey)...", 2);
+        for path in fs::read_dir(&keys_dir).into_iter().flatten().flatten().map(|entry| entry.path()) {
+            let Some(filename) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+
+            // Skip dayz.bikey (case insensitive), keys placed by hand, and keys a planned mod still ships
+            if filename.eq_ignore_ascii_case(VANILLA_KEY) {
+                continue;
+            }
+            if !self.force_clean && !links.keys.contains(filename) {
+                println_step(&format!("Keeping key {filename}, DZSM didn't install it"), 3);
+                continue;
+            }
+            if !self.force_clean && is_needed(links, filename) {
+                continue;
+            }
+            if self.dry_run {
+                println_step(&format!("{DRY_RUN} Would remove key: {filename}"), 3);
+            } else if fs::remove_file(&path).is_ok() {
+                println_step(&format!("Removed key: {filename}"), 3);
+                links.keys.remove(filename);
+                links.key_owners.remove(filename);
+            }
+        }
+
+        if !self.dry_run {
+            for owners in links.key_owners.values_mut() {
+                owners.retain(|id| planned_ids.contains(id));
+            }
+            links.key_owners.retain(|_, owners| !owners.is_empty());
+        }
+    }
+
+    /// Link or copy a downloaded mod and its keys into the server install dir,
+    /// as `mods.install_strategy` and `mods.key_strategy` say
+    pub fn apply(&self, planned: &PlannedMod) -> Result<()> {
+        let target = self.server_install_dir.join(&planned.folder);
+
+        if self.dry_run {
+            self.print_apply(planned, &target);
+            return Ok(());
+        }
+
+        let mut links = InstalledLinks::load(&self.server_install_dir);
+        let strategy = self.get_strategy(planned);
+        let is_kept_copy = strategy.is_copy() && links.copies.contains_key(&planned.folder);
+
+        // Cleanup leaves folders DZSM didn't create, a local mod by the same name would be replaced
+        if !is_kept_copy && fs::symlink_metadata(&target).is_ok() {
+            return Err(anyhow!(
+                "{} already exists and wasn't installed by DZSM, rename it or run with --force-clean to replace it",
+                target.display()
+            ));
+        }
+
+        // Recorded straight away, so a failure below doesn't leave a folder cleanup won't remove
+        links.mods.insert(planned.folder.clone());
+        if strategy.is_copy() {
+            links.copies.insert(planned.folder.clone(), planned.entry.id);
+            links.save(&self.server_install_dir)?;
+
+            println_step(&format!("Updating the {} of the mod...", strategy.as_str()), 5);
+            let stats = mod_links::sync_dir(&planned.source, &target, strategy == InstallStrategy::Hardlink)?;
+            println_step(&format!("{} file(s) copied, {} removed, {} unchanged", stats.copied, stats.removed, stats.unchanged), 6);
+        } else {
+            mod_links::link_dir(strategy, &planned.source, &target)?;
+            links.save(&self.server_install_dir)?;
+        }
+
+        self.apply_keys(planned, &mut links)
+    }
+
+    /// `mods.install_strategy`, but a junction for a local mod, as a copy of one would go stale
+    /// as soon as it's edited
+    fn get_strategy(&self, planned: &PlannedMod) -> InstallStrategy {
+        if planned.local && self.strategy.is_copy() {
+            InstallStrategy::Junction
+        } else {
+            self.strategy
+        }
+    }
+
+    /// Link or copy a mod's `.bikey` files into the server keys directory, recording the mod as
+    /// their owner, and remove keys DZSM installed for it that it no longer ships
+    fn apply_keys(&self, planned: &PlannedMod, links: &mut InstalledLinks) -> Result<()> {
+        let keys = get_mod_keys(&planned.source)?;
+        let server_keys_path = self.server_install_dir.join(SERVER_KEYS);
+        let key_names: BTreeSet<String> = keys.iter()
+            .filter_map(|key| key.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect();
+
+        // Keys a mod update renamed or dropped. Local mods have no ID to own keys by, theirs
+        // are removed by each cleanup and installed again.
+        let dropped: Vec<String> = links.key_owners.iter()
+            .filter(|_| !planned.local)
+            .filter(|(key_name, owners)| owners.contains(&planned.entry.id) && !key_names.contains(*key_name))
+            .map(|(key_name, _)| key_name.clone())
+            .collect();
+        for key_name in dropped {
+            let owners = links.key_owners.entry(key_name.clone()).or_default();
+            owners.remove(&planned.entry.id);
+            if owners.is_empty() {
+                links.key_owners.remove(&key_name);
+                if links.keys.remove(&key_name) {
+                    let path = server_keys_path.join(&key_name);
+                    fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+                    println_step(&format!("Removed key {key_name}, the mod no longer ships it"), 6);
+                }
+            }
+        }
+        for key_name in key_names.iter().filter(|_| !planned.local) {
+            links.key_owners.entry(key_name.clone()).or_default().insert(planned.entry.id);
+        }
+        links.save(&self.server_install_dir)?;
+
+        if keys.is_empty() {
+            println_step("No keys required for this mod (client-side or configuration mod)", 5);
+            return Ok(());
+        }
+
+        println_step("Installing mod keys...", 5);
+        for key_file_path in keys {
+            let Some(key_name) = key_file_path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let target_key_path = server_keys_path.join(&key_name);
+
+            // Keys placed by hand are left alone; DZSM's own are refreshed, a link may have broken
+            // or a copy gone stale since
+            if fs::symlink_metadata(&target_key_path).is_ok() && !links.keys.contains(&key_name) {
+                println_step(&format!("Key already exists, skipping: {key_name}"), 6);
+                continue;
+            }
+
+            if !mod_links::install_key(self.key_strategy, &key_file_path, &target_key_path)? {
+                println_step(&format!("Key up to date: {key_name}"), 6);
+                continue;
+            }
+
+            links.keys.insert(key_name.clone());
+            links.save(&self.server_install_dir)?;
+            println_step(&format!("Installed key: {key_name}"), 6);
+        }
+
+        Ok(())
+    }
+
+    /// The folder and keys `apply` would install for a mod; every key the mod ships is
+    /// listed, up to date or not
+    fn print_apply(&self, planned: &PlannedMod, target: &Path) {
+        println_step(&format!(
+            "{DRY_RUN} Would install {} from {} as a {}",
+            target.display(),
+            planned.source.display(),
+            self.get_strategy(planned).as_str()
+        ), 5);
+
+        if !planned.source.exists() {
+            println_step(&format!("{DRY_RUN} Its keys are installed once it's downloaded"), 5);
+            return;
+        }
+        for key_file_path in get_mod_keys(&planned.source).unwrap_or_default() {
+            if let Some(filename) = key_file_path.file_name() {
+                println_step(&format!("{DRY_RUN} Would {} key: {}", self.key_strategy.as_str(), filename.to_string_lossy()), 5);
+            }
+        }
+    }
+
+    /// Fix only what drifted since the last install, from the files `SteamCMD` already has:
+    /// folders and keys that are missing or point at nothing are put back, and DZSM's folders
+    /// and keys for mods no longer planned are removed. Nothing is downloaded, and copies that
+    /// are out of date are left to the next update. An `incomplete` plan removes nothing.
+    /// Returns how many entries were fixed.
+    pub fn repair(&self) -> Result<usize> {
+        let mut links = InstalledLinks::load(&self.server_install_dir);
+        let mut repaired = 0;
+
+        // Folders DZSM installed for mods that are no longer configured
+        let planned_folders: BTreeSet<&str> = self.mods.iter().chain(&self.local_mods).map(|planned| planned.folder.as_str()).collect();
+        let stale: Vec<String> = links.mods.iter()
+            .filter(|folder| !self.incomplete && !planned_folders.contains(folder.as_str()))
+            .cloned()
+            .collect();
+        if self.incomplete {
+            println_step("Not removing mods that aren't configured, the configured list is incomplete", 2);
+        }
+        for folder in stale {
+            let path = self.server_install_dir.join(&folder);
+            if fs::symlink_metadata(&path).is_ok() {
+                fs::remove_dir_all(&path).context(format!("Failed to remove {}", path.display()))?;
+                println_success(&format!("Removed {folder}, it's no longer configured"), 2);
+                repaired += 1;
+            }
+            links.mods.remove(&folder);
+            links.copies.remove(&folder);
+        }
+        links.save(&self.server_install_dir)?;
+
+        let keys_dir = self.server_install_dir.join(SERVER_KEYS);
+        let mut planned_keys = BTreeSet::new();
+        for planned in self.mods.iter().chain(&self.local_mods) {
+            if !planned.source.exists() {
+                println_failure(&format!("{} isn't downloaded, an update installs it", planned.entry.name), 2);
+                continue;
+            }
+            let keys = get_mod_keys(&planned.source)?;
+            planned_keys.extend(keys.iter().filter_map(|key| key.file_name()).map(|name| name.to_string_lossy().to_string()));
+
+            let target = self.server_install_dir.join(&planned.folder);
+            if fs::metadata(&target).is_err() {
+                // Left pointing at nothing; anything else in its place isn't DZSM's to replace
+                if fs::symlink_metadata(&target).is_ok() {
+                    if !links.mods.contains(&planned.folder) {
+                        println_failure(&format!("{} is broken but DZSM didn't install it, --force-clean replaces it", planned.folder), 2);
+                        continue;
+                    }
+                    fs::remove_dir_all(&target).context(format!("Failed to remove {}", target.display()))?;
+                }
+                self.apply(planned)?;
+                links = InstalledLinks::load(&self.server_install_dir);
+                println_success(&format!("Reinstalled {}", planned.folder), 2);
+                repaired += 1;
+                continue;
+            }
+
+            for key_path in keys {
+                let Some(key_name) = key_path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+                    continue;
+                };
+                let target_key = keys_dir.join(&key_name);
+                if fs::metadata(&target_key).is_ok() {
+                    continue;
+                }
+                if fs::symlink_metadata(&target_key).is_ok() && !links.keys.contains(&key_name) {
+                    println_failure(&format!("Key {key_name} is broken but DZSM didn't install it"), 2);
+                    continue;
+                }
+                mod_links::install_key(self.key_strategy, &key_path, &target_key)?;
+                links.keys.insert(key_name.clone());
+                if !planned.local {
+                    links.key_owners.entry(key_name.clone()).or_default().insert(planned.entry.id);
+                }
+                links.save(&self.server_install_dir)?;
+                println_success(&format!("Reinstalled key {key_name}"), 2);
+                repaired += 1;
+            }
+        }
+
+        // Keys DZSM installed that no planned mod ships, whose link now points at nothing
+        let stale_keys: Vec<String> = links.keys.iter()
+            .filter(|_| !self.incomplete)
+            .filter(|key_name| !planned_keys.contains(*key_name) && fs::metadata(keys_dir.join(key_name)).is_err())
+            .cloned()
+            .collect();
+        for key_name in stale_keys {
+            let path = keys_dir.join(&key_name);
+            if fs::symlink_metadata(&path).is_ok() {
+                fs::remove_file(&path).context(format!("Failed to remove {}", path.display()))?;
+                println_success(&format!("Removed key {key_name}, no configured mod ships it"), 2);
+                repaired += 1;
+            }
+            links.keys.remove(&key_name);
+            links.key_owners.remove(&key_name);
+        }
+        links.save(&self.server_install_dir)?;
+
+        Ok(repaired)
+    }
+
+    /// Compare the keys directory with the keys the planned mods ship. Mods that aren't
+    /// downloaded are checked against the keys recorded for them when they were installed.
+    pub fn check_keys(&self) -> Result<KeyCheck> {
+        let keys_dir = self.server_install_dir.join(SERVER_KEYS);
+        let links = InstalledLinks::load(&self.server_install_dir);
+        let planned_ids: BTreeSet<u64> = self.mods.iter().map(|planned| planned.entry.id).collect();
+        let mut check = KeyCheck::default();
+
+        // Key file name to each mod shipping it and the key's hash
+        let mut shipped: BTreeMap<String, Vec<(&str, u64)>> = BTreeMap::new();
+        for planned in self.mods.iter().filter(|planned| planned.source.exists()) {
+            for key_path in get_mod_keys(&planned.source)? {
+                let Some(key_name) = key_path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+                    continue;
+                };
+                if fs::metadata(keys_dir.join(&key_name)).is_err() {
+                    check.missing.push((planned.entry.name.clone(), key_name.clone()));
+                }
+                shipped.entry(key_name).or_default().push((planned.entry.name.as_str(), hash_file(&key_path)?));
+            }
+        }
+
+        for (key_name, shippers) in &shipped {
+            if shippers.iter().any(|(_, hash)| *hash != shippers[0].1) {
+                check.conflicts.push((key_name.clone(), shippers.iter().map(|(name, _)| (*name).to_string()).collect()));
+            }
+        }
+
+        check.orphaned = links.keys.iter()
+            .filter(|key_name| !shipped.contains_key(*key_name))
+            .filter(|key_name| links.key_owners.get(*key_name).is_none_or(|owners| owners.is_disjoint(&planned_ids)))
+            .cloned()
+            .collect();
+        Ok(check)
+    }
+
+    /// Check each PBO of the downloaded client mods is signed by a key in the keys directory,
+    /// as the anti-cheat kicks players loading one that isn't. Nothing is checked when
+    /// `verifySignatures = 0` in `serverDZ.cfg`.
+    pub fn check_signatures(&self) -> Result<SignatureCheck> {
+        let mut check = SignatureCheck::default();
+        let server_cfg = ServerDzConfig::load(&self.server_install_dir.join(SERVER_CONFIG));
+        if server_cfg.is_ok_and(|server_cfg| server_cfg.get("verifySignatures").is_some_and(|value| value.trim() == "0")) {
+            return Ok(check);
+        }
+
+        // A linked key whose workshop files are gone can't be read, so it trusts nothing
+        let trusted: BTreeSet<String> = list_files_with_extension(&self.server_install_dir.join(SERVER_KEYS), "bikey")?
+            .iter()
+            .filter_map(|key_path| read_authority(key_path).ok())
+            .collect();
+
+        for planned in self.mods.iter().filter(|planned| !planned.server_only && planned.source.exists()) {
+            let mut unsigned = Vec::new();
+            let mut untrusted = BTreeSet::new();
+            for (pbo, signatures) in get_mod_signatures(&planned.source)? {
+                if signatures.is_empty() {
+                    unsigned.push(pbo);
+                    continue;
+                }
+                let mut authorities = Vec::new();
+                for path in &signatures {
+                    match read_authority(path) {
+                        Ok(authority) => authorities.push(authority),
+                        Err(e) => check.unreadable.push((planned.entry.name.clone(), format!("{e:#}"))),
+                    }
+                }
+                // Signatures that can't be read can't tell whether the PBO is trusted
+                if !authorities.is_empty() && !authorities.iter().any(|authority| trusted.contains(authority)) {
+                    untrusted.extend(authorities);
+                }
+            }
+
+            if !unsigned.is_empty() {
+                check.unsigned.push((planned.entry.name.clone(), unsigned));
+            }
+            check.untrusted.extend(untrusted.into_iter().map(|authority| (planned.entry.name.clone(), authority)));
+        }
+        Ok(check)
+    }
+
+    /// Check an applied mod can be loaded: its folder resolves and has PBOs in `addons/`, and
+    /// each of its keys is in the keys directory, whether DZSM put it there or not
+    pub fn verify(&self, planned: &PlannedMod) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let mut problems: Vec<String> = self.find_content_problem(planned)?.into_iter().collect();
+        let server_keys_path = self.server_install_dir.join(SERVER_KEYS);
+        for key_file_path in get_mod_keys(&planned.source)? {
+            if let Some(key_name) = key_file_path.file_name()
+                && fs::metadata(server_keys_path.join(key_name)).is_err()
+            {
+                problems.push(format!("key {} is missing", key_name.to_string_lossy()));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Installed but unusable: {}", problems.join(", ")))
+        }
+    }
+
+    /// Whether an applied mod's folder resolves and has PBOs, so only keys can be missing
+    pub fn has_content(&self, planned: &PlannedMod) -> Result<bool> {
+        Ok(self.find_content_problem(planned)?.is_none())
+    }
+
+    fn find_content_problem(&self, planned: &PlannedMod) -> Result<Option<String>> {
+        let mod_path = self.server_install_dir.join(&planned.folder);
+        // Follows links, so a link to workshop files that are gone fails here
+        if fs::metadata(&mod_path).is_err() {
+            return Ok(Some(format!("{} doesn't resolve", planned.folder)));
+        }
+        // What a download cut off before any content leaves behind
+        if list_files_with_extension(&mod_path.join("addons"), "pbo")?.is_empty() {
+            return Ok(Some(format!("{} has no PBOs in addons/", planned.folder)));
+        }
+        Ok(None)
+    }
+
+    /// Install the keys of an applied mod that are missing from the keys directory again, e.g.
+    /// deleted by hand or linked to a file that's gone
+    pub fn reinstall_missing_keys(&self, planned: &PlannedMod) -> Result<()> {
+        let mut links = InstalledLinks::load(&self.server_install_dir);
+        let server_keys_path = self.server_install_dir.join(SERVER_KEYS);
+        for key_file_path in get_mod_keys(&planned.source)? {
+            let Some(key_name) = key_file_path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let target_key_path = server_keys_path.join(&key_name);
+            if fs::metadata(&target_key_path).is_ok() {
+                continue;
+            }
+
+            mod_links::install_key(self.key_strategy, &key_file_path, &target_key_path)?;
+            links.keys.insert(key_name.clone());
+            if !planned.local {
+                links.key_owners.entry(key_name.clone()).or_default().insert(planned.entry.id);
+            }
+            links.save(&self.server_install_dir)?;
+            println_step(&format!("Installed key: {key_name}"), 6);
+        }
+        Ok(())
+    }
+}
+
+/// Problems with the keys directory, see `InstallPlan::check_keys`
+#[derive(Default)]
+pub struct KeyCheck {
+    /// Mod name and a key it ships that isn't in the keys directory; players using it are kicked
+    pub missing: Vec<(String, String)>,
+    /// Key file name and the mods shipping different keys under it, of which only one can be installed
+    pub conflicts: Vec<(String, Vec<String>)>,
+    /// Keys DZSM installed that no planned mod ships
+    pub orphaned: Vec<String>,
+}
+
+impl KeyCheck {
+    pub const fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.conflicts.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// PBOs of a client mod that would get its players kicked, see `InstallPlan::check_signatures`
+#[derive(Default)]
+pub struct SignatureCheck {
+    /// Mod name and its PBOs without any `.bisign`
+    pub unsigned: Vec<(String, Vec<String>)>,
+    /// Mod name and an authority its PBOs are signed by, with no key of it in the keys directory
+    pub untrusted: Vec<(String, String)>,
+    /// Mod name and why one of its `.bisign` files couldn't be read
+    pub unreadable: Vec<(String, String)>,
+}
+
+impl SignatureCheck {
+    pub const fn is_empty(&self) -> bool {
+        self.unsigned.is_empty() && self.untrusted.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+/// The files in `dir` with an extension, none if there's no such directory
+fn list_files_with_extension(dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(dir)
+        .map_err(|e| anyhow!("Failed to read directory {}: {e}", dir.display()))?;
+    Ok(entries.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|found| found.eq_ignore_ascii_case(extension)))
+        .collect())
+}
+
+/// The `.bikey` files in a mod's `keys` directory
+fn get_mod_keys(mod_dir: &Path) -> Result<Vec<PathBuf>> {
+    list_files_with_extension(&mod_dir.join("keys"), "bikey")
+}
+
+/// Each PBO in a mod's `addons` directory with its signatures, named like `<pbo>.<authority>.bisign`
+fn get_mod_signatures(mod_dir: &Path) -> Result<Vec<(String, Vec<PathBuf>)>> {
+    let addons_dir = mod_dir.join("addons");
+    let signatures = list_files_with_extension(&addons_dir, "bisign")?;
+
+    let mut pbos: Vec<(String, Vec<PathBuf>)> = list_files_with_extension(&addons_dir, "pbo")?
+        .into_iter()
+        .filter_map(|path| path.file_name().map(|name| name.to_string_lossy().to_string()))
+        .map(|pbo| {
+            let prefix = format!("{}.", pbo.to_lowercase());
+            let signed_by = signatures.iter()
+                .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().to_lowercase().starts_with(&prefix)))
+                .cloned()
+                .collect();
+            (pbo, signed_by)
+        })
+        .collect();
+    pbos.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(pbos)
+}
+
+/// The authority a `.bikey` or `.bisign` starts with, which signatures are matched to keys by
+fn read_authority(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)
+        .context(format!("Failed to read {}", path.display()))?;
+    let end = bytes.iter()
+        .position(|byte| *byte == 0)
+        .filter(|end| *end > 0)
+        .ok_or_else(|| anyhow!("{} isn't a BattlEye key or signature", path.display()))?;
+    Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// A server install dir of its own for each test, removed afterwards
+    struct TempInstall(PathBuf);
+
+    impl TempInstall {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("dzsm-test-{name}-{}", process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(path.join(SERVER_KEYS)).unwrap();
+            Self(path)
+        }
+
+        fn add_dir(&self, name: &str) {
+            fs::create_dir_all(self.0.join(name)).unwrap();
+        }
+
+        fn add_key(&self, name: &str) {
+            fs::write(self.0.join(SERVER_KEYS).join(name), "key").unwrap();
+        }
+
+        fn has(&self, path: &str) -> bool {
+            self.0.join(path).exists()
+        }
+    }
+
+    impl Drop for TempInstall {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn mods_config(toml: &str) -> ModsConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn mod_entry(id: u64, name: &str) -> ModEntry {
+        ModEntry { id, name: name.to_string(), ..ModEntry::default() }
+    }
+
+    /// Record folders and keys as installed by DZSM, each key owned by a Workshop ID
+    fn record(install: &TempInstall, mods: &[&str], keys: &[(&str, u64)]) {
+        let links = InstalledLinks {
+            mods: mods.iter().map(|name| (*name).to_string()).collect(),
+            keys: keys.iter().map(|(name, _)| (*name).to_string()).collect(),
+            copies: BTreeMap::new(),
+            key_owners: keys.iter().map(|(name, id)| ((*name).to_string(), BTreeSet::from([*id]))).collect(),
+        };
+        links.save(&install.0).unwrap();
+    }
+
+    #[test]
+    fn plans_folders_and_sides() {
+        let install = TempInstall::new("plan");
+        let config = mods_config("
+            [[server_mod_list]]
+            id = 1
+            name = \"Server Tools\"
+
+            [folder_names]
+            \"Community Framework\" = \"CF\"
+        ");
+        let plan = InstallPlan::new(&config, &install.0, vec![
+            (mod_entry(1, "Server Tools"), install.0.join("source/1")),
+            (mod_entry(2, "Community Framework"), install.0.join("source/2")),
+        ]);
+
+        assert_eq!(plan.mods[0].folder, "@Server Tools");
+        assert!(plan.mods[0].server_only);
+        assert_eq!(plan.mods[1].folder, "@CF");
+        assert!(!plan.mods[1].server_only);
+        assert!(plan.local_mods.is_empty());
+    }
+
+    #[test]
+    fn clean_only_removes_what_dzsm_installed() {
+        let install = TempInstall::new("clean");
+        install.add_dir("@Installed");
+        install.add_dir("@ByHand");
+        install.add_key("installed.bikey");
+        install.add_key("byhand.bikey");
+        install.add_key(VANILLA_KEY);
+        record(&install, &["@Installed"], &[("installed.bikey", 1)]);
+
+        InstallPlan::new(&mods_config(""), &install.0, Vec::new()).clean();
+
+        assert!(!install.has("@Installed"));
+        assert!(install.has("@ByHand"));
+        assert!(!install.has("keys/installed.bikey"));
+        assert!(install.has("keys/byhand.bikey"));
+        assert!(install.has(&format!("keys/{VANILLA_KEY}")));
+        let links = InstalledLinks::load(&install.0);
+        assert!(links.mods.is_empty());
+        assert!(links.keys.is_empty());
+    }
+
+    #[test]
+    fn clean_keeps_keys_planned_mods_ship() {
+        let install = TempInstall::new("clean-keys");
+        install.add_key("kept.bikey");
+        install.add_key("dropped.bikey");
+        record(&install, &[], &[("kept.bikey", 1), ("dropped.bikey", 2)]);
+
+        let plan = InstallPlan::new(&mods_config(""), &install.0, vec![(mod_entry(1, "Kept"), install.0.join("source/1"))]);
+        plan.clean();
+
+        assert!(install.has("keys/kept.bikey"));
+        assert!(!install.has("keys/dropped.bikey"));
+        let links = InstalledLinks::load(&install.0);
+        assert_eq!(links.key_owners.keys().collect::<Vec<_>>(), ["kept.bikey"]);
+    }
+
+    #[test]
+    fn force_clean_removes_everything_but_the_vanilla_key() {
+        let install = TempInstall::new("force-clean");
+        install.add_dir("@Installed");
+        install.add_dir("@ByHand");
+        install.add_key("byhand.bikey");
+        install.add_key(VANILLA_KEY);
+        record(&install, &["@Installed"], &[]);
+
+        InstallPlan::new(&mods_config(""), &install.0, Vec::new()).force_clean(true).clean();
+
+        assert!(!install.has("@Installed"));
+        assert!(!install.has("@ByHand"));
+        assert!(!install.has("keys/byhand.bikey"));
+        assert!(install.has(&format!("keys/{VANILLA_KEY}")));
+    }
+
+    #[test]
+    fn dry_run_changes_nothing() {
+        let install = TempInstall::new("dry-run");
+        install.add_dir("@Installed");
+        install.add_dir("@ByHand");
+        install.add_key("installed.bikey");
+        record(&install, &["@Installed"], &[("installed.bikey", 1)]);
+
+        let plan = InstallPlan::new(&mods_config(""), &install.0, vec![(mod_entry(2, "New"), install.0.join("source/2"))])
+            .force_clean(true)
+            .dry_run();
+        plan.clean();
+        plan.apply(&plan.mods[0]).unwrap();
+        plan.verify(&plan.mods[0]).unwrap();
+
+        assert!(install.has("@Installed"));
+        assert!(install.has("@ByHand"));
+        assert!(install.has("keys/installed.bikey"));
+        assert!(!install.has("@New"));
+        assert_eq!(InstalledLinks::load(&install.0).mods, BTreeSet::from(["@Installed".to_string()]));
+    }
+
+    #[test]
+    fn repair_removes_stale_folders() {
+        let install = TempInstall::new("repair-stale");
+        install.add_dir("@Removed");
+        record(&install, &["@Removed"], &[]);
+
+        let repaired = InstallPlan::new(&mods_config(""), &install.0, Vec::new()).repair().unwrap();
+
+        assert_eq!(repaired, 1);
+        assert!(!install.has("@Removed"));
+        assert!(InstalledLinks::load(&install.0).mods.is_empty());
+    }
+
+    #[test]
+    fn incomplete_repair_keeps_stale_folders() {
+        let install = TempInstall::new("repair-incomplete");
+        install.add_dir("@FromCollection");
+        record(&install, &["@FromCollection"], &[]);
+
+        let repaired = InstallPlan::new(&mods_config(""), &install.0, Vec::new()).incomplete(true).repair().unwrap();
+
+        assert_eq!(repaired, 0);
+        assert!(install.has("@FromCollection"));
+        assert!(InstalledLinks::load(&install.0).mods.contains("@FromCollection"));
+    }
+
+    #[test]
+    fn reinstalls_only_missing_keys() {
+        let install = TempInstall::new("reinstall-keys");
+        install.add_dir("source/1/keys");
+        fs::write(install.0.join("source/1/keys/present.bikey"), "new").unwrap();
+        fs::write(install.0.join("source/1/keys/deleted.bikey"), "key").unwrap();
+        install.add_key("present.bikey");
+
+        let plan = InstallPlan::new(&mods_config("key_strategy = \"copy\""), &install.0, vec![
+            (mod_entry(1, "Keys"), install.0.join("source/1")),
+        ]);
+        plan.reinstall_missing_keys(&plan.mods[0]).unwrap();
+
+        assert_eq!(fs::read_to_string(install.0.join("keys/deleted.bikey")).unwrap(), "key");
+        assert_eq!(fs::read_to_string(install.0.join("keys/present.bikey")).unwrap(), "key");
+        let links = InstalledLinks::load(&install.0);
+        assert!(links.keys.contains("deleted.bikey"));
+        assert!(!links.keys.contains("present.bikey"));
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{Result, anyhow};
+
+use crate::config::{NotificationEvent, NotificationsConfig};
+use crate::http;
+
+// Discord rejects embed descriptions longer than this
+const DISCORD_DESCRIPTION_LIMIT: usize = 4096;
+const DISCORD_EMBED_COLOR: u32 = 0x004C_8C4A;
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+// Telegram rejects messages longer than this
+const TELEGRAM_TEXT_LIMIT: usize = 4096;
+
+/// A message to deliver to the configured notification channels
+pub struct Notification {
+    /// Decides which channels it goes to
+    pub event: NotificationEvent,
+    pub title: String,
+    /// Markdown body
+    pub body: String,
+}
+
+pub struct Notifier<'a> {
+    config: &'a NotificationsConfig,
+}
+
+impl<'a> Notifier<'a> {
+    pub const fn new(config: &'a NotificationsConfig) -> Self {
+        Self { config }
+    }
+
+    /// Whether any notification channel is configured
+    pub fn is_enabled(&self) -> bool {
+        self.discord_webhook_url().is_some() || self.telegram_bot().is_some()
+    }
+
+    /// Deliver a notification to every configured channel that subscribes to its event.
+    /// Every channel is tried even when one fails.
+    pub fn send(&self, notification: &Notification) -> Result<()> {
+        let mut errors = Vec::new();
+
+        if let Some(webhook_url) = self.discord_webhook_url()
+            && self.config.discord.accepts(notification.event)
+        {
+            let payload = serde_json::json!({
+                "username": "DZSM",
+                "embeds": [{
+                    "title": notification.title,
+                    "description": truncate(&notification.body, DISCORD_DESCRIPTION_LIMIT),
+                    "color": DISCORD_EMBED_COLOR,
+                }],
+            });
+            if let Err(e) = http::post_json(webhook_url, &payload.to_string()) {
+                errors.push(format!("Discord: {e}"));
+            }
+        }
+
+        if let Some((token, chat_id)) = self.telegram_bot()
+            && self.config.telegram.accepts(notification.event)
+        {
+            // Sent as plain text, Telegram's Markdown doesn't match Discord's
+            let payload = serde_json::json!({
+                "chat_id": chat_id,
+                "text": truncate(&format!("{}\n\n{}", notification.title, notification.body), TELEGRAM_TEXT_LIMIT),
+            });
+            if let Err(e) = http::post_json(&format!("{TELEGRAM_API_URL}/bot{token}/sendMessage"), &payload.to_string()) {
+                errors.push(format!("Telegram: {e}"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(errors.join("; ")))
+        }
+    }
+
+    fn discord_webhook_url(&self) -> Option<&str> {
+        self.config.discord_webhook_url.as_deref()
+            .filter(|url| !url.trim().is_empty())
+    }
+
+    /// Bot token and chat ID, when both are set
+    fn telegram_bot(&self) -> Option<(&str, &str)> {
+        let token = self.config.telegram_bot_token.as_deref().filter(|token| !token.trim().is_empty())?;
+        let chat_id = self.config.telegram_chat_id.as_deref().filter(|chat_id| !chat_id.trim().is_empty())?;
+        Some((token, chat_id))
+    }
+}
+
+/// Shorten text to at most `limit` characters, marking the cut with an ellipsis
+pub fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(limit.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
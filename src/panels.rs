@@ -0,0 +1,221 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::GenerateCommand;
+use crate::config::DEFAULT_CONFIG;
+use crate::lock::LOCK_FILE;
+use crate::server::{DEFAULT_GAME_PORT, SERVER_EXE};
+use crate::ui::status::{println_step, println_success};
+use crate::{AUTHORS, VERSION};
+
+const DZSM_EXE: &str = "dzsm.exe";
+const DOWNLOAD_URL: &str = concat!(env!("CARGO_PKG_REPOSITORY"), "/releases/latest/download/dzsm.exe");
+/// Printed when the server process starts, the point a panel shows the server as running
+const READY_PREFIX: &str = "Executing: ";
+/// Pterodactyl runs Linux containers, Windows executables go through Wine
+const PTERODACTYL_IMAGE: &str = "ghcr.io/parkervcp/yolks:wine_latest";
+const PTERODACTYL_INSTALLER_IMAGE: &str = "ghcr.io/parkervcp/installers:debian";
+/// The port line of `[server]` as the install script writes it, a quoted key no other line
+/// of config.toml starts with, such as a profile's port
+const PTERODACTYL_PORT_KEY: &str = "\"port\" = ";
+const PTERODACTYL_EGG_FILE: &str = "egg-dzsm.json";
+const AMP_MODULE_DIR: &str = "amp";
+const AMP_MODULE_NAME: &str = "dzsm";
+const DESCRIPTION: &str = "DayZ server run by DZSM, which downloads and updates the server and its Workshop mods. \
+    Server settings live in config.toml; the Steam account must own DayZ and be logged in once \
+    through the console so SteamCMD caches the credentials.";
+
+/// The contact email out of the crate's authors, which Pterodactyl requires on an egg
+fn get_author_email() -> &'static str {
+    AUTHORS.split(':')
+        .find_map(|author| author.split_once('<').and_then(|(_, rest)| rest.split_once('>')))
+        .map_or("", |(email, _)| email)
+}
+
+/// Shell that creates the DZSM setup on a fresh server: the executable, the lock file, and the
+/// default config, so the first start doesn't stop to ask for them
+fn get_install_script() -> String {
+    format!(
+        "#!/bin/bash\n\
+        # DZSM install script, generated by dzsm v{VERSION}\n\
+        apt-get update && apt-get install -y curl\n\
+        mkdir -p /mnt/server && cd /mnt/server\n\
+        curl -fsSL -o {DZSM_EXE} \"${{DZSM_DOWNLOAD_URL}}\"\n\
+        if [ ! -f {LOCK_FILE} ]; then\n\
+        \x20   printf 'Managed by DZSM v{VERSION} - DayZ Server Manager\\nCreated: %s\\n' \"$(date -u '+%Y-%m-%d %H:%M:%S UTC')\" > {LOCK_FILE}\n\
+        fi\n\
+        if [ ! -f config.toml ]; then\n\
+        cat > config.toml <<'DZSM_CONFIG'\n\
+        {DEFAULT_CONFIG}\
+        DZSM_CONFIG\n\
+        fi\n\
+        # Give the port of [server] a line of its own for the panel to fill in\n\
+        sed -i '/^\\[server\\]/,/^\\[/ s/^#\\? \\?port = .*/{PTERODACTYL_PORT_KEY}{DEFAULT_GAME_PORT}/' config.toml\n"
+    )
+}
+
+/// A panel variable as Pterodactyl stores it
+fn pterodactyl_variable(name: &str, description: &str, env_variable: &str, default_value: &str, rules: &str) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "env_variable": env_variable,
+        "default_value": default_value,
+        "user_viewable": true,
+        "user_editable": true,
+        "rules": rules,
+        "field_type": "text",
+    })
+}
+
+/// A Pterodactyl egg: the panel fills `[server]` of config.toml from its variables and port
+/// allocation on every start, and stops DZSM with Ctrl+C so it shuts the server down gracefully
+fn pterodactyl_egg() -> Result<Value> {
+    // Pterodactyl's `file` parser replaces whole lines starting with the key, in any section
+    let config_files = json!({
+        "config.toml": {
+            "parser": "file",
+            "find": {
+                "username = ": "username = \"{{server.build.env.STEAM_USERNAME}}\"",
+                PTERODACTYL_PORT_KEY: format!("{PTERODACTYL_PORT_KEY}{{{{server.build.default.port}}}}"),
+            },
+        },
+    });
+
+    Ok(json!({
+        "_comment": format!("Generated by dzsm v{VERSION} with `dzsm generate pterodactyl-egg`"),
+        "meta": {
+            "version": "PTDL_v2",
+            "update_url": null,
+        },
+        "exported_at": chrono::Local::now().to_rfc3339(),
+        "name": "DayZ (DZSM)",
+        "author": get_author_email(),
+        "description": DESCRIPTION,
+        "features": null,
+        "docker_images": {
+            "Wine": PTERODACTYL_IMAGE,
+        },
+        "file_denylist": [],
+        // No --non-interactive: the console's input goes to SteamCMD for the Steam Guard code
+        "startup": format!("wine ./{DZSM_EXE} --no-banner run"),
+        "config": {
+            "files": serde_json::to_string_pretty(&config_files)?,
+            "startup": serde_json::to_string(&json!({ "done": format!("{READY_PREFIX}{SERVER_EXE}") }))?,
+            "logs": "{}",
+            "stop": "^C",
+        },
+        "scripts": {
+            "installation": {
+                "script": get_install_script(),
+                "container": PTERODACTYL_INSTALLER_IMAGE,
+                "entrypoint": "bash",
+            },
+        },
+        "variables": [
+            pterodactyl_variable(
+                "Steam Username",
+                "Steam account that owns DayZ, log in once through the console to cache its credentials",
+                "STEAM_USERNAME",
+                "",
+                "required|string|max:64",
+            ),
+            pterodactyl_variable(
+                "DZSM Download URL",
+                "Where the install script downloads dzsm.exe from",
+                "DZSM_DOWNLOAD_URL",
+                DOWNLOAD_URL,
+                "required|url",
+            ),
+        ],
+    }))
+}
+
+/// The files of an AMP Generic module. AMP can't edit TOML, so the settings stay in config.toml,
+/// which DZSM creates on the first start (answer its setup prompt in AMP's console).
+#[allow(clippy::doc_markdown)]
+fn amp_module() -> Result<Vec<(String, String)>> {
+    let name = AMP_MODULE_NAME;
+    let kvp = format!(
+        "# Generated by dzsm v{VERSION} with `dzsm generate amp`\n\
+        Meta.DisplayName=DayZ (DZSM)\n\
+        Meta.Description={DESCRIPTION}\n\
+        Meta.OS=Windows\n\
+        Meta.AppConfigManifest={name}config.json\n\
+        Meta.PortsManifest={name}ports.json\n\
+        Meta.UpdatesManifest={name}updates.json\n\
+        App.RootDir=./dzsm/\n\
+        App.BaseDirectory=./dzsm/\n\
+        App.ExecutableWin={DZSM_EXE}\n\
+        App.WorkingDir=\n\
+        App.CommandLineArgs=--no-banner run\n\
+        App.ExitMethod=CtrlC\n\
+        App.ExitTimeout=300\n\
+        App.HasWriteableConsole=True\n\
+        Console.AppReadyRegex=^.*{}.*$\n",
+        format!("{READY_PREFIX}{SERVER_EXE}").replace('.', "\\.")
+    );
+
+    let ports = json!([{
+        "Protocol": "Both",
+        "Port": DEFAULT_GAME_PORT,
+        "Ref": "GamePort",
+        "Name": "Game Port",
+        "Description": "Must match `server.port` in config.toml",
+    }]);
+
+    let updates = json!([{
+        "UpdateStageName": "DZSM Download",
+        "UpdateSourcePlatform": "Windows",
+        "UpdateSource": "FetchURL",
+        "UpdateSourceData": DOWNLOAD_URL,
+        "UpdateSourceTarget": "{{$FullBaseDir}}",
+        "OverwriteExistingFiles": true,
+    }]);
+
+    Ok(vec![
+        (format!("{name}.kvp"), kvp),
+        (format!("{name}config.json"), serde_json::to_string_pretty(&json!([]))?),
+        (format!("{name}ports.json"), serde_json::to_string_pretty(&ports)?),
+        (format!("{name}updates.json"), serde_json::to_string_pretty(&updates)?),
+    ])
+}
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, content)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+/// Entry point for `dzsm generate ...`
+pub fn run(command: &GenerateCommand) -> Result<()> {
+    match command {
+        GenerateCommand::PterodactylEgg { output } => {
+            println_step("Generating Pterodactyl egg...", 0);
+            let path = output.clone().unwrap_or_else(|| PathBuf::from(PTERODACTYL_EGG_FILE));
+            write_file(&path, &serde_json::to_string_pretty(&pterodactyl_egg()?)?)?;
+            println_success(&format!("Egg written to {}, import it under Admin > Nests", path.display()), 0);
+        }
+        GenerateCommand::Amp { output } => {
+            println_step("Generating AMP Generic module...", 0);
+            let dir = output.clone().unwrap_or_else(|| PathBuf::from(AMP_MODULE_DIR));
+            for (file, content) in amp_module()? {
+                write_file(&dir.join(file), &content)?;
+            }
+            println_success(&format!(
+                "Module written to {}, copy its files into AMP's Generic module templates",
+                dir.display()
+            ), 0);
+        }
+        // Needs the config and install, main.rs hands it to launch_script::run instead
+        GenerateCommand::LaunchScript { .. } => {
+            return Err(anyhow!("The launch script is generated from the server's config, not as a panel file"));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::access_list::{AccessList, PRIORITY_FILE, WHITELIST_FILE, is_steam64_id};
+use crate::config::PlayersConfig;
+use crate::server::SERVER_CONFIG;
+use crate::server_cfg::ServerDzConfig;
+use crate::state::state_dir;
+use crate::ui::status::println_success;
+
+/// IDs DZSM put in each file, so ones taken out of `[players]` are removed without touching
+/// those added by hand or synced from other servers
+const STATE_FILE: &str = "players.json";
+
+#[derive(Default, Serialize, Deserialize)]
+struct Managed {
+    whitelist: BTreeSet<String>,
+    priority: BTreeSet<String>,
+}
+
+fn get_state_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(STATE_FILE)
+}
+
+fn load_managed(server_install_dir: &Path) -> Managed {
+    fs::read_to_string(get_state_path(server_install_dir))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_managed(server_install_dir: &Path, managed: &Managed) -> Result<()> {
+    let path = get_state_path(server_install_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context("Failed to create state directory")?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(managed)?)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+/// `[players]` entries by ID, each with its whole line as written to `whitelist.txt`
+fn parse_entries(entries: &[String], setting: &str) -> Result<BTreeMap<String, String>> {
+    entries.iter()
+        .map(|entry| {
+            let entry = entry.trim();
+            let id = entry.split("//").next().unwrap_or_default().trim();
+            if !is_steam64_id(id) {
+                return Err(anyhow!("`players.{setting}` entry '{entry}' doesn't start with a Steam64 ID"));
+            }
+            Ok((id.to_string(), entry.to_string()))
+        })
+        .collect()
+}
+
+/// The IDs in `priority.txt`, in file order
+fn load_priority(path: &Path) -> Result<Vec<String>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read {}", path.display()))?;
+    Ok(content.split([';', '\n'])
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Write `[players]` into `whitelist.txt`, `priority.txt`, and `serverDZ.cfg` before a start
+#[allow(clippy::doc_markdown)]
+pub fn apply_before_launch(config: &PlayersConfig, server_install_dir: &Path) -> Result<()> {
+    let managed = load_managed(server_install_dir);
+    let configured = !config.admins.is_empty() || !config.whitelist.is_empty() || !config.priority.is_empty();
+    if !configured && config.enforce_whitelist.is_none() && managed.whitelist.is_empty() && managed.priority.is_empty() {
+        return Ok(());
+    }
+
+    let admins = parse_entries(&config.admins, "admins")?;
+    let mut whitelist = admins.clone();
+    whitelist.extend(parse_entries(&config.whitelist, "whitelist")?);
+    let mut priority = admins;
+    priority.extend(parse_entries(&config.priority, "priority")?);
+
+    let whitelist_path = server_install_dir.join(WHITELIST_FILE);
+    let mut list = AccessList::load(&whitelist_path)?;
+    let before = list.clone();
+    list.entries.retain(|id, _| !managed.whitelist.contains(id) || whitelist.contains_key(id));
+    list.entries.extend(whitelist.clone());
+    if list != before {
+        list.save(&whitelist_path)?;
+    }
+
+    let priority_path = server_install_dir.join(PRIORITY_FILE);
+    let mut ids = load_priority(&priority_path)?;
+    let before = ids.clone();
+    ids.retain(|id| !managed.priority.contains(id) || priority.contains_key(id));
+    for id in priority.keys() {
+        if !ids.contains(id) {
+            ids.push(id.clone());
+        }
+    }
+    if ids != before {
+        fs::write(&priority_path, ids.join(";"))
+            .context(format!("Failed to write {}", priority_path.display()))?;
+    }
+
+    if let Some(enforce) = config.enforce_whitelist {
+        let mut server_cfg = ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))?;
+        let value = if enforce { "1" } else { "0" };
+        if server_cfg.get("enableWhitelist").as_deref() != Some(value) {
+            server_cfg.set_raw("enableWhitelist", value);
+            server_cfg.save()?;
+        }
+    }
+
+    let counts = (whitelist.len(), priority.len());
+    save_managed(server_install_dir, &Managed {
+        whitelist: whitelist.into_keys().collect(),
+        priority: priority.into_keys().collect(),
+    })?;
+    if configured {
+        let enforced = if config.enforce_whitelist == Some(true) { ", only whitelisted players can join" } else { "" };
+        println_success(&format!("{} whitelisted and {} priority player(s) from [players]{enforced}", counts.0, counts.1), 1);
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use anyhow::{Context, Result};
+use chrono::{Duration as ChronoDuration, Local};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::adm::{self, AdmEvent};
+use crate::cli::PositionsCommand;
+use crate::config::{Config, PositionsConfig, PrivacyConfig};
+use crate::privacy::Redactor;
+use crate::server::SERVER_CONFIG;
+use crate::server_cfg::ServerDzConfig;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const DEFAULT_EXPORT_FILE: &str = "positions.geojson";
+/// Longer histories are capped, a year of logs is more than any server keeps
+const MAX_HISTORY_MINUTES: u64 = 60 * 24 * 366;
+/// serverDZ.cfg setting that makes the server log every player's position every few minutes
+#[allow(clippy::doc_markdown)]
+const PLAYER_LIST_SETTING: &str = "adminLogPlayerList";
+
+/// A `GeoJSON` collection with each player's last position and, if they moved, their trail
+fn to_geojson(events: &[AdmEvent], redactor: &Redactor) -> Value {
+    let mut players: BTreeMap<&str, Vec<(&AdmEvent, [f64; 3])>> = BTreeMap::new();
+    for event in events {
+        if let Some(position) = event.position {
+            players.entry(&event.id).or_default().push((event, position));
+        }
+    }
+
+    let mut features = Vec::new();
+    for (id, trail) in &players {
+        let Some(&(last, [x, y, altitude])) = trail.last() else {
+            continue;
+        };
+
+        features.push(json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": [x, y] },
+            "properties": {
+                "kind": "position",
+                "name": redactor.name(&last.name, id),
+                "id": redactor.id(id),
+                "time": last.time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                "altitude": altitude,
+            },
+        }));
+
+        if trail.len() > 1 {
+            let coordinates: Vec<[f64; 2]> = trail.iter().map(|(_, [x, y, _])| [*x, *y]).collect();
+            features.push(json!({
+                "type": "Feature",
+                "geometry": { "type": "LineString", "coordinates": coordinates },
+                "properties": {
+                    "kind": "trail",
+                    "name": redactor.name(&last.name, id),
+                    "id": redactor.id(id),
+                    "from": trail[0].0.time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    "to": last.time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                },
+            }));
+        }
+    }
+
+    json!({
+        "type": "FeatureCollection",
+        "generated": Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        "features": features,
+    })
+}
+
+/// Write the positions from the last `history` of admin logs to `output` as `GeoJSON`.
+/// Returns how many players were seen.
+pub fn export(server_install_dir: &Path, output: &Path, history: ChronoDuration, privacy: &PrivacyConfig) -> Result<usize> {
+    let redactor = Redactor::new(privacy, server_install_dir)?;
+    let events = adm::read_since(server_install_dir, Local::now() - history)?;
+
+    let players = events.iter()
+        .filter(|event| event.position.is_some())
+        .map(|event| &event.id)
+        .collect::<BTreeSet<_>>()
+        .len();
+    let geojson = serde_json::to_string_pretty(&to_geojson(&events, &redactor))?;
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create {}", parent.display()))?;
+    }
+    // Written aside and renamed, so a map reading the file never sees half of it
+    let partial = output.with_extension("partial");
+    fs::write(&partial, geojson)
+        .context(format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, output)
+        .context(format!("Failed to write {}", output.display()))?;
+
+    Ok(players)
+}
+
+/// The export file from `[positions]`, if periodic exports are enabled
+pub fn get_export_path(config: &PositionsConfig, server_install_dir: &Path) -> Option<PathBuf> {
+    config.export_file.as_ref().map(|file| server_install_dir.join(file))
+}
+
+/// Minutes of history as a duration, capped at `MAX_HISTORY_MINUTES`
+pub fn history(minutes: u64) -> ChronoDuration {
+    ChronoDuration::minutes(i64::try_from(minutes.min(MAX_HISTORY_MINUTES)).unwrap_or_default())
+}
+
+/// Entry point for `dzsm positions ...`
+#[allow(clippy::doc_markdown)]
+pub fn run(command: &PositionsCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+
+    match command {
+        PositionsCommand::Export { out, minutes } => {
+            let enabled = ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))
+                .ok()
+                .and_then(|server_config| server_config.get(PLAYER_LIST_SETTING))
+                .is_some_and(|value| value.trim() == "1");
+            if !enabled {
+                println_failure(&format!("{PLAYER_LIST_SETTING} is not enabled in {SERVER_CONFIG}, positions are only logged for hits and kills"), 0);
+            }
+
+            let output = out.clone()
+                .or_else(|| get_export_path(&config.positions, server_install_dir))
+                .unwrap_or_else(|| server_install_dir.join(DEFAULT_EXPORT_FILE));
+            let history = history(minutes.unwrap_or(config.positions.history_minutes));
+
+            println_step("Exporting player positions from the admin logs...", 0);
+            let players = export(server_install_dir, &output, history, &config.privacy)?;
+            if players == 0 {
+                println_failure(&format!("No player positions in the admin logs of the last {} minutes", history.num_minutes()), 1);
+            }
+            println_success(&format!("{players} player(s) exported to {}", output.display()), 0);
+            Ok(())
+        }
+    }
+}
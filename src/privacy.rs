@@ -0,0 +1,184 @@
+use anyhow::{Context, Result, anyhow};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::cli::PrivacyCommand;
+use crate::config::{Config, PrivacyConfig};
+use crate::crash::CRASHES_DIR;
+use crate::crypto;
+use crate::positions;
+use crate::server::SERVER_PROFILES;
+use crate::state::{logs_dir, reports_dir, state_dir};
+use crate::storage::get_backups_dir;
+use crate::ui::status::{println_failure, println_step, println_success};
+use crate::wipe_day::WIPES_DIR;
+
+/// Key for the player ID hashes, so they can't be reversed by hashing every known ID
+const HASH_KEY_FILE: &str = "privacy.key";
+const HASH_KEY_LENGTH: usize = 32;
+/// Hex digits kept from each hash, plenty to tell a server's players apart
+const HASHED_ID_LENGTH: usize = 16;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// Server logs that name players
+pub const SERVER_LOG_EXTENSIONS: [&str; 4] = ["adm", "rpt", "log", "mdmp"];
+
+/// Applies `[privacy]` redaction to player names and IDs written into exports and reports
+pub struct Redactor {
+    key: Option<Vec<u8>>,
+    hide_names: bool,
+}
+
+impl Redactor {
+    pub fn new(config: &PrivacyConfig, server_install_dir: &Path) -> Result<Self> {
+        let key = if config.hash_player_ids {
+            Some(load_or_create_key(server_install_dir)?)
+        } else {
+            None
+        };
+
+        Ok(Self { key, hide_names: config.hide_player_names })
+    }
+
+    /// The player ID as it may appear in an export
+    pub fn id(&self, id: &str) -> String {
+        let Some(key) = &self.key else {
+            return id.to_string();
+        };
+
+        match crypto::hmac_sha256(key, id.as_bytes()) {
+            Ok(hash) => {
+                let mut hex = String::with_capacity(HASHED_ID_LENGTH);
+                for byte in hash.iter().take(HASHED_ID_LENGTH / 2) {
+                    let _ = write!(hex, "{byte:02x}");
+                }
+                hex
+            }
+            // Never fall back to the real ID
+            Err(_) => "redacted".to_string(),
+        }
+    }
+
+    /// The player name as it may appear in an export
+    pub fn name(&self, name: &str, id: &str) -> String {
+        if self.hide_names {
+            format!("player-{}", self.id(id))
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+/// The hash key, stored encrypted for the Windows user (DPAPI) like the other secrets, as
+/// anyone who can read it can tell which player a hash belongs to. A plaintext key from before
+/// is encrypted in place, keeping the hashes the same.
+fn load_or_create_key(server_install_dir: &Path) -> Result<Vec<u8>> {
+    let path = state_dir(server_install_dir).join(HASH_KEY_FILE);
+    if let Ok(stored) = fs::read(&path) {
+        if let Ok(key) = crypto::unprotect(&stored)
+            && key.len() == HASH_KEY_LENGTH
+        {
+            return Ok(key);
+        }
+        if stored.len() == HASH_KEY_LENGTH {
+            save_key(&path, &stored)?;
+            return Ok(stored);
+        }
+    }
+
+    let key = crypto::random_bytes(HASH_KEY_LENGTH)?;
+    save_key(&path, &key)?;
+    Ok(key)
+}
+
+fn save_key(path: &Path, key: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, crypto::protect(key)?)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+/// Files and directories holding player data that are older than the retention period: reports,
+/// crash captures, DZSM's logs, storage backups (characters are in them), and the position export
+fn find_expired(config: &Config, server_install_dir: &Path, cutoff: SystemTime) -> Vec<PathBuf> {
+    let mut locations = vec![
+        (reports_dir(server_install_dir), None),
+        (state_dir(server_install_dir).join(CRASHES_DIR), None),
+        (logs_dir(server_install_dir), None),
+        (get_backups_dir(server_install_dir), None),
+        (state_dir(server_install_dir).join(WIPES_DIR), None),
+    ];
+    if config.privacy.include_server_logs {
+        locations.push((server_install_dir.join(SERVER_PROFILES), Some(&SERVER_LOG_EXTENSIONS)));
+    }
+
+    let mut expired: Vec<PathBuf> = positions::get_export_path(&config.positions, server_install_dir)
+        .into_iter()
+        .filter(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).is_ok_and(|modified| modified < cutoff))
+        .collect();
+    for (dir, extensions) in locations {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let wanted = extensions.is_none_or(|extensions| {
+                path.extension().is_some_and(|e| extensions.iter().any(|wanted| e.eq_ignore_ascii_case(wanted)))
+            });
+            let modified = fs::metadata(&path).and_then(|metadata| metadata.modified());
+            if wanted && modified.is_ok_and(|modified| modified < cutoff) {
+                expired.push(path);
+            }
+        }
+    }
+    expired
+}
+
+/// Delete player data older than `retention_days`, returning how many files and directories went
+pub fn apply_retention(config: &Config, server_install_dir: &Path) -> usize {
+    let Some(days) = config.privacy.retention_days else {
+        return 0;
+    };
+    let retention = Duration::from_secs(days.saturating_mul(SECONDS_PER_DAY));
+    let Some(cutoff) = SystemTime::now().checked_sub(retention) else {
+        return 0;
+    };
+
+    let mut deleted = 0;
+    for path in find_expired(config, server_install_dir, cutoff) {
+        let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        match removed {
+            Ok(()) => deleted += 1,
+            // e.g. a log file still open, it goes on a later run
+            Err(e) => println_failure(&format!("Failed to delete {}: {e}", path.display()), 2),
+        }
+    }
+    deleted
+}
+
+/// Apply the retention period before a start, so old data doesn't pile up on a server nobody runs commands on
+pub fn apply_before_launch(config: &Config, server_install_dir: &Path) {
+    let deleted = apply_retention(config, server_install_dir);
+    if deleted > 0 {
+        println_success(&format!("Deleted {deleted} expired log(s), report(s), and backup(s)"), 1);
+    }
+}
+
+/// Entry point for `dzsm privacy ...`
+pub fn run(command: &PrivacyCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+
+    match command {
+        PrivacyCommand::Purge => {
+            let days = config.privacy.retention_days
+                .ok_or_else(|| anyhow!("No retention period set, set `privacy.retention_days` in config.toml"))?;
+
+            println_step(&format!("Deleting player data older than {days} days..."), 0);
+            let deleted = apply_retention(config, server_install_dir);
+            println_success(&format!("Deleted {deleted} expired log(s), report(s), and backup(s)"), 0);
+            Ok(())
+        }
+    }
+}
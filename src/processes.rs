@@ -0,0 +1,123 @@
+use std::ffi::OsString;
+use std::io;
+use std::mem;
+use std::os::windows::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+
+use windows_sys::Win32::Foundation::{CloseHandle, FALSE, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+    CreateToolhelp32Snapshot, PROCESSENTRY32W, Process32FirstW, Process32NextW, TH32CS_SNAPPROCESS,
+};
+use windows_sys::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+use windows_sys::Win32::System::Threading::{
+    OpenProcess, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_TERMINATE, QueryFullProcessImageNameW,
+    TerminateProcess,
+};
+
+const MAX_PATH_LENGTH: usize = 32768;
+
+/// A running process found by scanning the process list
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub exe_path: PathBuf,
+}
+
+/// Find running processes started from `exe_path`, e.g. the DayZ server of one install
+/// (other installs on the same machine run an exe with the same name)
+#[allow(clippy::doc_markdown)]
+pub fn find_by_exe_path(exe_path: &Path) -> Vec<ProcessInfo> {
+    let exe_name = exe_path.file_name().map(|name| name.to_string_lossy().to_lowercase());
+    let wanted = normalize(exe_path);
+
+    list_processes()
+        .into_iter()
+        .filter(|(_, name)| exe_name.as_deref() == Some(name.to_lowercase().as_str()))
+        .filter_map(|(pid, _)| Some(ProcessInfo { pid, exe_path: get_image_path(pid)? }))
+        .filter(|process| normalize(&process.exe_path) == wanted)
+        .collect()
+}
+
+/// Process ids and exe names of everything running
+fn list_processes() -> Vec<(u32, String)> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return Vec::new();
+    }
+
+    let mut processes = Vec::new();
+    let mut entry: PROCESSENTRY32W = unsafe { mem::zeroed() };
+    entry.dwSize = u32::try_from(mem::size_of::<PROCESSENTRY32W>()).unwrap_or(u32::MAX);
+
+    let mut found = unsafe { Process32FirstW(snapshot, &raw mut entry) } != 0;
+    while found {
+        let name_length = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+        let name = OsString::from_wide(&entry.szExeFile[..name_length]).to_string_lossy().to_string();
+        processes.push((entry.th32ProcessID, name));
+        found = unsafe { Process32NextW(snapshot, &raw mut entry) } != 0;
+    }
+
+    unsafe { CloseHandle(snapshot) };
+    processes
+}
+
+/// Full path of a process's exe, if it's running and we're allowed to look
+pub fn get_image_path(pid: u32) -> Option<PathBuf> {
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid) };
+    if handle.is_null() {
+        return None;
+    }
+
+    let mut buffer = vec![0u16; MAX_PATH_LENGTH];
+    let mut length = u32::try_from(buffer.len()).unwrap_or(u32::MAX);
+    let ok = unsafe { QueryFullProcessImageNameW(handle, PROCESS_NAME_WIN32, buffer.as_mut_ptr(), &raw mut length) };
+    unsafe { CloseHandle(handle) };
+
+    if ok == 0 {
+        return None;
+    }
+    Some(PathBuf::from(OsString::from_wide(&buffer[..length as usize])))
+}
+
+/// Bytes of RAM a process is using (its working set), if we're allowed to look
+pub fn get_memory_usage(pid: u32) -> Option<u64> {
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid) };
+    if handle.is_null() {
+        return None;
+    }
+
+    let mut counters: PROCESS_MEMORY_COUNTERS = unsafe { mem::zeroed() };
+    let size = u32::try_from(mem::size_of::<PROCESS_MEMORY_COUNTERS>()).unwrap_or(u32::MAX);
+    let ok = unsafe { GetProcessMemoryInfo(handle, &raw mut counters, size) };
+    unsafe { CloseHandle(handle) };
+
+    if ok == 0 {
+        return None;
+    }
+    u64::try_from(counters.WorkingSetSize).ok()
+}
+
+/// End a process straight away, like Task Manager's End task
+pub fn kill(pid: u32) -> io::Result<()> {
+    let handle = unsafe { OpenProcess(PROCESS_TERMINATE, FALSE, pid) };
+    if handle.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    let ok = unsafe { TerminateProcess(handle, 1) };
+    let error = io::Error::last_os_error();
+    unsafe { CloseHandle(handle) };
+
+    if ok == 0 {
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Windows paths compare case-insensitively, and the install dir may be relative
+fn normalize(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .trim_start_matches(r"\\?\")
+        .to_lowercase()
+}
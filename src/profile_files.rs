@@ -0,0 +1,251 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::cli::ProfileFilesCommand;
+use crate::config::{Config, ProfileFilesConfig};
+use crate::mod_validation::hash_file;
+use crate::server::SERVER_PROFILES;
+use crate::state::state_dir;
+use crate::storage::list_files;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const STATE_DIR: &str = "profile_files";
+/// Hash of each file as last deployed, to tell an edit in `profiles` from a change to its source
+const DEPLOYED_FILE: &str = "deployed.json";
+const DRIFTED_DIR: &str = "drifted";
+
+/// One file to keep in `profiles`
+struct ProfileFile {
+    /// Relative to `profiles`, with forward slashes
+    destination: String,
+    source: PathBuf,
+}
+
+/// How a file in `profiles` compares to its source
+#[derive(PartialEq, Eq)]
+enum FileState {
+    InSync,
+    Missing,
+    /// The source changed since it was deployed
+    Outdated,
+    /// Edited in `profiles` since it was deployed, or put there by hand before DZSM managed it
+    Drifted,
+}
+
+impl FileState {
+    const fn describe(&self) -> &'static str {
+        match self {
+            Self::InSync => "in sync",
+            Self::Missing => "not deployed yet",
+            Self::Outdated => "source changed, deployed on the next start or sync",
+            Self::Drifted => "edited in profiles since it was deployed",
+        }
+    }
+}
+
+struct ProfileFiles<'a> {
+    config: &'a ProfileFilesConfig,
+    server_install_dir: &'a Path,
+}
+
+impl<'a> ProfileFiles<'a> {
+    const fn new(config: &'a ProfileFilesConfig, server_install_dir: &'a Path) -> Self {
+        Self { config, server_install_dir }
+    }
+
+    fn get_profiles_dir(&self) -> PathBuf {
+        self.server_install_dir.join(SERVER_PROFILES)
+    }
+
+    fn get_state_dir(&self) -> PathBuf {
+        state_dir(self.server_install_dir).join(STATE_DIR)
+    }
+
+    /// `source_dir`'s files, then `files`, which replace any of them with the same destination
+    fn collect(&self) -> Result<Vec<ProfileFile>> {
+        let mut files = BTreeMap::new();
+
+        if let Some(source_dir) = &self.config.source_dir {
+            let source_dir = self.server_install_dir.join(source_dir);
+            if !source_dir.is_dir() {
+                return Err(anyhow!("`profile_files.source_dir` {} is not a folder", source_dir.display()));
+            }
+            for source in list_files(&source_dir)? {
+                let relative = source.strip_prefix(&source_dir).unwrap_or(&source);
+                files.insert(to_destination(relative)?, source);
+            }
+        }
+
+        for (destination, source) in &self.config.files {
+            let source = self.server_install_dir.join(source);
+            if !source.is_file() {
+                return Err(anyhow!("`profile_files.files` source {} doesn't exist", source.display()));
+            }
+            files.insert(to_destination(Path::new(destination))?, source);
+        }
+
+        Ok(files.into_iter().map(|(destination, source)| ProfileFile { destination, source }).collect())
+    }
+
+    fn load_deployed(&self) -> BTreeMap<String, u64> {
+        fs::read_to_string(self.get_state_dir().join(DEPLOYED_FILE))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_deployed(&self, deployed: &BTreeMap<String, u64>) -> Result<()> {
+        let dir = self.get_state_dir();
+        fs::create_dir_all(&dir)
+            .context(format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(DEPLOYED_FILE);
+        fs::write(&path, serde_json::to_string_pretty(deployed)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    fn check(&self, file: &ProfileFile, deployed: &BTreeMap<String, u64>) -> Result<FileState> {
+        let path = self.get_profiles_dir().join(&file.destination);
+        if !path.exists() {
+            return Ok(FileState::Missing);
+        }
+
+        let current = hash_file(&path)?;
+        let source = hash_file(&file.source)?;
+        if current == source {
+            return Ok(FileState::InSync);
+        }
+        match deployed.get(&file.destination) {
+            Some(hash) if *hash == current => Ok(FileState::Outdated),
+            _ => Ok(FileState::Drifted),
+        }
+    }
+
+    /// Copy every file that isn't in sync into `profiles`, returning how many were
+    fn sync(&self, dry_run: bool) -> Result<usize> {
+        let overwrite_drifted = match self.config.on_drift.as_str() {
+            "overwrite" => true,
+            "keep" => false,
+            other => return Err(anyhow!("Unknown `profile_files.on_drift` '{other}', expected \"overwrite\" or \"keep\"")),
+        };
+
+        let mut deployed = self.load_deployed();
+        let drifted_dir = self.get_state_dir().join(DRIFTED_DIR).join(Local::now().format("%Y-%m-%d_%H-%M-%S").to_string());
+        let mut copied = 0;
+
+        for file in self.collect()? {
+            let state = self.check(&file, &deployed)?;
+            let path = self.get_profiles_dir().join(&file.destination);
+            match state {
+                FileState::InSync => {
+                    deployed.insert(file.destination, hash_file(&path)?);
+                    continue;
+                }
+                FileState::Drifted if !overwrite_drifted => {
+                    println_failure(&format!("{}: {}, keeping it (see `profile_files.on_drift`)", file.destination, state.describe()), 1);
+                    continue;
+                }
+                FileState::Drifted => {
+                    println_failure(&format!(
+                        "{}: {}, overwriting it and saving the edited copy in {}",
+                        file.destination, state.describe(), drifted_dir.display()
+                    ), 1);
+                    if !dry_run {
+                        copy_file(&path, &drifted_dir.join(&file.destination))?;
+                    }
+                }
+                FileState::Missing | FileState::Outdated => {
+                    println_step(&format!("{}: {}", file.destination, state.describe()), 1);
+                }
+            }
+
+            if !dry_run {
+                copy_file(&file.source, &path)?;
+                deployed.insert(file.destination, hash_file(&path)?);
+            }
+            copied += 1;
+        }
+
+        if !dry_run {
+            self.save_deployed(&deployed)?;
+        }
+        Ok(copied)
+    }
+
+    fn print_status(&self) -> Result<()> {
+        let deployed = self.load_deployed();
+        let files = self.collect()?;
+        if files.is_empty() {
+            println_success("No profile files configured, see `[profile_files]`", 0);
+            return Ok(());
+        }
+
+        for file in &files {
+            let state = self.check(file, &deployed)?;
+            let message = format!("{} <- {}: {}", file.destination, file.source.display(), state.describe());
+            if state == FileState::Drifted {
+                println_failure(&message, 0);
+            } else {
+                println_step(&message, 0);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A path relative to `profiles`, refusing any that would land outside it
+fn to_destination(path: &Path) -> Result<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::CurDir => {}
+            _ => return Err(anyhow!("Profile file {} must stay inside {SERVER_PROFILES}/", path.display())),
+        }
+    }
+    if parts.is_empty() {
+        return Err(anyhow!("Profile file path is empty"));
+    }
+    Ok(parts.join("/"))
+}
+
+fn copy_file(source: &Path, destination: &Path) -> Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create {}", parent.display()))?;
+    }
+    fs::copy(source, destination)
+        .context(format!("Failed to copy {} to {}", source.display(), destination.display()))?;
+    Ok(())
+}
+
+/// Deploy the profile files before a start, so the server always starts with the configured ones
+pub fn apply_before_launch(config: &ProfileFilesConfig, server_install_dir: &Path) -> Result<()> {
+    if !config.is_enabled() {
+        return Ok(());
+    }
+
+    let copied = ProfileFiles::new(config, server_install_dir).sync(false)?;
+    if copied > 0 {
+        println_success(&format!("Deployed {copied} profile file(s)"), 1);
+    }
+    Ok(())
+}
+
+/// Entry point for `dzsm profile-files ...`
+pub fn run(command: &ProfileFilesCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    let profile_files = ProfileFiles::new(&config.profile_files, Path::new(server_install_dir));
+
+    match command {
+        ProfileFilesCommand::Status => profile_files.print_status(),
+        ProfileFilesCommand::Sync { dry_run } => {
+            println_step("Syncing profile files...", 0);
+            let copied = profile_files.sync(*dry_run)?;
+            let verb = if *dry_run { "Would deploy" } else { "Deployed" };
+            println_success(&format!("{verb} {copied} profile file(s)"), 0);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::VERSION;
+use crate::cli::PublicCommand;
+use crate::collection_sync::get_installed_workshop_mods;
+use crate::config::Config;
+use crate::config::mod_entry::ModEntry;
+use crate::load_order::sort_mods;
+use crate::quarantine;
+use crate::schedule::RestartSchedule;
+use crate::server::{DEFAULT_GAME_PORT, SERVER_CONFIG, SERVER_EXE};
+use crate::server_cfg::ServerDzConfig;
+use crate::status::{get_build_id, get_file_version};
+use crate::ui::json::print_output;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const INFO_PATH: &str = "/info.json";
+const MODS_PATH: &str = "/mods.json";
+const MOD_URL: &str = "https://steamcommunity.com/sharedfiles/filedetails/?id=";
+/// How long an answer is served again before it's collected anew, so a busy launcher or
+/// website doesn't have the install scanned for every request
+const CACHE_DURATION: Duration = Duration::from_secs(5);
+
+/// What anyone may know about the server; nothing here needs a password to find out in-game
+#[derive(Serialize)]
+struct PublicInfo {
+    name: Option<String>,
+    mission: Option<String>,
+    map: Option<String>,
+    port: u16,
+    max_players: Option<u32>,
+    versions: Versions,
+    /// Mods players need, in load order; server-side mods are left out
+    mods: Vec<PublicMod>,
+    restart_times: Vec<String>,
+    next_restart: Option<DateTime<Local>>,
+    generated: DateTime<Local>,
+}
+
+#[derive(Serialize)]
+struct Versions {
+    dzsm: &'static str,
+    server: Option<String>,
+    build: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PublicMod {
+    id: u64,
+    name: String,
+    url: String,
+}
+
+impl PublicInfo {
+    fn collect(config: &Config, server_install_dir: &Path) -> Result<Self> {
+        let server_cfg = ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG)).ok();
+        let get = |key: &str| server_cfg.as_ref().and_then(|cfg| cfg.get(key)).filter(|value| !value.trim().is_empty());
+
+        let mission = get("template");
+        // Missions are named `<mode>.<map>`, e.g. dayzOffline.chernarusplus
+        let map = mission.as_deref()
+            .map(|mission| mission.rsplit('.').next().unwrap_or(mission).to_string());
+
+        let mods = get_client_mods(config, server_install_dir)?
+            .into_iter()
+            .map(|mod_entry| PublicMod {
+                url: format!("{MOD_URL}{}", mod_entry.id),
+                id: mod_entry.id,
+                name: mod_entry.name,
+            })
+            .collect();
+
+        let now = Local::now();
+        let next_restart = RestartSchedule::from_config(&config.schedule).ok()
+            .and_then(|schedule| schedule.next_restart_after(now));
+
+        Ok(Self {
+            name: get("hostname"),
+            mission,
+            map,
+            port: config.server.port.unwrap_or(DEFAULT_GAME_PORT),
+            max_players: get("maxPlayers").and_then(|players| players.trim().parse().ok()),
+            versions: Versions {
+                dzsm: VERSION,
+                server: get_file_version(&server_install_dir.join(SERVER_EXE)),
+                build: get_build_id(server_install_dir),
+            },
+            mods,
+            restart_times: config.schedule.restart_times.clone(),
+            next_restart,
+            generated: now,
+        })
+    }
+
+    fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize server info")
+    }
+}
+
+/// The installed mods players need, in load order; server-side and quarantined mods are left out
+pub fn get_client_mods(config: &Config, server_install_dir: &Path) -> Result<Vec<ModEntry>> {
+    let quarantined = quarantine::get_quarantined(server_install_dir);
+    let client_mods: Vec<_> = get_installed_workshop_mods(server_install_dir)?
+        .into_iter()
+        .filter(|mod_entry| !config.mods.is_server_only(mod_entry) && !quarantined.contains_key(&mod_entry.id))
+        .collect();
+    Ok(sort_mods(&client_mods, &config.mods).unwrap_or(client_mods))
+}
+
+/// The mod list as DZSA Launcher reports a server's, `{"mods": [{"name", "steamWorkshopId"}]}`
+#[allow(clippy::doc_markdown)]
+pub fn to_launcher_json(mods: &[ModEntry]) -> Result<String> {
+    let mods: Vec<_> = mods.iter()
+        .map(|mod_entry| serde_json::json!({ "name": mod_entry.name, "steamWorkshopId": mod_entry.id }))
+        .collect();
+    serde_json::to_string_pretty(&serde_json::json!({ "mods": mods })).context("Failed to serialize mod list")
+}
+
+/// Answers `GET /info.json` with the current server info and `GET /mods.json` with the
+/// client mods for launchers, to anyone
+pub struct PublicServer {
+    config: Config,
+    server_install_dir: PathBuf,
+    /// Answers by path, with when they were collected
+    cache: HashMap<&'static str, (Instant, String)>,
+}
+
+impl PublicServer {
+    pub fn new(config: &Config, server_install_dir: &Path) -> Self {
+        Self {
+            config: config.clone(),
+            server_install_dir: server_install_dir.to_path_buf(),
+            cache: HashMap::new(),
+        }
+    }
+
+    fn serve(&mut self, listen: &str) -> Result<()> {
+        let server = tiny_http::Server::http(listen)
+            .map_err(|e| anyhow!("Failed to listen on {listen}: {e}"))?;
+        println_success(&format!("Public server info at http://{listen}{INFO_PATH}"), 0);
+
+        for request in server.incoming_requests() {
+            let (status, body) = self.handle(&request);
+            // Served to websites on other origins, which is the point
+            let response = tiny_http::Response::from_string(body)
+                .with_status_code(status)
+                .with_header(tiny_http::Header::from_bytes("Content-Type", "application/json").expect("valid header"))
+                .with_header(tiny_http::Header::from_bytes("Access-Control-Allow-Origin", "*").expect("valid header"));
+            if let Err(e) = request.respond(response) {
+                println_failure(&format!("Failed to respond to info request: {e}"), 1);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, request: &tiny_http::Request) -> (u16, String) {
+        if *request.method() != tiny_http::Method::Get {
+            return (405, r#"{"error":"Only GET is supported"}"#.to_string());
+        }
+        // Query strings are ignored, launchers add them to dodge caches
+        let path = match request.url().split('?').next().unwrap_or_default() {
+            INFO_PATH | "/" => INFO_PATH,
+            MODS_PATH => MODS_PATH,
+            _ => return (404, r#"{"error":"Not found"}"#.to_string()),
+        };
+        if let Some((collected, json)) = self.cache.get(path)
+            && collected.elapsed() < CACHE_DURATION
+        {
+            return (200, json.clone());
+        }
+
+        let json = if path == INFO_PATH {
+            PublicInfo::collect(&self.config, &self.server_install_dir).and_then(|info| info.to_json())
+        } else {
+            get_client_mods(&self.config, &self.server_install_dir).and_then(|mods| to_launcher_json(&mods))
+        };
+
+        match json {
+            Ok(json) => {
+                self.cache.insert(path, (Instant::now(), json.clone()));
+                (200, json)
+            }
+            Err(e) => {
+                println_failure(&format!("Failed to collect public server info: {e:#}"), 1);
+                (500, r#"{"error":"Server info unavailable"}"#.to_string())
+            }
+        }
+    }
+
+    /// Serve in the background for as long as DZSM runs, if `public.listen` is set
+    pub fn spawn(mut self) {
+        let Some(listen) = self.config.public.listen.clone() else {
+            return;
+        };
+
+        thread::spawn(move || {
+            if let Err(e) = self.serve(&listen) {
+                println_failure(&format!("Public server info stopped: {e:#}"), 0);
+            }
+        });
+    }
+}
+
+/// Write the info to `public.file` before a start, so mod and version changes show up;
+/// a failure here isn't worth holding the server back for
+pub fn apply_before_launch(config: &Config, server_install_dir: &Path) {
+    let Some(file) = config.public.file.as_deref() else {
+        return;
+    };
+
+    let result = PublicInfo::collect(config, server_install_dir)
+        .and_then(|info| info.to_json())
+        .and_then(|json| fs::write(file, json).context(format!("Failed to write {file}")));
+    if let Err(e) = result {
+        println_failure(&format!("Failed to publish server info: {e:#}"), 1);
+    }
+}
+
+/// Write the client mods to `public.mods_file` after mods are installed, so launchers pick up
+/// changes before players try to join; like the server info, a failure only gets reported
+pub fn write_mods_file(config: &Config, server_install_dir: &Path) {
+    let Some(file) = config.public.mods_file.as_deref() else {
+        return;
+    };
+
+    let result = get_client_mods(config, server_install_dir)
+        .and_then(|mods| to_launcher_json(&mods))
+        .and_then(|json| fs::write(file, json).context(format!("Failed to write {file}")));
+    match result {
+        Ok(()) => println_step(&format!("Launcher mod list written to {file}"), 1),
+        Err(e) => println_failure(&format!("Failed to write the launcher mod list: {e:#}"), 1),
+    }
+}
+
+/// Entry point for `dzsm public ...`
+pub fn run(command: &PublicCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+
+    match command {
+        PublicCommand::Show => {
+            print_output(&PublicInfo::collect(config, server_install_dir)?.to_json()?);
+            Ok(())
+        }
+        PublicCommand::Serve => {
+            let listen = config.public.listen.as_deref()
+                .ok_or_else(|| anyhow!("Set `public.listen` in config.toml to serve the server info"))?;
+            println_step("Serving public server info until stopped (Ctrl+C)...", 0);
+            PublicServer::new(config, server_install_dir).serve(listen)
+        }
+    }
+}
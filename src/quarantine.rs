@@ -0,0 +1,252 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::QuarantineCommand;
+use crate::config::CONFIG_FILE;
+use crate::config::mod_entry::ModEntry;
+use crate::mod_validation::hash_file;
+use crate::server::{MISSIONS_DIR, SERVER_CONFIG};
+use crate::server_cfg::ServerDzConfig;
+use crate::state::state_dir;
+use crate::status::get_build_id;
+use crate::storage::list_files;
+use crate::ui::status::{println_step, println_success};
+use crate::workshop_manifest::WorkshopManifest;
+
+const STATE_FILE: &str = "quarantine.json";
+/// A server that stays up this long is taken to be healthy with its current mods
+pub const HEALTHY_MINUTES: i64 = 15;
+
+#[derive(Default, Serialize, Deserialize)]
+struct QuarantineState {
+    /// What was installed during the last healthy run
+    healthy: Option<Snapshot>,
+    #[serde(default)]
+    quarantined: BTreeMap<u64, QuarantinedMod>,
+}
+
+#[derive(Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Snapshot {
+    /// Steam build of the server
+    build: Option<String>,
+    /// Workshop ID to the manifest (content version) `SteamCMD` installed
+    mods: BTreeMap<u64, String>,
+    /// Config and mission file, relative to the install directory, to its hash
+    #[serde(default)]
+    files: BTreeMap<String, u64>,
+}
+
+/// A mod left out of the server's mod list, its files stay installed
+#[derive(Serialize, Deserialize)]
+pub struct QuarantinedMod {
+    pub name: String,
+    /// The version that crashed; a different one is given another chance
+    manifest: String,
+    pub since: DateTime<Local>,
+}
+
+impl QuarantineState {
+    fn load(server_install_dir: &Path) -> Self {
+        fs::read_to_string(get_state_path(server_install_dir))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, server_install_dir: &Path) -> Result<()> {
+        let path = get_state_path(server_install_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+}
+
+fn get_state_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(STATE_FILE)
+}
+
+fn take_snapshot(server_install_dir: &Path, manifest: &WorkshopManifest, mods: &[ModEntry]) -> Snapshot {
+    Snapshot {
+        build: get_build_id(server_install_dir),
+        mods: mods.iter()
+            .filter_map(|mod_entry| manifest.get(mod_entry.id).map(|item| (mod_entry.id, item.manifest.clone())))
+            .collect(),
+        files: hash_config_files(server_install_dir),
+    }
+}
+
+/// The files the server depends on besides its build and mods: `config.toml`, `serverDZ.cfg`,
+/// and the active mission, leaving out its persistence
+#[allow(clippy::doc_markdown)]
+fn hash_config_files(server_install_dir: &Path) -> BTreeMap<String, u64> {
+    let server_config = server_install_dir.join(SERVER_CONFIG);
+    let mut paths = vec![PathBuf::from(CONFIG_FILE), server_config.clone()];
+    if let Some(template) = ServerDzConfig::load(&server_config).ok().and_then(|cfg| cfg.get("template")) {
+        let mission_dir = server_install_dir.join(MISSIONS_DIR).join(template);
+        paths.extend(list_files(&mission_dir).unwrap_or_default().into_iter().filter(|path| {
+            !path.strip_prefix(&mission_dir).ok()
+                .and_then(|relative| relative.components().next())
+                .is_some_and(|first| first.as_os_str().to_string_lossy().starts_with("storage_"))
+        }));
+    }
+
+    paths.iter()
+        .filter_map(|path| {
+            let hash = hash_file(path).ok()?;
+            let name = path.strip_prefix(server_install_dir).unwrap_or(path).display().to_string();
+            Some((name, hash))
+        })
+        .collect()
+}
+
+/// Remember the installed server and mods as a known good combination
+pub fn record_healthy(server_install_dir: &Path, manifest: &WorkshopManifest, mods: &[ModEntry]) -> Result<()> {
+    let mut state = QuarantineState::load(server_install_dir);
+    let snapshot = take_snapshot(server_install_dir, manifest, mods);
+    if state.healthy.as_ref() == Some(&snapshot) {
+        return Ok(());
+    }
+    state.healthy = Some(snapshot);
+    state.save(server_install_dir)
+}
+
+/// The mod to blame for a crash loop: only when it's the one thing that changed since the
+/// last healthy run, with the same server build and no mods removed
+pub fn find_suspect(server_install_dir: &Path, manifest: &WorkshopManifest, mods: &[ModEntry]) -> Option<ModEntry> {
+    let state = QuarantineState::load(server_install_dir);
+    let healthy = state.healthy?;
+    let current = take_snapshot(server_install_dir, manifest, mods);
+    if current.build != healthy.build || healthy.mods.keys().any(|id| !current.mods.contains_key(id)) {
+        return None;
+    }
+
+    let mut changed = current.mods.iter()
+        .filter(|(id, version)| healthy.mods.get(*id) != Some(*version))
+        .filter(|(id, _)| !state.quarantined.contains_key(*id));
+    match (changed.next(), changed.next()) {
+        (Some((id, _)), None) => mods.iter().find(|mod_entry| mod_entry.id == *id).cloned(),
+        _ => None,
+    }
+}
+
+/// What changed since the last healthy run, one line per server build, mod, or file;
+/// None if no run has been healthy yet
+pub fn describe_changes(server_install_dir: &Path, manifest: &WorkshopManifest, mods: &[ModEntry]) -> Option<Vec<String>> {
+    let healthy = QuarantineState::load(server_install_dir).healthy?;
+    let current = take_snapshot(server_install_dir, manifest, mods);
+    let mut changes = Vec::new();
+
+    if current.build != healthy.build {
+        let unknown = || "unknown".to_string();
+        changes.push(format!(
+            "Server build {} -> {}",
+            healthy.build.unwrap_or_else(unknown),
+            current.build.unwrap_or_else(unknown)
+        ));
+    }
+
+    let get_name = |id: u64| mods.iter()
+        .find(|mod_entry| mod_entry.id == id)
+        .map_or_else(|| format!("Workshop item {id}"), |mod_entry| format!("{} ({id})", mod_entry.name));
+    for (id, version) in &current.mods {
+        match healthy.mods.get(id) {
+            None => changes.push(format!("Mod added: {}", get_name(*id))),
+            Some(healthy_version) if healthy_version != version => changes.push(format!("Mod updated: {}", get_name(*id))),
+            Some(_) => {}
+        }
+    }
+    for id in healthy.mods.keys().filter(|id| !current.mods.contains_key(id)) {
+        changes.push(format!("Mod removed: {}", get_name(*id)));
+    }
+
+    // Snapshots from before files were tracked would list every file as added
+    if !healthy.files.is_empty() {
+        for (file, hash) in &current.files {
+            match healthy.files.get(file) {
+                None => changes.push(format!("File added: {file}")),
+                Some(healthy_hash) if healthy_hash != hash => changes.push(format!("File modified: {file}")),
+                Some(_) => {}
+            }
+        }
+        for file in healthy.files.keys().filter(|file| !current.files.contains_key(*file)) {
+            changes.push(format!("File removed: {file}"));
+        }
+    }
+
+    Some(changes)
+}
+
+/// Leave a mod out of the server until it's updated or released
+pub fn quarantine(server_install_dir: &Path, mod_entry: &ModEntry, manifest: &WorkshopManifest) -> Result<()> {
+    let mut state = QuarantineState::load(server_install_dir);
+    state.quarantined.insert(mod_entry.id, QuarantinedMod {
+        name: mod_entry.name.clone(),
+        manifest: manifest.get(mod_entry.id).map(|item| item.manifest.clone()).unwrap_or_default(),
+        since: Local::now(),
+    });
+    state.save(server_install_dir)
+}
+
+pub fn get_quarantined(server_install_dir: &Path) -> BTreeMap<u64, QuarantinedMod> {
+    QuarantineState::load(server_install_dir).quarantined
+}
+
+/// Release the quarantined mods that have been updated since, returning their names
+pub fn release_updated(server_install_dir: &Path, manifest: &WorkshopManifest) -> Result<Vec<String>> {
+    let mut state = QuarantineState::load(server_install_dir);
+    let updated: Vec<u64> = state.quarantined.iter()
+        .filter(|(id, quarantined)| manifest.get(**id).is_some_and(|item| item.manifest != quarantined.manifest))
+        .map(|(id, _)| *id)
+        .collect();
+    if updated.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let names = updated.iter()
+        .filter_map(|id| state.quarantined.remove(id))
+        .map(|quarantined| quarantined.name)
+        .collect();
+    state.save(server_install_dir)?;
+    Ok(names)
+}
+
+/// Entry point for `dzsm quarantine ...`
+pub fn run(command: &QuarantineCommand, server_install_dir: &str) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+
+    match command {
+        QuarantineCommand::List => {
+            let quarantined = get_quarantined(server_install_dir);
+            if quarantined.is_empty() {
+                println_success("No mods are quarantined", 0);
+            }
+            for (id, quarantined) in &quarantined {
+                println_step(&format!(
+                    "{} ({id}), quarantined {}",
+                    quarantined.name,
+                    quarantined.since.format("%Y-%m-%d %H:%M")
+                ), 0);
+            }
+            Ok(())
+        }
+        QuarantineCommand::Release { target } => {
+            let mut state = QuarantineState::load(server_install_dir);
+            let target = target.trim().trim_start_matches('@');
+            let id = state.quarantined.iter()
+                .find(|(id, quarantined)| id.to_string() == target || quarantined.name.eq_ignore_ascii_case(target))
+                .map(|(id, _)| *id)
+                .ok_or_else(|| anyhow!("{target} is not quarantined, see `dzsm quarantine list`"))?;
+            let released = state.quarantined.remove(&id).map(|quarantined| quarantined.name).unwrap_or_default();
+            state.save(server_install_dir)?;
+            println_success(&format!("Released {released}, it loads again from the next start"), 0);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{Context, Result, anyhow};
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::battleye::BeServerConfig;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_PACKET_SIZE: usize = 4096;
+
+const PACKET_LOGIN: u8 = 0x00;
+const PACKET_COMMAND: u8 = 0x01;
+const PACKET_SERVER_MESSAGE: u8 = 0x02;
+
+/// A player in the `players` list
+pub struct RconPlayer {
+    /// The number commands such as `say` and `kick` take
+    pub number: u32,
+    pub name: String,
+}
+
+/// Client for BattlEye RCon, the remote console DayZ servers expose over UDP
+#[allow(clippy::doc_markdown)]
+pub struct RconClient {
+    socket: UdpSocket,
+    sequence: u8,
+}
+
+impl RconClient {
+    /// Connect using the RCon settings in the server's `BEServer_x64.cfg`
+    #[allow(clippy::doc_markdown)]
+    pub fn connect_to_server(server_install_dir: &Path) -> Result<Self> {
+        let battleye_cfg = BeServerConfig::load(server_install_dir)?;
+
+        let password = battleye_cfg.get("RConPassword")
+            .ok_or_else(|| anyhow!("RConPassword is not set in BEServer_x64.cfg"))?;
+        let port: u16 = battleye_cfg.get("RConPort")
+            .ok_or_else(|| anyhow!("RConPort is not set in BEServer_x64.cfg"))?
+            .parse()
+            .context("RConPort in BEServer_x64.cfg is not a valid port")?;
+        // The server listens on every interface unless RConIP says otherwise
+        let host = battleye_cfg.get("RConIP")
+            .filter(|ip| *ip != "0.0.0.0")
+            .unwrap_or("127.0.0.1");
+
+        let address: SocketAddr = format!("{host}:{port}").parse()
+            .context("Invalid RCon address in BEServer_x64.cfg")?;
+        Self::connect(address, password)
+    }
+
+    pub fn connect(address: SocketAddr, password: &str) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .context("Failed to open RCon socket")?;
+        socket.connect(address)
+            .context(format!("Failed to connect to RCon at {address}"))?;
+        socket.set_read_timeout(Some(TIMEOUT))?;
+
+        let client = Self { socket, sequence: 0 };
+
+        let mut login = vec![PACKET_LOGIN];
+        login.extend_from_slice(password.as_bytes());
+        client.send(&login)?;
+
+        let response = client.receive()
+            .context(format!("No RCon response from {address}, is the server running?"))?;
+        match response.as_slice() {
+            [PACKET_LOGIN, 1, ..] => Ok(client),
+            [PACKET_LOGIN, ..] => Err(anyhow!("RCon login rejected, check RConPassword")),
+            _ => Err(anyhow!("Unexpected RCon login response")),
+        }
+    }
+
+    /// Run a command such as `#lock` and return its output
+    pub fn command(&mut self, command: &str) -> Result<String> {
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let mut packet = vec![PACKET_COMMAND, sequence];
+        packet.extend_from_slice(command.as_bytes());
+        self.send(&packet)?;
+
+        let mut parts: Vec<Option<Vec<u8>>> = Vec::new();
+        loop {
+            let response = self.receive()
+                .context(format!("No RCon response to '{command}'"))?;
+
+            match response.as_slice() {
+                [PACKET_SERVER_MESSAGE, message_sequence, ..] => {
+                    // Chat and log messages must be acknowledged or the server drops us
+                    self.send(&[PACKET_SERVER_MESSAGE, *message_sequence])?;
+                }
+                [PACKET_COMMAND, response_sequence, 0x00, count, index, data @ ..] if *response_sequence == sequence => {
+                    // Long responses arrive in several parts
+                    if parts.is_empty() {
+                        parts.resize(usize::from(*count), None);
+                    }
+                    if let Some(part) = parts.get_mut(usize::from(*index)) {
+                        *part = Some(data.to_vec());
+                    }
+                    if parts.iter().all(Option::is_some) {
+                        let bytes: Vec<u8> = parts.into_iter().flatten().flatten().collect();
+                        return Ok(String::from_utf8_lossy(&bytes).to_string());
+                    }
+                }
+                [PACKET_COMMAND, response_sequence, data @ ..] if *response_sequence == sequence => {
+                    return Ok(String::from_utf8_lossy(data).to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Send a message to every player
+    pub fn say_all(&mut self, message: &str) -> Result<()> {
+        self.command(&format!("say -1 {message}")).map(|_| ())
+    }
+
+    /// Send a message to one player, by their number in the `players` list
+    pub fn say(&mut self, player: u32, message: &str) -> Result<()> {
+        self.command(&format!("say {player} {message}")).map(|_| ())
+    }
+
+    /// The players on the server, including those still in the lobby
+    pub fn get_players(&mut self) -> Result<Vec<RconPlayer>> {
+        Ok(parse_players(&self.command("players")?))
+    }
+
+    fn send(&self, payload: &[u8]) -> Result<()> {
+        self.socket.send(&encode_packet(payload))
+            .context("Failed to send RCon packet")?;
+        Ok(())
+    }
+
+    /// Receive the next valid packet and return everything after the 0xFF marker
+    fn receive(&self) -> Result<Vec<u8>> {
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        loop {
+            let length = self.socket.recv(&mut buffer)?;
+            if let Some(payload) = decode_packet(&buffer[..length]) {
+                return Ok(payload.to_vec());
+            }
+        }
+    }
+}
+
+/// Rows of the `players` table, `<#> <IP:port> <ping> <GUID>(OK) <name>`, with ` (Lobby)`
+/// after the name of players still joining
+fn parse_players(output: &str) -> Vec<RconPlayer> {
+    output.lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let number = columns.next()?.parse().ok()?;
+            let name = columns.skip(3).collect::<Vec<_>>().join(" ");
+            let name = name.strip_suffix(" (Lobby)").unwrap_or(&name).to_string();
+            (!name.is_empty()).then_some(RconPlayer { number, name })
+        })
+        .collect()
+}
+
+/// `'B' 'E' <crc32 of the rest, little endian> 0xFF <payload>`
+fn encode_packet(payload: &[u8]) -> Vec<u8> {
+    let mut body = vec![0xFF];
+    body.extend_from_slice(payload);
+
+    let mut packet = b"BE".to_vec();
+    packet.extend_from_slice(&crc32(&body).to_le_bytes());
+    packet.extend_from_slice(&body);
+    packet
+}
+
+fn decode_packet(packet: &[u8]) -> Option<&[u8]> {
+    let (header, body) = packet.split_at_checked(6)?;
+    let checksum = header.strip_prefix(b"BE")?;
+    if checksum != crc32(body).to_le_bytes() || body.first() != Some(&0xFF) {
+        return None;
+    }
+    Some(&body[1..])
+}
+
+/// CRC-32 (IEEE), as used by the BattlEye RCon protocol
+#[allow(clippy::doc_markdown)]
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(0xFFFF_FFFF_u32, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| {
+            if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 }
+        })
+    })
+}
@@ -0,0 +1,69 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone};
+
+use crate::config::ScheduleConfig;
+
+/// Daily restart times from `[schedule]`
+pub struct RestartSchedule {
+    times: Vec<NaiveTime>,
+    warning_minutes: Vec<u64>,
+    warning_message: String,
+    max_defer: Option<Duration>,
+}
+
+impl RestartSchedule {
+    pub fn from_config(config: &ScheduleConfig) -> Result<Self> {
+        let mut times = config.restart_times.iter()
+            .map(|time| {
+                NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                    .map_err(|_| anyhow!("Invalid restart time '{time}' in `schedule.restart_times`, expected HH:MM"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        times.sort();
+        times.dedup();
+
+        // Largest first, so warnings go out in the order they fall due
+        let mut warning_minutes = config.warning_minutes.clone();
+        warning_minutes.sort_unstable_by(|a, b| b.cmp(a));
+        warning_minutes.dedup();
+
+        Ok(Self {
+            times,
+            warning_minutes,
+            warning_message: config.warning_message.clone(),
+            max_defer: config.defer_while_players_online
+                .then(|| {
+                    i64::try_from(config.max_defer_minutes).ok()
+                        .and_then(Duration::try_minutes)
+                        .unwrap_or(Duration::MAX)
+                }),
+        })
+    }
+
+    /// The first scheduled restart strictly after `now`
+    pub fn next_restart_after(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let today = now.date_naive();
+
+        [today, today + Duration::days(1)].into_iter()
+            .flat_map(|date| self.times.iter().map(move |time| date.and_time(*time)))
+            // Times skipped by a DST change don't exist locally
+            .filter_map(|naive| Local.from_local_datetime(&naive).earliest())
+            .find(|restart| *restart > now)
+    }
+
+    /// How long a restart may be held while players are online, if at all
+    pub const fn max_defer(&self) -> Option<Duration> {
+        self.max_defer
+    }
+
+    /// Warnings for a restart, as (when to send, message), earliest first
+    pub fn warnings_for(&self, restart: DateTime<Local>) -> Vec<(DateTime<Local>, String)> {
+        self.warning_minutes.iter()
+            .filter_map(|minutes| {
+                let at = restart - Duration::minutes(i64::try_from(*minutes).ok()?);
+                let message = self.warning_message.replace("{minutes}", &minutes.to_string());
+                Some((at, message))
+            })
+            .collect()
+    }
+}
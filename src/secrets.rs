@@ -0,0 +1,204 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::battleye::BeServerConfig;
+use crate::cli::SecretsCommand;
+use crate::config::secrets_config::SecretKind;
+use crate::config::{Config, SecretsConfig};
+use crate::crypto;
+use crate::server::SERVER_CONFIG;
+use crate::server_cfg::ServerDzConfig;
+use crate::state::state_dir;
+use crate::ui::json::print_output;
+use crate::ui::status::{println_step, println_success};
+
+const SECRETS_FILE: &str = "secrets.dat";
+
+/// Current passwords, stored encrypted in the state directory
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SecretsState {
+    server_password: Option<String>,
+    admin_password: Option<String>,
+    rcon_password: Option<String>,
+    last_rotated: Option<DateTime<Utc>>,
+}
+
+impl SecretsState {
+    fn get(&self, kind: SecretKind) -> Option<&str> {
+        match kind {
+            SecretKind::Server => self.server_password.as_deref(),
+            SecretKind::Admin => self.admin_password.as_deref(),
+            SecretKind::Rcon => self.rcon_password.as_deref(),
+        }
+    }
+
+    fn set(&mut self, kind: SecretKind, password: String) {
+        match kind {
+            SecretKind::Server => self.server_password = Some(password),
+            SecretKind::Admin => self.admin_password = Some(password),
+            SecretKind::Rcon => self.rcon_password = Some(password),
+        }
+    }
+}
+
+/// Rotates the server, admin, and RCON passwords on a schedule
+pub struct SecretsManager<'a> {
+    config: &'a SecretsConfig,
+    server_install_dir: &'a Path,
+}
+
+impl<'a> SecretsManager<'a> {
+    pub const fn new(config: &'a SecretsConfig, server_install_dir: &'a Path) -> Self {
+        Self { config, server_install_dir }
+    }
+
+    /// Rotate the configured passwords if the rotation interval has passed
+    pub fn rotate_if_due(&self) -> Result<()> {
+        if self.config.rotate.is_empty() {
+            return Ok(());
+        }
+
+        let state = self.load_state()?;
+        if let Some(next_rotation) = self.next_rotation(&state)
+            && Utc::now() < next_rotation
+        {
+            println_success(&format!(
+                "Passwords are current, next rotation: {}",
+                next_rotation.format("%Y-%m-%d")
+            ), 1);
+            return Ok(());
+        }
+
+        self.rotate(state)
+    }
+
+    /// Rotate the configured passwords immediately
+    pub fn rotate_now(&self) -> Result<()> {
+        if self.config.rotate.is_empty() {
+            return Err(anyhow!("No passwords to rotate. Set `secrets.rotate` in config.toml"));
+        }
+
+        let state = self.load_state()?;
+        self.rotate(state)
+    }
+
+    /// Print the current passwords
+    pub fn show(&self) -> Result<()> {
+        let state = self.load_state()?;
+
+        let Some(last_rotated) = state.last_rotated else {
+            println_step("No passwords have been rotated yet", 1);
+            return Ok(());
+        };
+
+        println_step(&format!("Passwords last rotated: {}", last_rotated.format("%Y-%m-%d %H:%M UTC")), 1);
+        for (label, kind) in [("server", SecretKind::Server), ("admin", SecretKind::Admin), ("rcon", SecretKind::Rcon)] {
+            if let Some(password) = state.get(kind) {
+                // Printed directly so passwords never end up in the log file
+                print_output(&format!("    {label}: {password}"));
+            }
+        }
+
+        if let Some(next_rotation) = self.next_rotation(&state) {
+            println_step(&format!("Next rotation: {}", next_rotation.format("%Y-%m-%d")), 1);
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&self, mut state: SecretsState) -> Result<()> {
+        println_step("Rotating passwords...", 1);
+
+        for kind in &self.config.rotate {
+            state.set(*kind, crypto::random_alphanumeric(self.config.password_length)?);
+        }
+
+        // Kept before they go into the config files, so a failed save can't leave the server
+        // with passwords no one knows; only marked rotated once they're in place
+        self.save_state(&state)?;
+        self.apply(&state)?;
+
+        state.last_rotated = Some(Utc::now());
+        self.save_state(&state)?;
+
+        println_success("Passwords rotated (view them with `dzsm secrets show`)", 1);
+        Ok(())
+    }
+
+    /// Write the rotated passwords into the server's config files
+    fn apply(&self, state: &SecretsState) -> Result<()> {
+        let rotates = |kind| self.config.rotate.contains(&kind);
+
+        if rotates(SecretKind::Server) || rotates(SecretKind::Admin) {
+            let mut server_cfg = ServerDzConfig::load(&self.server_install_dir.join(SERVER_CONFIG))?;
+            if let Some(password) = state.get(SecretKind::Server).filter(|_| rotates(SecretKind::Server)) {
+                server_cfg.set_string("password", password);
+            }
+            if let Some(password) = state.get(SecretKind::Admin).filter(|_| rotates(SecretKind::Admin)) {
+                server_cfg.set_string("passwordAdmin", password);
+            }
+            server_cfg.save()?;
+        }
+
+        if let Some(password) = state.get(SecretKind::Rcon).filter(|_| rotates(SecretKind::Rcon)) {
+            let mut battleye_cfg = BeServerConfig::load(self.server_install_dir)?;
+            battleye_cfg.set("RConPassword", password);
+            battleye_cfg.save()?;
+        }
+
+        Ok(())
+    }
+
+    fn next_rotation(&self, state: &SecretsState) -> Option<DateTime<Utc>> {
+        state.last_rotated
+            .map(|last| last + Duration::days(i64::from(self.config.rotate_every_days)))
+    }
+
+    fn get_secrets_path(&self) -> PathBuf {
+        state_dir(self.server_install_dir).join(SECRETS_FILE)
+    }
+
+    fn load_state(&self) -> Result<SecretsState> {
+        let path = self.get_secrets_path();
+        if !path.exists() {
+            return Ok(SecretsState::default());
+        }
+
+        let encrypted = fs::read(&path)
+            .context(format!("Failed to read {}", path.display()))?;
+        let decrypted = crypto::unprotect(&encrypted)?;
+        let content = String::from_utf8(decrypted)
+            .context("Stored secrets are not valid UTF-8")?;
+
+        toml::from_str(&content)
+            .context("Failed to parse stored secrets")
+    }
+
+    fn save_state(&self, state: &SecretsState) -> Result<()> {
+        let path = self.get_secrets_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create state directory")?;
+        }
+
+        let content = toml::to_string(state)
+            .context("Failed to serialize secrets")?;
+        let encrypted = crypto::protect(content.as_bytes())?;
+
+        fs::write(&path, encrypted)
+            .context(format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Entry point for `dzsm secrets ...`
+pub fn run(command: &SecretsCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    let manager = SecretsManager::new(&config.secrets, Path::new(server_install_dir));
+
+    match command {
+        SecretsCommand::Show => manager.show(),
+        SecretsCommand::Rotate => manager.rotate_now(),
+    }
+}
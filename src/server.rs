@@ -1,30 +1,90 @@
 use anyhow::{Context, Result, anyhow};
-use std::os::windows::fs::{symlink_dir, symlink_file};
-use std::fs;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
-use std::cell::OnceCell;
+use std::fs::{self, File};
+use std::io;
+use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::iter;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::cli::CliArgs;
 
-use crate::config::Config;
-use crate::config::mod_entry::ModEntry;
+use crate::config::{Config, NotificationEvent};
+use crate::config::mod_entry::{ModEntry, ModSide};
 
 use crate::steamcmd::{SteamCmdManager};
+use crate::steamcmd_errors::{self, SteamCmdFailure};
 
-use crate::ui::status::{println_step, println_success, println_failure};
+use crate::ui::status::{println_blank, println_step, println_step_concat, println_success, println_failure};
+use crate::ui::json::child_stdout;
+use crate::ui::prompt::child_stdin;
+use crate::ui::title::ConsoleTitle;
 
+use crate::a2s;
+use crate::battleye;
+use crate::cleanup;
+use crate::control;
+use crate::disk_space;
+use crate::economy;
+use crate::interrupt;
+use crate::maintenance;
+use crate::load_order::sort_mods;
+use crate::mods::{InstallPlan, PlannedMod};
+use crate::mod_validation;
+use crate::download_schedule;
+use crate::players;
+use crate::privacy;
+use crate::profile_files;
+use crate::quarantine;
+use crate::public_info;
+use crate::rcon::{RconClient, RconPlayer};
+use crate::server_builds;
+use crate::server_cfg::ServerDzConfig;
+use crate::server_logs;
+use crate::secrets::SecretsManager;
+use crate::server_time;
+use crate::state::logs_dir;
+use crate::storage::{StorageGuard, dir_size, format_size};
+use crate::supervisor::{clear_stale_stop_request, clear_stop_request, stop_requested, take_restart_request};
+use crate::weather;
+
+use crate::collection_dupes;
 use crate::collection_fetcher::CollectionFetcher;
+use crate::mod_info::ModInfoPrefetch;
+use crate::workshop_details::{self, WorkshopDetails};
 
-#[allow(clippy::unreadable_literal)]
-const DAYZ_SERVER_APP_ID: u32 = 223350;
-#[allow(clippy::unreadable_literal)]
-const DAYZ_GAME_APP_ID: u32 = 221100;
+use crate::crash::CrashReport;
+use crate::notifier::{Notification, Notifier};
+use crate::update_digest::UpdateDigest;
+use crate::workshop_manifest::WorkshopManifest;
 
-const SERVER_EXE: &str = "DayZServer_x64.exe";
-const SERVER_KEYS: &str = "keys";
-const SERVER_CONFIG: &str = "serverDZ.cfg";
-const SERVER_PROFILES: &str = "profiles";
+pub const SERVER_EXE: &str = "DayZServer_x64.exe";
+/// The game port when `server.port` isn't set
+pub const DEFAULT_GAME_PORT: u16 = 2302;
+const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+/// No console at all, rather than a new one or DZSM's
+const DETACHED_PROCESS: u32 = 0x0000_0008;
+const CREATE_BREAKAWAY_FROM_JOB: u32 = 0x0100_0000;
+/// Output of a server started with `--detach`
+const CONSOLE_LOG: &str = "server-console.log";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Roughly what a fresh server install downloads, for the free space check
+const SERVER_DOWNLOAD_ESTIMATE: u64 = 3 * 1024 * 1024 * 1024;
+/// A mod with less than this share of the size the Workshop lists on disk is taken as truncated
+const TRUNCATED_PERCENT: u64 = 99;
+/// Longest wait between mod download attempts, however many timed out
+const MAX_DOWNLOAD_RETRY_DELAY_SECONDS: u64 = 300;
+pub const SERVER_KEYS: &str = "keys";
+/// The server's own key, never removed
+pub const VANILLA_KEY: &str = "dayz.bikey";
+pub const SERVER_CONFIG: &str = "serverDZ.cfg";
+pub const SERVER_PROFILES: &str = "profiles";
+pub const MISSIONS_DIR: &str = "mpmissions";
+/// Marks what a dry run only reports
+pub const DRY_RUN: &str = "[dry-run]";
 
 pub struct ServerManager {
     args: CliArgs,
@@ -32,6 +92,17 @@ pub struct ServerManager {
     server_install_dir: PathBuf,
     steamcmd_manager: Option<SteamCmdManager>,
     collection_mod_list: OnceCell<Vec<ModEntry>>,
+    /// Why the collections couldn't be loaded, so they aren't fetched again for the rest of the run
+    collection_error: OnceCell<String>,
+    /// Collection pages and Workshop details being fetched while the server updates
+    mod_info_prefetch: RefCell<Option<ModInfoPrefetch>>,
+    /// Set once `server.username` gets a license error, the rest of the mods are downloaded
+    /// with `steam.workshop_username` straight away
+    use_workshop_account: Cell<bool>,
+    dry_run: bool,
+    detach: bool,
+    /// Called once the server has started for the first time
+    on_first_launch: RefCell<Option<Box<dyn FnOnce() + Send>>>,
 }
 
 impl ServerManager {
@@ -42,12 +113,58 @@ impl ServerManager {
             server_install_dir: PathBuf::from(server_install_dir),
             steamcmd_manager: None,
             collection_mod_list: OnceCell::new(),
+            collection_error: OnceCell::new(),
+            mod_info_prefetch: RefCell::new(None),
+            use_workshop_account: Cell::new(false),
+            dry_run: false,
+            detach: false,
+            on_first_launch: RefCell::new(None),
         }
     }
 
+    /// Report what would be deleted, downloaded, linked, and launched instead of doing it;
+    /// nothing on disk changes and nothing is fetched
+    pub const fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Call `callback` once the server has started for the first time, after updating went well
+    pub fn on_first_launch(self, callback: impl FnOnce() + Send + 'static) -> Self {
+        *self.on_first_launch.borrow_mut() = Some(Box::new(callback));
+        self
+    }
+
+    /// Start the server on its own with its output going to a log, and return once it's
+    /// running, so it outlives the console or remote session DZSM was started from
+    pub const fn detach(mut self) -> Self {
+        self.detach = true;
+        self
+    }
+
+    pub const fn config(&self) -> &Config {
+        &self.config
+    }
+
     pub fn setup_steamcmd(&mut self) -> Result<()> {  // Make self mutable
+        if self.dry_run {
+            let steamcmd = SteamCmdManager::without_install(&self.config.server.steamcmd_dir, self.args.offline)
+                .with_options(&self.config)?;
+            if !steamcmd.get_exe_path().exists() {
+                println_step(&format!("{DRY_RUN} Would install SteamCMD to {}", self.config.server.steamcmd_dir), 1);
+            }
+            if let Some(cell_id) = self.config.server.cell_id {
+                println_step(&format!("{DRY_RUN} Would set SteamCMD's content server cell to {cell_id}"), 1);
+            }
+            self.steamcmd_manager = Some(steamcmd);
+            return Ok(());
+        }
+
         // Handle the Result and extract the value
-        let steamcmd = SteamCmdManager::new(&self.config.server.steamcmd_dir, self.args.offline)?;
+        let steamcmd = SteamCmdManager::new(&self.config.server.steamcmd_dir, self.args.offline)?
+            .with_options(&self.config)?;
+        // Also clears an override left behind when `cell_id` is removed from the config
+        steamcmd.set_cell_override(self.config.server.cell_id)?;
         self.steamcmd_manager = Some(steamcmd);
         Ok(())
     }
@@ -58,6 +175,9 @@ impl ServerManager {
             return Err(anyhow!("SteamCMD has not been setup yet."));
         }
 
+        self.set_console_status("updating");
+        self.start_mod_info_prefetch();
+
         if self.args.offline {
             if self.get_server_exe_path().exists() {
                 println_step("Skipping checking for updates (offline mode enabled)...", 1);
@@ -67,56 +187,205 @@ impl ServerManager {
                     SERVER_EXE
                 ));
             }
+        } else if self.dry_run {
+            let validate = self.args.skip_validation || self.args.skip_server_validation;
+            println_step(&format!(
+                "{DRY_RUN} Would install or update the DayZ server (app {}) in {}{}",
+                self.config.server.branch.server_app_id(),
+                self.server_install_dir.display(),
+                if validate { ", validating it" } else { "" }
+            ), 1);
         } else {
             // Get reference to steamcmd manager
             let steamcmd = self.steamcmd_manager.as_ref().unwrap();
             let server_config = &self.config.server;  // Take reference
 
+            if server_builds::apply_pin(server_config, &self.server_install_dir)? {
+                return Ok(());
+            }
+            // Kept for `dzsm rollback` when the update breaks mods
+            if let Err(e) = server_builds::backup_before_update(server_config, &self.server_install_dir) {
+                println_failure(&format!("Failed to back up the installed server build: {e:#}"), 1);
+            }
+
+            // An update's size isn't known up front, only a fresh install's is roughly
+            let needed = if self.get_server_exe_path().exists() { 0 } else { SERVER_DOWNLOAD_ESTIMATE };
+            disk_space::ensure_free_space(&self.config.downloads, &self.server_install_dir, needed, "the DayZ server")?;
+
             println_step("Installing or updating DayZ Server application...\n", 1);
-            
-            steamcmd.install_or_update_app(
-                &self.server_install_dir.to_string_lossy(),  // Convert PathBuf to &str
-                &server_config.username,
-                DAYZ_SERVER_APP_ID,
-                self.args.skip_validation || self.args.skip_server_validation
-            )?; 
 
-            println!();
+            let branch = server_builds::get_branch(server_config, &self.server_install_dir);
+            let attempts = server_config.update_attempts.max(1);
+            let mut validate = self.args.skip_validation || self.args.skip_server_validation;
+            for attempt in 1..=attempts {
+                let partial_before = SteamCmdManager::get_partial_download_size(&self.server_install_dir, server_config.branch.server_app_id());
+                let Err(e) = steamcmd.install_or_update_app(
+                    &self.server_install_dir,
+                    self.config.steam.get_server_username(&server_config.username),
+                    server_config.branch.server_app_id(),
+                    branch.as_deref(),
+                    validate
+                ) else {
+                    break;
+                };
+                println_blank();
+
+                let retryable = steamcmd_errors::find_failure(&e).is_none_or(SteamCmdFailure::is_retryable);
+                if attempt == attempts || !retryable || interrupt::shutdown_requested() {
+                    if self.config.steam.anonymous_server_updates && steamcmd_errors::find_failure(&e) == Some(SteamCmdFailure::NoSubscription) {
+                        return Err(e.context("The server can't be downloaded anonymously, set `steam.anonymous_server_updates = false`"));
+                    }
+                    return Err(e.context(format!("Server update failed after {attempt} attempt(s)")));
+                }
+                self.report_partial_download(partial_before);
+
+                // Validating re-checks everything already downloaded, which on a flaky link
+                // can take longer than the connection stays up
+                validate = false;
+                println_step(&format!(
+                    "Server update failed ({e}), retrying in {} seconds (attempt {}/{attempts})...",
+                    server_config.update_retry_delay_seconds,
+                    attempt + 1
+                ), 1);
+                thread::sleep(Duration::from_secs(server_config.update_retry_delay_seconds));
+            }
+
+            println_blank();
         }
 
         Ok(())
     }
 
-    pub fn install_or_update_mods(&self) -> Result<()> {
-        self.uninstall_prev_mod_installations();
+    /// Stop before `SteamCMD` starts if the mods about to download won't fit, in its workshop dir
+    /// and, when they're copied to another drive, in the server install dir too
+    fn ensure_mod_space(&self, ids: &[u64], manifest: &WorkshopManifest, deferred: &BTreeSet<u64>) -> Result<()> {
+        if self.config.downloads.min_free_mb == 0 {
+            return Ok(());
+        }
+
+        let details = match self.get_workshop_details(ids) {
+            Ok(details) => details,
+            Err(e) => {
+                println_failure(&format!("Failed to check the size of mod downloads, skipping the free space check: {e:#}"), 1);
+                return Ok(());
+            }
+        };
+        let needed = download_schedule::get_download_size(ids, manifest, &details, deferred);
+        if needed == 0 {
+            return Ok(());
+        }
 
-        let individual_mods = self.get_individual_mods();
-        let collection_mods = self.get_collection_mods();
+        let steamcmd_dir = Path::new(&self.config.server.steamcmd_dir);
+        disk_space::ensure_free_space(&self.config.downloads, steamcmd_dir, needed, "mod downloads")?;
+        if self.config.mods.install_strategy.is_copy() && !disk_space::is_same_drive(steamcmd_dir, &self.server_install_dir) {
+            disk_space::ensure_free_space(&self.config.downloads, &self.server_install_dir, needed, "mod copies")?;
+        }
+        Ok(())
+    }
+
+    /// Say whether a failed update left a partial download for the next attempt to resume
+    fn report_partial_download(&self, size_before: u64) {
+        let size_after = SteamCmdManager::get_partial_download_size(&self.server_install_dir, self.config.server.branch.server_app_id());
+        if size_after < size_before {
+            println_failure(&format!(
+                "SteamCMD discarded {} of the partial download, the download restarted from scratch",
+                format_size(size_before - size_after)
+            ), 1);
+        } else if size_after > 0 {
+            println_step(&format!("{} downloaded so far, the next attempt resumes from there", format_size(size_after)), 1);
+        }
+    }
+
+    pub fn install_or_update_mods(&self) -> Result<()> {
+        let plan = self.plan_mods()?;
+        plan.clean();
         
         // Check if we have any mods to install
-        if individual_mods.is_empty() && collection_mods.is_empty() {
+        if plan.mods.is_empty() && plan.local_mods.is_empty() {
             println_success("No mods configured, skipping mod installation", 0);
             return Ok(());
         }
 
+        // Snapshot SteamCMD's workshop state so changed mods can be reported afterwards
+        let manifest_before = self.load_workshop_manifest();
+
+        // Mods the nightly check found damaged get a full validate
+        let damaged_mods = mod_validation::get_pending(&self.server_install_dir);
+
+        // Large updates wait for the download window, the installed versions are used until then
+        let ids: Vec<u64> = plan.mods.iter().map(|planned| planned.entry.id).filter(|id| !damaged_mods.contains(id)).collect();
+        let deferred_mods = match download_schedule::is_deferring(&self.config.downloads) {
+            Ok(true) if !self.args.offline && !self.dry_run => self.get_workshop_details(&ids)
+                .map(|details| download_schedule::find_deferred(&self.config.downloads, &ids, &manifest_before, &details)),
+            Ok(_) => Ok(BTreeSet::new()),
+            Err(e) => Err(e),
+        }.unwrap_or_else(|e| {
+            println_failure(&format!("Failed to check the size of mod updates, downloading them now: {e:#}"), 1);
+            BTreeSet::new()
+        });
+
+        if !self.args.offline && !self.dry_run {
+            self.ensure_mod_space(&ids, &manifest_before, &deferred_mods)?;
+        }
+
+        let shared_mods = self.find_shared_downloads(&ids);
+        let workshop_sizes = self.get_workshop_sizes(&ids);
+
         let mut failed_mods = Vec::new();
 
-        // Install individual mods
-        for mod_entry in individual_mods {
-            if let Err(e) = self.install_mod(mod_entry.id, &mod_entry.name) {
-                println_failure(&format!("Failed to install mod {}: {}", mod_entry.name, e), 3);
-                failed_mods.push(mod_entry.name.clone());
+        // Individual mods first, then collection mods
+        for planned in &plan.mods {
+            let (damaged, deferred) = (damaged_mods.contains(&planned.entry.id), deferred_mods.contains(&planned.entry.id));
+            let shared = shared_mods.get(&planned.entry.id).map(PathBuf::as_path);
+            let expected_size = workshop_sizes.get(&planned.entry.id).copied();
+            if let Err(e) = self.install_mod(&plan, planned, damaged, deferred, shared, expected_size) {
+                match planned.entry.collection.as_deref() {
+                    Some(collection) => println_failure(&format!("Failed to install mod {} (from {collection}): {e:#}", planned.entry.name), 3),
+                    None => println_failure(&format!("Failed to install mod {}: {e:#}", planned.entry.name), 3),
+                }
+                failed_mods.push(planned.entry.name.clone());
+            }
+        }
+        for planned in &plan.local_mods {
+            if let Err(e) = self.install_local_mod(&plan, planned) {
+                println_failure(&format!("Failed to install local mod {}: {e:#}", planned.entry.name), 3);
+                failed_mods.push(planned.entry.name.clone());
             }
         }
 
-        // Install collection mods
-        for mod_entry in collection_mods {
-            if let Err(e) = self.install_mod(mod_entry.id, &mod_entry.name) {
-                println_failure(&format!("Failed to install mod {}: {}", mod_entry.name, e), 3);
-                failed_mods.push(mod_entry.name.clone());
+        // Only one of two different keys with the same name can be installed, players of the other mod are kicked
+        if !self.dry_run {
+            match plan.check_keys() {
+                Ok(check) => for (key_name, mods) in &check.conflicts {
+                    println_failure(&format!("{} ship different keys named {key_name}, only one is installed", mods.join(" and ")), 1);
+                },
+                Err(e) => println_failure(&format!("Failed to check mod keys: {e:#}"), 1),
+            }
+            // Caught here rather than when players connect and get kicked
+            match plan.check_signatures() {
+                Ok(check) => {
+                    for (name, pbos) in &check.unsigned {
+                        println_failure(&format!("{name} has unsigned PBO(s), players using it are kicked: {}", pbos.join(", ")), 1);
+                    }
+                    for (name, authority) in &check.untrusted {
+                        println_failure(&format!("{name} is signed by {authority}, whose key isn't in {SERVER_KEYS}/; players using it are kicked"), 1);
+                    }
+                    for (name, e) in &check.unreadable {
+                        println_failure(&format!("Failed to check a signature of {name}: {e}"), 1);
+                    }
+                }
+                Err(e) => println_failure(&format!("Failed to check mod signatures: {e:#}"), 1),
             }
         }
 
+        if !self.args.offline && !self.dry_run {
+            self.publish_update_digest(&manifest_before);
+            self.release_updated_mods();
+        }
+        if !self.dry_run {
+            public_info::write_mods_file(&self.config, &self.server_install_dir);
+        }
+
         // Report results
         if failed_mods.is_empty() {
             println_success("All mods installed successfully", 0);
@@ -133,6 +402,206 @@ impl ServerManager {
     /// Run the DayZ server with configured mods
     #[allow(clippy::doc_markdown)]
     pub fn run_server(&self) -> Result<()> {
+        if self.dry_run {
+            println_step(&format!(
+                "{DRY_RUN} Would check persistence, run due cleanup tasks, trim old server logs, rotate due passwords, keep any maintenance password, and apply the BattlEye, player list, privacy, time, weather, economy, and profile file settings"
+            ), 1);
+            println_step(&format!("{DRY_RUN} Would execute: {SERVER_EXE} {}", self.build_launch_args()?.join(" ")), 1);
+            return Ok(());
+        }
+
+        // A stale request from a previous run would stop or restart the server straight away
+        clear_stale_stop_request(&self.server_install_dir)?;
+        take_restart_request(&self.server_install_dir)?;
+        // `dzsm stop` while updating
+        if stop_requested(&self.server_install_dir) {
+            clear_stop_request(&self.server_install_dir)?;
+            println_success("Stop requested, not starting the DayZ server", 0);
+            return Ok(());
+        }
+        let mut child = self.launch_server()?;
+        if self.detach {
+            println_success(&format!(
+                "DayZ server started in the background (PID {}), output goes to {}; stop it with `dzsm stop`",
+                child.id(),
+                self.get_console_log_path().display()
+            ), 0);
+            return Ok(());
+        }
+        let mut healthy_at = Instant::now() + Duration::from_mins(quarantine::HEALTHY_MINUTES.unsigned_abs());
+        let mut recorded_healthy = false;
+
+        // Poll rather than block so Ctrl+C, or another DZSM asking, can shut the server down gracefully
+        let status = loop {
+            if interrupt::shutdown_requested() || stop_requested(&self.server_install_dir) {
+                self.shutdown_server(&mut child, true)?;
+                clear_stop_request(&self.server_install_dir)?;
+                println_success("DayZ server has stopped", 0);
+                return Ok(());
+            }
+            // `dzsm restart` from another terminal
+            if let Some(update) = take_restart_request(&self.server_install_dir)? {
+                child = self.restart_server(&mut child, update)?;
+                healthy_at = Instant::now() + Duration::from_mins(quarantine::HEALTHY_MINUTES.unsigned_abs());
+                recorded_healthy = false;
+                continue;
+            }
+            if !recorded_healthy && Instant::now() >= healthy_at {
+                self.record_healthy_run();
+                recorded_healthy = true;
+            }
+
+            match child.try_wait() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) => thread::sleep(POLL_INTERVAL),
+                Err(e) => break Err(e),
+            }
+        };
+        interrupt::set_server_running(false);
+        self.set_console_status("stopped");
+        let status = status.context("Failed to wait for DayZ server process")?;
+
+        if !status.success() {
+            self.report_crash(status.code());
+            return Err(anyhow!(
+                "DayZ server exited with error code: {:?}", 
+                status.code()
+            ));
+        }
+        
+        println_success("DayZ server has stopped", 0);
+        Ok(())
+    }
+
+    /// Shut the server down with players warned, update the server and mods if asked, and start it again
+    fn restart_server(&self, child: &mut Child, update: bool) -> Result<Child> {
+        self.shutdown_server(child, true)?;
+        // Started with --no-update, which leaves SteamCMD as it is
+        if update && self.steamcmd_manager.is_none() {
+            println_failure("Not updating, the server was started with --no-update", 0);
+        } else if update {
+            self.install_or_update_server()?;
+            self.install_or_update_mods()?;
+        }
+        println_step("Restarting DayZ server...", 0);
+        self.launch_server()
+    }
+
+    /// Warn players, lock the server, wait out the grace period, then `#shutdown` it over RCon,
+    /// only killing the process if that fails. Pass `warn: false` when players were already warned.
+    #[allow(clippy::doc_markdown)]
+    pub fn shutdown_server(&self, child: &mut Child, warn: bool) -> Result<()> {
+        self.set_console_status("stopping");
+        println_step("Shutting down DayZ server...", 0);
+
+        let exited = match RconClient::connect_to_server(&self.server_install_dir) {
+            Ok(mut rcon) => self.shutdown_over_rcon(&mut rcon, child, warn)?,
+            Err(e) => {
+                println_failure(&format!("RCon unavailable, stopping the server process instead: {e:#}"), 1);
+                // Players can't be warned without RCon, at least record how many were dropped
+                if let Ok(info) = a2s::query_server(&self.server_install_dir)
+                    && info.human_players() > 0
+                {
+                    println_failure(&format!("{} player(s) online were dropped without a warning", info.human_players()), 1);
+                }
+                false
+            }
+        };
+
+        if !exited {
+            child.kill().context("Failed to stop DayZ server")?;
+            child.wait().context("Failed to wait for DayZ server process")?;
+        }
+
+        interrupt::set_server_running(false);
+        self.set_console_status("stopped");
+        Ok(())
+    }
+
+    /// Returns true if the server exited on its own
+    fn shutdown_over_rcon(&self, rcon: &mut RconClient, child: &mut Child, warn: bool) -> Result<bool> {
+        let config = &self.config.shutdown;
+
+        // Logged so who was dropped can be looked up later
+        let players = match rcon.get_players() {
+            Ok(players) => {
+                if players.is_empty() {
+                    println_step("No players online", 1);
+                } else {
+                    let names: Vec<&str> = players.iter().map(|player| player.name.as_str()).collect();
+                    println_step(&format!("Online at shutdown ({}): {}", players.len(), names.join(", ")), 1);
+                }
+                Some(players)
+            }
+            Err(e) => {
+                println_failure(&format!("Failed to list players: {e}"), 1);
+                None
+            }
+        };
+
+        // Nobody to warn on an empty server, so there's nothing to wait for either
+        if warn && players.as_ref().is_none_or(|players| !players.is_empty()) {
+            if config.lock {
+                match rcon.command("#lock") {
+                    Ok(_) => println_step("Server locked", 1),
+                    Err(e) => println_failure(&format!("Failed to lock server: {e}"), 1),
+                }
+            }
+
+            println_step(&format!(
+                "Waiting {} seconds before shutting down (press Ctrl+C again to skip)...",
+                config.grace_period_seconds
+            ), 1);
+            if self.count_down(rcon, child, players.as_deref())? {
+                return Ok(true);
+            }
+        }
+
+        if let Err(e) = rcon.command("#shutdown") {
+            println_failure(&format!("Failed to send #shutdown: {e}"), 1);
+            return Ok(false);
+        }
+        println_step("Sent #shutdown, waiting for the server to exit...", 1);
+
+        let exited = wait_for_exit(child, Duration::from_secs(config.exit_timeout_seconds))?;
+        if !exited {
+            println_failure("DayZ server did not exit in time, stopping the process", 1);
+        }
+        Ok(exited)
+    }
+
+    /// Wait out the grace period, warning players at the start and again at each
+    /// `shutdown.countdown_seconds` mark. Returns true if the server exited meanwhile.
+    fn count_down(&self, rcon: &mut RconClient, child: &mut Child, players: Option<&[RconPlayer]>) -> Result<bool> {
+        let config = &self.config.shutdown;
+        let mut marks: Vec<u64> = config.countdown_seconds.iter()
+            .copied()
+            .filter(|seconds| *seconds < config.grace_period_seconds)
+            .collect();
+        marks.sort_unstable_by(|a, b| b.cmp(a));
+        marks.dedup();
+
+        let mut remaining = config.grace_period_seconds;
+        for seconds_left in iter::once(remaining).chain(marks) {
+            if wait_for_exit(child, Duration::from_secs(remaining - seconds_left))? {
+                return Ok(true);
+            }
+            remaining = seconds_left;
+            warn_players(rcon, players, &config.warning_message.replace("{seconds}", &seconds_left.to_string()));
+        }
+
+        wait_for_exit(child, Duration::from_secs(remaining))
+    }
+
+    /// Send a message to every player over RCon
+    #[allow(clippy::doc_markdown)]
+    pub fn announce(&self, message: &str) -> Result<()> {
+        RconClient::connect_to_server(&self.server_install_dir)?.say_all(message)
+    }
+
+    /// Start the DayZ server with configured mods without waiting for it to exit
+    #[allow(clippy::doc_markdown)]
+    pub fn launch_server(&self) -> Result<Child> {
         let server_exe_path = self.get_server_exe_path();
         
         // Check if server executable exists
@@ -143,73 +612,121 @@ impl ServerManager {
             ));
         }
 
-        // Build the command arguments
+        // Starting on corrupt persistence makes it worse
+        StorageGuard::new(&self.config.storage, &self.server_install_dir).check_before_launch()?;
+        cleanup::apply_before_launch(&self.config.cleanup, &self.server_install_dir)?;
+        server_logs::apply_before_launch(&self.config.logging, &self.server_install_dir)?;
+
+        // Restarts are the only safe point to change passwords
+        SecretsManager::new(&self.config.secrets, &self.server_install_dir).rotate_if_due()?;
+        maintenance::apply_before_launch(&self.server_install_dir)?;
+        battleye::apply_before_launch(&self.config.battleye, &self.config.server, &self.server_install_dir)?;
+
+        players::apply_before_launch(&self.config.players, &self.server_install_dir)?;
+        privacy::apply_before_launch(&self.config, &self.server_install_dir);
+        server_time::apply_before_launch(&self.config.time, &self.server_install_dir)?;
+        weather::apply_before_launch(&self.config.weather, &self.server_install_dir)?;
+        economy::apply_before_launch(&self.config.mods, &self.config.economy, &self.server_install_dir)?;
+        public_info::apply_before_launch(&self.config, &self.server_install_dir);
+        profile_files::apply_before_launch(&self.config.profile_files, &self.server_install_dir)?;
+
+        // Run the server - this should be interactive like SteamCMD
+        let child = self.spawn_server_with_args(&self.build_launch_args()?)?;
+        control::record_server_pid(&self.server_install_dir, child.id())?;
+        interrupt::set_server_running(true);
+        self.set_console_status("running");
+        if let Some(callback) = self.on_first_launch.borrow_mut().take() {
+            callback();
+        }
+        Ok(child)
+    }
+
+    /// The server's command line: config, profiles, port, `[launch]` parameters, and mods in load order
+    pub fn build_launch_args(&self) -> Result<Vec<String>> {
+        self.load_collection_mods()?;
+        for quarantined in quarantine::get_quarantined(&self.server_install_dir).values() {
+            println_failure(&format!(
+                "Leaving out {}, quarantined since {} (see `dzsm quarantine`)",
+                quarantined.name,
+                quarantined.since.format("%Y-%m-%d %H:%M")
+            ), 1);
+        }
+
+        let disabled: Vec<&str> = self.get_individual_mods().iter()
+            .chain(self.get_collection_mods())
+            .filter(|mod_entry| self.config.mods.is_disabled(mod_entry))
+            .map(|mod_entry| mod_entry.name.as_str())
+            .collect();
+        if !disabled.is_empty() {
+            println_step(&format!(
+                "Leaving out {} disabled mod(s): {} (see `mods.disabled`, `mods.disabled_groups`, and `--mod-preset`)",
+                disabled.len(),
+                disabled.join(", ")
+            ), 1);
+        }
+
         let mut args = vec![format!("-config={SERVER_CONFIG}")];
 
         args.push(format!("-profiles={SERVER_PROFILES}"));
-        
+
+        if let Some(port) = self.config.server.port {
+            args.push(format!("-port={port}"));
+        }
+
+        args.extend(self.config.launch.to_args());
+
         // Add mods if any are configured
-        if let Some(mods_string) = self.build_mods_string() {
+        if let Some(mods_string) = self.build_mods_string()? {
             args.push(format!("-mod={mods_string}"));
         }
 
         // Add mods if any are configured
-        if let Some(mods_string) = self.build_server_mods_string() {
+        if let Some(mods_string) = self.build_server_mods_string()? {
             args.push(format!("-serverMod={mods_string}"));
         }
 
-        // Run the server - this should be interactive like SteamCMD
-        self.run_server_with_args(&args)?;
-        
-        println_success("DayZ server has stopped", 0);
-        Ok(())
+        Ok(args)
     }
 
-    /// Clean up all previous mod installations before installing new ones
-    fn uninstall_prev_mod_installations(&self) {
-        println_step("Cleaning up previous mod installations...", 1);
-        
-        // Remove all @* directories
-        self.cleanup_mod_directories();
-        
-        // Clear keys directory
-        self.cleanup_keys_directory();
+    /// Save the server's logs after a crash, fingerprint it, match it against known issues, and post a report
+    pub fn report_crash(&self, exit_code: Option<i32>) {
+        println_failure("DayZ server crashed, analyzing logs...", 0);
 
-        println_success("Previous mod installations cleaned up", 2);
-    }
+        let mut report = CrashReport::analyze(&self.server_install_dir, &self.config.crashes, exit_code);
+        report.print_rpt_tail(self.config.crashes.tail_lines);
 
-    /// Remove all @* directories from server install directory
-    fn cleanup_mod_directories(&self) {
-        if let Ok(entries) = fs::read_dir(&self.server_install_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                    if name.starts_with('@') {
-                        println_step(&format!("Removing: {name}"), 2);
-                        let _ = fs::remove_dir_all(&path);
-                    }
-                }
+        match report.capture(&self.server_install_dir) {
+            Ok(crash_dir) => println_success(&format!("Crash logs saved to {}", crash_dir.display()), 1),
+            Err(e) => println_failure(&format!("Failed to save crash logs: {e}"), 1),
+        }
+
+        report.print();
+        self.print_changes_since_healthy();
+
+        let notifier = Notifier::new(&self.config.notifications);
+        if self.config.notifications.crashes && notifier.is_enabled() {
+            match notifier.send(&report.to_notification(&self.get_server_name())) {
+                Ok(()) => println_success("Crash report posted", 1),
+                Err(e) => println_failure(&format!("Failed to post crash report: {e}"), 1),
             }
         }
     }
 
-    /// Remove all contents from keys directory except dayz.bikey
-    fn cleanup_keys_directory(&self) {
-        let keys_dir = self.server_install_dir.join("keys");
-        if keys_dir.exists() {
-            println_step("Clearing keys directory (keeping dayz.bikey)...", 2);
-            if let Ok(entries) = fs::read_dir(&keys_dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
-                        // Skip dayz.bikey (case insensitive)
-                        if filename.to_lowercase() != "dayz.bikey" {
-                            let _ = fs::remove_file(path);
-                        }
-                    }
-                }
-            }
+    /// Plan the install of the configured and collection mods, from where SteamCMD keeps them
+    #[allow(clippy::doc_markdown)]
+    fn plan_mods(&self) -> Result<InstallPlan> {
+        self.load_collection_mods()?;
+        let mut mods = Vec::new();
+        for mod_entry in self.get_all_mods() {
+            let steamcmd = self.steamcmd_manager.as_ref()
+                .ok_or_else(|| anyhow!("SteamCMD has not been setup yet."))?;
+            let source = steamcmd.get_workshop_mod_dir(self.config.server.branch.game_app_id(), mod_entry.id)?;
+            mods.push((mod_entry, source));
         }
+
+        let plan = InstallPlan::new(&self.config.mods, &self.server_install_dir, mods)
+            .force_clean(self.args.force_clean);
+        Ok(if self.dry_run { plan.dry_run() } else { plan })
     }
 
     /// Get individual mods from config
@@ -217,44 +734,209 @@ impl ServerManager {
         self.config.mods.server_mod_list.as_deref().unwrap_or(&[])
     }
 
-    /// Get collection mods (cached)
+    /// Get collection mods (cached), fetching them on first use if `load_collection_mods` didn't
     fn get_collection_mods(&self) -> &[ModEntry] {
-        self.collection_mod_list.get_or_init(|| {
-            self.config.mods.mod_collection_url.as_ref().map_or_else(
-                Vec::new, 
-                |collection_url| {
-                    if collection_url.trim().is_empty() {
-                        Vec::new()
-                    } else {
-                        CollectionFetcher::fetch_collection_mods(collection_url)
-                            .unwrap_or_else(|e| {
-                                println_failure(&format!("Failed to fetch collection: {e}"), 0);
-                                Vec::new()
-                            })
-                    }
+        if self.collection_mod_list.get().is_none()
+            && self.collection_error.get().is_none()
+            && let Err(e) = self.load_collection_mods()
+        {
+            println_failure(&format!("{e:#}"), 0);
+        }
+        self.collection_mod_list.get().map_or(&[], Vec::as_slice)
+    }
+
+    /// Fetch the collection mods once. A collection that can't be fetched is left out, or is an
+    /// error with `mods.require_collection`, so the server never starts without its mods. The
+    /// error is kept, later calls fail the same way without fetching again.
+    fn load_collection_mods(&self) -> Result<()> {
+        if self.collection_mod_list.get().is_some() {
+            return Ok(());
+        }
+        if let Some(e) = self.collection_error.get() {
+            return Err(anyhow!("{e}"));
+        }
+
+        match self.fetch_collection_mods() {
+            Ok(mods) => {
+                let _ = self.collection_mod_list.set(mods);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.collection_error.set(format!("{e:#}"));
+                Err(e)
+            }
+        }
+    }
+
+    fn fetch_collection_mods(&self) -> Result<Vec<ModEntry>> {
+        let collection_urls = self.config.mods.get_collection_urls();
+        if collection_urls.is_empty() {
+            return Ok(self.config.mods.filter_collection(Vec::new()));
+        }
+
+        // A dry run doesn't go online, the cached collections show what the launch would use
+        let merged = if self.args.offline || self.dry_run {
+            CollectionFetcher::load_collections_mods(&collection_urls)
+        } else {
+            let prefetched = self.mod_info_prefetch.borrow_mut().as_mut().and_then(ModInfoPrefetch::take_collections);
+            prefetched.map_or_else(|| CollectionFetcher::fetch_collections_mods(&collection_urls), CollectionFetcher::merge)
+        };
+        let require_collection = self.args.require_collection || self.config.mods.require_collection;
+        for e in merged.failed {
+            if self.dry_run {
+                println_step(&format!("{DRY_RUN} Would fetch the collection, its mods aren't listed here: {e:#}"), 1);
+            } else if require_collection {
+                return Err(e.context("Failed to fetch collection, not going on without its mods (see `mods.require_collection`)"));
+            } else {
+                println_failure(&format!("Failed to fetch collection, its mods are left out: {e:#}"), 0);
+            }
+        }
+        let mods = merged.mods;
+        if !mods.is_empty() && !self.args.offline && !self.dry_run {
+            let ids: Vec<u64> = mods.iter().map(|mod_entry| mod_entry.id).collect();
+            match self.get_workshop_details(&ids) {
+                Ok(details) => {
+                    collection_dupes::report_duplicates(&mods, &details, 1);
                 }
-            )
-        })
+                Err(e) => println_failure(&format!("Couldn't check the collection for duplicate mods: {e:#}"), 1),
+            }
+        }
+        Ok(self.config.mods.filter_collection(mods))
+    }
+
+    /// Fetch the collections and the Workshop details of every mod in the background, while
+    /// the server updates. Collections are only fetched once, like `get_collection_mods` does.
+    fn start_mod_info_prefetch(&self) {
+        if self.args.offline || self.dry_run {
+            return;
+        }
+
+        let loaded = self.collection_mod_list.get().is_some() || self.collection_error.get().is_some();
+        let collection_urls = if loaded {
+            Vec::new()
+        } else {
+            self.config.mods.get_collection_urls().into_iter().map(str::to_string).collect()
+        };
+        let ids = self.get_individual_mods().iter()
+            .chain(self.collection_mod_list.get().into_iter().flatten())
+            .map(|mod_entry| mod_entry.id)
+            .collect();
+        // Details are for duplicate checks on freshly fetched collections, deferring updates,
+        // and the free space check
+        let fetch_details = !collection_urls.is_empty()
+            || self.config.downloads.defer_over_mb.is_some()
+            || self.config.downloads.min_free_mb > 0;
+
+        *self.mod_info_prefetch.borrow_mut() = Some(ModInfoPrefetch::start(collection_urls, ids, fetch_details));
+    }
+
+    /// Workshop details of the mods, from the prefetch if one has them, otherwise fetched now
+    fn get_workshop_details(&self, ids: &[u64]) -> Result<HashMap<u64, WorkshopDetails>> {
+        if let Some(details) = self.mod_info_prefetch.borrow_mut().as_mut().and_then(ModInfoPrefetch::get_details) {
+            return details;
+        }
+        workshop_details::fetch(ids)
+    }
+
+    /// Bytes the Workshop lists for the latest upload of each mod, to check downloads against.
+    /// The Workshop API doesn't list an item's files, so the total size is all there is to compare.
+    #[allow(clippy::doc_markdown)]
+    fn get_workshop_sizes(&self, ids: &[u64]) -> HashMap<u64, u64> {
+        if self.args.offline || self.dry_run {
+            return HashMap::new();
+        }
+        match self.get_workshop_details(ids) {
+            Ok(details) => details.into_iter()
+                .filter(|(_, item)| item.file_size > 0)
+                .map(|(id, item)| (id, item.file_size))
+                .collect(),
+            Err(e) => {
+                println_failure(&format!("Failed to fetch mod sizes, downloads aren't checked for truncation: {e:#}"), 1);
+                HashMap::new()
+            }
+        }
     }
 
-    /// Installs a mod by downloading or updating its SteamCMD instance
-    /// Then symlinking the instance and its keys to the server install dir
+    /// Mods another server sharing this SteamCMD already downloaded at their latest Workshop
+    /// upload, by the install dir of that server. They don't need SteamCMD to run again.
     #[allow(clippy::doc_markdown)]
-    fn install_mod(&self, workshop_id: u64, name: &str) -> Result<()> {
+    fn find_shared_downloads(&self, ids: &[u64]) -> BTreeMap<u64, PathBuf> {
+        let Some(steamcmd) = self.steamcmd_manager.as_ref().filter(|_| !self.args.offline && !self.dry_run) else {
+            return BTreeMap::new();
+        };
+
+        let cache = steamcmd.load_mod_cache(self.config.server.branch.game_app_id());
+        let install_dir = std::path::absolute(&self.server_install_dir).unwrap_or_else(|_| self.server_install_dir.clone());
+        let candidates: Vec<u64> = ids.iter()
+            .copied()
+            .filter(|id| cache.get(*id).is_some_and(|cached| cached.by != install_dir))
+            .collect();
+        if candidates.is_empty() {
+            return BTreeMap::new();
+        }
+
+        let details = match self.get_workshop_details(&candidates) {
+            Ok(details) => details,
+            Err(e) => {
+                println_failure(&format!("Failed to check for mods other servers downloaded, updating them all: {e:#}"), 1);
+                return BTreeMap::new();
+            }
+        };
+        candidates.into_iter()
+            .filter_map(|id| {
+                let latest = details.get(&id)?.time_updated;
+                let cached = cache.get(id).filter(|cached| cached.is_current(latest))?;
+                Some((id, cached.by.clone()))
+            })
+            .collect()
+    }
+
+    /// Check the mod's download isn't short of the size the Workshop lists, as SteamCMD reports
+    /// success for some downloads that stopped short. One that wasn't `validated` is validated
+    /// once; a mod still short after that fails.
+    #[allow(clippy::doc_markdown)]
+    fn ensure_complete_download(&self, steamcmd: &SteamCmdManager, planned: &PlannedMod, expected_size: Option<u64>, validated: bool) -> Result<()> {
+        let Some(expected_size) = expected_size else {
+            return Ok(());
+        };
+        let Some(mut size) = get_truncated_size(&planned.source, expected_size) else {
+            return Ok(());
+        };
+
+        if !validated {
+            println_failure(&format!(
+                "Only {} of the {} the Workshop lists is on disk, validating the download...",
+                format_size(size), format_size(expected_size)
+            ), 3);
+            println_blank();
+            self.download_mod(steamcmd, planned.entry.id, true)?;
+            println_blank();
+            let Some(still) = get_truncated_size(&planned.source, expected_size) else {
+                return Ok(());
+            };
+            size = still;
+        }
+
+        Err(anyhow!(
+            "Only {} of the {} the Workshop lists is on disk, even after validating",
+            format_size(size), format_size(expected_size)
+        ))
+    }
+
+    /// Installs a mod by downloading or updating its SteamCMD instance,
+    /// then putting it in place as the plan says and checking it's usable
+    #[allow(clippy::doc_markdown)]
+    fn install_mod(&self, plan: &InstallPlan, planned: &PlannedMod, damaged: bool, deferred: bool, shared: Option<&Path>, expected_size: Option<u64>) -> Result<()> {
+        let (workshop_id, name) = (planned.entry.id, &planned.entry.name);
         println_step(&format!("Attempting to install {name} ({workshop_id})..."), 2);
         
         // Ensure SteamCMD is setup
-        if self.steamcmd_manager.is_none() {
+        let Some(steamcmd) = self.steamcmd_manager.as_ref() else {
             return Err(anyhow!("SteamCMD has not been setup yet."));
-        }
-
-        // Get reference to steamcmd manager
-        let steamcmd = self.steamcmd_manager.as_ref().unwrap();
-
-        let mod_source_path = steamcmd.get_workshop_mod_dir(DAYZ_GAME_APP_ID, workshop_id)?;
+        };
 
         if self.args.offline {
-            if mod_source_path.exists() {
+            if planned.source.exists() {
                 println_step("Skipping checking for updates (offline mode enabled)...", 3);
             } else {
                 return Err(anyhow!(
@@ -262,86 +944,293 @@ impl ServerManager {
                     workshop_id
                 ));
             }
+        } else if deferred {
+            println_step("Update deferred to the download window, using the installed version...", 3);
+        } else if let Some(by) = shared.filter(|_| planned.source.exists() && !self.args.skip_validation && !self.args.skip_mod_validation) {
+            println_step(&format!("Already brought up to date by the server in {}, skipping SteamCMD...", by.display()), 3);
+            self.ensure_complete_download(steamcmd, planned, expected_size, false)?;
+        } else if self.dry_run {
+            let validate = self.args.skip_validation || self.args.skip_mod_validation || damaged;
+            println_step(&format!(
+                "{DRY_RUN} Would download or update it with SteamCMD{}",
+                if validate { ", validating it" } else { "" }
+            ), 3);
         } else {
-            let server_config = &self.config.server;
-        
             println_step("Downloading or checking for updates...", 3);
-            println!();
+            println_blank();
+
+            let validate = self.args.skip_validation || self.args.skip_mod_validation || damaged;
+            self.download_mod(steamcmd, workshop_id, validate)?;
+
+            println_blank();
 
-            steamcmd.download_or_update_mod(
-                &server_config.username,
-                DAYZ_GAME_APP_ID,
-                workshop_id,
-                self.args.skip_validation || self.args.skip_mod_validation
-            )?;
+            self.ensure_complete_download(steamcmd, planned, expected_size, validate)?;
 
-            println!();
+            if damaged {
+                mod_validation::clear_pending(&self.server_install_dir, workshop_id)?;
+            }
         }
 
-        
         println_step("Installing...", 4);
+        plan.apply(planned)?;
+        if let Err(e) = plan.verify(planned) {
+            if plan.has_content(planned)? {
+                // The download is fine, only keys are missing
+                println_failure(&format!("{e:#}, installing its keys again..."), 3);
+                plan.reinstall_missing_keys(planned)?;
+            } else if self.args.offline || deferred {
+                return Err(e);
+            } else {
+                // A broken download would keep the server from booting, or boot it without the mod
+                println_failure(&format!("{e:#}, validating the download and installing it again..."), 3);
+                println_blank();
+                self.download_mod(steamcmd, workshop_id, true)?;
+                println_blank();
+                plan.apply(planned)?;
+            }
+            plan.verify(planned)
+                .context("Still unusable after repairing it")?;
+            println_success(&format!("Repaired {name}"), 3);
+        }
 
-        let mod_target_path = self.server_install_dir
-            .join(format!("@{name}"));
-
-        if symlink_dir(&mod_source_path, &mod_target_path).is_err() {
-            return Err(anyhow!("Failed to create a directory symlink from {mod_source_path:?} to {mod_target_path:?}."));
-        }
-
-        // Handle mod keys - symlink individual .bikey files to server keys directory
-        let mod_source_keys_path = mod_source_path.join("keys");
-        let server_keys_path = self.get_server_keys_path();
-
-        if mod_source_keys_path.exists() {
-            println_step("Installing mod keys...", 5);
-            
-            // Read the keys directory
-            match fs::read_dir(&mod_source_keys_path) {
-                Ok(entries) => {
-                    for entry in entries.flatten() {
-                        let key_file_path = entry.path();
-                        
-                        // Only process .bikey files
-                        if let Some(extension) = key_file_path.extension() {
-                            if extension.to_string_lossy().to_lowercase() == "bikey" {
-                                if let Some(filename) = key_file_path.file_name() {
-                                    let target_key_path = server_keys_path.join(filename);
-                                    
-                                    // Check if the target key file already exists
-                                    if target_key_path.exists() {
-                                        println_step(&format!("Key already exists, skipping: {}", filename.to_string_lossy()), 6);
-                                        continue;
-                                    }
-                                    
-                                    // Use symlink_file for individual files
-                                    if let Err(e) = symlink_file(&key_file_path, &target_key_path) {
-                                        return Err(anyhow!(
-                                            "Failed to create key file symlink from {key_file_path:?} to {target_key_path:?}: {e}"
-                                        ));
-                                    }
-                                    
-                                    println_step(&format!("Linked key: {}", filename.to_string_lossy()), 6);
-                                }
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    return Err(anyhow!(
-                        "Failed to read keys directory {mod_source_keys_path:?}: {e}"
-                    ));
-                }
+        if !self.dry_run {
+            println_success(&format!("Successfully installed {name}"), 2);
+        }
+        Ok(())
+    }
+
+    /// Link one of `mods.local_mods` into the server install as it is
+    fn install_local_mod(&self, plan: &InstallPlan, planned: &PlannedMod) -> Result<()> {
+        println_step(&format!("Installing local mod {} from {}...", planned.entry.name, planned.source.display()), 2);
+        if !self.dry_run && !planned.source.is_dir() {
+            return Err(anyhow!("{} is not a folder", planned.source.display()));
+        }
+
+        plan.apply(planned)?;
+        plan.verify(planned)?;
+
+        if !self.dry_run {
+            println_success(&format!("Successfully installed {}", planned.entry.name), 2);
+        }
+        Ok(())
+    }
+
+    /// Download or update a mod, switching to `steam.workshop_username` if the server's
+    /// account isn't licensed for Workshop downloads
+    fn download_mod(&self, steamcmd: &SteamCmdManager, workshop_id: u64, validate: bool) -> Result<()> {
+        let username = &self.config.server.username;
+        let workshop_username = self.config.steam.workshop_username.as_deref().filter(|workshop| workshop != username);
+        if let Some(workshop_username) = workshop_username.filter(|_| self.use_workshop_account.get()) {
+            return self.download_mod_as(steamcmd, workshop_username, workshop_id, validate);
+        }
+
+        let result = self.download_mod_as(steamcmd, username, workshop_id, validate);
+        let Err(e) = &result else {
+            return result;
+        };
+        let Some(workshop_username) = workshop_username.filter(|_| steamcmd_errors::find_failure(e) == Some(SteamCmdFailure::NoSubscription)) else {
+            return result;
+        };
+
+        println_step(&format!("{username} isn't licensed for DayZ Workshop downloads, using {workshop_username} instead..."), 3);
+        self.use_workshop_account.set(true);
+        self.download_mod_as(steamcmd, workshop_username, workshop_id, validate)
+    }
+
+    /// Download or update a mod as `username`, retrying a download that timed out after a
+    /// growing delay; SteamCMD picks up the partial download where it stopped
+    #[allow(clippy::doc_markdown)]
+    fn download_mod_as(&self, steamcmd: &SteamCmdManager, username: &str, workshop_id: u64, validate: bool) -> Result<()> {
+        let attempts = self.config.mods.download_attempts.max(1);
+        let mut delay = self.config.mods.download_retry_delay_seconds;
+        for attempt in 1..=attempts {
+            let Err(e) = steamcmd.download_or_update_mod(&self.server_install_dir, username, self.config.server.branch.game_app_id(), workshop_id, validate) else {
+                return Ok(());
+            };
+            println_blank();
+
+            if attempt == attempts || steamcmd_errors::find_failure(&e) != Some(SteamCmdFailure::Timeout) {
+                return Err(e.context(format!("Download failed after {attempt} attempt(s)")));
             }
-        } else {
-            println_step("No keys required for this mod (client-side or configuration mod)", 5);
+            println_step(&format!("Download timed out, resuming in {delay} seconds (attempt {}/{attempts})...", attempt + 1), 3);
+            thread::sleep(Duration::from_secs(delay));
+            delay = delay.saturating_mul(2).min(MAX_DOWNLOAD_RETRY_DELAY_SECONDS);
         }
 
-        println_success(&format!("Successfully installed {name}"), 2);
         Ok(())
     }
 
-    fn get_server_keys_path(&self) -> PathBuf {
-        self.server_install_dir.join(SERVER_KEYS)
+    /// Load SteamCMD's workshop manifest for DayZ, falling back to an empty one
+    #[allow(clippy::doc_markdown)]
+    fn load_workshop_manifest(&self) -> WorkshopManifest {
+        let Some(steamcmd) = self.steamcmd_manager.as_ref() else {
+            return WorkshopManifest::default();
+        };
+
+        // So it isn't read halfway through another DZSM's SteamCMD writing it; dry runs leave SteamCMD's dir alone
+        let game_app_id = self.config.server.branch.game_app_id();
+        let _lock = (!self.dry_run).then(|| steamcmd.lock_workshop(game_app_id).ok());
+        WorkshopManifest::load(&steamcmd.get_workshop_manifest_path(game_app_id))
+            .unwrap_or_else(|e| {
+                println_failure(&format!("Failed to read workshop manifest: {e}"), 2);
+                WorkshopManifest::default()
+            })
+    }
+
+    /// Configured and collection mods together
+    fn get_all_mods(&self) -> Vec<ModEntry> {
+        self.get_individual_mods().iter()
+            .chain(self.get_collection_mods())
+            .filter(|mod_entry| !self.config.mods.is_disabled(mod_entry))
+            .cloned()
+            .collect()
+    }
+
+    /// Remember the installed mods as working, once the server has stayed up long enough.
+    /// A run with a mod preset isn't recorded, its smaller mod set would make the next full run
+    /// look like it added the mods the preset left out.
+    pub fn record_healthy_run(&self) {
+        if self.config.mods.active_preset.is_some() {
+            return;
+        }
+        if let Err(e) = quarantine::record_healthy(&self.server_install_dir, &self.load_workshop_manifest(), &self.get_all_mods()) {
+            println_failure(&format!("Failed to record the healthy mod set: {e:#}"), 0);
+        }
+    }
+
+    /// Print what changed since the server last ran fine, so a crash is triaged from the actual difference
+    fn print_changes_since_healthy(&self) {
+        if self.config.mods.active_preset.is_some() {
+            println_step("A mod preset is in use, not comparing with the last healthy run", 1);
+            return;
+        }
+        let Some(changes) = quarantine::describe_changes(&self.server_install_dir, &self.load_workshop_manifest(), &self.get_all_mods()) else {
+            println_step("No healthy run recorded yet to compare with", 1);
+            return;
+        };
+
+        if changes.is_empty() {
+            println_step("Nothing changed since the server last ran fine", 1);
+            return;
+        }
+        println_step(&format!("{} change(s) since the server last ran fine:", changes.len()), 1);
+        for change in &changes {
+            println_step_concat(change, 2);
+        }
+    }
+
+    /// Quarantine the one mod that changed since the last healthy run, if there is exactly one,
+    /// so the server can come back up without it. Returns whether a mod was quarantined. Never
+    /// with a mod preset, the last healthy run was recorded with every mod.
+    pub fn quarantine_crash_suspect(&self) -> bool {
+        if self.config.mods.active_preset.is_some() {
+            return false;
+        }
+        let manifest = self.load_workshop_manifest();
+        let Some(suspect) = quarantine::find_suspect(&self.server_install_dir, &manifest, &self.get_all_mods()) else {
+            return false;
+        };
+        if let Err(e) = quarantine::quarantine(&self.server_install_dir, &suspect, &manifest) {
+            println_failure(&format!("Failed to quarantine {}: {e:#}", suspect.name), 0);
+            return false;
+        }
+
+        let message = format!(
+            "{} ({}) was the only change since the server last ran fine and it keeps crashing, \
+            so it's left out until it's updated or released with `dzsm quarantine release {}`",
+            suspect.name, suspect.id, suspect.id
+        );
+        println_failure(&format!("Quarantined {}: {message}", suspect.name), 0);
+
+        let notifier = Notifier::new(&self.config.notifications);
+        if self.config.notifications.crashes && notifier.is_enabled() {
+            let notification = Notification {
+                event: NotificationEvent::Quarantine,
+                title: format!("{}: mod quarantined", self.get_server_name()),
+                body: message,
+            };
+            if let Err(e) = notifier.send(&notification) {
+                println_failure(&format!("Failed to post quarantine notice: {e}"), 1);
+            }
+        }
+        true
+    }
+
+    /// Tell the notification channels an update failed and the installed version is starting,
+    /// `what` being "Server" or "Mod"
+    pub fn report_update_failure(&self, what: &str, error: &anyhow::Error) {
+        let notifier = Notifier::new(&self.config.notifications);
+        if !notifier.is_enabled() {
+            return;
+        }
+
+        let notification = Notification {
+            event: NotificationEvent::UpdateFailed,
+            title: format!("{}: {what} update failed", self.get_server_name()),
+            body: format!("Starting with the installed files instead.\n```\n{error:#}\n```"),
+        };
+        if let Err(e) = notifier.send(&notification) {
+            println_failure(&format!("Failed to post update failure: {e}"), 1);
+        }
+    }
+
+    /// Give quarantined mods another chance once their authors have published an update
+    fn release_updated_mods(&self) {
+        match quarantine::release_updated(&self.server_install_dir, &self.load_workshop_manifest()) {
+            Ok(released) => {
+                for name in released {
+                    println_success(&format!("{name} was updated, released it from quarantine"), 1);
+                }
+            }
+            Err(e) => println_failure(&format!("Failed to release updated mods from quarantine: {e:#}"), 1),
+        }
+    }
+
+    /// Write a digest of the mods changed by this update run and post it to the notification channels
+    fn publish_update_digest(&self, manifest_before: &WorkshopManifest) {
+        let all_mods = self.get_all_mods();
+
+        let mut digest = UpdateDigest::compare(&all_mods, manifest_before, &self.load_workshop_manifest());
+        if digest.is_empty() {
+            println_step("No mod changes since the last update", 1);
+            return;
+        }
+
+        println_step("Collecting change notes for updated mods...", 1);
+        digest.fetch_change_notes();
+
+        match digest.save(&self.server_install_dir) {
+            Ok(path) => println_success(&format!("Update digest written: {}", path.display()), 2),
+            Err(e) => println_failure(&format!("Failed to write update digest: {e}"), 2),
+        }
+
+        let notifier = Notifier::new(&self.config.notifications);
+        if self.config.notifications.update_digest && notifier.is_enabled() {
+            match notifier.send(&digest.to_notification()) {
+                Ok(()) => println_success("Update digest posted", 2),
+                Err(e) => println_failure(&format!("Failed to post update digest: {e}"), 2),
+            }
+        }
+    }
+
+    /// Show what DZSM is doing in the console window title
+    fn set_console_status(&self, status: &str) {
+        let mut title = ConsoleTitle::new(&self.get_server_name());
+        title.status = Some(status.to_string());
+        title.apply();
+    }
+
+    /// Show the running server's players and next restart in the console window title
+    pub fn set_running_title(&self, players: Option<u32>, next_restart: Option<String>) {
+        let mut title = ConsoleTitle::new(&self.get_server_name());
+        title.status = Some("running".to_string());
+        title.players = players;
+        title.next_restart = next_restart;
+        title.apply();
+    }
+
+    fn get_server_name(&self) -> String {
+        get_server_name(&self.server_install_dir)
     }
 
     /// Get the full path to the DayZ server executable
@@ -351,60 +1240,159 @@ impl ServerManager {
     }
 
     /// Build the mods string in the format: @ModName1;@ModName2;@ModName3
-    fn build_mods_string(&self) -> Option<String> {
-        let complete_mod_list = self.get_collection_mods();
-        if complete_mod_list.is_empty() {
-            None
-        } else {
-            Some(complete_mod_list.iter()
-                .map(|mod_entry| format!("@{}", mod_entry.name))
-                .collect::<Vec<String>>()
-                .join(";"))
-        }
+    fn build_mods_string(&self) -> Result<Option<String>> {
+        self.build_load_order_string(&self.get_mods_on_side(false))
     }
 
     /// Build the server mods string in the format: @ModName1;@ModName2;@ModName3
-    fn build_server_mods_string(&self) -> Option<String> {
-        let complete_mod_list = self.get_individual_mods();
-        if complete_mod_list.is_empty() {
-            None
-        } else {
-            Some(complete_mod_list.iter()
-                .map(|mod_entry| format!("@{}", mod_entry.name))
-                .collect::<Vec<String>>()
-                .join(";"))
+    fn build_server_mods_string(&self) -> Result<Option<String>> {
+        self.build_load_order_string(&self.get_mods_on_side(true))
+    }
+
+    /// Configured, collection, and local mods that load with `-serverMod` (`server_only`) or with `-mod`
+    fn get_mods_on_side(&self, server_only: bool) -> Vec<ModEntry> {
+        let individual_mods = self.get_individual_mods().iter().map(|mod_entry| (mod_entry, ModSide::Server));
+        let collection_mods = self.get_collection_mods().iter().map(|mod_entry| (mod_entry, ModSide::Client));
+        let quarantined = quarantine::get_quarantined(&self.server_install_dir);
+        let workshop_mods = individual_mods.chain(collection_mods)
+            .filter(|(mod_entry, _)| !quarantined.contains_key(&mod_entry.id) && !self.config.mods.is_disabled(mod_entry));
+
+        // Local mods load like collection mods, `get_local_mods` already left out disabled ones
+        let local_mods = self.config.mods.get_local_mods();
+        workshop_mods.chain(local_mods.iter().map(|(mod_entry, _)| (mod_entry, ModSide::Client)))
+            .filter(|(mod_entry, default)| self.config.mods.get_side(mod_entry, *default).is_server_only() == server_only)
+            .map(|(mod_entry, _)| mod_entry.clone())
+            .collect()
+    }
+
+    /// Join mods in load order, see `load_order::sort_mods`
+    fn build_load_order_string(&self, mods: &[ModEntry]) -> Result<Option<String>> {
+        if mods.is_empty() {
+            return Ok(None);
         }
+
+        let sorted = sort_mods(mods, &self.config.mods)?;
+        Ok(Some(sorted.iter()
+            .map(|mod_entry| self.config.mods.get_folder(mod_entry))
+            .collect::<Vec<String>>()
+            .join(";")))
     }
 
-    /// Run the DayZ server with arguments, allowing interactive input/output
+    /// Start the DayZ server with arguments, allowing interactive input/output
     #[allow(clippy::doc_markdown)]
-    fn run_server_with_args(&self, args: &[String]) -> Result<()> {
+    fn spawn_server_with_args(&self, args: &[String]) -> Result<Child> {
         let server_exe_path = self.get_server_exe_path();
         
         println_step(&format!("Executing: {} {}", SERVER_EXE, args.join(" ")), 1);
-        println!();
+        println_blank();
+
+        if self.detach {
+            return self.spawn_detached(&server_exe_path, args);
+        }
         
         // Use spawn() to allow interactive input/output (server console, etc.)
-        let mut child = Command::new(&server_exe_path)
+        Command::new(&server_exe_path)
             .args(args)
             .current_dir(&self.server_install_dir) // Set working directory to server install dir
-            .stdin(Stdio::inherit())   // Allow user input to server console
-            .stdout(Stdio::inherit())  // Show server output directly
+            .stdin(child_stdin())      // Allow user input to server console (unless non-interactive)
+            .stdout(child_stdout())   // Show server output directly
             .stderr(Stdio::inherit())  // Show server errors directly
+            .creation_flags(CREATE_NEW_PROCESS_GROUP) // Ctrl+C goes to DZSM only, which shuts the server down gracefully
             .spawn()
-            .context("Failed to execute DayZ server")?;
-        
-        // Wait for the server process to complete
-        let status = child.wait()
-            .context("Failed to wait for DayZ server process")?;
-        
-        if !status.success() {
-            return Err(anyhow!(
-                "DayZ server exited with error code: {:?}", 
-                status.code()
-            ));
+            .context("Failed to execute DayZ server")
+    }
+
+    fn get_console_log_path(&self) -> PathBuf {
+        logs_dir(&self.server_install_dir).join(CONSOLE_LOG)
+    }
+
+    /// Spawn the server without a console, its output in `.dzsm/logs/server-console.log`. It's
+    /// also taken out of DZSM's job where allowed, as closing an SSH session ends everything in it.
+    #[allow(clippy::doc_markdown)]
+    fn spawn_detached(&self, server_exe_path: &Path, args: &[String]) -> Result<Child> {
+        let log_path = self.get_console_log_path();
+        if let Some(parent) = log_path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create logs directory")?;
         }
+        let log = File::create(&log_path)
+            .context(format!("Failed to create {}", log_path.display()))?;
 
-        Ok(())
+        let spawn = |flags: u32| -> Result<Child> {
+            Ok(Command::new(server_exe_path)
+                .args(args)
+                .current_dir(&self.server_install_dir)
+                .stdin(Stdio::null())
+                .stdout(log.try_clone().context("Failed to open server console log")?)
+                .stderr(log.try_clone().context("Failed to open server console log")?)
+                .creation_flags(flags)
+                .spawn()?)
+        };
+        let flags = CREATE_NEW_PROCESS_GROUP | DETACHED_PROCESS;
+        match spawn(flags | CREATE_BREAKAWAY_FROM_JOB) {
+            Ok(child) => Ok(child),
+            // The job DZSM runs in doesn't allow it, the server ends with the job
+            Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied) => {
+                println_failure("Couldn't take the server out of this session's job, it may stop when the session ends", 1);
+                spawn(flags).context("Failed to execute DayZ server")
+            }
+            Err(e) => Err(e).context("Failed to execute DayZ server"),
+        }
+    }
+}
+
+/// The size of a mod's files when it's short of what the Workshop lists, which a complete
+/// download never is
+fn get_truncated_size(mod_dir: &Path, expected_size: u64) -> Option<u64> {
+    let size = dir_size(mod_dir).ok()?;
+    (size.saturating_mul(100) < expected_size.saturating_mul(TRUNCATED_PERCENT)).then_some(size)
+}
+
+/// Send the warning to each player by name, or to everyone when the player list isn't known
+fn warn_players(rcon: &mut RconClient, players: Option<&[RconPlayer]>, message: &str) {
+    let Some(players) = players else {
+        match rcon.say_all(&message.replace("{name}", "")) {
+            Ok(()) => println_step(&format!("Warned players: {message}"), 1),
+            Err(e) => println_failure(&format!("Failed to warn players: {e}"), 1),
+        }
+        return;
+    };
+
+    let mut warned = 0;
+    for player in players {
+        match rcon.say(player.number, &message.replace("{name}", &player.name)) {
+            Ok(()) => warned += 1,
+            Err(e) => println_failure(&format!("Failed to warn {}: {e}", player.name), 1),
+        }
+    }
+    println_step(&format!("Warned {warned} player(s): {message}"), 1);
+}
+
+/// Wait up to `timeout` for the server to exit, returning true if it did.
+/// A second Ctrl+C cuts the wait short.
+fn wait_for_exit(child: &mut Child, timeout: Duration) -> Result<bool> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if child.try_wait().context("Failed to check on DayZ server process")?.is_some() {
+            return Ok(true);
+        }
+        if interrupt::shutdown_forced() || Instant::now() >= deadline {
+            return Ok(false);
+        }
+        thread::sleep(POLL_INTERVAL);
     }
 }
+
+/// Server name from the hostname in serverDZ.cfg, falling back to the install directory name
+#[allow(clippy::doc_markdown)]
+pub fn get_server_name(server_install_dir: &Path) -> String {
+    ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))
+        .ok()
+        .and_then(|cfg| cfg.get("hostname"))
+        .filter(|hostname| !hostname.trim().is_empty())
+        .unwrap_or_else(|| {
+            server_install_dir
+                .file_name()
+                .map_or_else(|| "DayZ Server".to_string(), |n| n.to_string_lossy().to_string())
+        })
+}
@@ -0,0 +1,251 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::ServerConfig;
+use crate::processes;
+use crate::server::SERVER_EXE;
+use crate::state::state_dir;
+use crate::status::{get_app_manifest_path, get_build_id};
+use crate::storage::{copy_dir, list_files};
+use crate::ui::status::{println_failure, println_step, println_success};
+use crate::vdf;
+
+const BUILDS_DIR: &str = "server_builds";
+/// A backup still being written, so an interrupted one is never restored
+const PARTIAL_SUFFIX: &str = ".partial";
+/// The server's game data; with the executable and libraries next to it, everything a game
+/// update changes. The rest of the install dir is configuration, missions, mods, and logs.
+const GAME_DIRS: &[&str] = &["addons", "bliss", "dta"];
+const BATTLEYE_DIR: &str = "battleye";
+/// What SteamCMD calls the branch everyone gets
+#[allow(clippy::doc_markdown)]
+const PUBLIC_BRANCH: &str = "public";
+
+fn get_builds_dir(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(BUILDS_DIR)
+}
+
+/// The branch to pass to SteamCMD: `server.beta_branch`, or the public one to leave a beta
+/// branch the install is still on, which SteamCMD would otherwise keep updating from
+#[allow(clippy::doc_markdown)]
+pub fn get_branch(config: &ServerConfig, install_dir: &Path) -> Option<String> {
+    if let Some(branch) = &config.beta_branch {
+        return Some(branch.clone());
+    }
+
+    let content = fs::read_to_string(get_app_manifest_path(install_dir, config.branch.server_app_id())).ok()?;
+    vdf::parse(&content).ok()?
+        .get_path(&["AppState", "UserConfig", "BetaKey"])?
+        .as_str()
+        .filter(|branch| !branch.is_empty() && *branch != PUBLIC_BRANCH)
+        .map(|_| PUBLIC_BRANCH.to_string())
+}
+
+/// The installed server's files a game update changes, relative to the install dir
+fn list_build_files(config: &ServerConfig, server_install_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(server_install_dir).context(format!("Failed to read {}", server_install_dir.display()))? {
+        let path = entry?.path();
+        let is_binary = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("exe") || e.eq_ignore_ascii_case("dll"));
+        if path.is_file() && is_binary {
+            files.push(path);
+        }
+    }
+    for dir in GAME_DIRS {
+        let dir = server_install_dir.join(dir);
+        if dir.is_dir() {
+            files.extend(list_files(&dir)?);
+        }
+    }
+    // Only the anti-cheat itself, its config holds the RCON password
+    let battleye_dir = server_install_dir.join(BATTLEYE_DIR);
+    if battleye_dir.is_dir() {
+        files.extend(list_files(&battleye_dir)?
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|e| e.eq_ignore_ascii_case("dll"))));
+    }
+    // Tells SteamCMD which build is installed
+    files.push(get_app_manifest_path(server_install_dir, config.branch.server_app_id()));
+
+    Ok(files.into_iter()
+        .filter_map(|path| path.strip_prefix(server_install_dir).ok().map(Path::to_path_buf))
+        .collect())
+}
+
+/// Backed-up builds, oldest first, with when each was backed up
+fn list_backups(server_install_dir: &Path) -> Result<Vec<(String, DateTime<Local>)>> {
+    let builds_dir = get_builds_dir(server_install_dir);
+    if !builds_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(&builds_dir).context(format!("Failed to read {}", builds_dir.display()))?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !entry.path().is_dir() || name.ends_with(PARTIAL_SUFFIX) {
+            continue;
+        }
+        let modified = entry.metadata().and_then(|metadata| metadata.modified())
+            .context(format!("Failed to read {}", entry.path().display()))?;
+        backups.push((name, DateTime::from(modified)));
+    }
+    backups.sort_by_key(|(_, backed_up)| *backed_up);
+    Ok(backups)
+}
+
+/// Back up the installed build before SteamCMD may replace it, once per build, keeping the
+/// newest `server.keep_builds` besides the installed one and whichever `server.app_build` pins
+#[allow(clippy::doc_markdown)]
+pub fn backup_before_update(config: &ServerConfig, server_install_dir: &Path) -> Result<()> {
+    if config.keep_builds == 0 {
+        return Ok(());
+    }
+    // Nothing installed yet
+    let Some(installed) = back_up_installed(config, server_install_dir)? else {
+        return Ok(());
+    };
+
+    let pinned = config.app_build.map(|build| build.to_string());
+    let backups: Vec<String> = list_backups(server_install_dir)?
+        .into_iter()
+        .map(|(build, _)| build)
+        .filter(|backup| *backup != installed && Some(backup) != pinned.as_ref())
+        .collect();
+    for old_build in &backups[..backups.len().saturating_sub(config.keep_builds)] {
+        let old_dir = get_builds_dir(server_install_dir).join(old_build);
+        fs::remove_dir_all(&old_dir)
+            .context(format!("Failed to remove old server build backup {}", old_dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Back up the installed build unless it already is, returning which build that is
+fn back_up_installed(config: &ServerConfig, server_install_dir: &Path) -> Result<Option<String>> {
+    let Some(build) = get_build_id(server_install_dir) else {
+        return Ok(None);
+    };
+
+    let builds_dir = get_builds_dir(server_install_dir);
+    if builds_dir.join(&build).exists() {
+        return Ok(Some(build));
+    }
+
+    println_step(&format!("Backing up server build {build} for `dzsm rollback`..."), 1);
+    let partial_dir = builds_dir.join(format!("{build}{PARTIAL_SUFFIX}"));
+    if partial_dir.exists() {
+        fs::remove_dir_all(&partial_dir)
+            .context(format!("Failed to remove {}", partial_dir.display()))?;
+    }
+    for file in list_build_files(config, server_install_dir)? {
+        copy_file(&server_install_dir.join(&file), &partial_dir.join(&file))?;
+    }
+    fs::rename(&partial_dir, builds_dir.join(&build))
+        .context(format!("Failed to rename {}", partial_dir.display()))?;
+    Ok(Some(build))
+}
+
+/// Keep `server.app_build` installed, restoring its backup when an update replaced it.
+/// Returns whether it's installed, so the update is skipped.
+pub fn apply_pin(config: &ServerConfig, server_install_dir: &Path) -> Result<bool> {
+    let Some(pinned) = config.app_build.map(|build| build.to_string()) else {
+        return Ok(false);
+    };
+
+    let installed = get_build_id(server_install_dir);
+    if installed.as_deref() == Some(pinned.as_str()) {
+        println_success(&format!("Server build {pinned} is installed, not updating (see `server.app_build`)"), 1);
+        return Ok(true);
+    }
+    if !get_builds_dir(server_install_dir).join(&pinned).exists() {
+        println_failure(&format!(
+            "`server.app_build` is {pinned}, which has no backup to restore; SteamCMD only downloads \
+             a branch's latest build, updating as usual"
+        ), 1);
+        return Ok(false);
+    }
+
+    if let Err(e) = back_up_installed(config, server_install_dir) {
+        println_failure(&format!("Failed to back up the installed server build: {e:#}"), 1);
+    }
+    restore(server_install_dir, &pinned)?;
+    println_success(&format!("Restored server build {pinned} (see `server.app_build`)"), 1);
+    Ok(true)
+}
+
+/// Replace the installed build's files with a backed-up build's. Game data folders are
+/// emptied first, so nothing the newer build added is left to load.
+fn restore(server_install_dir: &Path, build: &str) -> Result<()> {
+    let backup_dir = get_builds_dir(server_install_dir).join(build);
+    for dir in GAME_DIRS {
+        let dir = server_install_dir.join(dir);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .context(format!("Failed to remove {}", dir.display()))?;
+        }
+    }
+    copy_dir(&backup_dir, server_install_dir)
+        .context(format!("Failed to restore server build {build}, run `dzsm` to reinstall the latest one"))
+}
+
+fn copy_file(source: &Path, destination: &Path) -> Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create {}", parent.display()))?;
+    }
+    fs::copy(source, destination)
+        .context(format!("Failed to copy {} to {}", source.display(), destination.display()))?;
+    Ok(())
+}
+
+/// Entry point for `dzsm rollback`
+pub fn run(config: &ServerConfig, server_install_dir: &str, build: Option<&str>, list: bool) -> Result<()> {
+    let server_install_dir = Path::new(server_install_dir);
+    let installed = get_build_id(server_install_dir);
+    let backups = list_backups(server_install_dir)?;
+
+    if list {
+        if backups.is_empty() {
+            println_step("No server builds backed up yet, one is before each update (see `server.keep_builds`)", 0);
+        }
+        for (backup, backed_up) in &backups {
+            let current = if installed.as_deref() == Some(backup.as_str()) { ", installed" } else { "" };
+            println_step(&format!("{backup} (backed up {}{current})", backed_up.format("%Y-%m-%d %H:%M")), 0);
+        }
+        return Ok(());
+    }
+
+    if !processes::find_by_exe_path(&server_install_dir.join(SERVER_EXE)).is_empty() {
+        return Err(anyhow!("The server is running, stop it before rolling back"));
+    }
+
+    // The newest backup of another build than the installed one
+    let build = match build {
+        Some(build) => build.to_string(),
+        None => backups.iter()
+            .rev()
+            .map(|(backup, _)| backup)
+            .find(|backup| installed.as_deref() != Some(backup.as_str()))
+            .cloned()
+            .ok_or_else(|| anyhow!("No earlier server build backed up, see `dzsm rollback --list`"))?,
+    };
+    if !backups.iter().any(|(backup, _)| *backup == build) {
+        return Err(anyhow!("Server build {build} isn't backed up, see `dzsm rollback --list`"));
+    }
+    if installed.as_deref() == Some(build.as_str()) {
+        println_success(&format!("Server build {build} is already installed"), 0);
+        return Ok(());
+    }
+
+    println_step(&format!("Rolling the server back to build {build}..."), 0);
+    // To roll forward again
+    back_up_installed(config, server_install_dir)?;
+    restore(server_install_dir, &build)?;
+    println_success(&format!("Server build {build} restored"), 0);
+    if config.app_build.map(|pinned| pinned.to_string()) != Some(build.clone()) {
+        println_step(&format!("Set `server.app_build = {build}` to stay on it, the next update installs the latest build again"), 1);
+    }
+    Ok(())
+}
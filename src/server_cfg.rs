@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::server::MISSIONS_DIR;
+
+/// The DayZ server's `serverDZ.cfg`, edited in place so comments and layout survive
+#[allow(clippy::doc_markdown)]
+pub struct ServerDzConfig {
+    path: PathBuf,
+    content: String,
+}
+
+impl ServerDzConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read {}", path.display()))?;
+        Ok(Self { path: path.to_path_buf(), content })
+    }
+
+    /// Get a top-level value such as `hostname`, with quotes removed
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.content.lines().find_map(|line| {
+            let (line_key, value) = split_assignment(line)?;
+            line_key.eq_ignore_ascii_case(key).then(|| unquote(value).to_string())
+        })
+    }
+
+    /// The active mission's directory, `mpmissions/<template>`
+    pub fn get_mission_dir(&self, server_install_dir: &Path) -> Option<PathBuf> {
+        self.get("template").map(|template| server_install_dir.join(MISSIONS_DIR).join(template))
+    }
+
+    /// Set a quoted string value, e.g. `password = "...";`
+    pub fn set_string(&mut self, key: &str, value: &str) {
+        self.set_raw(key, &format!("\"{value}\""));
+    }
+
+    /// Set a value exactly as written, e.g. `maxPlayers = 60;`
+    pub fn set_raw(&mut self, key: &str, raw_value: &str) {
+        let mut found = false;
+        let mut lines: Vec<String> = self.content.lines()
+            .map(|line| {
+                let Some((line_key, value)) = split_assignment(line) else {
+                    return line.to_string();
+                };
+                if found || !line_key.eq_ignore_ascii_case(key) {
+                    return line.to_string();
+                }
+
+                found = true;
+                // Keep indentation and any trailing comment
+                let value_start = line.find('=').unwrap_or(0) + 1;
+                let value_offset = line[value_start..].find(value).map_or(value_start, |o| value_start + o);
+                format!("{}{raw_value}{}", &line[..value_offset], &line[value_offset + value.len()..])
+            })
+            .collect();
+
+        if !found {
+            // New top-level settings go before the first class block
+            let insert_at = lines.iter()
+                .position(|line| line.trim_start().starts_with("class "))
+                .unwrap_or(lines.len());
+            lines.insert(insert_at, format!("{key} = {raw_value};"));
+        }
+
+        self.content = lines.join("\n");
+        self.content.push('\n');
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::write(&self.path, &self.content)
+            .context(format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// Split `key = value; // comment` into its key and raw value
+fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    let (key, rest) = line.split_once('=')?;
+    let key = key.trim();
+
+    // Skips comments and `class Missions {` style lines
+    if key.is_empty() || key.starts_with("//") || key.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let rest = rest.trim_start();
+    let value = if let Some(quoted) = rest.strip_prefix('"') {
+        // Quoted values may contain ';' or '//'
+        let end = quoted.find('"')?;
+        &rest[..end + 2]
+    } else {
+        rest.split([';', '/']).next()?.trim()
+    };
+
+    Some((key, value))
+}
+
+fn unquote(value: &str) -> &str {
+    value.strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+use crate::config::LoggingConfig;
+use crate::privacy::SERVER_LOG_EXTENSIONS;
+use crate::server::SERVER_PROFILES;
+use crate::state::logs_dir;
+use crate::storage::{format_size, list_files};
+use crate::ui::spinner::Spinner;
+use crate::ui::status::{println_failure, println_success};
+
+const ARCHIVE_DIR: &str = "archive";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A server log in the profiles directory
+struct LogFile {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+/// Which kind of log a file is, its name up to the timestamp plus its extension, e.g.
+/// `DayZServer_x64_2025-01-01_12-00-00.RPT` is `dayzserver_x64.rpt`
+fn get_kind(path: &Path) -> String {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let prefix = stem.split(|c: char| c.is_ascii_digit()).next().unwrap_or_default();
+    format!("{}.{extension}", prefix.trim_end_matches(['_', '-']))
+}
+
+fn find_logs(profiles_dir: &Path) -> Result<Vec<LogFile>> {
+    let mut logs = Vec::new();
+    for path in list_files(profiles_dir)? {
+        let is_log = path.extension()
+            .is_some_and(|e| SERVER_LOG_EXTENSIONS.iter().any(|wanted| e.eq_ignore_ascii_case(wanted)));
+        if !is_log {
+            continue;
+        }
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        logs.push(LogFile {
+            path,
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            size: metadata.len(),
+        });
+    }
+    Ok(logs)
+}
+
+/// The logs past `keep_days`, or beyond the newest `keep_count` of their kind
+fn find_expired(config: &LoggingConfig, logs: Vec<LogFile>) -> Vec<LogFile> {
+    let cutoff = config.keep_days
+        .and_then(|days| SystemTime::now().checked_sub(Duration::from_secs(days.saturating_mul(SECONDS_PER_DAY))));
+
+    let mut kinds: BTreeMap<String, Vec<LogFile>> = BTreeMap::new();
+    for log in logs {
+        kinds.entry(get_kind(&log.path)).or_default().push(log);
+    }
+
+    let mut expired = Vec::new();
+    for mut logs in kinds.into_values() {
+        // Newest first
+        logs.sort_by_key(|log| std::cmp::Reverse(log.modified));
+        for (index, log) in logs.into_iter().enumerate() {
+            let too_old = cutoff.is_some_and(|cutoff| log.modified < cutoff);
+            let too_many = config.keep_count.is_some_and(|count| index >= count);
+            if too_old || too_many {
+                expired.push(log);
+            }
+        }
+    }
+    expired
+}
+
+/// Zip the logs into `.dzsm/logs/archive/`, returning the archive
+fn archive(server_install_dir: &Path, profiles_dir: &Path, logs: &[LogFile]) -> Result<PathBuf> {
+    let archive_dir = logs_dir(server_install_dir).join(ARCHIVE_DIR);
+    fs::create_dir_all(&archive_dir)
+        .context(format!("Failed to create {}", archive_dir.display()))?;
+    let path = archive_dir.join(format!("server-logs_{}.zip", Local::now().format("%Y-%m-%d_%H-%M-%S")));
+
+    let file = File::create(&path)
+        .context(format!("Failed to create {}", path.display()))?;
+    let mut zip = ZipWriter::new(file);
+    for log in logs {
+        let name = log.path.strip_prefix(profiles_dir).unwrap_or(&log.path).to_string_lossy().replace('\\', "/");
+        zip.start_file(name, SimpleFileOptions::default())
+            .context(format!("Failed to write {}", path.display()))?;
+        let mut source = File::open(&log.path)
+            .context(format!("Failed to open {}", log.path.display()))?;
+        io::copy(&mut source, &mut zip)
+            .context(format!("Failed to archive {}", log.path.display()))?;
+    }
+    zip.finish()
+        .context(format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Apply `logging.keep_days` and `logging.keep_count` to the server's logs before a start,
+/// so the profiles directory doesn't grow forever
+pub fn apply_before_launch(config: &LoggingConfig, server_install_dir: &Path) -> Result<()> {
+    if config.keep_days.is_none() && config.keep_count.is_none() {
+        return Ok(());
+    }
+    let profiles_dir = server_install_dir.join(SERVER_PROFILES);
+    if !profiles_dir.exists() {
+        return Ok(());
+    }
+
+    let expired = find_expired(config, find_logs(&profiles_dir)?);
+    if expired.is_empty() {
+        return Ok(());
+    }
+
+    let archived = if config.archive {
+        let spinner = Spinner::start(&format!("Archiving {} old server log(s)...", expired.len()), 1);
+        let path = archive(server_install_dir, &profiles_dir, &expired)?;
+        spinner.finish();
+        Some(path)
+    } else {
+        None
+    };
+
+    let mut removed = 0;
+    let mut freed = 0;
+    for log in &expired {
+        match fs::remove_file(&log.path) {
+            Ok(()) => {
+                removed += 1;
+                freed += log.size;
+            }
+            // e.g. a log file still open, it goes on a later run
+            Err(e) => println_failure(&format!("Failed to remove {}: {e}", log.path.display()), 1),
+        }
+    }
+
+    // The archive takes up some of what the logs did
+    let destination = archived.map_or_else(String::new, |path| {
+        freed = freed.saturating_sub(fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or_default());
+        format!(", archived to {}", path.display())
+    });
+    println_success(&format!("Removed {removed} old server log(s), freeing {}{destination}", format_size(freed)), 1);
+    Ok(())
+}
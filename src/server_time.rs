@@ -0,0 +1,303 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveDate;
+use std::fmt;
+use std::path::Path;
+use toml_edit::{Item, Table, value};
+
+use crate::cli::TimeCommand;
+use crate::config::{Config, TimeConfig};
+use crate::server::SERVER_CONFIG;
+use crate::server_cfg::ServerDzConfig;
+use crate::ui::status::{println_step, println_success};
+
+const SYSTEM_TIME: &str = "SystemTime";
+const MAX_ACCELERATION: f64 = 24.0;
+const MIN_NIGHT_ACCELERATION: f64 = 0.1;
+const MAX_NIGHT_ACCELERATION: f64 = 64.0;
+
+/// Rough in-game day and night lengths in hours, for estimating the real-time cycle.
+/// The actual split depends on the map and the in-game date.
+const DAYLIGHT_HOURS: f64 = 15.0;
+const NIGHT_HOURS: f64 = 9.0;
+
+/// A named set of time settings
+struct TimePreset {
+    name: &'static str,
+    description: &'static str,
+    server_time: &'static str,
+    acceleration: f64,
+    night_acceleration: f64,
+    persistent: bool,
+}
+
+const PRESETS: &[TimePreset] = &[
+    TimePreset {
+        name: "realtime",
+        description: "In-game time follows the host's clock",
+        server_time: SYSTEM_TIME,
+        acceleration: 1.0,
+        night_acceleration: 1.0,
+        persistent: false,
+    },
+    TimePreset {
+        name: "always-day",
+        description: "Every start is a summer morning at 08:00; stays light with restarts at least every 10 hours",
+        server_time: "2024/06/15/08/00",
+        acceleration: 1.0,
+        night_acceleration: 1.0,
+        persistent: false,
+    },
+    TimePreset {
+        name: "3h-day-1h-night",
+        description: "Roughly 3 hours of daylight followed by 1 hour of night",
+        server_time: SYSTEM_TIME,
+        acceleration: 5.0,
+        night_acceleration: 2.0,
+        persistent: true,
+    },
+    TimePreset {
+        name: "fast-nights",
+        description: "Real-time days, nights over in about an hour",
+        server_time: SYSTEM_TIME,
+        acceleration: 1.0,
+        night_acceleration: 9.0,
+        persistent: true,
+    },
+];
+
+fn find_preset(name: &str) -> Result<&'static TimePreset> {
+    PRESETS.iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow!(
+            "Unknown time preset '{name}', expected one of: {}",
+            PRESETS.iter().map(|preset| preset.name).collect::<Vec<_>>().join(", ")
+        ))
+}
+
+/// Fully resolved and validated `serverDZ.cfg` time settings
+#[allow(clippy::doc_markdown)]
+pub struct TimeSettings {
+    preset: Option<&'static str>,
+    server_time: String,
+    acceleration: f64,
+    night_acceleration: f64,
+    persistent: bool,
+}
+
+impl TimeSettings {
+    /// Combine the preset with any individual settings, or `None` if `[time]` is empty
+    pub fn resolve(config: &TimeConfig) -> Result<Option<Self>> {
+        let preset = config.preset.as_deref().map(find_preset).transpose()?;
+
+        let has_overrides = config.server_time.is_some()
+            || config.acceleration.is_some()
+            || config.night_acceleration.is_some()
+            || config.persistent.is_some();
+        if preset.is_none() && !has_overrides {
+            return Ok(None);
+        }
+
+        // Without a preset, unset values fall back to DayZ's own defaults
+        let settings = Self {
+            preset: preset.map(|preset| preset.name),
+            server_time: config.server_time.clone()
+                .unwrap_or_else(|| preset.map_or(SYSTEM_TIME, |preset| preset.server_time).to_string()),
+            acceleration: config.acceleration
+                .unwrap_or_else(|| preset.map_or(1.0, |preset| preset.acceleration)),
+            night_acceleration: config.night_acceleration
+                .unwrap_or_else(|| preset.map_or(1.0, |preset| preset.night_acceleration)),
+            persistent: config.persistent
+                .unwrap_or_else(|| preset.is_some_and(|preset| preset.persistent)),
+        };
+        settings.validate()?;
+        Ok(Some(settings))
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_server_time(&self.server_time)?;
+
+        if !(0.0..=MAX_ACCELERATION).contains(&self.acceleration) {
+            return Err(anyhow!(
+                "time.acceleration must be between 0 and {MAX_ACCELERATION}, got {}",
+                self.acceleration
+            ));
+        }
+        if !(MIN_NIGHT_ACCELERATION..=MAX_NIGHT_ACCELERATION).contains(&self.night_acceleration) {
+            return Err(anyhow!(
+                "time.night_acceleration must be between {MIN_NIGHT_ACCELERATION} and {MAX_NIGHT_ACCELERATION}, got {}",
+                self.night_acceleration
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Write the settings into `serverDZ.cfg`
+    #[allow(clippy::doc_markdown)]
+    pub fn apply(&self, server_cfg: &mut ServerDzConfig) {
+        server_cfg.set_string("serverTime", &self.server_time);
+        server_cfg.set_raw("serverTimeAcceleration", &self.acceleration.to_string());
+        server_cfg.set_raw("serverNightTimeAcceleration", &self.night_acceleration.to_string());
+        server_cfg.set_raw("serverTimePersistent", if self.persistent { "1" } else { "0" });
+    }
+}
+
+impl fmt::Display for TimeSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(preset) = self.preset {
+            write!(f, "{preset}: ")?;
+        }
+        write!(
+            f,
+            "start {}, {}x speed, {}x at night, {}",
+            self.server_time,
+            self.acceleration,
+            self.night_acceleration,
+            if self.persistent { "persistent" } else { "reset on restart" }
+        )?;
+
+        if self.acceleration > 0.0 {
+            let day = DAYLIGHT_HOURS / self.acceleration;
+            let night = NIGHT_HOURS / (self.acceleration * self.night_acceleration);
+            write!(f, " (about {day:.1}h day / {night:.1}h night)")?;
+        }
+        Ok(())
+    }
+}
+
+/// `SystemTime` or `YYYY/MM/DD/HH/MM`
+fn validate_server_time(server_time: &str) -> Result<()> {
+    if server_time == SYSTEM_TIME {
+        return Ok(());
+    }
+
+    let invalid = || anyhow!("time.server_time must be \"{SYSTEM_TIME}\" or \"YYYY/MM/DD/HH/MM\", got \"{server_time}\"");
+
+    let parts = server_time.split('/')
+        .map(|part| part.trim().parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>>>()?;
+    let [year, month, day, hour, minute] = parts[..] else {
+        return Err(invalid());
+    };
+
+    let year = i32::try_from(year).map_err(|_| invalid())?;
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_opt(hour, minute, 0))
+        .map(|_| ())
+        .ok_or_else(invalid)
+}
+
+/// Write the `[time]` settings into `serverDZ.cfg` before a start, if any are configured
+#[allow(clippy::doc_markdown)]
+pub fn apply_before_launch(config: &TimeConfig, server_install_dir: &Path) -> Result<()> {
+    let Some(settings) = TimeSettings::resolve(config)? else {
+        return Ok(());
+    };
+
+    let config_path = server_install_dir.join(SERVER_CONFIG);
+    let mut server_cfg = ServerDzConfig::load(&config_path)?;
+    settings.apply(&mut server_cfg);
+    server_cfg.save()?;
+
+    println_success(&format!("In-game time: {settings}"), 1);
+    Ok(())
+}
+
+fn show(config: &TimeConfig) -> Result<()> {
+    match TimeSettings::resolve(config)? {
+        Some(settings) => println_step(&format!("In-game time: {settings}"), 0),
+        None => println_step(&format!("No [time] settings, {SERVER_CONFIG} is left as it is"), 0),
+    }
+    Ok(())
+}
+
+fn list_presets() {
+    for preset in PRESETS {
+        println_step(&format!("{:<16} {}", preset.name, preset.description), 0);
+    }
+}
+
+/// Update `[time]` (or `[profiles.<name>.time]`) in `config.toml`; a new preset clears the individual settings
+fn set(mut time: TimeConfig, preset: Option<&str>, overrides: TimeConfig, profile: Option<&str>) -> Result<()> {
+    if preset.is_none()
+        && overrides.server_time.is_none()
+        && overrides.acceleration.is_none()
+        && overrides.night_acceleration.is_none()
+        && overrides.persistent.is_none()
+    {
+        return Err(anyhow!("Nothing to set, give a preset and/or --server-time, --acceleration, --night-acceleration, --persistent"));
+    }
+
+    if let Some(preset) = preset {
+        time = TimeConfig {
+            preset: Some(find_preset(preset)?.name.to_string()),
+            ..TimeConfig::default()
+        };
+    }
+    time.server_time = overrides.server_time.or(time.server_time);
+    time.acceleration = overrides.acceleration.or(time.acceleration);
+    time.night_acceleration = overrides.night_acceleration.or(time.night_acceleration);
+    time.persistent = overrides.persistent.or(time.persistent);
+
+    // Validate before anything is written
+    let settings = TimeSettings::resolve(&time)?
+        .context("No time settings to apply")?;
+
+    let path = match profile {
+        Some(profile) => vec!["profiles", profile, "time"],
+        None => vec!["time"],
+    };
+    Config::edit(|document| {
+        let mut table = document.as_table_mut();
+        for key in &path {
+            table = table
+                .entry(key)
+                .or_insert(Item::Table(Table::new()))
+                .as_table_mut()
+                .context(format!("[{}] in config.toml is not a table", path.join(".")))?;
+        }
+
+        set_or_remove(table, "preset", time.preset.clone().map(value));
+        set_or_remove(table, "server_time", time.server_time.clone().map(value));
+        set_or_remove(table, "acceleration", time.acceleration.map(value));
+        set_or_remove(table, "night_acceleration", time.night_acceleration.map(value));
+        set_or_remove(table, "persistent", time.persistent.map(value));
+        Ok(())
+    })?;
+
+    println_success(&format!("In-game time set to {settings}"), 0);
+    println_step("Takes effect at the next server start", 1);
+    Ok(())
+}
+
+fn set_or_remove(table: &mut Table, key: &str, item: Option<Item>) {
+    match item {
+        Some(item) => {
+            table.insert(key, item);
+        }
+        None => {
+            table.remove(key);
+        }
+    }
+}
+
+/// Entry point for `dzsm time ...`
+pub fn run(command: &TimeCommand, config: &Config, profile: Option<&str>) -> Result<()> {
+    match command {
+        TimeCommand::Show => show(&config.time),
+        TimeCommand::Presets => {
+            list_presets();
+            Ok(())
+        }
+        TimeCommand::Set { preset, server_time, acceleration, night_acceleration, persistent } => {
+            let overrides = TimeConfig {
+                preset: None,
+                server_time: server_time.clone(),
+                acceleration: *acceleration,
+                night_acceleration: *night_acceleration,
+                persistent: *persistent,
+            };
+            set(config.time.clone(), preset.as_deref(), overrides, profile)
+        }
+    }
+}
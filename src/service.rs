@@ -0,0 +1,398 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+use std::ffi::c_void;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::ptr;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use windows_sys::Win32::Foundation::{ERROR_CALL_NOT_IMPLEMENTED, ERROR_SERVICE_SPECIFIC_ERROR, NO_ERROR};
+use windows_sys::Win32::System::Services::{
+    RegisterServiceCtrlHandlerExW, SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_CONTROL_INTERROGATE,
+    SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_RUNNING, SERVICE_START_PENDING, SERVICE_STATUS,
+    SERVICE_STATUS_CURRENT_STATE, SERVICE_STOP_PENDING, SERVICE_STOPPED, SERVICE_TABLE_ENTRYW,
+    SERVICE_WIN32_OWN_PROCESS, SetServiceStatus, StartServiceCtrlDispatcherW,
+};
+use windows_sys::core::PWSTR;
+
+use crate::cli::ServiceCommand;
+use crate::config::{CONFIG_FILE, Config, ShutdownConfig};
+use crate::control::{get_stop_timeout, kill_server};
+use crate::state::logs_dir;
+use crate::supervisor::request_stop;
+use crate::ui::json::print_output;
+use crate::ui::status::{println_step, println_success};
+
+const SERVICE_LOG: &str = "service.log";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+static SERVICE_NAME: OnceLock<String> = OnceLock::new();
+static SERVICE_TARGET: OnceLock<Target> = OnceLock::new();
+static STATUS_HANDLE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// The server a service runs: the directory with `config.toml` and, for `--profile`, which of its servers
+struct Target {
+    root_dir: PathBuf,
+    profile: Option<String>,
+    install_dir: PathBuf,
+    /// How long the supervisor gets to shut the server down before both are killed
+    stop_timeout: Duration,
+}
+
+impl Target {
+    fn resolve(root_dir: &Path, profile: Option<&str>) -> Result<Self> {
+        let config_path = root_dir.join(CONFIG_FILE);
+        let (shutdown, install_dir) = match profile {
+            Some(profile) => {
+                let (config, install_dir) = Config::load_profile(root_dir, profile)?;
+                (config.shutdown, install_dir)
+            }
+            // The supervisor writes the default config on its first run
+            None if !config_path.exists() => (ShutdownConfig::default(), root_dir.to_path_buf()),
+            None => {
+                let content = fs::read_to_string(&config_path)
+                    .context(format!("Failed to read {}", config_path.display()))?;
+                (Config::parse(&content)?.shutdown, root_dir.to_path_buf())
+            }
+        };
+
+        Ok(Self {
+            root_dir: root_dir.to_path_buf(),
+            profile: profile.map(str::to_string),
+            install_dir,
+            stop_timeout: get_stop_timeout(&shutdown),
+        })
+    }
+
+    /// ` --profile <name>` for commands run on this target's behalf
+    fn profile_arg(&self) -> String {
+        self.profile.as_ref()
+            .map(|profile| format!(" --profile \"{profile}\""))
+            .unwrap_or_default()
+    }
+}
+
+/// Entry point for `dzsm service ...`
+pub fn run(command: &ServiceCommand, root_dir: &Path, profile: Option<&str>) -> Result<()> {
+    // `run` gets its directory from the service's arguments, the rest from the command line
+    let resolve = || Target::resolve(root_dir, profile);
+    match command {
+        ServiceCommand::Run { name, dir } => run_as_service(name, Target::resolve(dir, profile)?),
+        ServiceCommand::Install { name, user, password } => {
+            let target = resolve()?;
+            install(&resolve_name(name.as_deref(), &target), &target, user.as_deref(), password.as_deref())
+        }
+        ServiceCommand::Uninstall { name } => uninstall(&resolve_name(name.as_deref(), &resolve()?)),
+        ServiceCommand::Start { name } => {
+            let target = resolve()?;
+            let name = resolve_name(name.as_deref(), &target);
+            sc(&["start", &name])?;
+            println_success(&format!("Service {name} started (log: {})", get_log_path(&target.install_dir).display()), 0);
+            Ok(())
+        }
+        ServiceCommand::Stop { name } => {
+            let name = resolve_name(name.as_deref(), &resolve()?);
+            sc(&["stop", &name])?;
+            println_success(&format!("Service {name} is stopping"), 0);
+            Ok(())
+        }
+        ServiceCommand::SystemdUnit => {
+            print_output(systemd_unit(&resolve()?)?.trim_end());
+            Ok(())
+        }
+    }
+}
+
+/// Default service name, derived from the install directory and profile so several servers can be installed side by side
+fn resolve_name(name: Option<&str>, target: &Target) -> String {
+    if let Some(name) = name {
+        return name.to_string();
+    }
+
+    let mut dir_name = target.root_dir
+        .file_name()
+        .map_or_else(|| "server".to_string(), |n| n.to_string_lossy().to_string());
+    if let Some(profile) = &target.profile {
+        dir_name = format!("{dir_name}-{profile}");
+    }
+    let sanitized: String = dir_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+
+    format!("dzsm-{sanitized}")
+}
+
+/// Register DZSM as an auto-start Windows service for this server install
+fn install(name: &str, target: &Target, user: Option<&str>, password: Option<&str>) -> Result<()> {
+    let exe = env::current_exe()
+        .context("Failed to locate the dzsm executable")?;
+
+    let bin_path = format!(
+        "\"{}\" service run --name \"{name}\" --dir \"{}\"{}",
+        exe.display(),
+        target.root_dir.display(),
+        target.profile_arg()
+    );
+    let display_name = format!("DZSM ({name})");
+
+    println_step(&format!("Installing service {name}..."), 0);
+
+    let mut args = vec!["create", name, "binPath=", &bin_path, "start=", "auto", "DisplayName=", &display_name];
+    if let Some(user) = user {
+        args.extend(["obj=", user]);
+        if let Some(password) = password {
+            args.extend(["password=", password]);
+        }
+    }
+    sc(&args)?;
+
+    let description = format!("Updates and runs the DayZ server in {}", target.install_dir.display());
+    sc(&["description", name, &description])?;
+
+    // Let Windows bring the service back if DZSM itself gives up (e.g. crash looping)
+    sc(&["failure", name, "reset=", "86400", "actions=", "restart/60000/restart/60000/restart/60000"])?;
+
+    println_success(&format!("Service {name} installed, start it with `dzsm service start`"), 0);
+    Ok(())
+}
+
+fn uninstall(name: &str) -> Result<()> {
+    println_step(&format!("Uninstalling service {name}..."), 0);
+
+    // Stopping fails if the service isn't running, which is fine
+    let _ = sc(&["stop", name]);
+    sc(&["delete", name])?;
+
+    println_success(&format!("Service {name} uninstalled"), 0);
+    Ok(())
+}
+
+/// Run the Windows service control tool, surfacing its output on failure
+fn sc(args: &[&str]) -> Result<()> {
+    let output = Command::new("sc.exe")
+        .args(args)
+        .output()
+        .context("Failed to run sc.exe")?;
+
+    if !output.status.success() {
+        // sc.exe reports errors on stdout
+        return Err(anyhow!(
+            "sc.exe {} failed (are you running as administrator?):\n{}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stdout).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// A systemd unit that runs the supervisor for this server install
+fn systemd_unit(target: &Target) -> Result<String> {
+    let exe = env::current_exe()
+        .context("Failed to locate the dzsm executable")?;
+    let dir = target.install_dir.display();
+    let log_path = get_log_path(&target.install_dir);
+
+    Ok(format!(
+        "[Unit]
+Description=DZSM DayZ server ({dir})
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=simple
+WorkingDirectory={root}
+ExecStart=\"{exe}\" run --supervise --non-interactive --no-banner{profile}
+ExecStop=/usr/bin/touch \"{dir}/.dzsm/stop.request\"
+TimeoutStopSec={timeout}
+Restart=on-failure
+RestartSec=60
+StandardOutput=append:{log}
+StandardError=append:{log}
+
+[Install]
+WantedBy=multi-user.target
+",
+        exe = exe.display(),
+        root = target.root_dir.display(),
+        profile = target.profile_arg(),
+        timeout = target.stop_timeout.as_secs(),
+        log = log_path.display(),
+    ))
+}
+
+fn get_log_path(server_install_dir: &Path) -> PathBuf {
+    logs_dir(server_install_dir).join(SERVICE_LOG)
+}
+
+fn open_log(server_install_dir: &Path) -> Result<File> {
+    let path = get_log_path(server_install_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context("Failed to create logs directory")?;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .context(format!("Failed to open {}", path.display()))
+}
+
+/// Services have no console, so anything worth knowing goes to the service log
+fn log_line(server_install_dir: &Path, message: &str) {
+    if let Ok(mut log) = open_log(server_install_dir) {
+        let _ = writeln!(log, "[{}] {message}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+    }
+}
+
+fn to_wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Hand this process over to the service control manager (`dzsm service run`, started by Windows)
+fn run_as_service(name: &str, target: Target) -> Result<()> {
+    let _ = SERVICE_NAME.set(name.to_string());
+    let _ = SERVICE_TARGET.set(target);
+
+    let mut service_name = to_wide(name);
+    let table = [
+        SERVICE_TABLE_ENTRYW { lpServiceName: service_name.as_mut_ptr(), lpServiceProc: Some(service_main) },
+        SERVICE_TABLE_ENTRYW { lpServiceName: ptr::null_mut(), lpServiceProc: None },
+    ];
+
+    // Blocks until the service has stopped
+    if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+        return Err(anyhow!(
+            "Failed to connect to the service control manager (this command is run by Windows, use `dzsm service start`): {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    Ok(())
+}
+
+unsafe extern "system" fn service_main(_argc: u32, _argv: *mut PWSTR) {
+    let (Some(name), Some(target)) = (SERVICE_NAME.get(), SERVICE_TARGET.get()) else {
+        return;
+    };
+    let dir = &target.install_dir;
+
+    let wide_name = to_wide(name);
+    let handle = unsafe { RegisterServiceCtrlHandlerExW(wide_name.as_ptr(), Some(control_handler), ptr::null()) };
+    if handle.is_null() {
+        return;
+    }
+    STATUS_HANDLE.store(handle, Ordering::SeqCst);
+
+    set_status(SERVICE_START_PENDING, 0);
+    log_line(dir, &format!("Service {name} starting"));
+
+    let exit_code = match supervise(target) {
+        Ok(()) => {
+            log_line(dir, &format!("Service {name} stopped"));
+            0
+        }
+        Err(e) => {
+            log_line(dir, &format!("Service {name} failed: {e:#}"));
+            1
+        }
+    };
+
+    set_status(SERVICE_STOPPED, exit_code);
+}
+
+unsafe extern "system" fn control_handler(control: u32, _event_type: u32, _event_data: *mut c_void, _context: *mut c_void) -> u32 {
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            STOP_REQUESTED.store(true, Ordering::SeqCst);
+            set_status(SERVICE_STOP_PENDING, 0);
+            NO_ERROR
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
+
+fn set_status(state: SERVICE_STATUS_CURRENT_STATE, exit_code: u32) {
+    let status = SERVICE_STATUS {
+        dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+        dwCurrentState: state,
+        dwControlsAccepted: if state == SERVICE_RUNNING { SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN } else { 0 },
+        dwWin32ExitCode: if exit_code == 0 { NO_ERROR } else { ERROR_SERVICE_SPECIFIC_ERROR },
+        dwServiceSpecificExitCode: exit_code,
+        dwCheckPoint: 0,
+        dwWaitHint: match SERVICE_TARGET.get() {
+            Some(target) if state == SERVICE_STOP_PENDING => u32::try_from(target.stop_timeout.as_millis()).unwrap_or(u32::MAX),
+            _ => 0,
+        },
+    };
+
+    unsafe {
+        SetServiceStatus(STATUS_HANDLE.load(Ordering::SeqCst), &raw const status);
+    }
+}
+
+/// Run `dzsm run --supervise` in the background, logging to a file, until Windows asks the service to stop
+fn supervise(target: &Target) -> Result<()> {
+    let log = open_log(&target.install_dir)?;
+    let exe = env::current_exe()
+        .context("Failed to locate the dzsm executable")?;
+
+    let mut command = Command::new(exe);
+    command.args(["run", "--supervise", "--non-interactive", "--no-banner"]);
+    if let Some(profile) = &target.profile {
+        command.args(["--profile", profile]);
+    }
+    let mut child = command
+        .current_dir(&target.root_dir)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone().context("Failed to open service log")?)
+        .stderr(log)
+        .spawn()
+        .context("Failed to start the DZSM supervisor")?;
+
+    set_status(SERVICE_RUNNING, 0);
+
+    loop {
+        if let Some(status) = child.try_wait().context("Failed to check on the DZSM supervisor")? {
+            if status.success() {
+                return Ok(());
+            }
+            return Err(anyhow!("DZSM supervisor exited with code {:?}", status.code()));
+        }
+
+        if STOP_REQUESTED.load(Ordering::SeqCst) {
+            return stop_supervisor(&mut child, target);
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Ask the supervisor to stop the server, killing both if it doesn't exit in time. The server
+/// is in a process group of its own, so killing the supervisor alone would leave it running.
+fn stop_supervisor(child: &mut Child, target: &Target) -> Result<()> {
+    let server_install_dir = &target.install_dir;
+    request_stop(server_install_dir)?;
+
+    let deadline = Instant::now() + target.stop_timeout;
+    while Instant::now() < deadline {
+        if child.try_wait().context("Failed to check on the DZSM supervisor")?.is_some() {
+            return Ok(());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    log_line(server_install_dir, "Supervisor did not stop in time, killing it and the server");
+    child.kill().context("Failed to kill the DZSM supervisor")?;
+    child.wait().context("Failed to wait for the DZSM supervisor")?;
+    kill_server(server_install_dir)
+}
@@ -0,0 +1,19 @@
+use std::path::{Path, PathBuf};
+
+/// Directory inside the server install dir where DZSM keeps its own files
+pub const STATE_DIR: &str = ".dzsm";
+
+/// Get the DZSM state directory for a server install
+pub fn state_dir(server_install_dir: &Path) -> PathBuf {
+    server_install_dir.join(STATE_DIR)
+}
+
+/// Get the directory DZSM writes its log files to
+pub fn logs_dir(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join("logs")
+}
+
+/// Get the directory reports for admins are saved to
+pub fn reports_dir(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join("reports")
+}
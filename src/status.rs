@@ -0,0 +1,378 @@
+use anyhow::{Result, anyhow};
+use std::collections::BTreeSet;
+use std::ffi::c_void;
+use std::fs;
+use std::iter;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use windows_sys::Win32::Storage::FileSystem::{GetFileVersionInfoSizeW, GetFileVersionInfoW, VS_FIXEDFILEINFO, VerQueryValueW};
+
+use crate::a2s;
+use crate::collection_fetcher::CollectionFetcher;
+use crate::config::Config;
+use crate::config::mod_entry::ModEntry;
+use crate::config::server_config::ServerBranch;
+use crate::crash;
+use crate::lock::read_lock_file;
+use crate::maintenance::Maintenance;
+use crate::mod_links::is_link;
+use crate::mods::InstallPlan;
+use crate::processes;
+use crate::server::{MISSIONS_DIR, SERVER_CONFIG, SERVER_EXE, SERVER_KEYS, SERVER_PROFILES, VANILLA_KEY};
+use crate::server_cfg::ServerDzConfig;
+use crate::steamcmd::SteamCmdManager;
+use crate::storage::{dir_size, format_size};
+use crate::ui::status::{println_failure, println_step, println_success};
+use crate::vdf;
+
+
+/// Health report for `dzsm status`. Problems are counted so the exit code can be used in scripts.
+struct StatusReport<'a> {
+    config: &'a Config,
+    server_install_dir: &'a Path,
+    offline: bool,
+    problems: usize,
+}
+
+impl StatusReport<'_> {
+    fn problem(&mut self, message: &str, level: usize) {
+        println_failure(message, level);
+        self.problems += 1;
+    }
+
+    fn report_server(&self) {
+        let exe_path = self.server_install_dir.join(SERVER_EXE);
+
+        match processes::find_by_exe_path(&exe_path).as_slice() {
+            [] => println_step("Server is not running", 1),
+            running => {
+                let pids: Vec<String> = running.iter().map(|process| process.pid.to_string()).collect();
+                println_success(&format!("Server is running (PID {})", pids.join(", ")), 1);
+
+                // Still loading its mission if the query port isn't answering yet
+                match a2s::query_server(self.server_install_dir) {
+                    Ok(info) => println_step(&format!(
+                        "{}: {}/{} players on {}, version {}, {} ms ping",
+                        info.name,
+                        info.human_players(),
+                        info.max_players,
+                        info.map,
+                        info.version,
+                        info.ping.as_millis()
+                    ), 2),
+                    Err(e) => println_step(&format!("Query port not answering: {e:#}"), 2),
+                }
+            }
+        }
+
+        if exe_path.exists() {
+            let version = get_file_version(&exe_path).unwrap_or_else(|| "unknown".to_string());
+            let build = get_build_id(self.server_install_dir).unwrap_or_else(|| "unknown".to_string());
+            println_step(&format!("Version {version}, Steam build {build}"), 1);
+        }
+
+        match Maintenance::load(self.server_install_dir) {
+            Ok(Some(maintenance)) => println_failure(&format!("{}, see `dzsm maintenance`", maintenance.describe()), 1),
+            Ok(None) => {}
+            Err(e) => println_failure(&format!("Failed to read the maintenance state: {e:#}"), 1),
+        }
+    }
+
+    fn report_files(&mut self) {
+        let mut files = vec![
+            SERVER_EXE.to_string(),
+            SERVER_CONFIG.to_string(),
+            format!("{SERVER_KEYS}/{VANILLA_KEY}"),
+        ];
+
+        // The mission serverDZ.cfg points at has to exist too
+        if let Ok(server_cfg) = ServerDzConfig::load(&self.server_install_dir.join(SERVER_CONFIG))
+            && let Some(template) = server_cfg.get("template")
+        {
+            files.push(format!("{MISSIONS_DIR}/{template}"));
+        }
+
+        for file in &files {
+            if self.server_install_dir.join(file).exists() {
+                println_success(file, 1);
+            } else {
+                self.problem(&format!("{file} is missing"), 1);
+            }
+        }
+    }
+
+    /// The configured mods, with the collections' when they can be fetched, and whether
+    /// every collection could be
+    fn get_configured_mods(&self) -> (Vec<ModEntry>, bool) {
+        let mut mods: Vec<ModEntry> = self.config.mods.server_mod_list.iter().flatten().cloned().collect();
+
+        let collection_urls = self.config.mods.get_collection_urls();
+        let mut collection_mods = Vec::new();
+        let mut complete = true;
+        if !collection_urls.is_empty() {
+            let fetched = if self.offline {
+                CollectionFetcher::load_collections_mods(&collection_urls)
+            } else {
+                CollectionFetcher::fetch_collections_mods(&collection_urls)
+            };
+            for e in &fetched.failed {
+                println_failure(&format!("Failed to fetch collection, its mods aren't checked: {e:#}"), 1);
+            }
+            complete = fetched.failed.is_empty();
+            collection_mods = fetched.mods;
+        }
+        mods.extend(self.config.mods.filter_collection(collection_mods));
+        mods.retain(|mod_entry| !self.config.mods.is_disabled(mod_entry));
+        (mods, complete)
+    }
+
+    /// Plan the configured mods from where `SteamCMD` keeps them, without setting it up
+    fn plan_mods(&self, mods: &[ModEntry]) -> Result<InstallPlan> {
+        let steamcmd = SteamCmdManager::without_install(&self.config.server.steamcmd_dir, true);
+        let planned: Vec<_> = mods.iter()
+            .map(|mod_entry| Ok((mod_entry.clone(), steamcmd.get_workshop_mod_dir(self.config.server.branch.game_app_id(), mod_entry.id)?)))
+            .collect::<Result<_>>()?;
+        Ok(InstallPlan::new(&self.config.mods, self.server_install_dir, planned))
+    }
+
+    /// Put drifted mod folders and keys back from SteamCMD's downloads, see `InstallPlan::repair`
+    #[allow(clippy::doc_markdown)]
+    fn repair_mods(&mut self, mods: &[ModEntry], complete: bool) {
+        println_step("Repairing mod folders and keys...", 1);
+        match self.plan_mods(mods).and_then(|plan| plan.incomplete(!complete).repair()) {
+            Ok(0) => println_success("Nothing to repair", 2),
+            Ok(repaired) => println_success(&format!("Repaired {repaired} mod folder(s) and key(s)"), 2),
+            Err(e) => self.problem(&format!("Repair failed: {e:#}"), 2),
+        }
+    }
+
+    /// Compare the `@mod` links in the install dir with the configured mods
+    fn report_mods(&mut self, mods: &[ModEntry]) {
+        let configured: BTreeSet<String> = mods.iter()
+            .map(|mod_entry| self.config.mods.get_folder(mod_entry).trim_start_matches('@').to_string())
+            .collect();
+
+        let mut installed = BTreeSet::new();
+        let mut broken = Vec::new();
+        for entry in fs::read_dir(self.server_install_dir).into_iter().flatten().flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(name) = file_name.strip_prefix('@') else {
+                continue;
+            };
+
+            // Follows the link, so a mod whose workshop files are gone fails here
+            if fs::metadata(entry.path()).is_err() {
+                broken.push(name.to_string());
+            }
+            installed.insert(name.to_string());
+        }
+
+        let missing: Vec<&String> = configured.difference(&installed).collect();
+        let stale: Vec<&String> = installed.difference(&configured).collect();
+
+        let installed_count = configured.len() - missing.len();
+        if missing.is_empty() && stale.is_empty() && broken.is_empty() {
+            println_success(&format!("{installed_count} of {} configured mod(s) installed", configured.len()), 1);
+            return;
+        }
+
+        println_step(&format!("{installed_count} of {} configured mod(s) installed", configured.len()), 1);
+        for name in missing {
+            self.problem(&format!("@{name} is configured but not installed"), 2);
+        }
+        for name in stale {
+            self.problem(&format!("@{name} is installed but no longer configured"), 2);
+        }
+        for name in broken {
+            self.problem(&format!("@{name} links to workshop files that no longer exist"), 2);
+        }
+    }
+
+    /// Key links whose workshop files are gone, and keys missing, orphaned, or clashing between
+    /// mods; a missing or clashing key gets players using that mod kicked
+    fn report_keys(&mut self, mods: &[ModEntry]) {
+        let broken: Vec<String> = fs::read_dir(self.server_install_dir.join(SERVER_KEYS)).into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| is_link(&entry.path()) && fs::metadata(entry.path()).is_err())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        for name in &broken {
+            self.problem(&format!(
+                "Key {name} links to workshop files that no longer exist, reinstall mods or set key_strategy = \"copy\""
+            ), 1);
+        }
+
+        let check = match self.plan_mods(mods).and_then(|plan| plan.check_keys()) {
+            Ok(check) => check,
+            Err(e) => {
+                self.problem(&format!("Failed to check mod keys: {e:#}"), 1);
+                return;
+            }
+        };
+        if check.is_empty() && broken.is_empty() {
+            println_success("Every configured mod's keys are installed", 1);
+            return;
+        }
+
+        for (name, key_name) in &check.missing {
+            self.problem(&format!("{name} ships key {key_name}, which isn't in {SERVER_KEYS}/; players using it are kicked"), 1);
+        }
+        for (key_name, mod_names) in &check.conflicts {
+            self.problem(&format!("{} ship different keys named {key_name}, only one can be installed", mod_names.join(" and ")), 1);
+        }
+        for key_name in &check.orphaned {
+            self.problem(&format!("Key {key_name} belongs to no configured mod, the next mod install removes it"), 1);
+        }
+    }
+
+    /// Client mods whose PBOs aren't signed by a key in the keys directory, see `InstallPlan::check_signatures`
+    fn report_signatures(&mut self, mods: &[ModEntry]) {
+        let check = match self.plan_mods(mods).and_then(|plan| plan.check_signatures()) {
+            Ok(check) => check,
+            Err(e) => {
+                self.problem(&format!("Failed to check mod signatures: {e:#}"), 1);
+                return;
+            }
+        };
+        if check.is_empty() {
+            println_success("Every client mod's PBOs are signed by an installed key", 1);
+            return;
+        }
+
+        for (name, pbos) in &check.unsigned {
+            self.problem(&format!("{name} has unsigned PBO(s), players using it are kicked: {}", pbos.join(", ")), 1);
+        }
+        for (name, authority) in &check.untrusted {
+            self.problem(&format!("{name} is signed by {authority}, whose key isn't in {SERVER_KEYS}/; players using it are kicked"), 1);
+        }
+        for (name, e) in &check.unreadable {
+            self.problem(&format!("Failed to check a signature of {name}: {e}"), 1);
+        }
+    }
+
+    fn report_profiles(&self) {
+        let profiles_dir = self.server_install_dir.join(SERVER_PROFILES);
+        match dir_size(&profiles_dir) {
+            Ok(size) => println_step(&format!("Profiles: {}", format_size(size)), 1),
+            Err(_) => println_step("Profiles: none yet", 1),
+        }
+    }
+
+    fn report_last_crash(&self) {
+        match crash::get_last_capture(self.server_install_dir) {
+            Some(crash_dir) => {
+                let name = crash_dir.file_name().map_or_else(String::new, |name| name.to_string_lossy().to_string());
+                println_step(&format!("Last crash: {name} ({})", crash_dir.display()), 1);
+            }
+            None => println_step("Last crash: none recorded", 1),
+        }
+    }
+}
+
+fn report_lock_file() {
+    match read_lock_file() {
+        Some(content) => {
+            let summary = content.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(", ");
+            println_success(&format!("Lock file: {summary}"), 1);
+        }
+        None => println_step("Lock file: none, this directory isn't managed by DZSM yet", 1),
+    }
+}
+
+/// `FileVersion` from the exe's version resource, e.g. 1.25.0.158593
+#[allow(clippy::doc_markdown)]
+pub fn get_file_version(path: &Path) -> Option<String> {
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(iter::once(0)).collect();
+
+    let size = unsafe { GetFileVersionInfoSizeW(wide_path.as_ptr(), ptr::null_mut()) };
+    if size == 0 {
+        return None;
+    }
+
+    let mut data = vec![0u8; size as usize];
+    if unsafe { GetFileVersionInfoW(wide_path.as_ptr(), 0, size, data.as_mut_ptr().cast()) } == 0 {
+        return None;
+    }
+
+    let root: Vec<u16> = "\\".encode_utf16().chain(iter::once(0)).collect();
+    let mut info: *mut c_void = ptr::null_mut();
+    let mut length = 0u32;
+    let ok = unsafe { VerQueryValueW(data.as_ptr().cast(), root.as_ptr(), &raw mut info, &raw mut length) };
+    if ok == 0 || info.is_null() || (length as usize) < size_of::<VS_FIXEDFILEINFO>() {
+        return None;
+    }
+
+    let info = unsafe { &*info.cast::<VS_FIXEDFILEINFO>() };
+    Some(format!(
+        "{}.{}.{}.{}",
+        info.dwFileVersionMS >> 16,
+        info.dwFileVersionMS & 0xFFFF,
+        info.dwFileVersionLS >> 16,
+        info.dwFileVersionLS & 0xFFFF
+    ))
+}
+
+/// Steam build id from the app manifest SteamCMD writes next to the server. With both
+/// branches' manifests there after switching `server.branch`, the newer one is the installed build.
+#[allow(clippy::doc_markdown)]
+pub fn get_build_id(server_install_dir: &Path) -> Option<String> {
+    let manifest_path = ServerBranch::ALL.iter()
+        .map(|branch| get_app_manifest_path(server_install_dir, branch.server_app_id()))
+        .filter_map(|path| fs::metadata(&path).and_then(|metadata| metadata.modified()).ok().map(|modified| (modified, path)))
+        .max()?
+        .1;
+    let content = fs::read_to_string(manifest_path).ok()?;
+
+    vdf::parse(&content).ok()?
+        .get_path(&["AppState", "buildid"])?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// The app manifest SteamCMD keeps for an app it installed in the server install dir
+#[allow(clippy::doc_markdown)]
+pub fn get_app_manifest_path(server_install_dir: &Path, app_id: u32) -> PathBuf {
+    server_install_dir.join("steamapps").join(format!("appmanifest_{app_id}.acf"))
+}
+
+/// Entry point for `dzsm status`, with `repair` fixing mod folders and keys before they're checked
+pub fn run(config: &Config, server_install_dir: &str, offline: bool, repair: bool) -> Result<()> {
+    let mut report = StatusReport {
+        config,
+        server_install_dir: Path::new(server_install_dir),
+        offline,
+        problems: 0,
+    };
+
+    println_step(&format!("Status of {server_install_dir}"), 0);
+    report_lock_file();
+    report.report_server();
+
+    println_step("Files", 0);
+    report.report_files();
+
+    println_step("Mods", 0);
+    let (mods, complete) = report.get_configured_mods();
+    if repair {
+        report.repair_mods(&mods, complete);
+    }
+    let problems_before = report.problems;
+    report.report_mods(&mods);
+    report.report_keys(&mods);
+    report.report_signatures(&mods);
+    if report.problems > problems_before && !repair {
+        println_step("`dzsm status --repair` fixes mod folders and keys from the downloaded files", 1);
+    }
+
+    println_step("History", 0);
+    report.report_profiles();
+    report.report_last_crash();
+
+    if report.problems > 0 {
+        return Err(anyhow!("{} problem(s) found", report.problems));
+    }
+    Ok(())
+}
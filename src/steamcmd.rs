@@ -1,19 +1,82 @@
 use anyhow::{Context, Result, anyhow};
-use std::fs;
-use std::path::PathBuf;
-use std::io::Cursor;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::os::windows::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::io::{self, Cursor, Read, Write};
+use std::iter;
 use curl::easy::Easy;
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
 
-use crate::ui::status::{println_failure, println_step, println_success};
-use crate::ui::prompt::prompt_yes_no;
+use crate::config::Config;
+use crate::credentials::get_steam_password;
+use crate::mod_cache::ModCache;
+use crate::steamcmd_errors::{SteamCmdError, SteamCmdFailure};
+use crate::storage::dir_size;
+use crate::ui::status::{println_debug, println_failure, println_step, println_success};
+use crate::ui::json::is_json_output;
+use crate::ui::progress::ProgressLine;
+use crate::ui::prompt::{child_stdin, prompt_yes_no};
+use crate::workshop_manifest::WorkshopManifest;
 
 const STEAMCMD_EXE: &str = "steamcmd.exe";
 const STEAMCMD_DOWNLOAD_URL: &str = "https://steamcdn-a.akamaihd.net/client/installer/steamcmd.zip";
+/// `SteamCMD`'s settings, where the content server cell override lives
+const CONFIG_VDF: &str = "config/config.vdf";
+const CELL_OVERRIDE_KEY: &str = "\"CellIDServerOverride\"";
+/// Written when `SteamCMD` hasn't created its config yet
+const EMPTY_CONFIG_VDF: &str = "\"InstallConfigStore\"\n{\n\t\"Software\"\n\t{\n\t\t\"Valve\"\n\t\t{\n\t\t\t\"Steam\"\n\t\t\t{\n\t\t\t}\n\t\t}\n\t}\n}\n";
+/// Lock file in the `SteamCMD` dir, see `SteamCmdLock`
+const STEAMCMD_LOCK: &str = "dzsm_steamcmd.lock";
+/// Script logging in with a stored password, see `LoginScript`
+const LOGIN_SCRIPT: &str = "dzsm_login";
+/// How often to retry a lock another DZSM holds
+const LOCK_POLL: Duration = Duration::from_secs(1);
+/// Windows' error for opening a file another process has open without sharing
+const ERROR_SHARING_VIOLATION: i32 = 32;
+/// How `SteamCMD` starts a progress update, e.g.
+/// `Update state (0x61) downloading, progress: 45.23 (1234567890 / 2729721234)`
+const PROGRESS_PREFIX: &str = "Update state";
 
 pub struct SteamCmdManager {
     steamcmd_dir: PathBuf,
     offline: bool,
+    /// From `[steamcmd]`, see `with_options`
+    extra_args: Vec<String>,
+    env: BTreeMap<String, String>,
+    /// Username to password, from the Credential Manager with `steam.credential_source`;
+    /// accounts without one use `SteamCMD`'s cached login
+    passwords: BTreeMap<String, String>,
+    /// From `downloads.throttle_kbps`
+    throttle_kbps: Option<u32>,
+}
+
+/// Held while a DZSM process works on a game's workshop content. Several DZSM instances
+/// sharing one `SteamCMD` would otherwise corrupt its `appworkshop` manifest. The lock is
+/// a file opened without sharing, so Windows releases it even if DZSM is killed.
+pub struct WorkshopLock {
+    _file: File,
+}
+
+/// Held while `SteamCMD` runs, it updates itself and its login on every start, so two
+/// running from one directory trample each other's files. Taken after any `WorkshopLock`.
+struct SteamCmdLock {
+    _file: File,
+}
+
+/// A `SteamCMD` script logging in with a stored password, run with `+runscript` so the password
+/// stays off the command line, where any local user can read it from the process list.
+/// Removed once dropped, keep it until `SteamCMD` has exited.
+pub struct LoginScript {
+    path: PathBuf,
+}
+
+impl Drop for LoginScript {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
 }
 
 impl SteamCmdManager {
@@ -23,6 +86,10 @@ impl SteamCmdManager {
         let manager = Self {
             steamcmd_dir: steamcmd_dir_path,
             offline,
+            extra_args: Vec::new(),
+            env: BTreeMap::new(),
+            passwords: BTreeMap::new(),
+            throttle_kbps: None,
         };
         
         // Check and install steamcmd during construction
@@ -30,23 +97,79 @@ impl SteamCmdManager {
         Ok(manager)
     }
 
-    /// Install or update a Steam application (like DayZ server)
+    /// A ``SteamCmdManager`` for an install that may not exist yet, without installing it;
+    /// for dry runs, which only need its paths
+    pub fn without_install(steamcmd_dir: &str, offline: bool) -> Self {
+        Self {
+            steamcmd_dir: PathBuf::from(steamcmd_dir),
+            offline,
+            extra_args: Vec::new(),
+            env: BTreeMap::new(),
+            passwords: BTreeMap::new(),
+            throttle_kbps: None,
+        }
+    }
+
+    /// Pass the `[steamcmd]` args and environment to every run, and log in as `[steam]` says
+    pub fn with_options(mut self, config: &Config) -> Result<Self> {
+        self.extra_args.clone_from(&config.steamcmd.args);
+        self.env.clone_from(&config.steamcmd.env);
+        self.throttle_kbps = config.downloads.throttle_kbps;
+        for username in iter::once(&config.server.username).chain(&config.steam.workshop_username) {
+            if let Some(password) = get_steam_password(config, username)? {
+                self.passwords.insert(username.clone(), password);
+            }
+        }
+        Ok(self)
+    }
+
+    /// `+login` for `username`, or with a stored password a `+runscript` of a `LoginScript`
+    /// that logs in with it
+    fn login_args(&self, username: &str) -> Result<(Vec<String>, Option<LoginScript>)> {
+        let Some(password) = self.passwords.get(username) else {
+            return Ok((vec!["+login".to_string(), username.to_string()], None));
+        };
+
+        let path = self.steamcmd_dir.join(format!("{LOGIN_SCRIPT}_{}.txt", std::process::id()));
+        fs::write(&path, format!("login \"{username}\" \"{password}\"\n"))
+            .context(format!("Failed to write {}", path.display()))?;
+        let script = LoginScript { path };
+        let args = vec!["+runscript".to_string(), script.path.to_string_lossy().to_string()];
+        Ok((args, Some(script)))
+    }
+
+    /// A `SteamCMD` command with the `[steamcmd]` args and environment, DZSM's args go after
+    fn command(&self) -> Command {
+        let mut command = Command::new(self.get_exe_path());
+        command.args(&self.extra_args).envs(&self.env);
+        command
+    }
+
+    /// Install or update a Steam application (like DayZ server), from the `beta` branch if given
     #[allow(clippy::doc_markdown)]
     pub fn install_or_update_app(
         &self, 
-        install_dir: &str, 
+        install_dir: &Path, 
         username: &str, 
         app_id: u32, 
+        beta: Option<&str>,
         validate: bool
     ) -> Result<()> {
+        // Always the same absolute path: SteamCMD only resumes a partial download
+        // into the exact install dir it was started with
+        let install_dir = std::path::absolute(install_dir)
+            .context("Failed to convert install directory to absolute path")?;
+
         let mut args = vec![
             "+force_install_dir".to_string(),
-            install_dir.to_string(),
-            "+login".to_string(),
-            username.to_string(),
-            "+app_update".to_string(),
-            app_id.to_string(),
+            install_dir.to_string_lossy().to_string(),
         ];
+        let (login, _login_script) = self.login_args(username)?;
+        args.extend(login);
+        args.extend(["+app_update".to_string(), app_id.to_string()]);
+        if let Some(beta) = beta {
+            args.extend(["-beta".to_string(), beta.to_string()]);
+        }
         
         if validate {
             args.push("validate".to_string());
@@ -57,21 +180,99 @@ impl SteamCmdManager {
         self.run_steamcmd_with_args(&args)
     }
 
-    /// Install or update a Steam Workshop mod
+    /// Start an app download in the background with no console output, for timing downloads.
+    /// The login script, if any, has to be kept until the download has exited.
+    #[allow(clippy::doc_markdown)]
+    pub fn spawn_app_download(&self, install_dir: &Path, username: &str, app_id: u32) -> Result<(Child, Option<LoginScript>)> {
+        let install_dir = std::path::absolute(install_dir)
+            .context("Failed to convert install directory to absolute path")?;
+
+        let (login, login_script) = self.login_args(username)?;
+        let child = self.command()
+            .args(["+force_install_dir", &install_dir.to_string_lossy()])
+            .args(login)
+            .args(["+app_update", &app_id.to_string(), "+quit"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to execute SteamCMD")?;
+        Ok((child, login_script))
+    }
+
+    /// SteamCMD's `config.vdf`, if it has written one yet
+    #[allow(clippy::doc_markdown)]
+    pub fn read_config_vdf(&self) -> Option<String> {
+        fs::read_to_string(self.steamcmd_dir.join(CONFIG_VDF)).ok()
+    }
+
+    /// Put back a `config.vdf` saved with `read_config_vdf`
+    #[allow(clippy::doc_markdown)]
+    pub fn restore_config_vdf(&self, content: Option<&str>) -> Result<()> {
+        let path = self.steamcmd_dir.join(CONFIG_VDF);
+        match content {
+            Some(content) => fs::write(&path, content)
+                .context(format!("Failed to write {}", path.display())),
+            None if path.exists() => fs::remove_file(&path)
+                .context(format!("Failed to remove {}", path.display())),
+            None => Ok(()),
+        }
+    }
+
+    /// Pin the content server cell (region) SteamCMD downloads from, or let Steam pick with `None`
+    #[allow(clippy::doc_markdown)]
+    pub fn set_cell_override(&self, cell_id: Option<u32>) -> Result<()> {
+        let path = self.steamcmd_dir.join(CONFIG_VDF);
+        let content = self.read_config_vdf().unwrap_or_else(|| EMPTY_CONFIG_VDF.to_string());
+
+        // Drop any override, then add the new one as the first key of "Steam"
+        let mut lines: Vec<String> = content.lines()
+            .filter(|line| !line.trim_start().starts_with(CELL_OVERRIDE_KEY))
+            .map(str::to_string)
+            .collect();
+        if let Some(cell_id) = cell_id {
+            let steam_key = lines.iter()
+                .position(|line| line.trim().eq_ignore_ascii_case("\"Steam\""))
+                .ok_or_else(|| anyhow!("No \"Steam\" section in {}", path.display()))?;
+            let open = steam_key + lines[steam_key..].iter()
+                .position(|line| line.trim() == "{")
+                .ok_or_else(|| anyhow!("Malformed \"Steam\" section in {}", path.display()))?;
+            let indent: String = lines[open].chars().take_while(|c| c.is_whitespace()).collect();
+            lines.insert(open + 1, format!("{indent}\t{CELL_OVERRIDE_KEY}\t\t\"{cell_id}\""));
+        }
+
+        let mut updated = lines.join("\n");
+        updated.push('\n');
+        if updated == content {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&path, updated)
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Install or update a Steam Workshop mod, waiting for any other DZSM using this
+    /// `SteamCMD` to finish with the game's workshop first. A successful download is
+    /// recorded in the shared `ModCache` as made for `server_install_dir`.
     pub fn download_or_update_mod(
         &self, 
+        server_install_dir: &Path,
         username: &str, 
         app_id: u32, 
         workshop_id: u64, 
         validate: bool
     ) -> Result<()> {
-        let mut args = vec![
-            "+login".to_string(),
-            username.to_string(),
+        let _lock = self.lock_workshop(app_id)?;
+
+        let (mut args, _login_script) = self.login_args(username)?;
+        args.extend([
             "+workshop_download_item".to_string(),
             app_id.to_string(),
             workshop_id.to_string(),
-        ];
+        ]);
         
         if validate {
             args.push("validate".to_string());
@@ -79,7 +280,54 @@ impl SteamCmdManager {
         
         args.push("+quit".to_string());
         
-        self.run_steamcmd_with_args(&args)
+        self.run_steamcmd_with_args(&args)?;
+
+        let manifest = WorkshopManifest::load(&self.get_workshop_manifest_path(app_id)).unwrap_or_default();
+        if let Some(item) = manifest.get(workshop_id) {
+            let mut cache = self.load_mod_cache(app_id);
+            cache.record(workshop_id, item.time_updated, server_install_dir);
+            if let Err(e) = cache.save() {
+                println_failure(&format!("Failed to update the shared mod cache: {e:#}"), 3);
+            }
+        }
+        Ok(())
+    }
+
+    /// The mods downloaded by every DZSM sharing this `SteamCMD`
+    pub fn load_mod_cache(&self, app_id: u32) -> ModCache {
+        ModCache::load(&ModCache::get_path(&self.get_workshop_dir(), app_id))
+    }
+
+    fn get_workshop_dir(&self) -> PathBuf {
+        self.steamcmd_dir.join("steamapps").join("workshop")
+    }
+
+    /// Take the workshop lock of a game, waiting as long as another DZSM holds it
+    pub fn lock_workshop(&self, app_id: u32) -> Result<WorkshopLock> {
+        let workshop_dir = self.get_workshop_dir();
+        fs::create_dir_all(&workshop_dir)
+            .context(format!("Failed to create {}", workshop_dir.display()))?;
+        let path = workshop_dir.join(format!("dzsm_{app_id}.lock"));
+
+        let file = wait_for_lock(&path, "Another DZSM is updating mods with this SteamCMD, waiting for it to finish...")?;
+        Ok(WorkshopLock { _file: file })
+    }
+
+    /// Take the lock on running `SteamCMD`, waiting as long as another DZSM holds it
+    fn lock_steamcmd(&self) -> Result<SteamCmdLock> {
+        let path = self.steamcmd_dir.join(STEAMCMD_LOCK);
+        let file = wait_for_lock(&path, "Another DZSM is running this SteamCMD, waiting for it to finish...")?;
+        Ok(SteamCmdLock { _file: file })
+    }
+
+    /// Bytes of an unfinished app download SteamCMD has kept to resume from, 0 if there is none
+    #[allow(clippy::doc_markdown)]
+    pub fn get_partial_download_size(install_dir: &Path, app_id: u32) -> u64 {
+        let downloading_dir = install_dir
+            .join("steamapps")
+            .join("downloading")
+            .join(app_id.to_string());
+        if downloading_dir.exists() { dir_size(&downloading_dir).unwrap_or(0) } else { 0 }
     }
 
     /// Get the path to the steamcmd executable
@@ -100,6 +348,12 @@ impl SteamCmdManager {
         .context("Failed to convert workshop directory to absolute path")
     }
 
+    /// Get the path of SteamCMD's workshop manifest for a specific game
+    #[allow(clippy::doc_markdown)]
+    pub fn get_workshop_manifest_path(&self, app_id: u32) -> PathBuf {
+        self.get_workshop_dir().join(format!("appworkshop_{app_id}.acf"))
+    }
+
     /// Check if steamcmd is installed and handle installation if needed
     fn check_and_install(&self) -> Result<()> {
         let steamcmd_exe_path = self.get_exe_path();
@@ -163,34 +417,50 @@ impl SteamCmdManager {
         Ok(())
     }
 
-    /// Run SteamCMD with arguments, allowing interactive input
+    /// Run SteamCMD with arguments, allowing interactive input. Its output is shown as it
+    /// comes and scanned for known errors, which fail the run even if SteamCMD exits with 0.
     #[allow(clippy::doc_markdown)]
     fn run_steamcmd_with_args(&self, args: &[String]) -> Result<()> {
-        let steamcmd_exe = self.get_exe_path();
-        
-        println!("Running SteamCMD with args: {args:?}");
+        // `bench` starts its downloads itself, measuring the connection unthrottled
+        let throttle = self.throttle_kbps
+            .map(|kbps| vec!["+set_download_throttle".to_string(), kbps.to_string()])
+            .unwrap_or_default();
+        let args = &[throttle, args.to_vec()].concat();
+
+        let shown: Vec<&String> = self.extra_args.iter().chain(args).collect();
+        println_debug(&format!("Running SteamCMD with args: {shown:?}"), 0);
+
+        let _lock = self.lock_steamcmd()?;
         
         // Use spawn() instead of output() to allow interactive input
-        let mut child = Command::new(&steamcmd_exe)
+        let mut child = self.command()
             .args(args)
-            .stdin(Stdio::inherit())   // Allow user input
-            .stdout(Stdio::inherit())  // Show output directly
-            .stderr(Stdio::inherit())  // Show errors directly
+            .stdin(child_stdin())      // Allow user input (unless non-interactive)
+            .stdout(Stdio::piped())    // Shown as it comes, see scan_output
+            .stderr(Stdio::piped())
             .spawn()
             .context("Failed to execute SteamCMD")?;
+
+        // With --json, stdout only carries JSON events
+        let json = is_json_output();
+        let stdout = child.stdout.take().map(|pipe| thread::spawn(move || {
+            if json { scan_output(pipe, io::stderr()) } else { scan_output(pipe, io::stdout()) }
+        }));
+        let stderr = child.stderr.take().map(|pipe| thread::spawn(move || scan_output(pipe, io::stderr())));
         
         // Wait for the process to complete
         let status = child.wait()
             .context("Failed to wait for SteamCMD process")?;
+        let found = [stdout, stderr].into_iter()
+            .flatten()
+            .find_map(|scanner| scanner.join().ok().flatten());
         
-        if !status.success() {
-            return Err(anyhow!(
-                "SteamCMD failed with exit code: {:?}", 
-                status.code()
-            ));
+        // The output only explains a failed exit, lines such as a retried timeout don't fail a run
+        if status.success() {
+            return Ok(());
         }
-
-        Ok(())
+        let (failure, line) = found.map_or((SteamCmdFailure::Unknown, None), |(failure, line)| (failure, Some(line)));
+        Err(SteamCmdError { failure, line, exit_code: status.code() }.into())
     }
 
     /// Check if the steamcmd directory is empty
@@ -202,13 +472,16 @@ impl SteamCmdManager {
     }
 
     /// Download steamcmd zip file using curl
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     fn download_steamcmd_zip() -> Result<Vec<u8>> {
         let mut data = Vec::new();
         let mut handle = Easy::new();
+        let mut progress = ProgressLine::new(3);
         
         handle.url(STEAMCMD_DOWNLOAD_URL)?;
         handle.follow_location(true)?;
         handle.timeout(std::time::Duration::from_secs(60))?; // 60 seconds total timeout
+        handle.progress(true)?;
         
         {
             let mut transfer = handle.transfer();
@@ -216,8 +489,16 @@ impl SteamCmdManager {
                 data.extend_from_slice(new_data);
                 Ok(new_data.len())
             })?;
+            // The total is 0 until the server has said how big the file is
+            transfer.progress_function(|total, done, _, _| {
+                if total > 0.0 {
+                    progress.update("Downloading", done as u64, total as u64);
+                }
+                true
+            })?;
             transfer.perform()?;
         }
+        progress.clear();
         
         // Check HTTP status
         let response_code = handle.response_code()?;
@@ -272,4 +553,96 @@ impl SteamCmdManager {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Open `path` without sharing, waiting while another process has it open
+fn wait_for_lock(path: &Path, waiting_message: &str) -> Result<File> {
+    let mut waiting = false;
+    loop {
+        let result = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .share_mode(0)
+            .open(path);
+        match result {
+            Ok(file) => return Ok(file),
+            Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => {
+                if !waiting {
+                    println_step(waiting_message, 3);
+                    waiting = true;
+                }
+                thread::sleep(LOCK_POLL);
+            }
+            Err(e) => return Err(e).context(format!("Failed to lock {}", path.display())),
+        }
+    }
+}
+
+/// Copy `SteamCMD` output through as it arrives, partial lines included so prompts like the
+/// Steam Guard code show up, returning the last known error it reported and the line saying so.
+/// Its progress updates are shown as a single progress line instead of one line each.
+fn scan_output(mut pipe: impl Read, mut out: impl Write) -> Option<(SteamCmdFailure, String)> {
+    let mut buffer = [0; 4096];
+    let mut line = Vec::new();
+    // How much of `line` has been written out already
+    let mut written = 0;
+    let mut progress = ProgressLine::new(1);
+    let mut found = None;
+    while let Ok(read) = pipe.read(&mut buffer) {
+        if read == 0 {
+            break;
+        }
+
+        // Progress updates may end in \r rather than \n
+        for &byte in &buffer[..read] {
+            if byte != b'\n' && byte != b'\r' {
+                line.push(byte);
+                continue;
+            }
+
+            let text = String::from_utf8_lossy(&line).trim().to_string();
+            if let Some((stage, done, total)) = parse_progress(&text).filter(|_| written == 0) {
+                progress.update(&stage, done, total);
+            } else if !(text.is_empty() && progress.is_shown()) {
+                progress.clear();
+                let _ = out.write_all(&line[written..]);
+                let _ = out.write_all(&[byte]);
+                if let Some(failure) = SteamCmdFailure::from_line(&text) {
+                    found = Some((failure, text));
+                }
+            }
+            line.clear();
+            written = 0;
+        }
+
+        // A prompt doesn't end its line, so whatever can't be a progress update is shown now
+        if written < line.len() && !is_progress_start(&line) {
+            progress.clear();
+            let _ = out.write_all(&line[written..]);
+            written = line.len();
+        }
+        let _ = out.flush();
+    }
+    progress.clear();
+    found
+}
+
+/// The stage and bytes done and in total of a `SteamCMD` progress update
+fn parse_progress(line: &str) -> Option<(String, u64, u64)> {
+    let (update, rest) = line.strip_prefix(PROGRESS_PREFIX)?.split_once(", progress:")?;
+    // "(0x61) downloading" becomes "Downloading"
+    let name = update.split_once(')').map_or(update, |(_, name)| name).trim();
+    let mut chars = name.chars();
+    let stage = chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default();
+
+    let (done, total) = rest.split_once('(')?.1.trim().trim_end_matches(')').split_once('/')?;
+    Some((stage, done.trim().parse().ok()?, total.trim().parse().ok()?))
+}
+
+/// Whether a partial line may turn out to be a progress update once it's complete
+fn is_progress_start(line: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(line);
+    let text = text.trim_start();
+    text.starts_with(PROGRESS_PREFIX) || PROGRESS_PREFIX.starts_with(text)
+}
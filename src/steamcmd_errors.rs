@@ -0,0 +1,135 @@
+use std::fmt;
+
+/// Why a `SteamCMD` run failed, as far as its output tells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SteamCmdFailure {
+    /// Steam throttled this account or IP, typically an app update stuck in state 0x402
+    RateLimited,
+    /// A workshop item download timed out
+    Timeout,
+    /// The account doesn't own the game, or the item isn't available to it
+    NoSubscription,
+    /// The target drive ran out of space
+    DiskFull,
+    /// Wrong password, a rejected Steam Guard code, or an expired login
+    LoginFailed,
+    /// Steam's servers couldn't be reached
+    NoConnection,
+    /// `SteamCMD` exited with an error its output didn't explain
+    Unknown,
+}
+
+/// Output patterns of each failure, matched case-insensitively against single lines. They only
+/// say why a run that exited with an error failed.
+const PATTERNS: &[(SteamCmdFailure, &[&str])] = &[
+    (SteamCmdFailure::RateLimited, &["state is 0x402", "rate limit exceeded", "(rate limited)"]),
+    (SteamCmdFailure::Timeout, &["failed (timeout)", "timeout downloading item", "timed out"]),
+    (SteamCmdFailure::NoSubscription, &["(no subscription)", "failed (access denied)", "(license missing)"]),
+    (SteamCmdFailure::DiskFull, &["state is 0x202", "not enough disk space", "disk write failure", "(disk full)"]),
+    (SteamCmdFailure::LoginFailed, &[
+        "failed (invalid password)",
+        "two-factor code mismatch",
+        "invalid login auth code",
+        "(expired login auth code)",
+        "(account login denied)",
+        "cached credentials not found",
+    ]),
+    (SteamCmdFailure::NoConnection, &["failed (no connection)", "connection to steam servers failed", "(service unavailable)"]),
+];
+
+impl SteamCmdFailure {
+    /// The failure a line of `SteamCMD` output reports, if any
+    pub fn from_line(line: &str) -> Option<Self> {
+        let line = line.to_lowercase();
+        PATTERNS.iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| line.contains(pattern)))
+            .map(|(failure, _)| *failure)
+    }
+
+    /// Stable name for scripts and logs
+    pub const fn reason(self) -> &'static str {
+        match self {
+            Self::RateLimited => "steamcmd_rate_limited",
+            Self::Timeout => "steamcmd_timeout",
+            Self::NoSubscription => "steamcmd_no_subscription",
+            Self::DiskFull => "steamcmd_disk_full",
+            Self::LoginFailed => "steamcmd_login_failed",
+            Self::NoConnection => "steamcmd_no_connection",
+            Self::Unknown => "steamcmd_failed",
+        }
+    }
+
+    /// Process exit code DZSM ends with, so wrappers can tell failures apart without parsing text
+    pub const fn exit_code(self) -> u8 {
+        match self {
+            Self::Unknown => 10,
+            Self::RateLimited => 11,
+            Self::Timeout => 12,
+            Self::NoSubscription => 13,
+            Self::DiskFull => 14,
+            Self::LoginFailed => 15,
+            Self::NoConnection => 16,
+        }
+    }
+
+    /// Whether retrying after a short wait can succeed; a rate limit only gets longer
+    pub const fn is_retryable(self) -> bool {
+        matches!(self, Self::Timeout | Self::NoConnection | Self::Unknown)
+    }
+
+    const fn describe(self) -> &'static str {
+        match self {
+            Self::RateLimited => "Steam is rate limiting this account or connection",
+            Self::Timeout => "The workshop download timed out",
+            Self::NoSubscription => "The Steam account doesn't own DayZ or can't access the item",
+            Self::DiskFull => "The drive ran out of space",
+            Self::LoginFailed => "Steam rejected the login",
+            Self::NoConnection => "Steam's servers couldn't be reached",
+            Self::Unknown => "SteamCMD failed",
+        }
+    }
+
+    /// What to do about it
+    pub const fn guidance(self) -> &'static str {
+        match self {
+            Self::RateLimited => "Wait 15-30 minutes before trying again, retrying straight away extends the limit",
+            Self::Timeout => "Try again; large mods often need several attempts, and a download resumes where it stopped",
+            Self::NoSubscription => "Log in with an account that owns DayZ; Family Sharing and free weekends don't let SteamCMD download \
+                Workshop items, set `steam.workshop_username` to an account that owns it. Check the mod is public and still on the Workshop",
+            Self::DiskFull => "Free up space on the server and SteamCMD drives, see `dzsm storage`",
+            Self::LoginFailed => "Run SteamCMD once by hand to log in again and cache the credentials, entering the Steam Guard code; with `steam.credential_source = \"credential_manager\"`, store the password again with `dzsm steam login`",
+            Self::NoConnection => "Check the internet connection and Steam's status, or run with --offline to use what's installed",
+            Self::Unknown => "Check the SteamCMD output above",
+        }
+    }
+}
+
+/// A failed `SteamCMD` run, carrying why so callers can retry or pick an exit code
+#[derive(Debug)]
+pub struct SteamCmdError {
+    pub failure: SteamCmdFailure,
+    /// The output line the failure was recognised from
+    pub line: Option<String>,
+    pub exit_code: Option<i32>,
+}
+
+impl fmt::Display for SteamCmdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]", self.failure.describe(), self.failure.reason())?;
+        if let Some(line) = &self.line {
+            write!(f, ": {line}")?;
+        } else if let Some(code) = self.exit_code {
+            write!(f, ", exit code {code}")?;
+        }
+        write!(f, ". {}", self.failure.guidance())
+    }
+}
+
+impl std::error::Error for SteamCmdError {}
+
+/// The `SteamCMD` failure behind an error, if it came from one
+pub fn find_failure(error: &anyhow::Error) -> Option<SteamCmdFailure> {
+    error.chain()
+        .find_map(|cause| cause.downcast_ref::<SteamCmdError>())
+        .map(|error| error.failure)
+}
@@ -0,0 +1,393 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::cli::StorageCommand;
+use crate::config::{Config, StorageConfig};
+use crate::instance;
+use crate::server::{MISSIONS_DIR, SERVER_CONFIG};
+use crate::server_cfg::ServerDzConfig;
+use crate::state::state_dir;
+use crate::ui::prompt::prompt_yes_no;
+use crate::ui::spinner::Spinner;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+/// The characters; everything else in the storage directory is the world
+pub const PLAYERS_DB: &str = "players.db";
+const BACKUPS_DIR: &str = "storage_backups";
+const QUARANTINE_DIR: &str = "storage_quarantine";
+const SIZE_HISTORY_FILE: &str = "storage_history.json";
+const SIZE_HISTORY_LENGTH: usize = 10;
+
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+const SQLITE_HEADER_SIZE: usize = 100;
+
+/// The active mission's persistence directory, checked for corruption before the server starts
+pub struct StorageGuard<'a> {
+    config: &'a StorageConfig,
+    server_install_dir: &'a Path,
+}
+
+impl<'a> StorageGuard<'a> {
+    pub const fn new(config: &'a StorageConfig, server_install_dir: &'a Path) -> Self {
+        Self { config, server_install_dir }
+    }
+
+    /// Refuse to start on storage that looks corrupt, offering to restore the latest backup.
+    /// Healthy storage is backed up and its size recorded for future checks.
+    pub fn check_before_launch(&self) -> Result<()> {
+        if !self.config.check {
+            return Ok(());
+        }
+
+        let Some(storage_dir) = self.get_storage_dir()? else {
+            // Fresh server, nothing persisted yet
+            return Ok(());
+        };
+
+        println_step("Checking persistence files...", 1);
+        let problems = self.find_problems(&storage_dir)?;
+
+        if problems.is_empty() {
+            println_success("Persistence files look healthy", 2);
+            self.record_size(&storage_dir)?;
+            if self.config.backup_on_start {
+                self.backup(&storage_dir)?;
+            }
+            return Ok(());
+        }
+
+        for problem in &problems {
+            println_failure(problem, 2);
+        }
+
+        if let Some(backup) = self.latest_backup()?
+            && prompt_yes_no(&format!("Restore storage from backup {}?", display_name(&backup)), false, 2)?
+        {
+            return self.restore(&storage_dir, &backup);
+        }
+
+        Err(anyhow!(
+            "Refusing to start on storage that looks corrupt: {}\n\
+             Restore a backup with `dzsm storage restore`, fix the files by hand, or set `storage.check = false`.",
+            storage_dir.display()
+        ))
+    }
+
+    fn get_storage_dir(&self) -> Result<Option<PathBuf>> {
+        get_storage_dir(self.server_install_dir)
+    }
+
+    fn find_problems(&self, storage_dir: &Path) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+
+        for path in list_files(storage_dir)? {
+            let is_bin = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("bin"));
+            if is_bin && fs::metadata(&path)?.len() == 0 {
+                problems.push(format!("Empty persistence file: {}", relative(&path, storage_dir)));
+            }
+        }
+
+        let players_db = storage_dir.join(PLAYERS_DB);
+        if players_db.exists()
+            && let Some(problem) = check_sqlite(&players_db)?
+        {
+            problems.push(format!("{PLAYERS_DB}: {problem}"));
+        }
+
+        let size = dir_size(storage_dir)?;
+        if let Some(&previous) = self.load_size_history()?.last() {
+            let minimum = previous / 100 * (100 - u64::from(self.config.max_size_drop_percent.min(100)));
+            if size < minimum {
+                problems.push(format!(
+                    "Storage shrank from {} to {} since the last start",
+                    format_size(previous),
+                    format_size(size)
+                ));
+            }
+        }
+
+        Ok(problems)
+    }
+
+    fn get_backups_dir(&self) -> PathBuf {
+        get_backups_dir(self.server_install_dir)
+    }
+
+    fn backup(&self, storage_dir: &Path) -> Result<()> {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        let backup_dir = self.get_backups_dir().join(timestamp);
+
+        let spinner = Spinner::start("Backing up storage...", 2);
+        copy_dir(storage_dir, &backup_dir)
+            .context("Failed to back up storage")?;
+        spinner.finish();
+        println_success(&format!("Storage backed up to {}", backup_dir.display()), 2);
+
+        // Oldest backups go first, timestamps sort chronologically
+        let backups = self.list_backups()?;
+        let excess = backups.len().saturating_sub(self.config.keep_backups.max(1));
+        for old_backup in &backups[..excess] {
+            fs::remove_dir_all(old_backup)
+                .context(format!("Failed to remove old backup {}", old_backup.display()))?;
+        }
+
+        Ok(())
+    }
+
+    fn list_backups(&self) -> Result<Vec<PathBuf>> {
+        list_backups(self.server_install_dir)
+    }
+
+    fn latest_backup(&self) -> Result<Option<PathBuf>> {
+        Ok(self.list_backups()?.pop())
+    }
+
+    /// Swap in a backup, keeping the suspect storage aside in `.dzsm/storage_quarantine/`
+    fn restore(&self, storage_dir: &Path, backup: &Path) -> Result<()> {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        let quarantine_dir = state_dir(self.server_install_dir).join(QUARANTINE_DIR).join(timestamp);
+
+        println_step(&format!("Restoring storage from backup {}...", display_name(backup)), 2);
+
+        // After a wipe there's nothing to keep aside
+        if !storage_dir.exists() {
+            copy_dir(backup, storage_dir)
+                .context("Failed to restore storage backup")?;
+            println_success("Storage restored", 2);
+            return Ok(());
+        }
+
+        if let Some(parent) = quarantine_dir.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // A rename can't cross drives, fall back to copying
+        if fs::rename(storage_dir, &quarantine_dir).is_err() {
+            copy_dir(storage_dir, &quarantine_dir)?;
+            fs::remove_dir_all(storage_dir)?;
+        }
+        copy_dir(backup, storage_dir)
+            .context("Failed to restore storage backup")?;
+
+        println_success(&format!(
+            "Storage restored, the previous files were moved to {}",
+            quarantine_dir.display()
+        ), 2);
+        Ok(())
+    }
+
+    fn get_size_history_path(&self) -> PathBuf {
+        state_dir(self.server_install_dir).join(SIZE_HISTORY_FILE)
+    }
+
+    fn load_size_history(&self) -> Result<Vec<u64>> {
+        let path = self.get_size_history_path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&path)
+            .context(format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .context(format!("Failed to parse {}", path.display()))
+    }
+
+    fn record_size(&self, storage_dir: &Path) -> Result<()> {
+        let mut history = self.load_size_history()?;
+        history.push(dir_size(storage_dir)?);
+        history = history.split_off(history.len().saturating_sub(SIZE_HISTORY_LENGTH));
+
+        let path = self.get_size_history_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create state directory")?;
+        }
+        fs::write(&path, serde_json::to_string(&history)?)
+            .context(format!("Failed to write {}", path.display()))
+    }
+}
+
+/// `mpmissions/<template>/storage_<instanceId>`, if the server has persisted anything yet
+pub fn get_storage_dir(server_install_dir: &Path) -> Result<Option<PathBuf>> {
+    Ok(get_storage_path(server_install_dir)?.filter(|storage_dir| storage_dir.exists()))
+}
+
+/// Where the active mission's persistence goes, whether or not it exists yet; None without a
+/// mission template
+fn get_storage_path(server_install_dir: &Path) -> Result<Option<PathBuf>> {
+    let config_path = server_install_dir.join(SERVER_CONFIG);
+    if !config_path.exists() {
+        return Ok(None);
+    }
+
+    let server_cfg = ServerDzConfig::load(&config_path)?;
+    let Some(template) = server_cfg.get("template") else {
+        return Ok(None);
+    };
+    let instance_id = server_cfg.get("instanceId").unwrap_or_else(|| "1".to_string());
+
+    Ok(Some(server_install_dir
+        .join(MISSIONS_DIR)
+        .join(template)
+        .join(format!("storage_{instance_id}"))))
+}
+
+/// Where storage is backed up before each start, `.dzsm/storage_backups`
+pub fn get_backups_dir(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(BACKUPS_DIR)
+}
+
+/// The storage backups, oldest first
+pub fn list_backups(server_install_dir: &Path) -> Result<Vec<PathBuf>> {
+    let backups_dir = get_backups_dir(server_install_dir);
+    if !backups_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups: Vec<PathBuf> = fs::read_dir(&backups_dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    // Timestamps sort chronologically
+    backups.sort();
+    Ok(backups)
+}
+
+/// Forget the recorded storage sizes, so a wiped storage isn't taken for a corrupt one
+pub fn reset_size_history(server_install_dir: &Path) -> Result<()> {
+    let path = state_dir(server_install_dir).join(SIZE_HISTORY_FILE);
+    if path.exists() {
+        fs::remove_file(&path)
+            .context(format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Check a SQLite database's header and length, returning what is wrong with it
+#[allow(clippy::doc_markdown)]
+fn check_sqlite(path: &Path) -> Result<Option<String>> {
+    let length = fs::metadata(path)?.len();
+    if length == 0 {
+        return Ok(Some("file is empty".to_string()));
+    }
+
+    let mut header = [0u8; SQLITE_HEADER_SIZE];
+    let mut file = File::open(path)
+        .context(format!("Failed to open {}", path.display()))?;
+    if file.read_exact(&mut header).is_err() {
+        return Ok(Some(format!("file is truncated ({length} bytes)")));
+    }
+
+    if &header[..16] != SQLITE_MAGIC {
+        return Ok(Some("not a SQLite database".to_string()));
+    }
+
+    // A page size of 1 means 65536
+    let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+        1 => 65536,
+        size => u64::from(size),
+    };
+    if page_size < 512 || length % page_size != 0 {
+        return Ok(Some(format!("file is truncated ({length} bytes is not a whole number of {page_size} byte pages)")));
+    }
+
+    let page_count = u64::from(u32::from_be_bytes([header[28], header[29], header[30], header[31]]));
+    if page_count > 0 && length < page_count * page_size {
+        return Ok(Some(format!("file is truncated ({} of {page_count} pages)", length / page_size)));
+    }
+
+    Ok(None)
+}
+
+pub fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).context(format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(list_files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+pub fn dir_size(dir: &Path) -> Result<u64> {
+    list_files(dir)?
+        .iter()
+        .map(|path| Ok(fs::metadata(path)?.len()))
+        .sum()
+}
+
+pub fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)
+        .context(format!("Failed to create {}", to.display()))?;
+
+    for entry in fs::read_dir(from).context(format!("Failed to read {}", from.display()))? {
+        let path = entry?.path();
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+
+        if path.is_dir() {
+            copy_dir(&path, &to.join(name))?;
+        } else {
+            fs::copy(&path, to.join(name))
+                .context(format!("Failed to copy {}", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn relative(path: &Path, base: &Path) -> String {
+    path.strip_prefix(base).unwrap_or(path).display().to_string()
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().to_string())
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub fn format_size(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1024.0 / 1024.0)
+}
+
+/// Entry point for `dzsm storage ...`
+pub fn run(command: &StorageCommand, config: &Config, server_install_dir: &str) -> Result<()> {
+    let install_dir = Path::new(server_install_dir);
+    let guard = StorageGuard::new(&config.storage, install_dir);
+
+    match command {
+        StorageCommand::Check => {
+            let Some(storage_dir) = guard.get_storage_dir()? else {
+                println_step("No persistence files yet, the server hasn't saved anything", 0);
+                return Ok(());
+            };
+            println_step(&format!("Checking {}...", storage_dir.display()), 0);
+            let problems = guard.find_problems(&storage_dir)?;
+            if problems.is_empty() {
+                println_success("Persistence files look healthy", 1);
+                return Ok(());
+            }
+
+            for problem in &problems {
+                println_failure(problem, 1);
+            }
+            Err(anyhow!("Storage looks corrupt"))
+        }
+        // Also brings storage back after a wipe, so it needn't exist yet
+        StorageCommand::Restore => {
+            if let Some(pid) = instance::find_running(install_dir) {
+                return Err(anyhow!("DZSM (PID {pid}) is running the server, stop it with `dzsm stop` before restoring storage"));
+            }
+            let storage_dir = get_storage_path(install_dir)?
+                .ok_or_else(|| anyhow!("No mission template set in {SERVER_CONFIG}"))?;
+            let backup = guard.latest_backup()?
+                .ok_or_else(|| anyhow!("No storage backups in {}", guard.get_backups_dir().display()))?;
+            guard.restore(&storage_dir, &backup)
+        }
+    }
+}
@@ -0,0 +1,589 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Duration as ChronoDuration, Local, Utc};
+use std::cell::Cell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::thread;
+use std::time::Duration;
+
+use crate::a2s;
+use crate::adm_alerts::AdmAlerts;
+use crate::config::SuperviseConfig;
+use crate::health::HealthMonitor;
+use crate::heartbeat::{HeartbeatWriter, SupervisorState};
+use crate::instance;
+use crate::interrupt;
+use crate::mod_validation::NightlyValidator;
+use crate::positions;
+use crate::quarantine;
+use crate::schedule::RestartSchedule;
+use crate::server::{DRY_RUN, ServerManager};
+use crate::state::state_dir;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const STOP_REQUEST_FILE: &str = "stop.request";
+const RESTART_REQUEST_FILE: &str = "restart.request";
+/// Contents of a restart request that updates the server and mods before starting again
+const UPDATE_REQUEST: &str = "update";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Restarts are counted over the last hour
+const RESTART_WINDOW_MINUTES: i64 = 60;
+/// How often a held restart checks whether the server has emptied
+const DEFER_CHECK_SECONDS: i64 = 60;
+/// How often the players online in the console title are counted again
+const TITLE_UPDATE_SECONDS: i64 = 30;
+
+/// Wall clock, or a simulated one that jumps straight to the next event for dry runs
+enum Clock {
+    Real,
+    Simulated(Cell<DateTime<Local>>),
+}
+
+impl Clock {
+    fn now(&self) -> DateTime<Local> {
+        match self {
+            Self::Real => Local::now(),
+            Self::Simulated(now) => now.get(),
+        }
+    }
+
+    /// Wait one poll interval, or until `next_event` when simulated
+    fn wait(&self, next_event: DateTime<Local>) {
+        match self {
+            Self::Real => thread::sleep(POLL_INTERVAL),
+            Self::Simulated(now) => now.set(next_event.max(now.get() + ChronoDuration::seconds(1))),
+        }
+    }
+}
+
+/// The running DayZ server, or a stand-in for dry runs
+#[allow(clippy::doc_markdown)]
+enum ServerProcess {
+    Running(Child),
+    Simulated,
+}
+
+/// Why the server stopped running
+enum Exit {
+    Crashed(Option<i32>),
+    /// `warn` is set when the restart was held for online players, whose warnings are long gone
+    Scheduled { warn: bool },
+    StopRequested,
+    /// `update` is set when the server and mods should be updated before it starts again
+    RestartRequested { update: bool },
+    /// The server ran past `supervise.max_memory_mb` or below `supervise.min_fps`, for the reason given
+    Unhealthy(String),
+    DryRunComplete,
+}
+
+/// Keeps the DayZ server running: updates, launches, and restarts it on schedule or whenever it exits
+#[allow(clippy::doc_markdown)]
+pub struct Supervisor {
+    server_manager: ServerManager,
+    config: SuperviseConfig,
+    schedule: RestartSchedule,
+    server_install_dir: PathBuf,
+    clock: Clock,
+    dry_run_until: Option<DateTime<Local>>,
+    crashes: Vec<DateTime<Local>>,
+    validator: Option<NightlyValidator>,
+    heartbeat: Option<HeartbeatWriter>,
+}
+
+impl Supervisor {
+    pub fn new(server_manager: ServerManager, server_install_dir: &str) -> Result<Self> {
+        let config = server_manager.config().supervise.clone();
+        let schedule = RestartSchedule::from_config(&server_manager.config().schedule)?;
+        let validator = NightlyValidator::from_config(&server_manager.config().validation, Path::new(server_install_dir))?;
+        let heartbeat = HeartbeatWriter::from_config(&config, Path::new(server_install_dir));
+
+        Ok(Self {
+            server_manager,
+            config,
+            schedule,
+            server_install_dir: PathBuf::from(server_install_dir),
+            clock: Clock::Real,
+            dry_run_until: None,
+            crashes: Vec::new(),
+            validator,
+            heartbeat,
+        })
+    }
+
+    /// Simulate `hours` of supervision without touching the server: starts, stops,
+    /// updates, and player announcements are only logged, and time is fast-forwarded
+    pub fn dry_run(mut self, hours: u64) -> Self {
+        let now = Local::now();
+        let hours = i64::try_from(hours).unwrap_or(i64::MAX / 3600);
+        self.clock = Clock::Simulated(Cell::new(now));
+        self.dry_run_until = Some(now + ChronoDuration::hours(hours));
+        self.heartbeat = None;
+        self
+    }
+
+    const fn is_dry_run(&self) -> bool {
+        self.dry_run_until.is_some()
+    }
+
+    /// Whether `dzsm stop` asked to stop the server. A dry run's simulated time leaves the
+    /// requests to the DZSM running the server, which they're meant for.
+    #[allow(clippy::doc_markdown)]
+    fn is_stop_requested(&self) -> bool {
+        matches!(self.clock, Clock::Real) && stop_requested(&self.server_install_dir)
+    }
+
+    /// Forget a stop request once it's handled; a dry run leaves it for the DZSM running the
+    /// server, which it's meant for
+    #[allow(clippy::doc_markdown)]
+    fn clear_stop_request(&self) -> Result<()> {
+        if self.is_dry_run() {
+            return Ok(());
+        }
+        clear_stop_request(&self.server_install_dir)
+    }
+
+    /// Run the update + run + restart loop until a stop is requested
+    pub fn run(&mut self) -> Result<()> {
+        if self.is_dry_run() {
+            // Any requests are for a DZSM that's actually running the server
+            println_step(&format!("{DRY_RUN} Simulating supervision, nothing will be started or stopped"), 0);
+        } else {
+            // A stale request from a previous run would stop the server straight away
+            clear_stale_stop_request(&self.server_install_dir)?;
+            take_restart_request(&self.server_install_dir)?;
+            self.server_manager.setup_steamcmd()?;
+        }
+
+        let mut first_run = true;
+        let mut update_requested = false;
+        let mut scheduled_restarts = 0;
+
+        loop {
+            if first_run || update_requested || self.config.update_on_restart {
+                self.update();
+            }
+            first_run = false;
+            update_requested = false;
+
+            // `dzsm stop` while updating
+            if self.is_stop_requested() {
+                self.clear_stop_request()?;
+                self.beat(SupervisorState::Stopped);
+                println_success("Stop requested, not starting the DayZ server", 0);
+                return Ok(());
+            }
+
+            let mut server = self.start()?;
+
+            match self.watch(&mut server)? {
+                Exit::StopRequested => {
+                    self.stop(server, true)?;
+                    self.clear_stop_request()?;
+                    self.beat(SupervisorState::Stopped);
+                    println_success("Stop requested, DayZ server has been stopped", 0);
+                    return Ok(());
+                }
+                Exit::RestartRequested { update } => {
+                    let action = if update { "Update and restart" } else { "Restart" };
+                    println_step(&format!("{} {action} requested", self.format_time(self.clock.now())), 0);
+                    self.stop(server, true)?;
+                    update_requested = update;
+                }
+                Exit::Unhealthy(reason) => {
+                    println_failure(&format!("{} Restarting the DayZ server because {reason}", self.format_time(self.clock.now())), 0);
+                    self.stop(server, true)?;
+                }
+                Exit::DryRunComplete => {
+                    self.stop(server, false)?;
+                    println_success(&format!(
+                        "{DRY_RUN} Simulation complete: {scheduled_restarts} scheduled restart(s), ending {}",
+                        self.format_time(self.clock.now())
+                    ), 0);
+                    return Ok(());
+                }
+                Exit::Scheduled { warn } => {
+                    println_step(&format!("{} Scheduled restart", self.format_time(self.clock.now())), 0);
+                    // Unless the restart was held, players have already had the schedule's warnings
+                    self.stop(server, warn)?;
+                    scheduled_restarts += 1;
+                }
+                Exit::Crashed(code) => {
+                    println_failure(&format!("DayZ server exited with code {code:?}"), 0);
+                    self.server_manager.report_crash(code);
+                    self.record_crash()?;
+                    self.beat(SupervisorState::Restarting);
+
+                    println_step(&format!("Restarting in {} seconds...", self.config.restart_delay_seconds), 0);
+                    let delay = i64::try_from(self.config.restart_delay_seconds).ok()
+                        .and_then(ChronoDuration::try_seconds)
+                        .unwrap_or(ChronoDuration::MAX);
+                    let until = self.clock.now().checked_add_signed(delay)
+                        .unwrap_or_else(|| DateTime::<Utc>::MAX_UTC.with_timezone(&Local));
+                    if self.sleep_unless_stopped(until) {
+                        self.clear_stop_request()?;
+                        self.beat(SupervisorState::Stopped);
+                        println_success("Stop requested, not restarting the DayZ server", 0);
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Update the server and mods, carrying on with the installed files if that fails
+    fn update(&self) {
+        if self.is_dry_run() {
+            println_step(&format!("{} {DRY_RUN} Would update the server and mods", self.format_time(self.clock.now())), 0);
+            return;
+        }
+
+        self.beat(SupervisorState::Updating);
+        if let Err(e) = self.server_manager.install_or_update_server() {
+            println_failure(&format!("Server update failed, starting the installed version: {e}"), 0);
+            self.server_manager.report_update_failure("Server", &e);
+        }
+        if let Err(e) = self.server_manager.install_or_update_mods() {
+            println_failure(&format!("Mod update failed: {e}"), 0);
+            self.server_manager.report_update_failure("Mod", &e);
+        }
+    }
+
+    fn start(&self) -> Result<ServerProcess> {
+        let next_restart = self.schedule.next_restart_after(self.clock.now())
+            .map_or_else(|| "none scheduled".to_string(), |restart| self.format_time(restart));
+
+        if self.is_dry_run() {
+            println_step(&format!(
+                "{} {DRY_RUN} Would start the DayZ server (next restart: {next_restart})",
+                self.format_time(self.clock.now())
+            ), 0);
+            return Ok(ServerProcess::Simulated);
+        }
+
+        let child = self.server_manager.launch_server()?;
+        println_success(&format!(
+            "Supervising DayZ server, next restart: {next_restart} (create .dzsm/stop.request to stop)"
+        ), 0);
+        Ok(ServerProcess::Running(child))
+    }
+
+    fn stop(&self, server: ServerProcess, warn: bool) -> Result<()> {
+        match server {
+            ServerProcess::Running(mut child) => self.server_manager.shutdown_server(&mut child, warn)?,
+            ServerProcess::Simulated => {
+                let action = if warn { "warn players, lock, and shut down" } else { "shut down" };
+                println_step(&format!(
+                    "{} {DRY_RUN} Would {action} the DayZ server over RCon",
+                    self.format_time(self.clock.now())
+                ), 0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a message to the players on the server
+    fn announce(&self, message: &str) {
+        if self.is_dry_run() {
+            println_step(&format!("{} {DRY_RUN} Would announce over RCon: {message}", self.format_time(self.clock.now())), 0);
+            return;
+        }
+
+        match self.server_manager.announce(message) {
+            Ok(()) => println_step(&format!("Announced: {message}"), 0),
+            Err(e) => println_failure(&format!("Failed to announce '{message}': {e:#}"), 0),
+        }
+    }
+
+    /// Watch the server until it exits, a restart is due, or a stop is requested
+    fn watch(&self, server: &mut ServerProcess) -> Result<Exit> {
+        let mut restart = self.schedule.next_restart_after(self.clock.now());
+        let defer_deadline = restart.and_then(|restart| self.get_defer_deadline(restart));
+        let mut deferred = false;
+        let mut warnings = restart.map(|restart| self.schedule.warnings_for(restart)).unwrap_or_default();
+        // Warnings already overdue when the server starts would only be noise
+        let now = self.clock.now();
+        warnings.retain(|(at, _)| *at >= now);
+        let started = now;
+        // None once the interval is too long to ever come round
+        let mut next_positions_export = Some(now);
+        let mut next_title_update = now;
+        let healthy_at = now + ChronoDuration::minutes(quarantine::HEALTHY_MINUTES);
+        let mut recorded_healthy = false;
+        let mut health = HealthMonitor::from_config(&self.config, &self.server_install_dir, now);
+        let mut alerts = if self.is_dry_run() {
+            None
+        } else {
+            AdmAlerts::from_config(self.server_manager.config(), &self.server_install_dir, now)
+        };
+        if let ServerProcess::Running(child) = server
+            && let Some(heartbeat) = &self.heartbeat
+        {
+            heartbeat.beat(SupervisorState::Running, Some((child.id(), started)), None, now);
+        }
+
+        loop {
+            if let ServerProcess::Running(child) = server
+                && let Some(status) = child.try_wait().context("Failed to check on DayZ server process")?
+            {
+                interrupt::set_server_running(false);
+                return Ok(Exit::Crashed(status.code()));
+            }
+
+            if self.is_stop_requested() || interrupt::shutdown_requested() {
+                return Ok(Exit::StopRequested);
+            }
+            if !self.is_dry_run()
+                && let Some(update) = take_restart_request(&self.server_install_dir)?
+            {
+                return Ok(Exit::RestartRequested { update });
+            }
+
+            let now = self.clock.now();
+            if self.dry_run_until.is_some_and(|until| now >= until) {
+                return Ok(Exit::DryRunComplete);
+            }
+            if restart.is_some_and(|restart| now >= restart) {
+                if self.should_defer(now, defer_deadline) {
+                    deferred = true;
+                    restart = Some(now + ChronoDuration::seconds(DEFER_CHECK_SECONDS));
+                    continue;
+                }
+                return Ok(Exit::Scheduled { warn: deferred });
+            }
+
+            if let ServerProcess::Running(child) = server
+                && let Some(heartbeat) = &self.heartbeat
+            {
+                heartbeat.poll(&self.server_install_dir, (child.id(), started), now);
+            }
+
+            if let ServerProcess::Running(_) = server
+                && now >= next_title_update
+            {
+                self.update_title(restart);
+                next_title_update = now + ChronoDuration::seconds(TITLE_UPDATE_SECONDS);
+            }
+
+            if next_positions_export.is_some_and(|at| now >= at) {
+                self.export_positions();
+                let interval = i64::try_from(self.server_manager.config().positions.interval_minutes.max(1)).ok()
+                    .and_then(ChronoDuration::try_minutes)
+                    .unwrap_or(ChronoDuration::MAX);
+                next_positions_export = now.checked_add_signed(interval);
+            }
+
+            if let Some(validator) = &self.validator
+                && !self.is_dry_run()
+            {
+                validator.poll(now);
+            }
+
+            if let Some(alerts) = &mut alerts {
+                alerts.poll(now);
+            }
+
+            if let ServerProcess::Running(child) = server
+                && let Some(health) = &mut health
+                && let Some(reason) = health.poll(child.id(), now)
+            {
+                return Ok(Exit::Unhealthy(reason));
+            }
+
+            if !recorded_healthy && now >= healthy_at && !self.is_dry_run() {
+                self.server_manager.record_healthy_run();
+                recorded_healthy = true;
+            }
+
+            while let Some((at, message)) = warnings.first() {
+                if *at > now {
+                    break;
+                }
+                self.announce(message);
+                warnings.remove(0);
+            }
+
+            let next_event = [warnings.first().map(|(at, _)| *at), restart, self.dry_run_until]
+                .into_iter()
+                .flatten()
+                .min()
+                .unwrap_or(now);
+            self.clock.wait(next_event);
+        }
+    }
+
+    /// Show the players online and the next restart in the console title
+    fn update_title(&self, restart: Option<DateTime<Local>>) {
+        let players = a2s::query_server(&self.server_install_dir).ok().map(|info| u32::from(info.human_players()));
+        self.server_manager.set_running_title(players, restart.map(|restart| self.format_time(restart)));
+    }
+
+    /// Write the heartbeat when the supervisor's state changes, if heartbeats are on
+    fn beat(&self, state: SupervisorState) {
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.beat(state, None, None, self.clock.now());
+        }
+    }
+
+    /// Rewrite the `[positions]` export file, if one is configured
+    fn export_positions(&self) {
+        let config = &self.server_manager.config().positions;
+        let Some(output) = positions::get_export_path(config, &self.server_install_dir) else {
+            return;
+        };
+        if self.is_dry_run() {
+            return;
+        }
+
+        let history = positions::history(config.history_minutes);
+        if let Err(e) = positions::export(&self.server_install_dir, &output, history, &self.server_manager.config().privacy) {
+            println_failure(&format!("Failed to export player positions: {e:#}"), 0);
+        }
+    }
+
+    /// When a restart held for online players goes ahead anyway, if restarts are held at all
+    fn get_defer_deadline(&self, restart: DateTime<Local>) -> Option<DateTime<Local>> {
+        let max_defer = self.schedule.max_defer()?;
+        // A defer too long to add up is held for as long as players stay, like an unbounded one
+        Some(restart.checked_add_signed(max_defer).unwrap_or_else(|| DateTime::<Utc>::MAX_UTC.with_timezone(&Local)))
+    }
+
+    /// Whether a due restart should be held because players are online
+    fn should_defer(&self, now: DateTime<Local>, deadline: Option<DateTime<Local>>) -> bool {
+        let Some(deadline) = deadline else {
+            return false;
+        };
+        if now >= deadline {
+            println_step("Players are still online, restarting anyway (see `schedule.max_defer_minutes`)", 0);
+            return false;
+        }
+
+        if self.is_dry_run() {
+            println_step(&format!("{} {DRY_RUN} Would hold the restart if players are online", self.format_time(now)), 0);
+            return false;
+        }
+
+        match a2s::query_server(&self.server_install_dir) {
+            Ok(info) if info.human_players() > 0 => {
+                println_step(&format!("Holding scheduled restart, {} player(s) online", info.human_players()), 0);
+                true
+            }
+            Ok(_) => false,
+            Err(e) => {
+                println_failure(&format!("Failed to check for online players, restarting as scheduled: {e:#}"), 0);
+                false
+            }
+        }
+    }
+
+    /// Wait until `until`, waking early if a stop is requested. Returns true if one was.
+    fn sleep_unless_stopped(&self, until: DateTime<Local>) -> bool {
+        while self.clock.now() < until {
+            if self.is_stop_requested() {
+                return true;
+            }
+            self.clock.wait(until);
+        }
+        self.is_stop_requested()
+    }
+
+    /// Track unexpected exits and give up if the server is crash looping
+    fn record_crash(&mut self) -> Result<()> {
+        let now = self.clock.now();
+        self.crashes.retain(|crash| now - *crash < ChronoDuration::minutes(RESTART_WINDOW_MINUTES));
+        self.crashes.push(now);
+
+        // Coming back up without the mod gets a clean slate
+        let quarantine_after = self.config.quarantine_after_crashes;
+        if quarantine_after > 0
+            && self.crashes.len() >= quarantine_after
+            && self.server_manager.quarantine_crash_suspect()
+        {
+            self.crashes.clear();
+            return Ok(());
+        }
+
+        if self.crashes.len() > self.config.max_restarts_per_hour {
+            return Err(anyhow!(
+                "DayZ server exited {} times within an hour, giving up (see `supervise.max_restarts_per_hour`)",
+                self.crashes.len()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Timestamps only matter in dry runs, where they show the simulated timeline
+    fn format_time(&self, time: DateTime<Local>) -> String {
+        if self.is_dry_run() {
+            time.format("%a %Y-%m-%d %H:%M").to_string()
+        } else {
+            time.format("%H:%M").to_string()
+        }
+    }
+}
+
+fn get_stop_request_path(server_install_dir: &Path) -> PathBuf {
+    state_dir(server_install_dir).join(STOP_REQUEST_FILE)
+}
+
+/// Whether a stop was asked for, see `request_stop`
+pub fn stop_requested(server_install_dir: &Path) -> bool {
+    get_stop_request_path(server_install_dir).exists()
+}
+
+/// Ask a supervisor running in `server_install_dir` to stop the server and exit
+pub fn request_stop(server_install_dir: &Path) -> Result<()> {
+    write_request(&get_stop_request_path(server_install_dir), "")
+}
+
+/// Ask a supervisor running in `server_install_dir` to restart the server, with players warned
+/// like a held scheduled restart; `update` updates the server and mods before it starts again
+pub fn request_restart(server_install_dir: &Path, update: bool) -> Result<()> {
+    let contents = if update { UPDATE_REQUEST } else { "" };
+    write_request(&state_dir(server_install_dir).join(RESTART_REQUEST_FILE), contents)
+}
+
+fn write_request(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context("Failed to create state directory")?;
+    }
+
+    fs::write(path, contents)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+/// Remove a pending restart request, returning whether it asked for an update
+pub fn take_restart_request(server_install_dir: &Path) -> Result<Option<bool>> {
+    let path = state_dir(server_install_dir).join(RESTART_REQUEST_FILE);
+    let Ok(contents) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+
+    fs::remove_file(&path)
+        .context(format!("Failed to remove {}", path.display()))?;
+    Ok(Some(contents.trim() == UPDATE_REQUEST))
+}
+
+/// Withdraw a stop request left over from before this DZSM took the instance lock. One made
+/// since, e.g. by `dzsm stop` while this DZSM was updating, is kept for it to act on.
+pub fn clear_stale_stop_request(server_install_dir: &Path) -> Result<()> {
+    let Ok(requested) = fs::metadata(get_stop_request_path(server_install_dir)).and_then(|metadata| metadata.modified()) else {
+        return Ok(());
+    };
+    if instance::locked_since(server_install_dir).is_some_and(|locked| DateTime::<Local>::from(requested) >= locked) {
+        return Ok(());
+    }
+    clear_stop_request(server_install_dir)
+}
+
+/// Withdraw a stop request nothing picked up
+pub fn clear_stop_request(server_install_dir: &Path) -> Result<()> {
+    let path = get_stop_request_path(server_install_dir);
+    if path.exists() {
+        fs::remove_file(&path)
+            .context(format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
@@ -1,6 +1,20 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use crate::{VERSION, AUTHORS};
 
+static BANNER_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Skip the banner entirely, including the terminal width probe,
+/// for wrapper scripts, services, and other embedders
+pub fn disable_banner() {
+    BANNER_ENABLED.store(false, Ordering::Relaxed);
+}
+
 pub fn print_banner() {
+    if !BANNER_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
     let banner = include_str!("../../banner.ascii");
     let term_width = term_size::dimensions().map_or(80, |(w, _)| w);
 
@@ -0,0 +1,65 @@
+use chrono::Local;
+use serde_json::{Value, json};
+use std::io::{self, Write};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Print JSON on stdout instead of the usual lines, one object per line: an event such as
+/// `{"type": "step", "level": 1, "message": "...", "time": "..."}` for each line of output,
+/// then `{"type": "result", "success": true, "exit_code": 0}` once the command is done.
+/// The log file is unaffected.
+pub fn set_json_output() {
+    JSON_OUTPUT.store(true, Ordering::Relaxed);
+}
+
+pub fn is_json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// A line of status output, `kind` being step, success, failure, detail, or debug
+pub(super) fn print_event(kind: &str, message: &str, level: usize) {
+    print_object(&json!({
+        "type": kind,
+        "level": level,
+        "message": message,
+        "time": Local::now().to_rfc3339(),
+    }));
+}
+
+/// What a command prints as its answer, such as `dzsm public show`'s JSON:
+/// printed as is, or as an `output` event with `--json`
+pub fn print_output(text: &str) {
+    if is_json_output() {
+        print_object(&json!({ "type": "output", "text": text }));
+    } else {
+        println!("{text}");
+    }
+}
+
+/// The `result` event ending the output, with the error a failed command exits with
+pub fn print_result(error: Option<&anyhow::Error>, exit_code: u8) {
+    let result = match error {
+        None => json!({ "type": "result", "success": true, "exit_code": exit_code }),
+        Some(e) => json!({ "type": "result", "success": false, "exit_code": exit_code, "error": format!("{e:#}") }),
+    };
+    print_object(&result);
+}
+
+fn print_object(value: &Value) {
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{value}");
+    let _ = stdout.flush();
+}
+
+/// Stdout for child processes such as SteamCMD and the server: the console,
+/// or stderr with `--json` so stdout only carries JSON
+#[allow(clippy::doc_markdown)]
+pub fn child_stdout() -> Stdio {
+    if is_json_output() {
+        Stdio::from(io::stderr())
+    } else {
+        Stdio::inherit()
+    }
+}
@@ -1,3 +1,8 @@
 pub mod banner;
+pub mod json;
+pub mod progress;
 pub mod prompt;
+pub mod spinner;
 pub mod status;
+pub mod timing;
+pub mod title;
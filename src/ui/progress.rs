@@ -0,0 +1,105 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use super::json::is_json_output;
+use super::status::{ARROW, println_step};
+use crate::storage::format_size;
+
+const BAR_WIDTH: usize = 24;
+/// Downloads report many times a second, redrawing each one would slow the console down
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+/// Without a console, a line is logged each time another this many percent are done
+const LOGGED_PERCENT_STEP: u64 = 10;
+
+/// A download's progress on one console line, redrawn in place as
+/// `→ Downloading [#########...............]  38.2%  241.3 MB / 631.0 MB  12.4 MB/s`.
+/// When output isn't a console (a service, redirected to a file, or --json) a line is
+/// logged every 10% instead.
+pub struct ProgressLine {
+    level: usize,
+    stage: String,
+    /// When the stage began and how much was done by then, for its speed
+    stage_started: Instant,
+    stage_start_done: u64,
+    last_draw: Option<Instant>,
+    logged_percent: Option<u64>,
+    is_terminal: bool,
+    /// Length of the line on screen, to blank it out when it's redrawn shorter or cleared
+    shown_len: usize,
+}
+
+impl ProgressLine {
+    pub fn new(level: usize) -> Self {
+        Self {
+            level,
+            stage: String::new(),
+            stage_started: Instant::now(),
+            stage_start_done: 0,
+            last_draw: None,
+            logged_percent: None,
+            is_terminal: io::stdout().is_terminal() && !is_json_output(),
+            shown_len: 0,
+        }
+    }
+
+    /// Show `done` of `total` bytes for a stage, e.g. "Downloading" or "Verifying"
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn update(&mut self, stage: &str, done: u64, total: u64) {
+        if stage != self.stage {
+            self.stage = stage.to_string();
+            self.stage_started = Instant::now();
+            self.stage_start_done = done;
+            self.logged_percent = None;
+        }
+
+        let percent = if total == 0 { 0.0 } else { (done as f64 * 100.0 / total as f64).min(100.0) };
+        if !self.is_terminal {
+            let step = percent as u64 / LOGGED_PERCENT_STEP * LOGGED_PERCENT_STEP;
+            if self.logged_percent.is_none_or(|logged| step > logged) {
+                self.logged_percent = Some(step);
+                println_step(&format!("{stage} {step}% of {}", format_size(total)), self.level);
+            }
+            return;
+        }
+
+        if self.last_draw.is_some_and(|last| last.elapsed() < REDRAW_INTERVAL) && done < total {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+
+        let elapsed = self.stage_started.elapsed().as_secs_f64();
+        let speed = if elapsed > 0.0 { done.saturating_sub(self.stage_start_done) as f64 / elapsed } else { 0.0 };
+        let filled = (percent / 100.0 * BAR_WIDTH as f64) as usize;
+        let line = format!(
+            "{}{ARROW} {stage} [{}{}] {percent:5.1}%  {} / {}  {}/s",
+            "  ".repeat(self.level),
+            "#".repeat(filled),
+            ".".repeat(BAR_WIDTH - filled),
+            format_size(done),
+            format_size(total),
+            format_size(speed as u64)
+        );
+        self.draw(&line);
+    }
+
+    /// Blank the line so other output starts at the beginning of it
+    pub fn clear(&mut self) {
+        if self.shown_len > 0 {
+            self.draw("");
+        }
+    }
+
+    /// Whether a progress line is on screen
+    pub const fn is_shown(&self) -> bool {
+        self.shown_len > 0
+    }
+
+    fn draw(&mut self, line: &str) {
+        let len = line.chars().count();
+        let padding = " ".repeat(self.shown_len.saturating_sub(len));
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\r{line}{padding}\r{line}");
+        let _ = stdout.flush();
+        self.shown_len = len;
+    }
+}
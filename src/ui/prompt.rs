@@ -1,12 +1,47 @@
-use super::status::print_step_concat;
+use super::status::{print_step_concat, println_blank, println_step_concat};
 
-use anyhow::{Result};
+use anyhow::{Result, anyhow};
 use std::io::{self, Write};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use windows_sys::Win32::System::Console::{
+    CONSOLE_MODE, ENABLE_ECHO_INPUT, GetConsoleMode, GetStdHandle, STD_INPUT_HANDLE, SetConsoleMode,
+};
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Never read from stdin: prompts take their default answer
+/// and child processes get no console input
+pub fn set_non_interactive() {
+    NON_INTERACTIVE.store(true, Ordering::Relaxed);
+}
+
+pub fn is_non_interactive() -> bool {
+    NON_INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// Stdin for child processes: inherited normally, closed in non-interactive mode
+/// so tools waiting for input (e.g. a Steam Guard code) fail fast instead of hanging
+pub fn child_stdin() -> Stdio {
+    if is_non_interactive() {
+        Stdio::null()
+    } else {
+        Stdio::inherit()
+    }
+}
 
 pub fn prompt_yes_no(prompt: &str, default: bool, level: usize) -> Result<bool> {
     let options = if default { "(Y/n)" } else { "(y/N)" };
     
-    println!();
+    println_blank();
+
+    if is_non_interactive() {
+        let answer = if default { "yes" } else { "no" };
+        println_step_concat(&format!("{prompt} {options}: {answer} (non-interactive)"), level);
+        return Ok(default);
+    }
+
     print_step_concat(&format!("{prompt} {options}: "), level);
     io::stdout().flush()?;
 
@@ -24,3 +59,33 @@ pub fn prompt_yes_no(prompt: &str, default: bool, level: usize) -> Result<bool>
         }
     })
 }
+
+/// Read a line without echoing it, for passwords; fails in non-interactive mode
+pub fn prompt_password(prompt: &str, level: usize) -> Result<String> {
+    if is_non_interactive() {
+        return Err(anyhow!("{prompt} is needed, but input is disabled (non-interactive)"));
+    }
+
+    print_step_concat(&format!("{prompt}: "), level);
+    io::stdout().flush()?;
+
+    // Echo is only turned off on a console; piped input has nothing to hide
+    let handle = unsafe { GetStdHandle(STD_INPUT_HANDLE) };
+    let mut mode: CONSOLE_MODE = 0;
+    let is_console = unsafe { GetConsoleMode(handle, &raw mut mode) } != 0;
+    if is_console {
+        unsafe { SetConsoleMode(handle, mode & !ENABLE_ECHO_INPUT) };
+    }
+
+    let mut input = String::new();
+    let result = io::stdin().read_line(&mut input);
+
+    if is_console {
+        unsafe { SetConsoleMode(handle, mode) };
+        // The Enter key wasn't echoed either
+        println!();
+    }
+    result?;
+
+    Ok(input.trim_end_matches(['\r', '\n']).to_string())
+}
@@ -0,0 +1,72 @@
+use log::Level;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::json::is_json_output;
+use super::status::{CYAN, RESET, is_color_enabled, println_step};
+use crate::logging::is_console_enabled;
+
+const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A step that takes a while without printing anything, such as copying storage: the step
+/// is printed, with a spinner and the seconds so far on the line below until it's done.
+/// Without a console, or with --json or --quiet, only the step is printed.
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub fn start(message: &str, level: usize) -> Self {
+        println_step(message, level);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let animated = io::stdout().is_terminal() && !is_json_output() && is_console_enabled(Level::Info);
+        let thread = animated.then(|| {
+            let stop = Arc::clone(&stop);
+            let indent = "  ".repeat(level + 1);
+            thread::spawn(move || spin(&stop, &indent))
+        });
+        Self { stop, thread }
+    }
+
+    /// Take the spinner off the screen, for the step's result to follow
+    pub fn finish(self) {
+        drop(self);
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn spin(stop: &AtomicBool, indent: &str) {
+    let started = Instant::now();
+    let (color, reset) = if is_color_enabled() { (CYAN, RESET) } else { ("", "") };
+    let mut stdout = io::stdout();
+    let mut shown_len = 0;
+
+    for frame in FRAMES.iter().cycle() {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        let elapsed = format!("{}s", started.elapsed().as_secs());
+        // The frame and the space after it take two columns
+        shown_len = indent.len() + 2 + elapsed.len();
+        let _ = write!(stdout, "\r{indent}{color}{frame}{reset} {elapsed}");
+        let _ = stdout.flush();
+        thread::sleep(FRAME_INTERVAL);
+    }
+
+    let _ = write!(stdout, "\r{}\r", " ".repeat(shown_len));
+    let _ = stdout.flush();
+}
@@ -1,28 +1,116 @@
+use super::json::{is_json_output, print_event};
+use crate::logging::is_console_enabled;
+use log::Level;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+use windows_sys::Win32::System::Console::{
+    ENABLE_VIRTUAL_TERMINAL_PROCESSING, GetConsoleMode, GetStdHandle, STD_OUTPUT_HANDLE, SetConsoleMode,
+};
+
 const CHECK_MARK: &str = "✓";
 const CROSS_MARK: &str = "✗";
-const ARROW: &str = "→";
+pub(super) const ARROW: &str = "→";
+
+pub(super) const GREEN: &str = "\x1b[32m";
+pub const RED: &str = "\x1b[31m";
+pub(super) const CYAN: &str = "\x1b[36m";
+pub(super) const DIM: &str = "\x1b[2m";
+pub const RESET: &str = "\x1b[0m";
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Output goes through the logger so it reaches both the console and the log file;
+// with --json the console gets an event instead, see `ui::json`. The line's kind goes
+// along as the log target, for the console to color it by; the log file stays plain.
+
+fn emit(log_level: Level, kind: &str, line: &str, message: &str, level: usize) {
+    if is_json_output() && is_console_enabled(log_level) {
+        print_event(kind, message, level);
+    }
+    log::log!(target: kind, log_level, "{}{line}", "  ".repeat(level));
+}
+
+/// Color the console output, unless `--no-color` or `NO_COLOR` asks not to, output isn't a
+/// console (a service, a file, or --json), or the console can't show colors
+pub fn configure_color(no_color: bool) {
+    let wanted = !no_color
+        && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        && !is_json_output()
+        && io::stdout().is_terminal();
+    COLOR_ENABLED.store(wanted && enable_virtual_terminal(), Ordering::Relaxed);
+}
+
+pub fn is_color_enabled() -> bool {
+    COLOR_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Windows consoles only show ANSI colors once asked to, which older ones refuse
+fn enable_virtual_terminal() -> bool {
+    unsafe {
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+        if handle.is_null() || handle == INVALID_HANDLE_VALUE || GetConsoleMode(handle, &raw mut mode) == 0 {
+            return false;
+        }
+        SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+    }
+}
+
+/// A status line as the console shows it: the mark of a step or success in color, a failure
+/// all in red, and debug detail dimmed. Lines of other kinds, or with colors off, are left as is.
+pub fn paint_line(kind: &str, line: &str) -> String {
+    if !is_color_enabled() {
+        return line.to_string();
+    }
+
+    let body = line.trim_start_matches(' ');
+    let indent = &line[..line.len() - body.len()];
+    let paint_mark = |color: &str| match body.split_once(' ') {
+        Some((mark, rest)) => format!("{indent}{color}{mark}{RESET} {rest}"),
+        None => line.to_string(),
+    };
+    match kind {
+        "failure" => format!("{indent}{RED}{body}{RESET}"),
+        "success" => paint_mark(GREEN),
+        "step" => paint_mark(CYAN),
+        "debug" => format!("{indent}{DIM}{body}{RESET}"),
+        _ => line.to_string(),
+    }
+}
 
 pub fn println_failure(message: &str, level: usize) {
-    let indent = "  ".repeat(level);
-    println!("{indent}{CROSS_MARK} {message}");
+    emit(Level::Error, "failure", &format!("{CROSS_MARK} {message}"), message, level);
 }
 
 pub fn println_step(message: &str, level: usize) {
-    let indent = "  ".repeat(level);
-    println!("{indent}{ARROW} {message}");
+    emit(Level::Info, "step", &format!("{ARROW} {message}"), message, level);
 }
 
 pub fn println_step_concat(message: &str, level: usize) {
-    let indent = "  ".repeat(level);
-    println!("{indent}  {message}");
+    emit(Level::Info, "detail", &format!("  {message}"), message, level);
 }
 
+/// Partial line for prompts, printed straight to the console
 pub fn print_step_concat(message: &str, level: usize) {
     let indent = "  ".repeat(level);
     print!("{indent}  {message}");
 }
 
 pub fn println_success(message: &str, level: usize) {
-    let indent = "  ".repeat(level);
-    println!("{indent}{CHECK_MARK} {message}");
+    emit(Level::Info, "success", &format!("{CHECK_MARK} {message}"), message, level);
+}
+
+/// Extra detail, only shown with --verbose
+pub fn println_debug(message: &str, level: usize) {
+    emit(Level::Debug, "debug", &format!("{ARROW} {message}"), message, level);
+}
+
+/// An empty line to space out the console, left out of --json output
+pub fn println_blank() {
+    if !is_json_output() {
+        println!();
+    }
 }
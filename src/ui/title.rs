@@ -0,0 +1,53 @@
+use std::fmt;
+
+use windows_sys::Win32::System::Console::SetConsoleTitleW;
+
+/// Console window title, e.g. "DZSM — My Server — 12 online — next restart 04:00",
+/// so several DZSM windows can be told apart at a glance
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleTitle {
+    pub server_name: String,
+    pub status: Option<String>,
+    pub players: Option<u32>,
+    pub next_restart: Option<String>,
+}
+
+impl ConsoleTitle {
+    pub fn new(server_name: &str) -> Self {
+        Self {
+            server_name: server_name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Set the title of the console window DZSM is running in
+    pub fn apply(&self) {
+        let wide: Vec<u16> = self.to_string()
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .collect();
+
+        // Fails harmlessly when there is no console (e.g. running as a service)
+        unsafe {
+            SetConsoleTitleW(wide.as_ptr());
+        }
+    }
+}
+
+impl fmt::Display for ConsoleTitle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DZSM — {}", self.server_name)?;
+
+        if let Some(status) = &self.status {
+            write!(f, " — {status}")?;
+        }
+        if let Some(players) = self.players {
+            write!(f, " — {players} online")?;
+        }
+        if let Some(next_restart) = &self.next_restart {
+            write!(f, " — next restart {next_restart}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use scraper::{Html, Selector};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::NotificationEvent;
+use crate::config::mod_entry::ModEntry;
+use crate::http;
+use crate::notifier::{Notification, truncate};
+use crate::state::state_dir;
+use crate::workshop_manifest::WorkshopManifest;
+
+const CHANGELOG_URL: &str = "https://steamcommunity.com/sharedfiles/filedetails/changelog/";
+const DIGESTS_DIR: &str = "digests";
+const CHANGE_NOTES_LIMIT: usize = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Updated,
+}
+
+/// A workshop mod that changed during an update run
+pub struct ModChange {
+    pub mod_entry: ModEntry,
+    pub kind: ChangeKind,
+    pub time_updated: i64,
+    pub change_notes: Option<String>,
+}
+
+/// Summary of the mods that changed during an update run, for sharing with players
+pub struct UpdateDigest {
+    created: DateTime<Utc>,
+    changes: Vec<ModChange>,
+}
+
+impl UpdateDigest {
+    /// Compare SteamCMD's workshop manifest from before and after the update
+    #[allow(clippy::doc_markdown)]
+    pub fn compare(mods: &[ModEntry], before: &WorkshopManifest, after: &WorkshopManifest) -> Self {
+        let mut changes = Vec::new();
+
+        for mod_entry in mods {
+            let Some(current) = after.get(mod_entry.id) else {
+                continue;
+            };
+
+            let kind = match before.get(mod_entry.id) {
+                None => ChangeKind::Added,
+                Some(previous) if previous.time_updated < current.time_updated
+                    || previous.manifest != current.manifest => ChangeKind::Updated,
+                Some(_) => continue,
+            };
+
+            // The same mod can be listed individually and in the collection
+            if changes.iter().any(|c: &ModChange| c.mod_entry.id == mod_entry.id) {
+                continue;
+            }
+
+            changes.push(ModChange {
+                mod_entry: mod_entry.clone(),
+                kind,
+                time_updated: current.time_updated,
+                change_notes: None,
+            });
+        }
+
+        Self { created: Utc::now(), changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Scrape the latest change notes of every updated mod from its Workshop changelog
+    pub fn fetch_change_notes(&mut self) {
+        for change in self.changes.iter_mut().filter(|c| c.kind == ChangeKind::Updated) {
+            change.change_notes = http::get_page(&format!("{CHANGELOG_URL}{}", change.mod_entry.id))
+                .ok()
+                .and_then(|html| parse_latest_change_notes(&html));
+        }
+    }
+
+    /// Render the digest as a standalone Markdown document
+    pub fn to_markdown(&self) -> String {
+        format!(
+            "# Mod updates - {}\n\n{}",
+            self.created.format("%Y-%m-%d %H:%M UTC"),
+            self.render_sections()
+        )
+    }
+
+    /// Render the digest as a notification for the configured channels
+    pub fn to_notification(&self) -> Notification {
+        Notification {
+            event: NotificationEvent::UpdateDigest,
+            title: format!("Mod updates ({})", self.changes.len()),
+            body: self.render_sections(),
+        }
+    }
+
+    /// Write the digest to the state directory, returning the path written
+    pub fn save(&self, server_install_dir: &Path) -> Result<PathBuf> {
+        let digests_dir = state_dir(server_install_dir).join(DIGESTS_DIR);
+        fs::create_dir_all(&digests_dir)
+            .context("Failed to create digests directory")?;
+
+        let path = digests_dir.join(format!("{}.md", self.created.format("%Y-%m-%d_%H%M%S")));
+        fs::write(&path, self.to_markdown())
+            .context(format!("Failed to write update digest {}", path.display()))?;
+
+        Ok(path)
+    }
+
+    fn render_sections(&self) -> String {
+        let mut markdown = String::new();
+
+        for (heading, kind) in [("Updated", ChangeKind::Updated), ("New", ChangeKind::Added)] {
+            let changes: Vec<&ModChange> = self.changes.iter().filter(|c| c.kind == kind).collect();
+            if changes.is_empty() {
+                continue;
+            }
+
+            let _ = writeln!(markdown, "## {heading}");
+            for change in changes {
+                let updated = DateTime::from_timestamp(change.time_updated, 0)
+                    .map(|time| format!(" - updated {}", time.format("%Y-%m-%d %H:%M UTC")))
+                    .unwrap_or_default();
+
+                let _ = writeln!(
+                    markdown,
+                    "- **[{}]({})**{updated}",
+                    change.mod_entry.name,
+                    change.mod_entry.workshop_url()
+                );
+
+                if let Some(notes) = &change.change_notes {
+                    for line in truncate(notes, CHANGE_NOTES_LIMIT).lines().filter(|l| !l.trim().is_empty()) {
+                        let _ = writeln!(markdown, "  > {}", line.trim());
+                    }
+                } else if change.kind == ChangeKind::Updated {
+                    let _ = writeln!(markdown, "  > [Change notes]({CHANGELOG_URL}{})", change.mod_entry.id);
+                }
+            }
+            markdown.push('\n');
+        }
+
+        markdown
+    }
+}
+
+/// Extract the newest entry from a Workshop changelog page
+fn parse_latest_change_notes(html_content: &str) -> Option<String> {
+    let document = Html::parse_document(html_content);
+    let entry_selector = Selector::parse(".changeLogCtn").ok()?;
+    let notes_selector = Selector::parse("p").ok()?;
+
+    let entry = document.select(&entry_selector).next()?;
+    let notes = entry.select(&notes_selector).next()?;
+
+    // Line breaks are <br> elements, so join the text nodes line by line
+    let text = notes.text()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    if text.is_empty() { None } else { Some(text) }
+}
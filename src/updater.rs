@@ -0,0 +1,173 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::NaiveTime;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cli::{CliArgs, UpdaterCommand};
+use crate::config::Config;
+use crate::launch_script::{quote_if_spaced, to_batch_script};
+use crate::processes;
+use crate::server::{SERVER_EXE, ServerManager};
+use crate::state::{logs_dir, state_dir};
+use crate::ui::status::{println_step, println_success};
+
+const DEFAULT_SCRIPT: &str = "start_server.bat";
+/// What the scheduled task runs: Task Scheduler can't set a working directory, DZSM needs one
+const TASK_SCRIPT: &str = "updater-task.bat";
+const UPDATER_LOG: &str = "updater.log";
+
+/// Entry point for `dzsm updater ...`
+pub fn run(command: &UpdaterCommand, args: &CliArgs, config: &Config, root_dir: &Path, server_install_dir: &str) -> Result<()> {
+    match command {
+        UpdaterCommand::Run { script, params } => update(args, config, server_install_dir, script.as_deref(), params.as_deref()),
+        UpdaterCommand::Install { at, name, user, password, script, params } => {
+            let name = resolve_name(name.as_deref(), root_dir, args.profile.as_deref());
+            let mut updater_args = Vec::new();
+            if let Some(script) = script {
+                updater_args.push(format!("--script \"{}\"", absolute(script)?.display()));
+            }
+            if let Some(params) = params {
+                updater_args.push(format!("--params \"{}\"", absolute(params)?.display()));
+            }
+            if let Some(profile) = &args.profile {
+                updater_args.push(format!("--profile \"{profile}\""));
+            }
+
+            let task = ScheduledTask { name: &name, at, user: user.as_deref(), password: password.as_deref() };
+            task.install(root_dir, Path::new(server_install_dir), &updater_args.join(" "))
+        }
+        UpdaterCommand::Uninstall { name } => {
+            let name = resolve_name(name.as_deref(), root_dir, args.profile.as_deref());
+            schtasks(&["/Delete", "/F", "/TN", &name])?;
+            println_success(&format!("Scheduled task {name} removed"), 0);
+            Ok(())
+        }
+    }
+}
+
+/// Update the server and mods, then write the launch script the other launcher starts the server with
+fn update(args: &CliArgs, config: &Config, server_install_dir: &str, script: Option<&Path>, params: Option<&Path>) -> Result<()> {
+    let install_dir = Path::new(server_install_dir);
+    if !processes::find_by_exe_path(&install_dir.join(SERVER_EXE)).is_empty() {
+        return Err(anyhow!(
+            "The server is running, and updating its files in place would fail or break it. \
+            Run the updater while the launcher has the server stopped"
+        ));
+    }
+
+    let mut server_manager = ServerManager::new(args.clone(), config.clone(), server_install_dir);
+    server_manager.setup_steamcmd()?;
+    server_manager.install_or_update_server()?;
+    server_manager.install_or_update_mods()?;
+
+    let launch_args = server_manager.build_launch_args()?;
+    let script = script.map_or_else(|| install_dir.join(DEFAULT_SCRIPT), Path::to_path_buf);
+    write_file(&script, &to_batch_script(install_dir, &launch_args))?;
+    println_success(&format!("Launch script written to {}", script.display()), 0);
+
+    if let Some(params) = params {
+        let line: Vec<String> = launch_args.iter().map(|arg| quote_if_spaced(arg)).collect();
+        write_file(params, &line.join(" "))?;
+        println_success(&format!("Launch parameters written to {}", params.display()), 0);
+    }
+
+    Ok(())
+}
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, content)
+        .context(format!("Failed to write {}", path.display()))
+}
+
+/// Paths are passed to a task that doesn't run from this directory
+fn absolute(path: &Path) -> Result<PathBuf> {
+    std::path::absolute(path).context(format!("Failed to resolve {}", path.display()))
+}
+
+/// Default task name, derived from the directory and profile like the service's
+fn resolve_name(name: Option<&str>, root_dir: &Path, profile: Option<&str>) -> String {
+    if let Some(name) = name {
+        return name.to_string();
+    }
+
+    let mut dir_name = root_dir
+        .file_name()
+        .map_or_else(|| "server".to_string(), |n| n.to_string_lossy().to_string());
+    if let Some(profile) = profile {
+        dir_name = format!("{dir_name}-{profile}");
+    }
+    let sanitized: String = dir_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+
+    format!("dzsm-update-{sanitized}")
+}
+
+/// A daily Windows scheduled task running `dzsm updater run`
+struct ScheduledTask<'a> {
+    name: &'a str,
+    at: &'a str,
+    user: Option<&'a str>,
+    password: Option<&'a str>,
+}
+
+impl ScheduledTask<'_> {
+    fn install(&self, root_dir: &Path, server_install_dir: &Path, updater_args: &str) -> Result<()> {
+        let at = NaiveTime::parse_from_str(self.at.trim(), "%H:%M")
+            .context(format!("Invalid time '{}', expected HH:MM", self.at))?;
+        let exe = env::current_exe()
+            .context("Failed to locate the dzsm executable")?;
+
+        let task_script = state_dir(server_install_dir).join(TASK_SCRIPT);
+        let log_path = logs_dir(server_install_dir).join(UPDATER_LOG);
+        write_file(&task_script, &format!(
+            "@echo off\r\n\
+            cd /d \"{}\"\r\n\
+            \"{}\" updater run --non-interactive --no-banner {updater_args} >> \"{}\" 2>&1\r\n",
+            root_dir.display(),
+            exe.display(),
+            log_path.display()
+        ))?;
+
+        println_step(&format!("Scheduling {} to update the server daily at {}...", self.name, at.format("%H:%M")), 0);
+
+        let task_command = format!("\"{}\"", task_script.display());
+        let start_time = at.format("%H:%M").to_string();
+        let mut args = vec!["/Create", "/F", "/TN", self.name, "/TR", &task_command, "/SC", "DAILY", "/ST", &start_time];
+        if let Some(user) = self.user {
+            args.extend(["/RU", user]);
+            if let Some(password) = self.password {
+                args.extend(["/RP", password]);
+            }
+        }
+        schtasks(&args)?;
+
+        println_success(&format!("Scheduled task {} installed (log: {})", self.name, log_path.display()), 0);
+        Ok(())
+    }
+}
+
+/// Run the Windows task scheduler tool, surfacing its output on failure
+fn schtasks(args: &[&str]) -> Result<()> {
+    let output = Command::new("schtasks.exe")
+        .args(args)
+        .output()
+        .context("Failed to run schtasks.exe")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "schtasks.exe {} failed:\n{}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
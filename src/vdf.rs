@@ -0,0 +1,149 @@
+use anyhow::{Result, anyhow};
+
+/// A node in a Valve key-value (VDF/ACF) document
+#[derive(Debug, Clone)]
+pub enum VdfValue {
+    String(String),
+    Object(Vec<(String, VdfValue)>),
+}
+
+impl VdfValue {
+    /// Look up a child by key (VDF keys are case-insensitive)
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        self.entries()
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+
+    /// Follow a path of keys, e.g. `["AppState", "buildid"]`
+    pub fn get_path(&self, path: &[&str]) -> Option<&Self> {
+        path.iter().try_fold(self, |node, key| node.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            Self::Object(_) => None,
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, Self)] {
+        match self {
+            Self::String(_) => &[],
+            Self::Object(entries) => entries,
+        }
+    }
+}
+
+/// Parse a VDF document into a root object
+pub fn parse(text: &str) -> Result<VdfValue> {
+    let tokens = tokenize(text)?;
+    let mut position = 0;
+    let entries = parse_entries(&tokens, &mut position, true)?;
+    Ok(VdfValue::Object(entries))
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Text(String),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '{' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            '}' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(other) => value.push(other),
+                            None => return Err(anyhow!("Unterminated escape in VDF string")),
+                        },
+                        Some(other) => value.push(other),
+                        None => return Err(anyhow!("Unterminated VDF string")),
+                    }
+                }
+                tokens.push(Token::Text(value));
+            }
+            '/' => {
+                // Line comment
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                // Unquoted token
+                let mut value = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '{' || c == '}' || c == '"' {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Text(value));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_entries(tokens: &[Token], position: &mut usize, is_root: bool) -> Result<Vec<(String, VdfValue)>> {
+    let mut entries = Vec::new();
+
+    while *position < tokens.len() {
+        let key = match &tokens[*position] {
+            Token::Close if !is_root => {
+                *position += 1;
+                return Ok(entries);
+            }
+            Token::Text(key) => key.clone(),
+            token => return Err(anyhow!("Unexpected VDF token: {token:?}")),
+        };
+        *position += 1;
+
+        let value = match tokens.get(*position) {
+            Some(Token::Text(value)) => {
+                *position += 1;
+                VdfValue::String(value.clone())
+            }
+            Some(Token::Open) => {
+                *position += 1;
+                VdfValue::Object(parse_entries(tokens, position, false)?)
+            }
+            _ => return Err(anyhow!("Missing value for VDF key '{key}'")),
+        };
+
+        entries.push((key, value));
+    }
+
+    if is_root {
+        Ok(entries)
+    } else {
+        Err(anyhow!("Unterminated VDF object"))
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Datelike, Local, NaiveTime, Weekday};
+use std::fs;
+use std::path::Path;
+
+use crate::config::WeatherConfig;
+use crate::config::weather_config::{WeatherPreset, WeatherRule};
+use crate::server::SERVER_CONFIG;
+use crate::server_cfg::ServerDzConfig;
+use crate::ui::status::println_success;
+
+const WEATHER_FILE: &str = "cfgweather.xml";
+const MAX_WIND: f64 = 20.0;
+
+/// Presets available without defining them in `[weather.presets]`
+fn builtin_preset(name: &str) -> Option<WeatherPreset> {
+    let preset = match name {
+        "clear" => WeatherPreset {
+            overcast: Some([0.0, 0.3]),
+            fog: Some([0.0, 0.05]),
+            rain: Some([0.0, 0.0]),
+            rain_chance: Some(0.0),
+            wind: Some([0.0, 6.0]),
+        },
+        "overcast" => WeatherPreset {
+            overcast: Some([0.5, 1.0]),
+            fog: Some([0.0, 0.2]),
+            rain: Some([0.0, 0.5]),
+            rain_chance: Some(0.4),
+            wind: Some([3.0, 12.0]),
+        },
+        "foggy" => WeatherPreset {
+            overcast: Some([0.3, 0.8]),
+            fog: Some([0.6, 1.0]),
+            rain: Some([0.0, 0.2]),
+            rain_chance: Some(0.2),
+            wind: Some([0.0, 3.0]),
+        },
+        "stormy" => WeatherPreset {
+            overcast: Some([0.8, 1.0]),
+            fog: Some([0.1, 0.3]),
+            rain: Some([0.5, 1.0]),
+            rain_chance: Some(0.9),
+            wind: Some([10.0, MAX_WIND]),
+        },
+        _ => return None,
+    };
+    Some(preset)
+}
+
+impl WeatherConfig {
+    /// Name of the preset for a start at `now`: the first matching rule, else `preset`
+    fn select_preset(&self, now: DateTime<Local>) -> Result<Option<&str>> {
+        for rule in &self.rules {
+            if rule_matches(rule, now)? {
+                return Ok(Some(&rule.preset));
+            }
+        }
+        Ok(self.preset.as_deref())
+    }
+
+    /// Custom presets shadow built-in ones of the same name
+    fn get_preset(&self, name: &str) -> Result<WeatherPreset> {
+        self.presets.get(name).cloned()
+            .or_else(|| builtin_preset(name))
+            .ok_or_else(|| anyhow!(
+                "Unknown weather preset '{name}', define it in [weather.presets] or use one of: clear, overcast, foggy, stormy"
+            ))
+    }
+
+    /// Check every preset and rule, so a typo fails the first start rather than a weekend one
+    fn validate(&self) -> Result<()> {
+        for (name, preset) in &self.presets {
+            validate_preset(preset).context(format!("Invalid weather preset '{name}'"))?;
+        }
+
+        let now = Local::now();
+        for rule in &self.rules {
+            self.get_preset(&rule.preset)?;
+            rule_matches(rule, now)?;
+        }
+        if let Some(preset) = &self.preset {
+            self.get_preset(preset)?;
+        }
+        Ok(())
+    }
+}
+
+fn rule_matches(rule: &WeatherRule, now: DateTime<Local>) -> Result<bool> {
+    let days = rule.days.iter()
+        .map(|day| day.parse::<Weekday>().map_err(|_| anyhow!("Invalid day '{day}' in a [[weather.rules]] entry")))
+        .collect::<Result<Vec<_>>>()?;
+    if !days.is_empty() && !days.contains(&now.weekday()) {
+        return Ok(false);
+    }
+
+    let parse_time = |time: &Option<String>| {
+        time.as_deref()
+            .map(|time| {
+                NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                    .map_err(|_| anyhow!("Invalid time '{time}' in a [[weather.rules]] entry, expected HH:MM"))
+            })
+            .transpose()
+    };
+    let time = now.time();
+    Ok(match (parse_time(&rule.from)?, parse_time(&rule.to)?) {
+        (Some(from), Some(to)) if from <= to => from <= time && time < to,
+        // The window wraps past midnight
+        (Some(from), Some(to)) => time >= from || time < to,
+        (Some(from), None) => time >= from,
+        (None, Some(to)) => time < to,
+        (None, None) => true,
+    })
+}
+
+fn validate_preset(preset: &WeatherPreset) -> Result<()> {
+    let check_range = |name: &str, range: Option<[f64; 2]>, max: f64| {
+        let Some([min_value, max_value]) = range else {
+            return Ok(());
+        };
+        if !(0.0..=max).contains(&min_value) || !(0.0..=max).contains(&max_value) || min_value > max_value {
+            return Err(anyhow!("{name} must be [min, max] with 0 <= min <= max <= {max}, got [{min_value}, {max_value}]"));
+        }
+        Ok(())
+    };
+
+    check_range("overcast", preset.overcast, 1.0)?;
+    check_range("fog", preset.fog, 1.0)?;
+    check_range("rain", preset.rain, 1.0)?;
+    check_range("wind", preset.wind, MAX_WIND)?;
+
+    if let Some(rain_chance) = preset.rain_chance
+        && !(0.0..=1.0).contains(&rain_chance)
+    {
+        return Err(anyhow!("rain_chance must be between 0 and 1, got {rain_chance}"));
+    }
+    Ok(())
+}
+
+/// Write a preset's ranges into `cfgweather.xml`, starting each value mid-range
+fn apply_preset(xml: &mut String, preset: &WeatherPreset) -> Result<()> {
+    // Without reset the server carries on with the weather it saved on shutdown
+    set_attribute(xml, &["weather"], "reset", "1")?;
+    set_attribute(xml, &["weather"], "enable", "1")?;
+
+    let ranges = [
+        ("overcast", preset.overcast),
+        ("fog", preset.fog),
+        ("rain", preset.rain),
+        ("windMagnitude", preset.wind),
+    ];
+    for (element, range) in ranges {
+        let Some([min, max]) = range else {
+            continue;
+        };
+        set_attribute(xml, &["weather", element, "limits"], "min", &min.to_string())?;
+        set_attribute(xml, &["weather", element, "limits"], "max", &max.to_string())?;
+        set_attribute(xml, &["weather", element, "current"], "actual", &f64::midpoint(min, max).to_string())?;
+    }
+
+    // Rain starts once overcast passes the threshold, so a higher chance means a lower threshold
+    if let Some(rain_chance) = preset.rain_chance {
+        set_attribute(xml, &["weather", "rain", "thresholds"], "min", &(1.0 - rain_chance).to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Set an attribute on the element at `path`, e.g. `["weather", "fog", "limits"]`, keeping the rest of the file as it is
+fn set_attribute(xml: &mut String, path: &[&str], attribute: &str, value: &str) -> Result<()> {
+    let mut start = 0;
+    let mut end = xml.len();
+    let mut tag = (0, 0);
+
+    for name in path {
+        let element = find_element(xml, start, end, name)
+            .ok_or_else(|| anyhow!("<{}> not found in {WEATHER_FILE}", path.join("/")))?;
+        tag = (element.0, element.1);
+        start = element.1;
+        end = element.2;
+    }
+
+    let (tag_start, tag_end) = tag;
+    let needle = format!(" {attribute}=\"");
+    if let Some(offset) = xml[tag_start..tag_end].find(&needle) {
+        let value_start = tag_start + offset + needle.len();
+        let value_end = xml[value_start..tag_end].find('"')
+            .map(|length| value_start + length)
+            .ok_or_else(|| anyhow!("Malformed <{}> in {WEATHER_FILE}", path.join("/")))?;
+        xml.replace_range(value_start..value_end, value);
+    } else {
+        let insert_at = if xml[..tag_end].ends_with("/>") { tag_end - 2 } else { tag_end - 1 };
+        let insert_at = xml[..insert_at].trim_end().len();
+        xml.insert_str(insert_at, &format!(" {attribute}=\"{value}\""));
+    }
+    Ok(())
+}
+
+/// Find `<name ...>` between `start` and `end`, returning where its opening tag
+/// starts and ends and where the whole element ends
+fn find_element(xml: &str, start: usize, end: usize, name: &str) -> Option<(usize, usize, usize)> {
+    let open = format!("<{name}");
+    let mut search = start;
+
+    let tag_start = loop {
+        let position = search + xml[search..end].find(&open)?;
+        let next = xml[position + open.len()..].chars().next()?;
+        if next.is_whitespace() || next == '>' || next == '/' {
+            break position;
+        }
+        search = position + open.len();
+    };
+
+    let tag_end = tag_start + xml[tag_start..end].find('>')? + 1;
+    if xml[..tag_end].ends_with("/>") {
+        return Some((tag_start, tag_end, tag_end));
+    }
+
+    let close = format!("</{name}>");
+    let element_end = tag_end + xml[tag_end..end].find(&close)? + close.len();
+    Some((tag_start, tag_end, element_end))
+}
+
+/// Write the weather preset for this start into the mission's `cfgweather.xml`, if one is configured
+pub fn apply_before_launch(config: &WeatherConfig, server_install_dir: &Path) -> Result<()> {
+    config.validate()?;
+
+    let Some(preset_name) = config.select_preset(Local::now())? else {
+        return Ok(());
+    };
+    let preset = config.get_preset(preset_name)?;
+
+    let mission_dir = ServerDzConfig::load(&server_install_dir.join(SERVER_CONFIG))?
+        .get_mission_dir(server_install_dir)
+        .ok_or_else(|| anyhow!("No mission template set in {SERVER_CONFIG}"))?;
+    let weather_path = mission_dir.join(WEATHER_FILE);
+
+    let mut xml = fs::read_to_string(&weather_path)
+        .context(format!("Failed to read {}", weather_path.display()))?;
+    apply_preset(&mut xml, &preset)?;
+    fs::write(&weather_path, xml)
+        .context(format!("Failed to write {}", weather_path.display()))?;
+
+    println_success(&format!("Weather: {preset_name}"), 1);
+    Ok(())
+}
@@ -0,0 +1,314 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use serde::Serialize;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::a2s;
+use crate::collection_sync::get_installed_workshop_mods;
+use crate::config::Config;
+use crate::crypto::constant_time_eq;
+use crate::heartbeat::Heartbeat;
+use crate::logging::get_log_path;
+use crate::maintenance::Maintenance;
+use crate::processes;
+use crate::quarantine;
+use crate::server::SERVER_EXE;
+use crate::state::logs_dir;
+use crate::status::{get_build_id, get_file_version};
+use crate::supervisor::{request_restart, request_stop};
+use crate::ui::status::{println_failure, println_step, println_success};
+
+const DASHBOARD: &str = include_str!("../assets/dashboard.html");
+const SUPERVISOR_LOG: &str = "web-supervisor.log";
+/// The token shown in the default config, which anyone could guess
+const PLACEHOLDER_TOKEN: &str = "change-me";
+/// How long a request with a wrong token is held, which slows guessing down for everyone
+const FAILED_AUTH_DELAY: Duration = Duration::from_secs(1);
+/// Ctrl+C in the dashboard's console leaves a supervisor it started running
+const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// Everything the dashboard shows besides the log
+#[derive(Serialize)]
+struct ServerStatus {
+    running: bool,
+    pids: Vec<u32>,
+    /// From the query port, once the server has loaded its mission
+    query: Option<QueryStatus>,
+    version: Option<String>,
+    build: Option<String>,
+    /// Set while maintenance mode is on, describing it
+    maintenance: Option<String>,
+    mods: Vec<DashboardMod>,
+}
+
+#[derive(Serialize)]
+struct QueryStatus {
+    name: String,
+    map: String,
+    players: u8,
+    max_players: u8,
+}
+
+#[derive(Serialize)]
+struct DashboardMod {
+    id: u64,
+    name: String,
+    server_only: bool,
+    disabled: bool,
+    quarantined: bool,
+}
+
+impl ServerStatus {
+    fn collect(config: &Config, server_install_dir: &Path) -> Result<Self> {
+        let exe_path = server_install_dir.join(SERVER_EXE);
+        let pids: Vec<u32> = processes::find_by_exe_path(&exe_path).iter().map(|process| process.pid).collect();
+        let running = !pids.is_empty();
+
+        let query = running.then(|| a2s::query_server(server_install_dir).ok()).flatten()
+            .map(|info| QueryStatus {
+                players: info.human_players(),
+                name: info.name,
+                map: info.map,
+                max_players: info.max_players,
+            });
+
+        let quarantined = quarantine::get_quarantined(server_install_dir);
+        let mods = get_installed_workshop_mods(server_install_dir)?
+            .into_iter()
+            .map(|mod_entry| DashboardMod {
+                server_only: config.mods.is_server_only(&mod_entry),
+                disabled: config.mods.is_disabled(&mod_entry),
+                quarantined: quarantined.contains_key(&mod_entry.id),
+                id: mod_entry.id,
+                name: mod_entry.name,
+            })
+            .collect();
+
+        Ok(Self {
+            running,
+            pids,
+            query,
+            version: get_file_version(&exe_path),
+            build: get_build_id(server_install_dir),
+            maintenance: Maintenance::load(server_install_dir).ok().flatten().map(|maintenance| maintenance.describe()),
+            mods,
+        })
+    }
+}
+
+/// Serves the dashboard page and `/healthz` to anyone and its API to requests carrying
+/// `web.token`; the page itself holds nothing but the code that asks for the token
+struct Dashboard {
+    config: Config,
+    token: String,
+    root_dir: PathBuf,
+    server_install_dir: PathBuf,
+    profile: Option<String>,
+    /// The supervisor started from the dashboard, if any
+    supervisor: Option<Child>,
+}
+
+impl Dashboard {
+    fn serve(&mut self, listen: &str) -> Result<()> {
+        let server = tiny_http::Server::http(listen)
+            .map_err(|e| anyhow!("Failed to listen on {listen}: {e}"))?;
+        println_success(&format!("Dashboard at http://{listen}/"), 0);
+
+        for request in server.incoming_requests() {
+            let (status, content_type, body) = match self.handle(&request) {
+                Ok((content_type, body)) => (200, content_type, body),
+                Err((status, message)) => (status, "application/json", serde_json::json!({ "error": message }).to_string()),
+            };
+
+            let response = tiny_http::Response::from_string(body)
+                .with_status_code(status)
+                .with_header(tiny_http::Header::from_bytes("Content-Type", content_type).expect("valid header"))
+                .with_header(tiny_http::Header::from_bytes("Cache-Control", "no-store").expect("valid header"));
+            if let Err(e) = request.respond(response) {
+                println_failure(&format!("Failed to respond to dashboard request: {e}"), 1);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, request: &tiny_http::Request) -> Result<(&'static str, String), (u16, String)> {
+        let path = request.url().split('?').next().unwrap_or_default();
+        let method = request.method().clone();
+
+        if method == tiny_http::Method::Get && path == "/" {
+            return Ok(("text/html; charset=utf-8", DASHBOARD.to_string()));
+        }
+        if method == tiny_http::Method::Get && path == "/healthz" {
+            return self.check_health();
+        }
+
+        let expected = format!("Bearer {}", self.token);
+        let authorized = request.headers().iter().any(|header| {
+            header.field.equiv("Authorization") && constant_time_eq(header.value.as_str().as_bytes(), expected.as_bytes())
+        });
+        if !authorized {
+            thread::sleep(FAILED_AUTH_DELAY);
+            return Err((401, "Invalid dashboard token".to_string()));
+        }
+
+        let body = match (method, path) {
+            (tiny_http::Method::Get, "/api/status") => {
+                let status = ServerStatus::collect(&self.config, &self.server_install_dir)
+                    .map_err(|e| (500, format!("{e:#}")))?;
+                serde_json::to_string(&status).map_err(|e| (500, e.to_string()))?
+            }
+            (tiny_http::Method::Get, "/api/log") => {
+                serde_json::json!({ "lines": self.tail_log().map_err(|e| (500, format!("{e:#}")))? }).to_string()
+            }
+            (tiny_http::Method::Post, "/api/start" | "/api/update") if !self.is_server_running() => {
+                // A fresh supervisor updates before its first start anyway
+                let message = self.start().map_err(|e| (500, format!("{e:#}")))?;
+                serde_json::json!({ "message": message }).to_string()
+            }
+            (tiny_http::Method::Post, "/api/start") => return Err((409, "The server is already running".to_string())),
+            (tiny_http::Method::Post, "/api/stop" | "/api/restart") if !self.is_server_running() => {
+                return Err((409, "The server isn't running".to_string()));
+            }
+            (tiny_http::Method::Post, action @ ("/api/stop" | "/api/restart" | "/api/update")) => {
+                let message = self.request(action).map_err(|e| (500, format!("{e:#}")))?;
+                serde_json::json!({ "message": message }).to_string()
+            }
+            (tiny_http::Method::Get | tiny_http::Method::Post, _) => return Err((404, "Not found".to_string())),
+            _ => return Err((405, "Only GET and POST are supported".to_string())),
+        };
+
+        Ok(("application/json", body))
+    }
+
+    /// The supervisor's heartbeat for external monitors, a 503 once it's late or the server was stopped
+    fn check_health(&self) -> Result<(&'static str, String), (u16, String)> {
+        let heartbeat = Heartbeat::load(&self.server_install_dir)
+            .ok_or_else(|| (503, "No heartbeat yet, the server isn't supervised (see `supervise.heartbeat_seconds`)".to_string()))?;
+        if let Some(problem) = heartbeat.check(Local::now()) {
+            return Err((503, problem));
+        }
+        let body = serde_json::to_string(&heartbeat).map_err(|e| (500, e.to_string()))?;
+        Ok(("application/json", body))
+    }
+
+    fn is_server_running(&self) -> bool {
+        !processes::find_by_exe_path(&self.server_install_dir.join(SERVER_EXE)).is_empty()
+    }
+
+    /// Start `dzsm run --supervise` in the background, like the Windows service does
+    fn start(&mut self) -> Result<String> {
+        if let Some(child) = &mut self.supervisor
+            && child.try_wait().context("Failed to check on the DZSM supervisor")?.is_none()
+        {
+            return Ok("The supervisor is still updating or starting the server".to_string());
+        }
+
+        let log_path = logs_dir(&self.server_install_dir).join(SUPERVISOR_LOG);
+        if let Some(parent) = log_path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create logs directory")?;
+        }
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .context(format!("Failed to open {}", log_path.display()))?;
+        let exe = env::current_exe()
+            .context("Failed to locate the dzsm executable")?;
+
+        let mut command = Command::new(exe);
+        command.args(["run", "--supervise", "--non-interactive", "--no-banner"]);
+        if let Some(profile) = &self.profile {
+            command.args(["--profile", profile]);
+        }
+        let child = command
+            .current_dir(&self.root_dir)
+            .stdin(Stdio::null())
+            .stdout(log.try_clone().context("Failed to open supervisor log")?)
+            .stderr(log)
+            .creation_flags(CREATE_NEW_PROCESS_GROUP | CREATE_NO_WINDOW)
+            .spawn()
+            .context("Failed to start the DZSM supervisor")?;
+
+        println_step(&format!("Dashboard started the supervisor (PID {})", child.id()), 1);
+        self.supervisor = Some(child);
+        Ok("Updating and starting the server".to_string())
+    }
+
+    /// Leave a stop or restart request for the supervisor running the server
+    fn request(&self, action: &str) -> Result<String> {
+        let message = match action {
+            "/api/stop" => {
+                request_stop(&self.server_install_dir)?;
+                "Stopping the server"
+            }
+            "/api/restart" => {
+                request_restart(&self.server_install_dir, false)?;
+                "Restarting the server"
+            }
+            _ => {
+                request_restart(&self.server_install_dir, true)?;
+                "Restarting the server to update it"
+            }
+        };
+
+        println_step(&format!("Dashboard: {message}"), 1);
+        Ok(format!("{message}; players are warned first. Only a server run with --supervise picks this up"))
+    }
+
+    /// The last `web.log_lines` lines of the DZSM log
+    fn tail_log(&self) -> Result<Vec<String>> {
+        if !self.config.logging.file {
+            return Ok(vec!["Logging to a file is off, see `logging.file`".to_string()]);
+        }
+
+        let path = get_log_path(&self.server_install_dir);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).context(format!("Failed to read {}", path.display())),
+        };
+
+        let text = String::from_utf8_lossy(&bytes);
+        let lines: Vec<&str> = text.lines().collect();
+        let start = lines.len().saturating_sub(self.config.web.log_lines);
+        Ok(lines[start..].iter().map(|line| (*line).to_string()).collect())
+    }
+}
+
+/// `web.listen` with its port swapped for `port`
+fn with_port(listen: &str, port: u16) -> String {
+    let host = listen.rsplit_once(':').map_or(listen, |(host, _)| host);
+    format!("{host}:{port}")
+}
+
+/// Entry point for `dzsm web`
+pub fn run(config: &Config, root_dir: &Path, server_install_dir: &str, port: Option<u16>, profile: Option<&str>) -> Result<()> {
+    let token = config.web.token.clone()
+        .filter(|token| !token.trim().is_empty())
+        .ok_or_else(|| anyhow!("Set `web.token` in config.toml before serving the dashboard"))?;
+    if token.trim() == PLACEHOLDER_TOKEN {
+        return Err(anyhow!("`web.token` is still {PLACEHOLDER_TOKEN:?}, set it to a secret of your own"));
+    }
+    let listen = port.map_or_else(|| config.web.listen.clone(), |port| with_port(&config.web.listen, port));
+
+    let mut dashboard = Dashboard {
+        config: config.clone(),
+        token,
+        root_dir: root_dir.to_path_buf(),
+        server_install_dir: PathBuf::from(server_install_dir),
+        profile: profile.map(str::to_string),
+        supervisor: None,
+    };
+
+    println_step("Serving the dashboard until stopped (Ctrl+C)...", 0);
+    dashboard.serve(&listen)
+}
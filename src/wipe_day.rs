@@ -0,0 +1,201 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use std::fs;
+use std::path::Path;
+
+use crate::cli::{CliArgs, WipeDayArgs};
+use crate::config::{Config, NotificationEvent};
+use crate::control;
+use crate::instance::{self, InstanceLock};
+use crate::missions;
+use crate::notifier::{Notification, Notifier};
+use crate::processes;
+use crate::rcon::RconClient;
+use crate::server::{MISSIONS_DIR, SERVER_CONFIG, SERVER_EXE, ServerManager, get_server_name};
+use crate::server_cfg::ServerDzConfig;
+use crate::state::state_dir;
+use crate::storage::{PLAYERS_DB, copy_dir, get_storage_dir, reset_size_history};
+use crate::supervisor::Supervisor;
+use crate::ui::prompt::prompt_yes_no;
+use crate::ui::spinner::Spinner;
+use crate::ui::status::{println_failure, println_step, println_success};
+
+/// Storage as it was before each wipe, never pruned like the start-up backups, only deleted
+/// after `privacy.retention_days`
+pub const WIPES_DIR: &str = "wipes";
+const DEFAULT_MESSAGE: &str = "The server is going down for the wipe";
+
+/// Entry point for `dzsm wipe-day`
+pub fn run(wipe: &WipeDayArgs, args: &CliArgs, config: &Config, server_install_dir: &str) -> Result<()> {
+    let install_dir = Path::new(server_install_dir);
+
+    // Everything that can be checked is, before players are kicked
+    if let Some(mission) = &wipe.mission
+        && !install_dir.join(MISSIONS_DIR).join(mission).is_dir()
+    {
+        return Err(anyhow!("Mission {mission} is not in {MISSIONS_DIR}, install it with `dzsm mission install {mission}`"));
+    }
+    let mut server_cfg = ServerDzConfig::load(&install_dir.join(SERVER_CONFIG))?;
+    let instance_id: u64 = server_cfg.get("instanceId").unwrap_or_else(|| "1".to_string()).trim().parse()
+        .context(format!("`instanceId` in {SERVER_CONFIG} is not a number"))?;
+
+    println_step("Wipe day:", 0);
+    for step in describe(wipe, instance_id) {
+        println_step(&step, 1);
+    }
+    if !wipe.yes && !prompt_yes_no("Go ahead?", false, 0)? {
+        return Err(anyhow!("Wipe cancelled, pass --yes to skip the question"));
+    }
+
+    let message = wipe.message.as_deref().unwrap_or(DEFAULT_MESSAGE);
+    announce(config, install_dir, message);
+    // The DZSM running the server exits once it's stopped it, freeing the instance lock
+    if instance::find_running(install_dir).is_some() {
+        control::stop_server(config, install_dir, false)
+            .context("Stop the server and run `dzsm wipe-day` again")?;
+    }
+    // Like `dzsm run`, and taken before anything is stopped or wiped, so another DZSM can't
+    // start this server in the middle of the wipe
+    let Some(_instance_lock) = InstanceLock::acquire(install_dir, wipe.supervise, &config.shutdown)? else {
+        return Err(anyhow!("Another DZSM is running the server, nothing was wiped"));
+    };
+    control::stop_server(config, install_dir, false)
+        .context("Stop the server and run `dzsm wipe-day` again")?;
+
+    if let Some(storage_dir) = get_storage_dir(install_dir)? {
+        let backup_dir = state_dir(install_dir)
+            .join(WIPES_DIR)
+            .join(Local::now().format("%Y-%m-%d_%H-%M-%S").to_string())
+            .join(storage_dir.file_name().unwrap_or_default());
+        let spinner = Spinner::start("Backing up storage...", 1);
+        copy_dir(&storage_dir, &backup_dir)
+            .context("Failed to back up storage, nothing was wiped")?;
+        spinner.finish();
+        println_success(&format!("Storage backed up to {}", backup_dir.display()), 1);
+
+        if wipe.wipe != "none" {
+            wipe_storage(&storage_dir, &wipe.wipe)?;
+            println_success(&format!("Wiped {}", describe_wipe(&wipe.wipe)), 1);
+        }
+    } else {
+        println_step("No storage yet, nothing to back up or wipe", 1);
+    }
+    // What's left is much smaller than before, which isn't corruption
+    reset_size_history(install_dir)?;
+
+    if let Some(mission) = &wipe.mission {
+        missions::set(install_dir, mission)?;
+    }
+    if wipe.refresh_mission {
+        missions::refresh(config, install_dir, args.offline)?;
+        missions::merge_active(install_dir)
+            .context("The server was left stopped, fix the mission and start it")?;
+    }
+    if wipe.new_instance {
+        // Reloaded, `mission` may have changed the template
+        server_cfg = ServerDzConfig::load(&install_dir.join(SERVER_CONFIG))?;
+        server_cfg.set_raw("instanceId", &(instance_id + 1).to_string());
+        server_cfg.save()?;
+        println_success(&format!("instanceId raised to {}, storage_{instance_id} is kept as it is", instance_id + 1), 1);
+    }
+
+    // Sent once the server is back up, an update that fails leaves it down
+    let notify_done = {
+        let config = config.clone();
+        let install_dir = install_dir.to_path_buf();
+        let body = format!("Wiped {}, the server is back up.", describe_wipe(&wipe.wipe));
+        move || notify(&config, &install_dir, "wipe done", body)
+    };
+
+    // Updating publishes the digest of changed mods, like any other update
+    let mut server_manager = ServerManager::new(args.clone(), config.clone(), server_install_dir)
+        .on_first_launch(notify_done);
+    if wipe.supervise {
+        return Supervisor::new(server_manager, server_install_dir)?.run();
+    }
+    server_manager.setup_steamcmd()?;
+    server_manager.install_or_update_server()?;
+    server_manager.install_or_update_mods()?;
+    server_manager.run_server()
+}
+
+/// What `dzsm wipe-day` is about to do, to confirm before anything happens
+fn describe(wipe: &WipeDayArgs, instance_id: u64) -> Vec<String> {
+    let mut steps = vec![
+        "Warn players and stop the server".to_string(),
+        format!("Back up the storage to .dzsm/{WIPES_DIR}/ and wipe {}", describe_wipe(&wipe.wipe)),
+    ];
+    if let Some(mission) = &wipe.mission {
+        steps.push(format!("Make {mission} the active mission"));
+    }
+    if wipe.refresh_mission {
+        steps.push("Merge the active mission with the latest vanilla missions".to_string());
+    }
+    if wipe.new_instance {
+        steps.push(format!("Raise instanceId from {instance_id} to {}", instance_id + 1));
+    }
+    let start = if wipe.supervise { "start and supervise it" } else { "start it" };
+    steps.push(format!("Update the server and mods, then {start}"));
+    steps
+}
+
+fn describe_wipe(mode: &str) -> &'static str {
+    match mode {
+        "full" => "everything",
+        "world" => "loot, bases, and vehicles, keeping characters",
+        "characters" => "characters, keeping loot, bases, and vehicles",
+        _ => "nothing",
+    }
+}
+
+/// Tell the players on the server and the notification channels
+fn announce(config: &Config, server_install_dir: &Path, message: &str) {
+    notify(config, server_install_dir, "wipe starting", message.to_string());
+
+    if processes::find_by_exe_path(&server_install_dir.join(SERVER_EXE)).is_empty() {
+        return;
+    }
+    match RconClient::connect_to_server(server_install_dir).and_then(|mut rcon| rcon.say_all(message)) {
+        Ok(()) => println_success("Players told about the wipe", 1),
+        Err(e) => println_failure(&format!("Failed to tell players about the wipe: {e:#}"), 1),
+    }
+}
+
+fn notify(config: &Config, server_install_dir: &Path, title: &str, body: String) {
+    let notifier = Notifier::new(&config.notifications);
+    if !notifier.is_enabled() {
+        return;
+    }
+
+    let notification = Notification {
+        event: NotificationEvent::Wipe,
+        title: format!("{}: {title}", get_server_name(server_install_dir)),
+        body,
+    };
+    if let Err(e) = notifier.send(&notification) {
+        println_failure(&format!("Failed to post wipe notice: {e}"), 1);
+    }
+}
+
+/// Remove what `mode` wipes: the whole storage, or the characters (`players.db` and its
+/// journal) or everything but them
+fn wipe_storage(storage_dir: &Path, mode: &str) -> Result<()> {
+    if mode == "full" {
+        return fs::remove_dir_all(storage_dir)
+            .context(format!("Failed to remove {}", storage_dir.display()));
+    }
+
+    let entries = fs::read_dir(storage_dir)
+        .context(format!("Failed to read {}", storage_dir.display()))?;
+    for entry in entries.flatten() {
+        let is_characters = entry.file_name().to_string_lossy().to_lowercase().starts_with(PLAYERS_DB);
+        if is_characters != (mode == "characters") {
+            continue;
+        }
+
+        let path = entry.path();
+        let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        removed.context(format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
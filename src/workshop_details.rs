@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::http;
+
+const DETAILS_URL: &str = "https://api.steampowered.com/ISteamRemoteStorage/GetPublishedFileDetails/v1/";
+/// Items asked about per request
+const DETAILS_BATCH: usize = 100;
+/// The `result` of an item Steam found
+const RESULT_OK: u32 = 1;
+
+#[derive(Deserialize)]
+struct DetailsResponse {
+    response: DetailsList,
+}
+
+#[derive(Deserialize)]
+struct DetailsList {
+    #[serde(default)]
+    publishedfiledetails: Vec<WorkshopDetails>,
+}
+
+/// What the Workshop says about an item, without logging in
+#[derive(Clone, Deserialize)]
+pub struct WorkshopDetails {
+    #[serde(default)]
+    result: u32,
+    #[serde(rename = "publishedfileid")]
+    id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    /// Unix time of the latest upload
+    #[serde(default)]
+    pub time_updated: i64,
+    /// Bytes to download for the latest upload
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub file_size: u64,
+}
+
+/// Workshop details of the items, by ID; items Steam doesn't know or hides are left out
+pub fn fetch(ids: &[u64]) -> Result<HashMap<u64, WorkshopDetails>> {
+    let mut details = HashMap::new();
+
+    for batch in ids.chunks(DETAILS_BATCH) {
+        let mut form = format!("itemcount={}", batch.len());
+        for (i, id) in batch.iter().enumerate() {
+            let _ = write!(form, "&publishedfileids[{i}]={id}");
+        }
+
+        let text = http::post_form(DETAILS_URL, &form)
+            .context("Failed to fetch Workshop item details")?;
+        let response: DetailsResponse = serde_json::from_str(&text)
+            .context("Failed to parse Workshop item details")?;
+        details.extend(response.response.publishedfiledetails.into_iter()
+            .filter(|item| item.result == RESULT_OK)
+            .filter_map(|item| item.id.parse().ok().map(|id| (id, item))));
+    }
+
+    Ok(details)
+}
+
+/// Sizes come back as numbers or as strings, depending on the item
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Number(u64),
+        Text(String),
+    }
+
+    Ok(match Size::deserialize(deserializer)? {
+        Size::Number(size) => size,
+        Size::Text(text) => text.parse().unwrap_or(0),
+    })
+}
@@ -0,0 +1,57 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::vdf;
+
+/// Install state of a single workshop item as recorded by SteamCMD
+#[allow(clippy::doc_markdown)]
+#[derive(Debug, Clone)]
+pub struct WorkshopItemState {
+    pub time_updated: i64,
+    pub manifest: String,
+}
+
+/// Parsed `appworkshop_<app id>.acf` from the SteamCMD workshop directory
+#[allow(clippy::doc_markdown)]
+#[derive(Debug, Clone, Default)]
+pub struct WorkshopManifest {
+    items: HashMap<u64, WorkshopItemState>,
+}
+
+impl WorkshopManifest {
+    /// Load the manifest, returning an empty one if SteamCMD hasn't written it yet
+    #[allow(clippy::doc_markdown)]
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read workshop manifest {}", path.display()))?;
+        let root = vdf::parse(&content)
+            .context(format!("Failed to parse workshop manifest {}", path.display()))?;
+
+        let mut items = HashMap::new();
+        if let Some(installed) = root.get_path(&["AppWorkshop", "WorkshopItemsInstalled"]) {
+            for (id, item) in installed.entries() {
+                let Ok(id) = id.parse::<u64>() else {
+                    continue;
+                };
+
+                let field = |key: &str| item.get(key).and_then(|v| v.as_str()).unwrap_or_default();
+                items.insert(id, WorkshopItemState {
+                    time_updated: field("timeupdated").parse().unwrap_or(0),
+                    manifest: field("manifest").to_string(),
+                });
+            }
+        }
+
+        Ok(Self { items })
+    }
+
+    pub fn get(&self, workshop_id: u64) -> Option<&WorkshopItemState> {
+        self.items.get(&workshop_id)
+    }
+}